@@ -85,6 +85,8 @@ struct BucketLocalAlias {
 struct ApiBucketQuotas {
 	max_size: Option<u64>,
 	max_objects: Option<u64>,
+	#[serde(default)]
+	strict: bool,
 }
 
 pub async fn handle_get_bucket_info(
@@ -227,6 +229,7 @@ async fn bucket_info_results(
 			quotas: ApiBucketQuotas {
 				max_size: quotas.max_size,
 				max_objects: quotas.max_objects,
+				strict: quotas.strict,
 			},
 		};
 
@@ -276,7 +279,7 @@ pub async fn handle_create_bucket(
 	let helper = garage.locked_helper().await;
 
 	if let Some(ga) = &req.global_alias {
-		if !is_valid_bucket_name(ga) {
+		if !is_valid_bucket_name(ga, garage.config.s3_api.relaxed_bucket_names) {
 			return Err(Error::bad_request(format!(
 				"{}: {}",
 				ga, INVALID_BUCKET_NAME_MESSAGE
@@ -291,7 +294,7 @@ pub async fn handle_create_bucket(
 	}
 
 	if let Some(la) = &req.local_alias {
-		if !is_valid_bucket_name(&la.alias) {
+		if !is_valid_bucket_name(&la.alias, garage.config.s3_api.relaxed_bucket_names) {
 			return Err(Error::bad_request(format!(
 				"{}: {}",
 				la.alias, INVALID_BUCKET_NAME_MESSAGE
@@ -438,6 +441,7 @@ pub async fn handle_update_bucket(
 		state.quotas.update(BucketQuotas {
 			max_size: q.max_size,
 			max_objects: q.max_objects,
+			strict: q.strict,
 		});
 	}
 
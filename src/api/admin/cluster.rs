@@ -2,7 +2,8 @@ use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
-use hyper::{body::Incoming as IncomingBody, Request, Response};
+use hyper::header::{ACCEPT, CONTENT_TYPE};
+use hyper::{body::Incoming as IncomingBody, Request, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 
 use garage_util::crdt::*;
@@ -14,7 +15,7 @@ use garage_model::garage::Garage;
 
 use crate::admin::api_server::ResBody;
 use crate::admin::error::*;
-use crate::helpers::{json_ok_response, parse_json_body};
+use crate::helpers::{json_ok_response, parse_json_body, string_body};
 
 pub async fn handle_get_cluster_status(garage: &Arc<Garage>) -> Result<Response<ResBody>, Error> {
 	let layout = garage.system.cluster_layout();
@@ -135,6 +136,80 @@ pub async fn handle_get_cluster_health(garage: &Arc<Garage>) -> Result<Response<
 	Ok(json_ok_response(&health)?)
 }
 
+/// Lightweight health check for load balancers: returns 200 if this node
+/// can reach a quorum for all partitions, and 503 otherwise. The body is a
+/// short text message, or a JSON summary if requested with `Accept: application/json`
+pub fn handle_health(
+	garage: &Arc<Garage>,
+	req: &Request<IncomingBody>,
+) -> Result<Response<ResBody>, Error> {
+	use garage_rpc::system::ClusterHealthStatus;
+	let health = garage.system.health();
+
+	let (status_code, status, status_str) = match health.status {
+		ClusterHealthStatus::Healthy => (StatusCode::OK, "healthy", "Garage is fully operational"),
+		ClusterHealthStatus::Degraded => (
+			StatusCode::OK,
+			"degraded",
+			"Garage is operational but some storage nodes are unavailable",
+		),
+		ClusterHealthStatus::Unavailable => (
+			StatusCode::SERVICE_UNAVAILABLE,
+			"unavailable",
+			"Quorum is not available for some/all partitions, reads and writes will fail",
+		),
+	};
+
+	let wants_json = req
+		.headers()
+		.get_all(ACCEPT)
+		.iter()
+		.filter_map(|v| v.to_str().ok())
+		.flat_map(|v| v.split(','))
+		.any(|v| v.split(';').next().unwrap_or_default().trim() == "application/json");
+	if !wants_json {
+		let status_str = format!(
+			"{}\nConsult the full health check API endpoint at /v1/health for more details\n",
+			status_str
+		);
+		return Ok(Response::builder()
+			.status(status_code)
+			.header(CONTENT_TYPE, "text/plain")
+			.body(string_body(status_str))?);
+	}
+
+	let (mut connected_peers, mut down_nodes) = (vec![], vec![]);
+	for node in garage.system.get_known_nodes() {
+		if node.id == garage.system.id {
+			continue;
+		}
+		match node.is_up {
+			true => connected_peers.push(hex::encode(node.id)),
+			false => down_nodes.push(hex::encode(node.id)),
+		}
+	}
+
+	let layout_version = garage.system.cluster_layout().current().version;
+	let tables_synced = garage
+		.system
+		.layout_manager
+		.table_sync_versions()
+		.values()
+		.all(|v| *v >= layout_version);
+
+	let res = HealthResponse {
+		status,
+		connected_peers,
+		down_nodes,
+		layout_version,
+		tables_synced,
+	};
+
+	let mut resp = json_ok_response::<Error, _>(&res)?;
+	*resp.status_mut() = status_code;
+	Ok(resp)
+}
+
 pub async fn handle_connect_cluster_nodes(
 	garage: &Arc<Garage>,
 	req: Request<IncomingBody>,
@@ -225,6 +300,16 @@ pub struct ClusterHealth {
 	partitions_all_ok: usize,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HealthResponse {
+	status: &'static str,
+	connected_peers: Vec<String>,
+	down_nodes: Vec<String>,
+	layout_version: u64,
+	tables_synced: bool,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct GetClusterStatusResponse {
@@ -11,7 +11,12 @@ pub enum CommonError {
 	// ---- INTERNAL ERRORS ----
 	/// Error related to deeper parts of Garage
 	#[error(display = "Internal error: {}", _0)]
-	InternalError(#[error(source)] GarageError),
+	InternalError(#[error(source, no_from)] GarageError),
+
+	/// Not enough nodes could be reached to serve the request,
+	/// the client should retry later
+	#[error(display = "Service unavailable: {}", _0)]
+	ServiceUnavailable(#[error(source, no_from)] GarageError),
 
 	/// Error related to Hyper
 	#[error(display = "Internal error (Hyper error): {}", _0)]
@@ -30,6 +35,11 @@ pub enum CommonError {
 	#[error(display = "Bad request: {}", _0)]
 	BadRequest(String),
 
+	/// The access key used exceeded its request or bandwidth rate limit,
+	/// the client should retry after the given number of seconds
+	#[error(display = "Rate limit exceeded, retry in {} seconds", _0)]
+	RateLimited(u64),
+
 	/// The client sent a header with invalid value
 	#[error(display = "Invalid header value: {}", _0)]
 	InvalidHeader(#[error(source)] hyper::header::ToStrError),
@@ -58,9 +68,9 @@ pub enum CommonError {
 impl CommonError {
 	pub fn http_status_code(&self) -> StatusCode {
 		match self {
-			CommonError::InternalError(
-				GarageError::Timeout | GarageError::RemoteError(_) | GarageError::Quorum(..),
-			) => StatusCode::SERVICE_UNAVAILABLE,
+			CommonError::ServiceUnavailable(_)
+			| CommonError::RateLimited(_)
+			| CommonError::InternalError(GarageError::RemoteError(_)) => StatusCode::SERVICE_UNAVAILABLE,
 			CommonError::InternalError(_) | CommonError::Hyper(_) | CommonError::Http(_) => {
 				StatusCode::INTERNAL_SERVER_ERROR
 			}
@@ -77,9 +87,8 @@ impl CommonError {
 	pub fn aws_code(&self) -> &'static str {
 		match self {
 			CommonError::Forbidden(_) => "AccessDenied",
-			CommonError::InternalError(
-				GarageError::Timeout | GarageError::RemoteError(_) | GarageError::Quorum(..),
-			) => "ServiceUnavailable",
+			CommonError::ServiceUnavailable(_) | CommonError::RateLimited(_) => "SlowDown",
+			CommonError::InternalError(GarageError::RemoteError(_)) => "ServiceUnavailable",
 			CommonError::InternalError(_) | CommonError::Hyper(_) | CommonError::Http(_) => {
 				"InternalError"
 			}
@@ -95,15 +104,35 @@ impl CommonError {
 	pub fn bad_request<M: ToString>(msg: M) -> Self {
 		CommonError::BadRequest(msg.to_string())
 	}
+
+	/// Number of seconds after which the client should retry, for errors
+	/// that are caused by a temporary unavailability
+	pub fn retry_after(&self) -> Option<u64> {
+		match self {
+			CommonError::ServiceUnavailable(_) => Some(1),
+			CommonError::RateLimited(secs) => Some(*secs),
+			_ => None,
+		}
+	}
+}
+
+impl From<GarageError> for CommonError {
+	fn from(err: GarageError) -> Self {
+		match err {
+			GarageError::Quorum(..) | GarageError::Timeout => Self::ServiceUnavailable(err),
+			e => Self::InternalError(e),
+		}
+	}
 }
 
 impl From<HelperError> for CommonError {
 	fn from(err: HelperError) -> Self {
 		match err {
-			HelperError::Internal(i) => Self::InternalError(i),
+			HelperError::Internal(i) => Self::from(i),
 			HelperError::BadRequest(b) => Self::BadRequest(b),
 			HelperError::InvalidBucketName(n) => Self::InvalidBucketName(n),
 			HelperError::NoSuchBucket(n) => Self::NoSuchBucket(n),
+			HelperError::BucketNotEmpty(_) => Self::BucketNotEmpty,
 			e => Self::bad_request(format!("{}", e)),
 		}
 	}
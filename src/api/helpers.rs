@@ -1,4 +1,5 @@
 use std::convert::Infallible;
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use futures::{Stream, StreamExt, TryStreamExt};
@@ -16,6 +17,9 @@ use garage_model::garage::Garage;
 use garage_model::key_table::Key;
 use garage_util::data::Uuid;
 use garage_util::error::Error as GarageError;
+use garage_util::forwarded_headers::{client_ip, IpNet};
+
+use crate::generic_server::PeerAddr;
 
 use crate::common_error::{CommonError as Error, *};
 
@@ -41,6 +45,129 @@ pub struct ReqCtx {
 	pub api_key: Key,
 }
 
+/// Enforce the request and bandwidth rate limits configured on an access key.
+/// The bytes transferred by the request are not known in advance: they are
+/// charged as the request and response bodies are streamed, see
+/// `rate_limit_request` and `rate_limit_response`.
+pub fn check_rate_limit(garage: &Garage, api_key: &Key) -> Result<(), Error> {
+	let limit = match api_key.params() {
+		Some(p) => p.rate_limit.get(),
+		None => return Ok(()),
+	};
+	garage
+		.rate_limiter
+		.check_rate_limit(&api_key.key_id, limit, 0)
+		.map_err(|wait| Error::RateLimited(std::cmp::max(1, wait.as_secs_f64().ceil() as u64)))
+}
+
+/// Address of the client that made a request, taking into account the
+/// X-Forwarded-For header if the request comes from a trusted proxy
+pub fn request_client_ip<B>(req: &Request<B>, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+	let peer = req.extensions().get::<PeerAddr>().and_then(|a| a.0);
+	client_ip(peer, req.headers(), trusted_proxies)
+}
+
+/// Refuse requests made with an access key from outside of the IP ranges
+/// it is restricted to, if any
+pub fn check_source_ip(api_key: &Key, ip: Option<IpAddr>) -> Result<(), Error> {
+	let params = match api_key.params() {
+		Some(p) => p,
+		None => return Ok(()),
+	};
+	if params.allows_source_ip(ip) {
+		Ok(())
+	} else {
+		Err(Error::Forbidden(format!(
+			"Access key {} cannot be used from {}",
+			api_key.key_id,
+			ip.map(|ip| ip.to_string())
+				.unwrap_or_else(|| "an unknown address".into())
+		)))
+	}
+}
+
+/// Charge the bytes of the request body to the bandwidth limit of the access key
+/// as they are read by the handler, including for chunked uploads whose size
+/// is not announced in advance
+pub fn rate_limit_request<E: 'static>(
+	garage: &Arc<Garage>,
+	api_key: &Key,
+	req: Request<BoxBody<E>>,
+) -> Request<BoxBody<E>> {
+	match bandwidth_charge(garage, api_key) {
+		Some(charge) => req.map(|body| charged_body(body, charge)),
+		None => req,
+	}
+}
+
+/// Charge the bytes of the response body to the bandwidth limit of the access key
+/// as they are handed to the HTTP connection, so that the requests that follow
+/// a large download are delayed until it has been paid back
+pub fn rate_limit_response<E: 'static>(
+	garage: &Arc<Garage>,
+	api_key: &Key,
+	resp: Response<BoxBody<E>>,
+) -> Response<BoxBody<E>> {
+	match bandwidth_charge(garage, api_key) {
+		Some(charge) => resp.map(|body| charged_body(body, charge)),
+		None => resp,
+	}
+}
+
+/// Charge the chunks of a data stream to the bandwidth limit of the access key
+/// as they are read, for request bodies that are not read as a whole
+pub fn rate_limit_stream<S, E>(
+	garage: &Arc<Garage>,
+	api_key: &Key,
+	stream: S,
+) -> impl Stream<Item = Result<Bytes, E>>
+where
+	S: Stream<Item = Result<Bytes, E>>,
+{
+	let charge = bandwidth_charge(garage, api_key);
+	stream.inspect(move |chunk| {
+		if let (Some(charge), Ok(bytes)) = (&charge, chunk) {
+			charge(bytes.len() as u64);
+		}
+	})
+}
+
+/// Function that takes bytes from the bandwidth bucket of an access key,
+/// or None if the key has no bandwidth limit
+fn bandwidth_charge(
+	garage: &Arc<Garage>,
+	api_key: &Key,
+) -> Option<impl Fn(u64) + Send + Sync + 'static> {
+	let limit = match api_key.params() {
+		Some(p) if p.rate_limit.get().bytes_per_sec.is_some() => p.rate_limit.get().clone(),
+		_ => return None,
+	};
+	let garage = garage.clone();
+	let key_id = api_key.key_id.clone();
+	Some(move |bytes| garage.rate_limiter.consume_bytes(&key_id, &limit, bytes))
+}
+
+fn charged_body<E: 'static>(
+	body: BoxBody<E>,
+	charge: impl Fn(u64) + Send + Sync + 'static,
+) -> BoxBody<E> {
+	BoxBody::new(body.map_frame(move |frame| {
+		if let Some(data) = frame.data_ref() {
+			charge(data.len() as u64);
+		}
+		frame
+	}))
+}
+
+/// Value of the Content-Length header of a request, or zero if absent
+pub fn content_length_hint<B>(req: &Request<B>) -> u64 {
+	req.headers()
+		.get(hyper::header::CONTENT_LENGTH)
+		.and_then(|x| x.to_str().ok())
+		.and_then(|x| x.parse().ok())
+		.unwrap_or(0)
+}
+
 /// Host to bucket
 ///
 /// Convert a host, like "bucket.garage-site.tld" to the corresponding bucket "bucket",
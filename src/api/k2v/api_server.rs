@@ -8,6 +8,7 @@ use tokio::sync::watch;
 use opentelemetry::{trace::SpanRef, KeyValue};
 
 use garage_util::error::Error as GarageError;
+use garage_util::forwarded_headers::IpNet;
 use garage_util::socket_address::UnixOrTCPSocketAddress;
 
 use garage_model::garage::Garage;
@@ -29,6 +30,7 @@ pub type ResBody = BoxBody<Error>;
 
 pub struct K2VApiServer {
 	garage: Arc<Garage>,
+	trusted_proxies: Vec<IpNet>,
 }
 
 pub(crate) struct K2VApiEndpoint {
@@ -43,9 +45,20 @@ impl K2VApiServer {
 		s3_region: String,
 		must_exit: watch::Receiver<bool>,
 	) -> Result<(), GarageError> {
-		ApiServer::new(s3_region, K2VApiServer { garage })
-			.run_server(bind_addr, None, must_exit)
-			.await
+		let options = match &garage.config.k2v_api {
+			Some(k2v_api) => ServerOptions::from_config(&k2v_api.http)?,
+			None => ServerOptions::default(),
+		};
+		let trusted_proxies = options.trusted_proxies.clone();
+		ApiServer::new(
+			s3_region,
+			K2VApiServer {
+				garage,
+				trusted_proxies,
+			},
+		)
+		.run_server(bind_addr, None, options, must_exit)
+		.await
 	}
 }
 
@@ -87,6 +100,10 @@ impl ApiHandler for K2VApiServer {
 
 		let (req, api_key, _content_sha256) = verify_request(&garage, req, "k2v").await?;
 
+		check_source_ip(&api_key, request_client_ip(&req, &self.trusted_proxies))?;
+		check_rate_limit(&garage, &api_key)?;
+		let req = rate_limit_request(&garage, &api_key, req);
+
 		let bucket_id = garage
 			.bucket_helper()
 			.resolve_bucket(&bucket_name, &api_key)
@@ -122,11 +139,11 @@ impl ApiHandler for K2VApiServer {
 		};
 
 		let ctx = ReqCtx {
-			garage,
+			garage: garage.clone(),
 			bucket_id,
 			bucket_name,
 			bucket_params,
-			api_key,
+			api_key: api_key.clone(),
 		};
 
 		let resp = match endpoint {
@@ -174,7 +191,7 @@ impl ApiHandler for K2VApiServer {
 				.ok_or_internal_error("Invalid bucket CORS configuration")?;
 		}
 
-		Ok(resp_ok)
+		Ok(rate_limit_response(&garage, &api_key, resp_ok))
 	}
 }
 
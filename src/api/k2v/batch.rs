@@ -3,6 +3,7 @@ use hyper::{Request, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 
 use garage_table::{EnumerationOrder, TableSchema};
+use garage_util::time::now_msec;
 
 use garage_model::k2v::causality::*;
 use garage_model::k2v::item_table::*;
@@ -201,6 +202,7 @@ async fn handle_delete_batch_query(
 						i.sort_key,
 						Some(cc),
 						DvvsValue::Deleted,
+						None,
 					)
 					.await?;
 				1
@@ -362,7 +364,7 @@ impl ReadBatchResponseItem {
 	fn from(i: K2VItem) -> Self {
 		let ct = i.causal_context().serialize();
 		let v = i
-			.values()
+			.values_at(now_msec())
 			.iter()
 			.map(|v| match v {
 				DvvsValue::Value(x) => Some(BASE64_STANDARD.encode(x)),
@@ -17,7 +17,7 @@ pub enum Error {
 
 	// Category: cannot process
 	/// Authorization Header Malformed
-	#[error(display = "Authorization header malformed, unexpected scope: {}", _0)]
+	#[error(display = "Authorization header malformed, {}", _0)]
 	AuthorizationHeaderMalformed(String),
 
 	/// The object requested don't exists
@@ -56,6 +56,7 @@ impl From<SignatureError> for Error {
 				Self::AuthorizationHeaderMalformed(c)
 			}
 			SignatureError::InvalidUtf8Str(i) => Self::InvalidUtf8Str(i),
+			SignatureError::BadDigest(m) => Self::bad_request(m),
 		}
 	}
 }
@@ -92,6 +93,11 @@ impl ApiError for Error {
 	fn add_http_headers(&self, header_map: &mut HeaderMap<HeaderValue>) {
 		use hyper::header;
 		header_map.append(header::CONTENT_TYPE, "application/json".parse().unwrap());
+		if let Error::Common(c) = self {
+			if let Some(secs) = c.retry_after() {
+				header_map.append(header::RETRY_AFTER, secs.into());
+			}
+		}
 	}
 
 	fn http_body(&self, garage_region: &str, path: &str) -> ErrorBody {
@@ -3,6 +3,8 @@ use http::header;
 
 use hyper::{Request, Response, StatusCode};
 
+use garage_util::time::now_msec;
+
 use garage_model::k2v::causality::*;
 use garage_model::k2v::item_table::*;
 
@@ -11,6 +13,7 @@ use crate::k2v::api_server::{ReqBody, ResBody};
 use crate::k2v::error::*;
 
 pub const X_GARAGE_CAUSALITY_TOKEN: &str = "X-Garage-Causality-Token";
+pub const X_GARAGE_TTL: &str = "X-Garage-TTL";
 
 pub enum ReturnFormat {
 	Json,
@@ -38,7 +41,7 @@ impl ReturnFormat {
 	}
 
 	pub fn make_response(&self, item: &K2VItem) -> Result<Response<ResBody>, Error> {
-		let vals = item.values();
+		let vals = item.values_at(now_msec());
 
 		if vals.is_empty() {
 			return Err(Error::NoSuchKey);
@@ -138,6 +141,20 @@ pub async fn handle_insert_item(
 		.transpose()?
 		.map(CausalContext::parse_helper)
 		.transpose()?;
+	let ttl_secs = req
+		.headers()
+		.get(X_GARAGE_TTL)
+		.map(|s| s.to_str())
+		.transpose()?
+		.map(|s| {
+			s.parse::<u64>()
+				.ok()
+				.filter(|ttl| *ttl > 0)
+				.ok_or_bad_request(
+					"Invalid X-Garage-TTL header, must be a positive number of seconds",
+				)
+		})
+		.transpose()?;
 
 	let body = http_body_util::BodyExt::collect(req.into_body())
 		.await?
@@ -154,6 +171,7 @@ pub async fn handle_insert_item(
 			sort_key.to_string(),
 			causal_context,
 			value,
+			ttl_secs,
 		)
 		.await?;
 
@@ -190,6 +208,7 @@ pub async fn handle_delete_item(
 			sort_key.to_string(),
 			causal_context,
 			value,
+			None,
 		)
 		.await?;
 
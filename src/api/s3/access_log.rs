@@ -0,0 +1,155 @@
+use std::path::Path;
+use std::time::Instant;
+
+use hyper::header::CONTENT_LENGTH;
+use hyper::Response;
+use serde::Serialize;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+use garage_util::error::Error as GarageError;
+
+use crate::generic_server::ApiError;
+use crate::s3::api_server::ResBody;
+use crate::s3::error::Error;
+
+/// Access log for requests made to the S3 API. Each completed request
+/// is logged as an event of the `garage_api::s3::access_log` tracing target,
+/// and optionally appended as a JSON line to a file. Lines are written to
+/// the file by a background task, so that requests never wait on disk I/O.
+pub(crate) struct AccessLog {
+	file_writer: Option<mpsc::UnboundedSender<String>>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct AccessLogEntry {
+	time: String,
+	bucket: Option<String>,
+	key: Option<String>,
+	operation: &'static str,
+	key_id: Option<String>,
+	status: u16,
+	bytes_received: u64,
+	bytes_sent: Option<u64>,
+	duration_ms: u64,
+
+	#[serde(skip)]
+	start: Instant,
+}
+
+impl AccessLog {
+	pub(crate) async fn new(file: Option<&Path>) -> Result<Self, GarageError> {
+		let file_writer = match file {
+			Some(path) => {
+				let file = OpenOptions::new()
+					.create(true)
+					.append(true)
+					.open(path)
+					.await
+					.map_err(|e| {
+						GarageError::Message(format!(
+							"Unable to open access log file {}: {}",
+							path.display(),
+							e
+						))
+					})?;
+				let (tx, rx) = mpsc::unbounded_channel();
+				tokio::spawn(write_access_log_file(file, rx));
+				Some(tx)
+			}
+			None => None,
+		};
+		Ok(Self { file_writer })
+	}
+
+	pub(crate) fn log(&self, entry: &AccessLogEntry) {
+		info!(
+			target: "garage_api::s3::access_log",
+			bucket = entry.bucket.as_deref().unwrap_or("-"),
+			key = entry.key.as_deref().unwrap_or("-"),
+			operation = entry.operation,
+			key_id = entry.key_id.as_deref().unwrap_or("-"),
+			status = entry.status,
+			bytes_received = entry.bytes_received,
+			bytes_sent = entry.bytes_sent.unwrap_or(0),
+			duration_ms = entry.duration_ms,
+			"{} {}",
+			entry.operation,
+			entry.status,
+		);
+
+		if let Some(file_writer) = &self.file_writer {
+			let mut line = match serde_json::to_string(entry) {
+				Ok(line) => line,
+				Err(e) => {
+					warn!("Could not serialize access log entry: {}", e);
+					return;
+				}
+			};
+			line.push('\n');
+			if file_writer.send(line).is_err() {
+				warn!("Access log file writer has exited, entry not written");
+			}
+		}
+	}
+}
+
+/// Append the lines received on `rx` to the access log file, until the
+/// access log is dropped
+async fn write_access_log_file(mut file: tokio::fs::File, mut rx: mpsc::UnboundedReceiver<String>) {
+	while let Some(line) = rx.recv().await {
+		let res = match file.write_all(line.as_bytes()).await {
+			Ok(()) => file.flush().await,
+			Err(e) => Err(e),
+		};
+		if let Err(e) = res {
+			warn!("Could not write to access log file: {}", e);
+		}
+	}
+}
+
+impl AccessLogEntry {
+	pub(crate) fn new(
+		bucket: Option<String>,
+		key: Option<String>,
+		operation: &'static str,
+		bytes_received: u64,
+	) -> Self {
+		Self {
+			time: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+			bucket,
+			key,
+			operation,
+			key_id: None,
+			status: 0,
+			bytes_received,
+			bytes_sent: None,
+			duration_ms: 0,
+			start: Instant::now(),
+		}
+	}
+
+	/// Fill in the fields that are known once the request has been processed
+	pub(crate) fn finish(
+		&mut self,
+		key_id: Option<String>,
+		res: &Result<Response<ResBody>, Error>,
+	) {
+		self.key_id = key_id;
+		match res {
+			Ok(resp) => {
+				self.status = resp.status().as_u16();
+				self.bytes_sent = resp
+					.headers()
+					.get(CONTENT_LENGTH)
+					.and_then(|x| x.to_str().ok())
+					.and_then(|x| x.parse().ok());
+			}
+			Err(e) => {
+				self.status = e.http_status_code().as_u16();
+			}
+		}
+		self.duration_ms = self.start.elapsed().as_millis() as u64;
+	}
+}
@@ -0,0 +1,386 @@
+use std::collections::BTreeSet;
+
+use quick_xml::de::from_reader;
+
+use http_body_util::BodyExt;
+use hyper::{Request, Response, StatusCode};
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::helpers::*;
+use crate::s3::api_server::{ReqBody, ResBody};
+use crate::s3::error::*;
+use crate::s3::xml::{to_xml_with_header, xmlns_tag, Value};
+use crate::signature::verify_signed_content;
+
+use garage_model::bucket_table::{Bucket, BucketParams, CannedAcl};
+use garage_model::garage::Garage;
+use garage_model::key_table::Key;
+use garage_model::s3::object_table::*;
+use garage_util::data::*;
+
+pub const X_AMZ_ACL: &str = "x-amz-acl";
+
+const ALL_USERS: &str = "http://acs.amazonaws.com/groups/global/AllUsers";
+const AUTHENTICATED_USERS: &str = "http://acs.amazonaws.com/groups/global/AuthenticatedUsers";
+
+pub async fn handle_get_bucket_acl(ctx: ReqCtx) -> Result<Response<ResBody>, Error> {
+	let ReqCtx {
+		bucket_params,
+		api_key,
+		..
+	} = ctx;
+
+	let key_p = api_key.params().ok_or_internal_error(
+		"Key should not be in deleted state at this point (in handle_get_bucket_acl)",
+	)?;
+	let owner = Owner {
+		id: Value(api_key.key_id.to_string()),
+		display_name: Value(key_p.name.get().to_string()),
+	};
+
+	let policy = AccessControlPolicy::from_canned_acl(*bucket_params.canned_acl.get(), owner);
+	let xml = to_xml_with_header(&policy)?;
+	Ok(Response::builder()
+		.status(StatusCode::OK)
+		.header(http::header::CONTENT_TYPE, "application/xml")
+		.body(string_body(xml))?)
+}
+
+pub async fn handle_put_bucket_acl(
+	ctx: ReqCtx,
+	req: Request<ReqBody>,
+	content_sha256: Option<Hash>,
+) -> Result<Response<ResBody>, Error> {
+	let ReqCtx {
+		garage,
+		bucket_id,
+		mut bucket_params,
+		..
+	} = ctx;
+
+	if req
+		.headers()
+		.keys()
+		.any(|h| h.as_str().starts_with("x-amz-grant-"))
+	{
+		return Err(Error::NotImplemented(
+			"x-amz-grant-* headers are not supported, only canned ACLs are".into(),
+		));
+	}
+
+	let header_acl = parse_canned_acl_header(req.headers())?;
+
+	let body = BodyExt::collect(req.into_body()).await?.to_bytes();
+
+	if let Some(content_sha256) = content_sha256 {
+		verify_signed_content(content_sha256, &body[..])?;
+	}
+
+	let acl = match header_acl {
+		Some(acl) => {
+			if !body.is_empty() {
+				return Err(Error::bad_request(
+					"Cannot specify both an x-amz-acl header and an ACL in the request body",
+				));
+			}
+			acl
+		}
+		None => {
+			let policy: AccessControlPolicy = from_reader(&body as &[u8])?;
+			policy.into_canned_acl()?
+		}
+	};
+
+	bucket_params.canned_acl.update(acl);
+	garage
+		.bucket_table
+		.insert(&Bucket::present(bucket_id, bucket_params))
+		.await?;
+
+	Ok(Response::builder()
+		.status(StatusCode::OK)
+		.body(empty_body())?)
+}
+
+/// Parse the canned ACL given in the x-amz-acl header, if any
+pub(crate) fn parse_canned_acl_header(
+	headers: &http::HeaderMap,
+) -> Result<Option<CannedAcl>, Error> {
+	match headers.get(X_AMZ_ACL) {
+		None => Ok(None),
+		Some(v) => {
+			let v = v.to_str()?;
+			let acl = CannedAcl::parse(v)
+				.ok_or_else(|| Error::bad_request(format!("Unsupported canned ACL: {}", v)))?;
+			Ok(Some(acl))
+		}
+	}
+}
+
+/// Check that the canned ACL given in the x-amz-acl header of a request that
+/// writes an object can be set by the key making the request. Making an
+/// object readable by users that have no access to the bucket requires the
+/// owner permission, as for the ACL of the bucket itself.
+pub(crate) fn check_object_acl_allowed(
+	api_key: &Key,
+	bucket_id: &Uuid,
+	headers: &http::HeaderMap,
+) -> Result<(), Error> {
+	match parse_canned_acl_header(headers)? {
+		Some(acl) if acl != CannedAcl::Private && !api_key.allow_owner(bucket_id) => Err(
+			Error::forbidden("Setting a public ACL on an object requires the owner permission"),
+		),
+		_ => Ok(()),
+	}
+}
+
+/// Canned ACL stored in the metadata of an object version. This is only
+/// available for objects that are not encrypted.
+pub(crate) fn object_version_acl(version: &ObjectVersion) -> Option<CannedAcl> {
+	let meta = match &version.state {
+		ObjectVersionState::Complete(ObjectVersionData::Inline(meta, _))
+		| ObjectVersionState::Complete(ObjectVersionData::FirstBlock(meta, _)) => meta,
+		_ => return None,
+	};
+	match &meta.encryption {
+		ObjectVersionEncryption::Plaintext { inner } => inner
+			.headers
+			.iter()
+			.find(|(k, _)| k == X_AMZ_ACL)
+			.and_then(|(_, v)| CannedAcl::parse(v)),
+		_ => None,
+	}
+}
+
+/// Check whether the canned ACLs of a bucket or of the object itself allow
+/// an object to be read without a permission on the bucket, either
+/// anonymously or, if `authenticated` is set, with any access key.
+pub async fn acl_allows_object_read(
+	garage: &Garage,
+	bucket_id: Uuid,
+	bucket_params: &BucketParams,
+	key: &str,
+	authenticated: bool,
+) -> Result<bool, Error> {
+	let allows = |acl: CannedAcl| match authenticated {
+		true => acl.allows_authenticated_read(),
+		false => acl.allows_anonymous_read(),
+	};
+
+	if allows(*bucket_params.canned_acl.get()) {
+		return Ok(true);
+	}
+
+	let object = garage
+		.object_table
+		.get(&bucket_id, &key.to_string())
+		.await?;
+	let object_acl = object
+		.as_ref()
+		.and_then(|o| o.versions().iter().rev().find(|v| v.is_data()))
+		.and_then(object_version_acl);
+	Ok(object_acl.map(allows).unwrap_or(false))
+}
+
+// ---- SERIALIZATION AND DESERIALIZATION TO/FROM S3 XML ----
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccessControlPolicy {
+	#[serde(serialize_with = "xmlns_tag", skip_deserializing)]
+	pub xmlns: (),
+	#[serde(rename = "Owner", skip_serializing_if = "Option::is_none")]
+	pub owner: Option<Owner>,
+	#[serde(rename = "AccessControlList")]
+	pub access_control_list: AccessControlList,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Owner {
+	#[serde(rename = "ID")]
+	pub id: Value,
+	#[serde(rename = "DisplayName")]
+	pub display_name: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccessControlList {
+	#[serde(rename = "Grant", default)]
+	pub grants: Vec<Grant>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Grant {
+	#[serde(rename = "Grantee")]
+	pub grantee: Grantee,
+	#[serde(rename = "Permission")]
+	pub permission: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Grantee {
+	#[serde(
+		rename = "xmlns:xsi",
+		serialize_with = "xmlns_xsi_tag",
+		skip_deserializing
+	)]
+	pub xmlns_xsi: (),
+	#[serde(rename = "xsi:type", skip_deserializing)]
+	pub grantee_type: String,
+	#[serde(rename = "ID", skip_serializing_if = "Option::is_none")]
+	pub id: Option<Value>,
+	#[serde(rename = "DisplayName", skip_serializing_if = "Option::is_none")]
+	pub display_name: Option<Value>,
+	#[serde(rename = "URI", skip_serializing_if = "Option::is_none")]
+	pub uri: Option<Value>,
+}
+
+fn xmlns_xsi_tag<S: Serializer>(_v: &(), s: S) -> Result<S::Ok, S::Error> {
+	s.serialize_str("http://www.w3.org/2001/XMLSchema-instance")
+}
+
+impl AccessControlPolicy {
+	pub fn from_canned_acl(acl: CannedAcl, owner: Owner) -> Self {
+		let mut grants = vec![Grant {
+			grantee: Grantee {
+				xmlns_xsi: (),
+				grantee_type: "CanonicalUser".into(),
+				id: Some(owner.id.clone()),
+				display_name: Some(owner.display_name.clone()),
+				uri: None,
+			},
+			permission: "FULL_CONTROL".into(),
+		}];
+
+		let group_grants: &[(&str, &str)] = match acl {
+			CannedAcl::Private => &[],
+			CannedAcl::PublicRead => &[(ALL_USERS, "READ")],
+			CannedAcl::PublicReadWrite => &[(ALL_USERS, "READ"), (ALL_USERS, "WRITE")],
+			CannedAcl::AuthenticatedRead => &[(AUTHENTICATED_USERS, "READ")],
+		};
+		for (uri, permission) in group_grants {
+			grants.push(Grant {
+				grantee: Grantee {
+					xmlns_xsi: (),
+					grantee_type: "Group".into(),
+					id: None,
+					display_name: None,
+					uri: Some((*uri).into()),
+				},
+				permission: (*permission).into(),
+			});
+		}
+
+		AccessControlPolicy {
+			xmlns: (),
+			owner: Some(owner),
+			access_control_list: AccessControlList { grants },
+		}
+	}
+
+	/// Find the canned ACL equivalent to this policy. Grants to individual
+	/// users are ignored, as permissions of access keys are managed by
+	/// the cluster administrator.
+	pub fn into_canned_acl(self) -> Result<CannedAcl, Error> {
+		let mut group_grants = BTreeSet::new();
+		for grant in self.access_control_list.grants.iter() {
+			match (&grant.grantee.id, &grant.grantee.uri) {
+				(Some(_), None) => (),
+				(None, Some(uri)) => {
+					group_grants.insert((uri.0.as_str(), grant.permission.0.as_str()));
+				}
+				_ => return Err(Error::bad_request("Invalid ACL grantee")),
+			}
+		}
+
+		let group_grants = group_grants.into_iter().collect::<Vec<_>>();
+		match &group_grants[..] {
+			[] => Ok(CannedAcl::Private),
+			[(ALL_USERS, "READ")] => Ok(CannedAcl::PublicRead),
+			[(ALL_USERS, "READ"), (ALL_USERS, "WRITE")] => Ok(CannedAcl::PublicReadWrite),
+			[(AUTHENTICATED_USERS, "READ")] => Ok(CannedAcl::AuthenticatedRead),
+			_ => Err(Error::NotImplemented(
+				"only ACLs equivalent to a canned ACL are supported".into(),
+			)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use quick_xml::de::from_str;
+
+	fn owner() -> Owner {
+		Owner {
+			id: "GK31c2f218a2e44f485b94239e".into(),
+			display_name: "test-key".into(),
+		}
+	}
+
+	#[test]
+	fn test_deserialize_acl() -> Result<(), Error> {
+		let message = r#"<?xml version="1.0" encoding="UTF-8"?>
+<AccessControlPolicy xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Owner>
+    <ID>GK31c2f218a2e44f485b94239e</ID>
+    <DisplayName>test-key</DisplayName>
+  </Owner>
+  <AccessControlList>
+    <Grant>
+      <Grantee xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="CanonicalUser">
+        <ID>GK31c2f218a2e44f485b94239e</ID>
+      </Grantee>
+      <Permission>FULL_CONTROL</Permission>
+    </Grant>
+    <Grant>
+      <Grantee xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="Group">
+        <URI>http://acs.amazonaws.com/groups/global/AllUsers</URI>
+      </Grantee>
+      <Permission>READ</Permission>
+    </Grant>
+  </AccessControlList>
+</AccessControlPolicy>"#;
+		let policy: AccessControlPolicy = from_str(message).unwrap();
+		assert_eq!(policy.access_control_list.grants.len(), 2);
+		assert_eq!(policy.into_canned_acl()?, CannedAcl::PublicRead);
+		Ok(())
+	}
+
+	#[test]
+	fn test_canned_acl_roundtrip() -> Result<(), Error> {
+		for acl in [
+			CannedAcl::Private,
+			CannedAcl::PublicRead,
+			CannedAcl::PublicReadWrite,
+			CannedAcl::AuthenticatedRead,
+		] {
+			let xml = to_xml_with_header(&AccessControlPolicy::from_canned_acl(acl, owner()))?;
+			let policy: AccessControlPolicy = from_str(&xml).unwrap();
+			assert_eq!(policy.into_canned_acl()?, acl);
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn test_unsupported_acl() {
+		let policy = AccessControlPolicy {
+			xmlns: (),
+			owner: None,
+			access_control_list: AccessControlList {
+				grants: vec![Grant {
+					grantee: Grantee {
+						xmlns_xsi: (),
+						grantee_type: String::new(),
+						id: None,
+						display_name: None,
+						uri: Some(AUTHENTICATED_USERS.into()),
+					},
+					permission: "WRITE".into(),
+				}],
+			},
+		};
+		assert!(policy.into_canned_acl().is_err());
+	}
+}
@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 
@@ -8,18 +9,23 @@ use tokio::sync::watch;
 
 use opentelemetry::{trace::SpanRef, KeyValue};
 
-use garage_util::error::Error as GarageError;
+use garage_util::error::{Error as GarageError, OkOrMessage};
+use garage_util::forwarded_headers::IpNet;
 use garage_util::socket_address::UnixOrTCPSocketAddress;
 
 use garage_model::garage::Garage;
 use garage_model::key_table::Key;
 
+use crate::common_error::CommonError;
 use crate::generic_server::*;
 use crate::s3::error::*;
 
-use crate::signature::verify_request;
+use crate::signature::verify_request_allow_anonymous;
 
 use crate::helpers::*;
+use crate::s3::access_log::*;
+use crate::s3::acl::*;
+use crate::s3::bandwidth::BandwidthMetrics;
 use crate::s3::bucket::*;
 use crate::s3::copy::*;
 use crate::s3::cors::*;
@@ -28,9 +34,11 @@ use crate::s3::get::*;
 use crate::s3::lifecycle::*;
 use crate::s3::list::*;
 use crate::s3::multipart::*;
+use crate::s3::object_lock::*;
 use crate::s3::post_object::handle_post_object;
 use crate::s3::put::*;
 use crate::s3::router::Endpoint;
+use crate::s3::tagging::*;
 use crate::s3::website::*;
 
 pub use crate::signature::streaming::ReqBody;
@@ -38,6 +46,10 @@ pub type ResBody = BoxBody<Error>;
 
 pub struct S3ApiServer {
 	garage: Arc<Garage>,
+	access_log: Option<AccessLog>,
+	request_timeout: Option<Duration>,
+	trusted_proxies: Vec<IpNet>,
+	bandwidth: BandwidthMetrics,
 }
 
 pub(crate) struct S3ApiEndpoint {
@@ -52,9 +64,32 @@ impl S3ApiServer {
 		s3_region: String,
 		must_exit: watch::Receiver<bool>,
 	) -> Result<(), GarageError> {
-		ApiServer::new(s3_region, S3ApiServer { garage })
-			.run_server(addr, None, must_exit)
-			.await
+		let access_log = if garage.config.s3_api.access_log {
+			Some(AccessLog::new(garage.config.s3_api.access_log_file.as_deref()).await?)
+		} else {
+			None
+		};
+		let request_timeout = garage
+			.config
+			.s3_api
+			.request_timeout
+			.as_deref()
+			.map(|t| parse_duration::parse(t).ok_or_message("Invalid `s3_api.request_timeout`"))
+			.transpose()?
+			.filter(|t| !t.is_zero());
+		let options = ServerOptions::from_config(&garage.config.s3_api.http)?;
+		ApiServer::new(
+			s3_region,
+			S3ApiServer {
+				garage,
+				access_log,
+				request_timeout,
+				trusted_proxies: options.trusted_proxies.clone(),
+				bandwidth: BandwidthMetrics::new(),
+			},
+		)
+		.run_server(addr, None, options, must_exit)
+		.await
 	}
 
 	async fn handle_request_without_bucket(
@@ -68,6 +103,66 @@ impl S3ApiServer {
 			endpoint => Err(Error::NotImplemented(endpoint.name().to_owned())),
 		}
 	}
+
+	/// Handle a request made without an access key: only reading objects
+	/// made public by a canned ACL is allowed
+	async fn handle_anonymous_request(
+		&self,
+		req: Request<ReqBody>,
+		bucket_name: Option<String>,
+		endpoint: Endpoint,
+	) -> Result<Response<ResBody>, Error> {
+		let bucket_name = match bucket_name {
+			Some(b) if endpoint.is_object_read() => b,
+			_ => {
+				return Err(Error::forbidden(
+					"Anonymous access is only allowed for reading public objects",
+				))
+			}
+		};
+		let garage = self.garage.clone();
+
+		let bucket_id = garage
+			.bucket_helper()
+			.resolve_global_bucket_name(&bucket_name)
+			.await?
+			.ok_or_else(|| Error::Common(CommonError::NoSuchBucket(bucket_name.clone())))?;
+		let bucket = garage
+			.bucket_helper()
+			.get_existing_bucket(bucket_id)
+			.await?;
+		let bucket_params = bucket.state.into_option().unwrap();
+
+		let key = endpoint.get_key().unwrap_or_default();
+		if !acl_allows_object_read(&garage, bucket_id, &bucket_params, key, false).await? {
+			return Err(Error::forbidden("Access denied"));
+		}
+
+		let matching_cors_rule = find_matching_cors_rule(&bucket_params, &req)?.cloned();
+
+		let overrides = GetObjectOverrides::from_endpoint(&endpoint);
+		overrides.check_anonymous()?;
+
+		let resp = match endpoint {
+			Endpoint::HeadObject {
+				key, part_number, ..
+			} => {
+				handle_head_without_ctx(garage, &req, bucket_id, &key, part_number, overrides).await
+			}
+			Endpoint::GetObject {
+				key, part_number, ..
+			} => handle_get_without_ctx(garage, &req, bucket_id, &key, part_number, overrides).await,
+			_ => unreachable!(),
+		};
+
+		let mut resp_ok = resp?;
+		if let Some(rule) = matching_cors_rule {
+			add_cors_headers(&mut resp_ok, &rule)
+				.ok_or_internal_error("Invalid bucket CORS configuration")?;
+		}
+
+		Ok(resp_ok)
+	}
 }
 
 #[async_trait]
@@ -108,6 +203,55 @@ impl ApiHandler for S3ApiServer {
 		&self,
 		req: Request<IncomingBody>,
 		endpoint: S3ApiEndpoint,
+	) -> Result<Response<ResBody>, Error> {
+		// CompleteMultipartUpload only deals with metadata, but for uploads with many
+		// parts it can take a long time, so it is never interrupted.
+		let timeout = match endpoint.endpoint {
+			Endpoint::CompleteMultipartUpload { .. } => None,
+			_ => self.request_timeout,
+		};
+
+		let endpoint_name = endpoint.endpoint.name();
+		let log_entry = self.access_log.as_ref().map(|_| {
+			AccessLogEntry::new(
+				endpoint.bucket_name.clone(),
+				endpoint.endpoint.get_key().map(String::from),
+				endpoint_name,
+				content_length_hint(&req),
+			)
+		});
+
+		let mut used_key = None;
+		let mut resolved_bucket = None;
+		let res = with_timeout(
+			timeout,
+			self.handle_inner(req, endpoint, &mut used_key, &mut resolved_bucket),
+		)
+		.await;
+
+		if let (Some(access_log), Some(mut entry)) = (&self.access_log, log_entry) {
+			entry.finish(used_key.as_ref().map(|k| k.key_id.clone()), &res);
+			access_log.log(&entry);
+		}
+
+		let bandwidth_tags = BandwidthMetrics::tags(endpoint_name, resolved_bucket.as_deref());
+		res.map(|resp| {
+			let resp = match &used_key {
+				Some(api_key) => rate_limit_response(&self.garage, api_key, resp),
+				None => resp,
+			};
+			self.bandwidth.count_response(resp, bandwidth_tags)
+		})
+	}
+}
+
+impl S3ApiServer {
+	async fn handle_inner(
+		&self,
+		req: Request<IncomingBody>,
+		endpoint: S3ApiEndpoint,
+		used_key: &mut Option<Key>,
+		resolved_bucket: &mut Option<String>,
 	) -> Result<Response<ResBody>, Error> {
 		let S3ApiEndpoint {
 			bucket_name,
@@ -115,28 +259,64 @@ impl ApiHandler for S3ApiServer {
 		} = endpoint;
 		let garage = self.garage.clone();
 
+		// Data transfers are only labeled with the bucket name once the
+		// bucket has been resolved for an authenticated request, so that
+		// clients cannot create arbitrary metric labels
+		let unlabeled_tags = BandwidthMetrics::tags(endpoint.name(), None);
+
 		// Some endpoints are processed early, before we even check for an API key
 		if let Endpoint::PostObject = endpoint {
-			return handle_post_object(garage, req, bucket_name.unwrap()).await;
+			let req = req.map(|body| {
+				ReqBody::new(http_body_util::BodyExt::map_err(
+					body,
+					crate::signature::error::Error::from,
+				))
+			});
+			let req = self.bandwidth.count_request(req, unlabeled_tags);
+			return handle_post_object(
+				garage,
+				req,
+				bucket_name.unwrap(),
+				&self.trusted_proxies,
+				used_key,
+			)
+			.await;
 		}
 		if let Endpoint::Options = endpoint {
 			let options_res = handle_options_api(garage, &req, bucket_name).await?;
 			return Ok(options_res.map(|_empty_body: EmptyBody| empty_body()));
 		}
 
-		let (req, api_key, content_sha256) = verify_request(&garage, req, "s3").await?;
+		let (req, api_key, content_sha256) =
+			verify_request_allow_anonymous(&garage, req, "s3").await?;
+		let api_key = match api_key {
+			Some(api_key) => api_key,
+			None => {
+				let req = self.bandwidth.count_request(req, unlabeled_tags);
+				return self
+					.handle_anonymous_request(req, bucket_name, endpoint)
+					.await;
+			}
+		};
+		*used_key = Some(api_key.clone());
+
+		check_source_ip(&api_key, request_client_ip(&req, &self.trusted_proxies))?;
+		check_rate_limit(&garage, &api_key)?;
+		let req = rate_limit_request(&garage, &api_key, req);
 
 		let bucket_name = match bucket_name {
 			None => {
+				let req = self.bandwidth.count_request(req, unlabeled_tags);
 				return self
 					.handle_request_without_bucket(req, api_key, endpoint)
-					.await
+					.await;
 			}
 			Some(bucket) => bucket.to_string(),
 		};
 
 		// Special code path for CreateBucket API endpoint
 		if let Endpoint::CreateBucket {} = endpoint {
+			let req = self.bandwidth.count_request(req, unlabeled_tags);
 			return handle_create_bucket(
 				&garage,
 				req,
@@ -158,7 +338,16 @@ impl ApiHandler for S3ApiServer {
 		let bucket_params = bucket.state.into_option().unwrap();
 
 		let allowed = match endpoint.authorization_type() {
-			Authorization::Read => api_key.allow_read(&bucket_id),
+			Authorization::Read => {
+				api_key.allow_read(&bucket_id)
+					|| match endpoint.get_key() {
+						Some(key) if endpoint.is_object_read() => {
+							acl_allows_object_read(&garage, bucket_id, &bucket_params, key, true)
+								.await?
+						}
+						_ => false,
+					}
+			}
 			Authorization::Write => api_key.allow_write(&bucket_id),
 			Authorization::Owner => api_key.allow_owner(&bucket_id),
 			_ => unreachable!(),
@@ -168,6 +357,12 @@ impl ApiHandler for S3ApiServer {
 			return Err(Error::forbidden("Operation is not allowed for this key."));
 		}
 
+		let req = self.bandwidth.count_request(
+			req,
+			BandwidthMetrics::tags(endpoint.name(), Some(&bucket_name)),
+		);
+		*resolved_bucket = Some(bucket_name.clone());
+
 		let matching_cors_rule = find_matching_cors_rule(&bucket_params, &req)?.cloned();
 
 		let ctx = ReqCtx {
@@ -178,30 +373,17 @@ impl ApiHandler for S3ApiServer {
 			api_key,
 		};
 
+		let overrides = GetObjectOverrides::from_endpoint(&endpoint);
+
 		let resp = match endpoint {
 			Endpoint::HeadObject {
 				key, part_number, ..
-			} => handle_head(ctx, &req, &key, part_number).await,
+			} => handle_head(ctx, &req, &key, part_number, overrides).await,
 			Endpoint::GetObject {
-				key,
-				part_number,
-				response_cache_control,
-				response_content_disposition,
-				response_content_encoding,
-				response_content_language,
-				response_content_type,
-				response_expires,
-				..
-			} => {
-				let overrides = GetObjectOverrides {
-					response_cache_control,
-					response_content_disposition,
-					response_content_encoding,
-					response_content_language,
-					response_content_type,
-					response_expires,
-				};
-				handle_get(ctx, &req, &key, part_number, overrides).await
+				key, part_number, ..
+			} => handle_get(ctx, &req, &key, part_number, overrides).await,
+			Endpoint::GetObjectAttributes { key, version_id } => {
+				handle_get_object_attributes(ctx, &req, &key, version_id).await
 			}
 			Endpoint::UploadPart {
 				key,
@@ -218,7 +400,7 @@ impl ApiHandler for S3ApiServer {
 			Endpoint::AbortMultipartUpload { key, upload_id } => {
 				handle_abort_multipart_upload(ctx, &key, &upload_id).await
 			}
-			Endpoint::DeleteObject { key, .. } => handle_delete(ctx, &key).await,
+			Endpoint::DeleteObject { key, .. } => handle_delete(ctx, &req, &key).await,
 			Endpoint::CreateMultipartUpload { key } => {
 				handle_create_multipart_upload(ctx, &req, &key).await
 			}
@@ -232,7 +414,10 @@ impl ApiHandler for S3ApiServer {
 			}
 			Endpoint::DeleteBucket {} => handle_delete_bucket(ctx).await,
 			Endpoint::GetBucketLocation {} => handle_get_bucket_location(ctx),
-			Endpoint::GetBucketVersioning {} => handle_get_bucket_versioning(),
+			Endpoint::GetBucketVersioning {} => handle_get_bucket_versioning(ctx),
+			Endpoint::PutBucketVersioning {} => {
+				handle_put_bucket_versioning(ctx, req, content_sha256).await
+			}
 			Endpoint::ListObjects {
 				delimiter,
 				encoding_type,
@@ -250,12 +435,35 @@ impl ApiHandler for S3ApiServer {
 						urlencode_resp: encoding_type.map(|e| e == "url").unwrap_or(false),
 					},
 					is_v2: false,
+					fetch_owner: true,
 					marker,
 					continuation_token: None,
 					start_after: None,
 				};
 				handle_list(ctx, &query).await
 			}
+			Endpoint::ListObjectVersions {
+				delimiter,
+				encoding_type,
+				key_marker,
+				max_keys,
+				prefix,
+				version_id_marker,
+			} => {
+				let query = ListObjectVersionsQuery {
+					common: ListQueryCommon {
+						bucket_name: ctx.bucket_name.clone(),
+						bucket_id,
+						delimiter,
+						page_size: max_keys.unwrap_or(1000).clamp(1, 1000),
+						prefix: prefix.unwrap_or_default(),
+						urlencode_resp: encoding_type.map(|e| e == "url").unwrap_or(false),
+					},
+					key_marker,
+					version_id_marker,
+				};
+				handle_list_object_versions(ctx, &query).await
+			}
 			Endpoint::ListObjectsV2 {
 				delimiter,
 				encoding_type,
@@ -264,7 +472,7 @@ impl ApiHandler for S3ApiServer {
 				continuation_token,
 				start_after,
 				list_type,
-				..
+				fetch_owner,
 			} => {
 				if list_type == "2" {
 					let query = ListObjectsQuery {
@@ -277,6 +485,7 @@ impl ApiHandler for S3ApiServer {
 							prefix: prefix.unwrap_or_default(),
 						},
 						is_v2: true,
+						fetch_owner: fetch_owner.unwrap_or(false),
 						marker: None,
 						continuation_token,
 						start_after,
@@ -339,6 +548,17 @@ impl ApiHandler for S3ApiServer {
 				handle_put_lifecycle(ctx, req, content_sha256).await
 			}
 			Endpoint::DeleteBucketLifecycle {} => handle_delete_lifecycle(ctx).await,
+			Endpoint::GetObjectLockConfiguration {} => handle_get_object_lock_config(ctx).await,
+			Endpoint::PutObjectLockConfiguration {} => {
+				handle_put_object_lock_config(ctx, req, content_sha256).await
+			}
+			Endpoint::GetBucketTagging {} => handle_get_bucket_tagging(ctx).await,
+			Endpoint::PutBucketTagging {} => {
+				handle_put_bucket_tagging(ctx, req, content_sha256).await
+			}
+			Endpoint::DeleteBucketTagging {} => handle_delete_bucket_tagging(ctx).await,
+			Endpoint::GetBucketAcl {} => handle_get_bucket_acl(ctx).await,
+			Endpoint::PutBucketAcl {} => handle_put_bucket_acl(ctx, req, content_sha256).await,
 			endpoint => Err(Error::NotImplemented(endpoint.name().to_owned())),
 		};
 
@@ -354,6 +574,25 @@ impl ApiHandler for S3ApiServer {
 	}
 }
 
+/// Run the processing of a request with a time limit. When the limit is reached,
+/// the request future is dropped, which cancels all in-flight work and triggers
+/// the cleanup of interrupted uploads.
+async fn with_timeout(
+	timeout: Option<Duration>,
+	fut: impl std::future::Future<Output = Result<Response<ResBody>, Error>>,
+) -> Result<Response<ResBody>, Error> {
+	match timeout {
+		Some(t) => match tokio::time::timeout(t, fut).await {
+			Ok(res) => res,
+			Err(_) => {
+				warn!("S3 request interrupted after timeout of {:?}", t);
+				Err(Error::RequestTimeout)
+			}
+		},
+		None => fut.await,
+	}
+}
+
 impl ApiEndpoint for S3ApiEndpoint {
 	fn name(&self) -> &'static str {
 		self.endpoint.name()
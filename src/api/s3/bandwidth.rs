@@ -0,0 +1,71 @@
+use http_body_util::BodyExt;
+use hyper::{Request, Response};
+use opentelemetry::{global, metrics::Counter, KeyValue};
+
+use crate::helpers::BoxBody;
+
+/// Counters of the amount of data received in request bodies and sent in
+/// response bodies by the S3 API, labeled by API endpoint and bucket
+pub(crate) struct BandwidthMetrics {
+	bytes_received: Counter<u64>,
+	bytes_sent: Counter<u64>,
+}
+
+impl BandwidthMetrics {
+	pub(crate) fn new() -> Self {
+		let meter = global::meter("garage/api");
+		Self {
+			bytes_received: meter
+				.u64_counter("api.s3.bytes_received")
+				.with_description("Number of bytes received in the body of S3 API requests")
+				.init(),
+			bytes_sent: meter
+				.u64_counter("api.s3.bytes_sent")
+				.with_description("Number of bytes sent in the body of S3 API responses")
+				.init(),
+		}
+	}
+
+	/// Labels of the counters for a request to the given endpoint and bucket
+	pub(crate) fn tags(endpoint: &'static str, bucket: Option<&str>) -> Vec<KeyValue> {
+		vec![
+			KeyValue::new("api_endpoint", endpoint),
+			KeyValue::new("bucket", bucket.unwrap_or("").to_string()),
+		]
+	}
+
+	/// Count the bytes of the request body as they are read by the handler
+	pub(crate) fn count_request<E: 'static>(
+		&self,
+		req: Request<BoxBody<E>>,
+		tags: Vec<KeyValue>,
+	) -> Request<BoxBody<E>> {
+		let counter = self.bytes_received.clone();
+		req.map(|body| counting_body(body, counter, tags))
+	}
+
+	/// Count the bytes of the response body as they are handed to the HTTP
+	/// connection, so that responses interrupted by a client disconnection
+	/// only count what was actually sent
+	pub(crate) fn count_response<E: 'static>(
+		&self,
+		resp: Response<BoxBody<E>>,
+		tags: Vec<KeyValue>,
+	) -> Response<BoxBody<E>> {
+		let counter = self.bytes_sent.clone();
+		resp.map(|body| counting_body(body, counter, tags))
+	}
+}
+
+fn counting_body<E: 'static>(
+	body: BoxBody<E>,
+	counter: Counter<u64>,
+	tags: Vec<KeyValue>,
+) -> BoxBody<E> {
+	BoxBody::new(body.map_frame(move |frame| {
+		if let Some(data) = frame.data_ref() {
+			counter.add(data.len() as u64, &tags);
+		}
+		frame
+	}))
+}
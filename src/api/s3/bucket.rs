@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use quick_xml::de::from_reader;
+
+use std::collections::{BTreeMap, HashMap};
 
 use http_body_util::BodyExt;
 use hyper::{Request, Response, StatusCode};
 
 use garage_model::bucket_alias_table::*;
-use garage_model::bucket_table::Bucket;
+use garage_model::bucket_table::{Bucket, VersioningState};
 use garage_model::garage::Garage;
 use garage_model::key_table::Key;
 use garage_model::permission::BucketKeyPerm;
@@ -33,11 +35,12 @@ pub fn handle_get_bucket_location(ctx: ReqCtx) -> Result<Response<ResBody>, Erro
 		.body(string_body(xml))?)
 }
 
-pub fn handle_get_bucket_versioning() -> Result<Response<ResBody>, Error> {
-	let versioning = s3_xml::VersioningConfiguration {
-		xmlns: (),
-		status: None,
+pub fn handle_get_bucket_versioning(ctx: ReqCtx) -> Result<Response<ResBody>, Error> {
+	let status = match *ctx.bucket_params.versioning.get() {
+		VersioningState::Unset => None,
+		VersioningState::Suspended => Some(s3_xml::Value("Suspended".to_string())),
 	};
+	let versioning = s3_xml::VersioningConfiguration { xmlns: (), status };
 
 	let xml = s3_xml::to_xml_with_header(&versioning)?;
 
@@ -46,6 +49,57 @@ pub fn handle_get_bucket_versioning() -> Result<Response<ResBody>, Error> {
 		.body(string_body(xml))?)
 }
 
+pub async fn handle_put_bucket_versioning(
+	ctx: ReqCtx,
+	req: Request<ReqBody>,
+	content_sha256: Option<Hash>,
+) -> Result<Response<ResBody>, Error> {
+	let ReqCtx {
+		garage,
+		bucket_id,
+		mut bucket_params,
+		..
+	} = ctx;
+
+	let body = BodyExt::collect(req.into_body()).await?.to_bytes();
+
+	if let Some(content_sha256) = content_sha256 {
+		verify_signed_content(content_sha256, &body[..])?;
+	}
+
+	let conf: s3_xml::VersioningConfiguration = from_reader(&body as &[u8])?;
+	let state = match conf.status.as_ref().map(|x| x.0.as_str()) {
+		// Garage only keeps the latest version of each object, so it cannot
+		// pretend that versioning is enabled
+		Some("Enabled") => {
+			return Err(Error::NotImplemented(
+				"Object versioning is not supported, only Suspended can be set".into(),
+			))
+		}
+		Some("Suspended") => VersioningState::Suspended,
+		_ => return Err(Error::bad_request("Invalid versioning status")),
+	};
+
+	bucket_params.versioning.update(state);
+	garage
+		.bucket_table
+		.insert(&Bucket::present(bucket_id, bucket_params))
+		.await?;
+
+	Ok(Response::builder()
+		.status(StatusCode::OK)
+		.body(empty_body())?)
+}
+
+/// Version id reported to clients for a newly written object version:
+/// objects written while versioning is suspended get the null version id
+pub(crate) fn reported_version_id(versioning: VersioningState, version_uuid: Uuid) -> String {
+	match versioning {
+		VersioningState::Suspended => "null".to_string(),
+		_ => hex::encode(version_uuid),
+	}
+}
+
 pub async fn handle_list_buckets(
 	garage: &Garage,
 	api_key: &Key,
@@ -67,7 +121,8 @@ pub async fn handle_list_buckets(
 		.collect::<Vec<_>>();
 
 	let mut buckets_by_id = HashMap::new();
-	let mut aliases = HashMap::new();
+	// Buckets are listed in alphabetical order of their names, as in AWS S3
+	let mut aliases = BTreeMap::new();
 
 	for bucket_id in ids.iter() {
 		let bucket = garage.bucket_table.get(&EmptyKey, bucket_id).await?;
@@ -169,6 +224,14 @@ pub async fn handle_create_bucket(
 			return Err(CommonError::BucketAlreadyExists.into());
 		}
 	} else {
+		if let Err(reason) =
+			validate_bucket_name(&bucket_name, garage.config.s3_api.relaxed_bucket_names)
+		{
+			return Err(
+				CommonError::InvalidBucketName(format!("{}: {}", bucket_name, reason)).into(),
+			);
+		}
+
 		// Check user is allowed to create bucket
 		if !key_params.allow_create_bucket.get() {
 			return Err(CommonError::Forbidden(format!(
@@ -179,13 +242,6 @@ pub async fn handle_create_bucket(
 		}
 
 		// Create the bucket!
-		if !is_valid_bucket_name(&bucket_name) {
-			return Err(Error::bad_request(format!(
-				"{}: {}",
-				bucket_name, INVALID_BUCKET_NAME_MESSAGE
-			)));
-		}
-
 		let bucket = Bucket::new();
 		garage.bucket_table.insert(&bucket).await?;
 
@@ -142,7 +142,7 @@ impl Checksums {
 			match self.md5 {
 				Some(md5) if BASE64_STANDARD.encode(&md5) == expected_md5.trim_matches('"') => (),
 				_ => {
-					return Err(Error::InvalidDigest(
+					return Err(Error::BadDigest(
 						"MD5 checksum verification failed (from content-md5)".into(),
 					))
 				}
@@ -152,7 +152,7 @@ impl Checksums {
 			match self.sha256 {
 				Some(sha256) if &sha256[..] == expected_sha256.as_slice() => (),
 				_ => {
-					return Err(Error::InvalidDigest(
+					return Err(Error::XAmzContentSHA256Mismatch(
 						"SHA256 checksum verification failed (from x-amz-content-sha256)".into(),
 					))
 				}
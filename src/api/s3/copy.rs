@@ -21,13 +21,18 @@ use garage_model::s3::object_table::*;
 use garage_model::s3::version_table::*;
 
 use crate::helpers::*;
+use crate::s3::acl::{check_object_acl_allowed, X_AMZ_ACL};
 use crate::s3::api_server::{ReqBody, ResBody};
+use crate::s3::bucket::reported_version_id;
 use crate::s3::checksum::*;
 use crate::s3::encryption::EncryptionParams;
 use crate::s3::error::*;
-use crate::s3::get::full_object_byte_stream;
+use crate::s3::get::{etag_list_matches, full_object_byte_stream};
 use crate::s3::multipart;
-use crate::s3::put::{get_headers, save_stream, ChecksumMode, SaveStreamResult};
+use crate::s3::object_lock::{check_key_not_locked, new_object_retention};
+use crate::s3::put::{
+	get_headers, get_storage_class, next_timestamp, save_stream, ChecksumMode, SaveStreamResult,
+};
 use crate::s3::xml::{self as s3_xml, xmlns_tag};
 
 // -------- CopyObject ---------
@@ -38,11 +43,21 @@ pub async fn handle_copy(
 	dest_key: &str,
 ) -> Result<Response<ResBody>, Error> {
 	let copy_precondition = CopyPreconditionHeaders::parse(req)?;
+	let metadata_directive = CopyDirective::parse(req, "x-amz-metadata-directive")?;
+	// Object tags are not stored by Garage, so there is nothing to copy or replace,
+	// but we still reject invalid values of the tagging directive.
+	let _tagging_directive = CopyDirective::parse(req, "x-amz-tagging-directive")?;
 
 	let checksum_algorithm = request_checksum_algorithm(req.headers())?;
+	// The storage class is not copied from the source object
+	let storage_class = get_storage_class(&ctx.garage, req.headers())?;
 
 	let source_object = get_copy_source(&ctx, req).await?;
 
+	check_key_not_locked(&ctx, dest_key).await?;
+
+	let versioning = *ctx.bucket_params.versioning.get();
+
 	let (source_version, source_version_data, source_version_meta) =
 		extract_source_info(&source_object)?;
 
@@ -70,11 +85,17 @@ pub async fn handle_copy(
 	// Determine metadata of destination object
 	let was_multipart = source_version_meta.etag.contains('-');
 	let dest_object_meta = ObjectVersionMetaInner {
-		headers: match req.headers().get("x-amz-metadata-directive") {
-			Some(v) if v == hyper::header::HeaderValue::from_static("REPLACE") => {
-				get_headers(req.headers())?
+		headers: match metadata_directive {
+			CopyDirective::Replace => {
+				check_object_acl_allowed(&ctx.api_key, &ctx.bucket_id, req.headers())?;
+				get_headers(&ctx.garage, req.headers())?
+			}
+			// As in AWS, the ACL of the source object is not copied
+			CopyDirective::Copy => {
+				let mut headers = source_object_meta_inner.into_owned().headers;
+				headers.retain(|(k, _)| k != X_AMZ_ACL);
+				headers
 			}
-			_ => source_object_meta_inner.into_owned().headers,
 		},
 		checksum: source_checksum,
 	};
@@ -99,6 +120,18 @@ pub async fn handle_copy(
 		|| source_checksum_algorithm != checksum_algorithm
 		|| (was_multipart && checksum_algorithm.is_some());
 
+	// Copying an object onto itself is only allowed if something changes
+	if source_object.bucket_id == ctx.bucket_id
+		&& source_object.key == dest_key
+		&& metadata_directive == CopyDirective::Copy
+		&& storage_class == source_version_meta.storage_class
+		&& !must_recopy
+	{
+		return Err(Error::bad_request(
+			"This copy request is illegal because it is trying to copy an object to itself without changing the object's metadata, storage class, website redirect location or encryption attributes.",
+		));
+	}
+
 	let res = if !must_recopy {
 		// In most cases, we can just copy the metadata and link blocks of the
 		// old object from the new object.
@@ -106,6 +139,7 @@ pub async fn handle_copy(
 			ctx,
 			dest_key,
 			dest_object_meta,
+			storage_class,
 			dest_encryption,
 			source_version,
 			source_version_data,
@@ -129,6 +163,7 @@ pub async fn handle_copy(
 			ctx,
 			dest_key,
 			dest_object_meta,
+			storage_class,
 			dest_encryption,
 			source_version,
 			source_version_data,
@@ -147,7 +182,10 @@ pub async fn handle_copy(
 
 	let mut resp = Response::builder()
 		.header("Content-Type", "application/xml")
-		.header("x-amz-version-id", hex::encode(res.version_uuid))
+		.header(
+			"x-amz-version-id",
+			reported_version_id(versioning, res.version_uuid),
+		)
 		.header(
 			"x-amz-copy-source-version-id",
 			hex::encode(source_version.uuid),
@@ -160,6 +198,7 @@ async fn handle_copy_metaonly(
 	ctx: ReqCtx,
 	dest_key: &str,
 	dest_object_meta: ObjectVersionMetaInner,
+	storage_class: Option<String>,
 	dest_encryption: EncryptionParams,
 	source_version: &ObjectVersion,
 	source_version_data: &ObjectVersionData,
@@ -171,14 +210,23 @@ async fn handle_copy_metaonly(
 		..
 	} = ctx;
 
-	// Generate parameters for copied object
+	// Generate parameters for copied object. The new version must be more
+	// recent than all existing versions of the destination object, which
+	// include the source version itself when updating the metadata of an
+	// object by copying it onto itself.
+	let existing_object = garage
+		.object_table
+		.get(&dest_bucket_id, &dest_key.to_string())
+		.await?;
 	let new_uuid = gen_uuid();
-	let new_timestamp = now_msec();
+	let new_timestamp = next_timestamp(existing_object.as_ref());
 
 	let new_meta = ObjectVersionMeta {
 		encryption: dest_encryption.encrypt_meta(dest_object_meta)?,
 		size: source_version_meta.size,
 		etag: source_version_meta.etag.clone(),
+		storage_class,
+		retention: new_object_retention(&ctx.bucket_params, new_timestamp),
 	};
 
 	let res = SaveStreamResult {
@@ -226,6 +274,7 @@ async fn handle_copy_metaonly(
 					encryption: new_meta.encryption.clone(),
 					checksum_algorithm: None,
 					multipart: false,
+					storage_class: None,
 				},
 			};
 			let tmp_dest_object = Object::new(
@@ -298,6 +347,7 @@ async fn handle_copy_reencrypt(
 	ctx: ReqCtx,
 	dest_key: &str,
 	dest_object_meta: ObjectVersionMetaInner,
+	storage_class: Option<String>,
 	dest_encryption: EncryptionParams,
 	source_version: &ObjectVersion,
 	source_version_data: &ObjectVersionData,
@@ -317,10 +367,12 @@ async fn handle_copy_reencrypt(
 	save_stream(
 		&ctx,
 		dest_object_meta,
+		storage_class,
 		dest_encryption,
 		source_stream.map_err(|e| Error::from(GarageError::from(e))),
 		&dest_key.to_string(),
 		checksum_mode,
+		None,
 	)
 	.await
 }
@@ -372,18 +424,7 @@ pub async fn handle_upload_part_copy(
 
 	// Check source range is valid
 	let source_range = match req.headers().get("x-amz-copy-source-range") {
-		Some(range) => {
-			let range_str = range.to_str()?;
-			let mut ranges = http_range::HttpRange::parse(range_str, source_version_meta.size)
-				.map_err(|e| (e, source_version_meta.size))?;
-			if ranges.len() != 1 {
-				return Err(Error::bad_request(
-					"Invalid x-amz-copy-source-range header: exactly 1 range must be given",
-				));
-			} else {
-				ranges.pop().unwrap()
-			}
-		}
+		Some(range) => parse_copy_source_range(range.to_str()?, source_version_meta.size)?,
 		None => http_range::HttpRange {
 			start: 0,
 			length: source_version_meta.size,
@@ -643,6 +684,29 @@ pub async fn handle_upload_part_copy(
 	Ok(resp.body(string_body(resp_xml))?)
 }
 
+/// Value of the `x-amz-metadata-directive` and `x-amz-tagging-directive` headers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CopyDirective {
+	Copy,
+	Replace,
+}
+
+impl CopyDirective {
+	fn parse(req: &Request<ReqBody>, header: &str) -> Result<Self, Error> {
+		match req.headers().get(header) {
+			None => Ok(Self::Copy),
+			Some(v) => match v.to_str()? {
+				"COPY" => Ok(Self::Copy),
+				"REPLACE" => Ok(Self::Replace),
+				x => Err(Error::bad_request(format!(
+					"Invalid value for {}: {}",
+					header, x
+				))),
+			},
+		}
+	}
+}
+
 async fn get_copy_source(ctx: &ReqCtx, req: &Request<ReqBody>) -> Result<Object, Error> {
 	let ReqCtx {
 		garage, api_key, ..
@@ -702,9 +766,9 @@ fn extract_source_info(
 }
 
 struct CopyPreconditionHeaders {
-	copy_source_if_match: Option<Vec<String>>,
+	copy_source_if_match: Option<String>,
 	copy_source_if_modified_since: Option<SystemTime>,
-	copy_source_if_none_match: Option<Vec<String>>,
+	copy_source_if_none_match: Option<String>,
 	copy_source_if_unmodified_since: Option<SystemTime>,
 }
 
@@ -716,11 +780,7 @@ impl CopyPreconditionHeaders {
 				.get("x-amz-copy-source-if-match")
 				.map(|x| x.to_str())
 				.transpose()?
-				.map(|x| {
-					x.split(',')
-						.map(|m| m.trim().trim_matches('"').to_string())
-						.collect::<Vec<_>>()
-				}),
+				.map(str::to_string),
 			copy_source_if_modified_since: req
 				.headers()
 				.get("x-amz-copy-source-if-modified-since")
@@ -734,11 +794,7 @@ impl CopyPreconditionHeaders {
 				.get("x-amz-copy-source-if-none-match")
 				.map(|x| x.to_str())
 				.transpose()?
-				.map(|x| {
-					x.split(',')
-						.map(|m| m.trim().trim_matches('"').to_string())
-						.collect::<Vec<_>>()
-				}),
+				.map(str::to_string),
 			copy_source_if_unmodified_since: req
 				.headers()
 				.get("x-amz-copy-source-if-unmodified-since")
@@ -766,15 +822,15 @@ impl CopyPreconditionHeaders {
 			// because in the spec it says that if if-match evaluates to
 			// true but if-unmodified-since evaluates to false,
 			// the copy is still done.
-			(Some(im), _, None, None) => im.iter().any(|x| x == etag || x == "*"),
+			(Some(im), _, None, None) => etag_list_matches(im, etag, false),
 			(None, Some(ius), None, None) => v_date <= *ius,
 
 			// If we have both if-none-match and if-modified-since,
 			// then both of the two conditions must evaluate to true
 			(None, None, Some(inm), Some(ims)) => {
-				!inm.iter().any(|x| x == etag || x == "*") && v_date > *ims
+				!etag_list_matches(inm, etag, true) && v_date > *ims
 			}
-			(None, None, Some(inm), None) => !inm.iter().any(|x| x == etag || x == "*"),
+			(None, None, Some(inm), None) => !etag_list_matches(inm, etag, true),
 			(None, None, None, Some(ims)) => v_date > *ims,
 			(None, None, None, None) => true,
 			_ => {
@@ -858,6 +914,39 @@ pub struct CopyPartResult {
 	pub etag: s3_xml::Value,
 }
 
+/// Parse the value of a x-amz-copy-source-range header. Contrary to the Range
+/// header of GetObject, S3 only accepts the `bytes=first-last` form here, and
+/// the range must lie entirely within the source object.
+fn parse_copy_source_range(range: &str, size: u64) -> Result<http_range::HttpRange, Error> {
+	let invalid = || {
+		Error::bad_request(
+			"Invalid x-amz-copy-source-range header: must be of the form bytes=first-last",
+		)
+	};
+
+	let (first, last) = range
+		.strip_prefix("bytes=")
+		.and_then(|r| r.split_once('-'))
+		.ok_or_else(invalid)?;
+	let first = first.trim().parse::<u64>().map_err(|_| invalid())?;
+	let last = last.trim().parse::<u64>().map_err(|_| invalid())?;
+
+	if first > last {
+		return Err(invalid());
+	}
+	if last >= size {
+		return Err(Error::InvalidRange((
+			http_range::HttpRangeParseError::NoOverlap,
+			size,
+		)));
+	}
+
+	Ok(http_range::HttpRange {
+		start: first,
+		length: last - first + 1,
+	})
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -898,4 +987,36 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[test]
+	fn copy_source_range() {
+		let range = |r| parse_copy_source_range(r, 1000).map(|r| (r.start, r.length));
+
+		assert_eq!(range("bytes=0-999").unwrap(), (0, 1000));
+		assert_eq!(range("bytes=100-199").unwrap(), (100, 100));
+		assert_eq!(range("bytes=999-999").unwrap(), (999, 1));
+
+		assert!(matches!(
+			range("bytes=500-1000"),
+			Err(Error::InvalidRange(_))
+		));
+		assert!(matches!(
+			range("bytes=1000-1000"),
+			Err(Error::InvalidRange(_))
+		));
+
+		for invalid in [
+			"bytes=-100",
+			"bytes=100-",
+			"bytes=200-100",
+			"bytes=0-10,20-30",
+			"0-100",
+		] {
+			assert!(
+				matches!(range(invalid), Err(Error::Common(_))),
+				"{}",
+				invalid
+			);
+		}
+	}
 }
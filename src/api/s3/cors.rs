@@ -6,8 +6,7 @@ use http::header::{
 	ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD,
 };
 use hyper::{
-	body::Body, body::Incoming as IncomingBody, header::HeaderName, Method, Request, Response,
-	StatusCode,
+	body::Body, body::Incoming as IncomingBody, header::HeaderName, Request, Response, StatusCode,
 };
 
 use http_body_util::BodyExt;
@@ -41,9 +40,7 @@ pub async fn handle_get_cors(ctx: ReqCtx) -> Result<Response<ResBody>, Error> {
 			.header(http::header::CONTENT_TYPE, "application/xml")
 			.body(string_body(xml))?)
 	} else {
-		Ok(Response::builder()
-			.status(StatusCode::NO_CONTENT)
-			.body(empty_body())?)
+		Err(Error::NoSuchCorsConfiguration)
 	}
 }
 
@@ -292,8 +289,25 @@ pub struct ExposeHeader {
 	pub expose_header: Value,
 }
 
+/// Maximum number of rules in a CORS configuration, as in AWS S3
+const MAX_CORS_RULES: usize = 100;
+
+/// HTTP methods that can be allowed by a CORS rule, as in AWS S3
+const CORS_ALLOWED_METHODS: &[&str] = &["GET", "PUT", "HEAD", "POST", "DELETE"];
+
 impl CorsConfiguration {
 	pub fn validate(&self) -> Result<(), Error> {
+		if self.cors_rules.is_empty() {
+			return Err(Error::bad_request(
+				"CORS configuration must contain at least one rule",
+			));
+		}
+		if self.cors_rules.len() > MAX_CORS_RULES {
+			return Err(Error::bad_request(format!(
+				"CORS configuration cannot contain more than {} rules",
+				MAX_CORS_RULES
+			)));
+		}
 		for r in self.cors_rules.iter() {
 			r.validate()?;
 		}
@@ -311,11 +325,35 @@ impl CorsConfiguration {
 
 impl CorsRule {
 	pub fn validate(&self) -> Result<(), Error> {
+		if self.allowed_methods.is_empty() || self.allowed_origins.is_empty() {
+			return Err(Error::bad_request(
+				"CORSRule must have at least one AllowedOrigin and one AllowedMethod",
+			));
+		}
 		for method in self.allowed_methods.iter() {
-			method
-				.0
-				.parse::<Method>()
-				.ok_or_bad_request("Invalid CORSRule method")?;
+			if !CORS_ALLOWED_METHODS.contains(&method.0.as_str()) {
+				return Err(Error::bad_request(format!(
+					"Found unsupported HTTP method in CORS config. Unsupported method is {}",
+					method.0
+				)));
+			}
+		}
+		for origin in self.allowed_origins.iter() {
+			if origin.0.is_empty()
+				|| origin.0.matches('*').count() > 1
+				|| origin
+					.0
+					.chars()
+					.any(|c| c.is_whitespace() || c.is_control())
+			{
+				return Err(Error::bad_request(format!(
+					"Invalid CORSRule origin: {}",
+					origin.0
+				)));
+			}
+		}
+		if matches!(&self.max_age_seconds, Some(x) if x.0 < 0) {
+			return Err(Error::bad_request("Invalid CORSRule MaxAgeSeconds"));
 		}
 		for header in self
 			.allowed_headers
@@ -436,4 +474,44 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[test]
+	fn test_validate() {
+		let rule = |methods: &[&str], origins: &[&str]| CorsRule {
+			id: None,
+			max_age_seconds: None,
+			allowed_origins: origins.iter().map(|x| (*x).into()).collect(),
+			allowed_methods: methods.iter().map(|x| (*x).into()).collect(),
+			allowed_headers: vec![],
+			expose_headers: vec![],
+		};
+		let conf = |rules: Vec<CorsRule>| CorsConfiguration {
+			xmlns: (),
+			cors_rules: rules,
+		};
+
+		assert!(conf(vec![rule(&["GET", "HEAD"], &["*"])])
+			.validate()
+			.is_ok());
+		assert!(conf(vec![rule(&["PUT"], &["https://*.example.com"])])
+			.validate()
+			.is_ok());
+
+		assert!(conf(vec![]).validate().is_err());
+		assert!(conf(vec![rule(&["PATCH"], &["*"])]).validate().is_err());
+		assert!(conf(vec![rule(&["get"], &["*"])]).validate().is_err());
+		assert!(conf(vec![rule(&[], &["*"])]).validate().is_err());
+		assert!(conf(vec![rule(&["GET"], &[])]).validate().is_err());
+		assert!(conf(vec![rule(&["GET"], &["https://*.*.example.com"])])
+			.validate()
+			.is_err());
+		assert!(conf(vec![rule(&["GET"], &["https://example.com "])])
+			.validate()
+			.is_err());
+
+		let many = (0..=MAX_CORS_RULES)
+			.map(|_| rule(&["GET"], &["*"]))
+			.collect::<Vec<_>>();
+		assert!(conf(many).validate().is_err());
+	}
 }
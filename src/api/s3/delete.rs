@@ -3,24 +3,35 @@ use hyper::{Request, Response, StatusCode};
 
 use garage_util::data::*;
 
+use garage_model::bucket_table::VersioningState;
 use garage_model::s3::object_table::*;
 
 use crate::helpers::*;
 use crate::s3::api_server::{ReqBody, ResBody};
 use crate::s3::error::*;
-use crate::s3::put::next_timestamp;
+use crate::s3::object_lock::{bypass_governance_retention, check_object_not_locked};
+use crate::s3::put::{next_timestamp, IfMatch};
 use crate::s3::xml as s3_xml;
 use crate::signature::verify_signed_content;
 
-async fn handle_delete_internal(ctx: &ReqCtx, key: &str) -> Result<(Uuid, Uuid), Error> {
+async fn handle_delete_internal(
+	ctx: &ReqCtx,
+	key: &str,
+	bypass_governance: bool,
+	if_match: Option<&IfMatch>,
+) -> Result<(Uuid, Uuid), Error> {
 	let ReqCtx {
 		garage, bucket_id, ..
 	} = ctx;
-	let object = garage
-		.object_table
-		.get(bucket_id, &key.to_string())
-		.await?
-		.ok_or(Error::NoSuchKey)?; // No need to delete
+	let object = garage.object_table.get(bucket_id, &key.to_string()).await?;
+
+	if let Some(if_match) = if_match {
+		if_match.check(object.as_ref())?;
+	}
+
+	let object = object.ok_or(Error::NoSuchKey)?; // No need to delete
+
+	check_object_not_locked(ctx, Some(&object), bypass_governance)?;
 
 	let del_timestamp = next_timestamp(Some(&object));
 	let del_uuid = gen_uuid();
@@ -54,14 +65,27 @@ async fn handle_delete_internal(ctx: &ReqCtx, key: &str) -> Result<(Uuid, Uuid),
 	Ok((deleted_version, del_uuid))
 }
 
-pub async fn handle_delete(ctx: ReqCtx, key: &str) -> Result<Response<ResBody>, Error> {
-	match handle_delete_internal(&ctx, key).await {
-		Ok(_) | Err(Error::NoSuchKey) => Ok(Response::builder()
-			.status(StatusCode::NO_CONTENT)
-			.body(empty_body())
-			.unwrap()),
-		Err(e) => Err(e),
+pub async fn handle_delete(
+	ctx: ReqCtx,
+	req: &Request<ReqBody>,
+	key: &str,
+) -> Result<Response<ResBody>, Error> {
+	let bypass_governance = bypass_governance_retention(req.headers());
+	let if_match = IfMatch::from_headers(req.headers())?;
+	let mut resp = Response::builder().status(StatusCode::NO_CONTENT);
+	match handle_delete_internal(&ctx, key, bypass_governance, if_match.as_ref()).await {
+		Ok(_) => {
+			// Deletions are always done by inserting a delete marker, which is
+			// then garbage collected. When versioning is suspended the object
+			// is gone and the null version is reported to the client.
+			if *ctx.bucket_params.versioning.get() == VersioningState::Suspended {
+				resp = resp.header("x-amz-version-id", "null");
+			}
+		}
+		Err(Error::NoSuchKey) => (),
+		Err(e) => return Err(e),
 	}
+	Ok(resp.body(empty_body())?)
 }
 
 pub async fn handle_delete_objects(
@@ -69,6 +93,7 @@ pub async fn handle_delete_objects(
 	req: Request<ReqBody>,
 	content_sha256: Option<Hash>,
 ) -> Result<Response<ResBody>, Error> {
+	let bypass_governance = bypass_governance_retention(req.headers());
 	let body = BodyExt::collect(req.into_body()).await?.to_bytes();
 
 	if let Some(content_sha256) = content_sha256 {
@@ -82,7 +107,7 @@ pub async fn handle_delete_objects(
 	let mut ret_errors = Vec::new();
 
 	for obj in cmd.objects.iter() {
-		match handle_delete_internal(&ctx, &obj.key).await {
+		match handle_delete_internal(&ctx, &obj.key, bypass_governance, None).await {
 			Ok((deleted_version, delete_marker_version)) => {
 				if cmd.quiet {
 					continue;
@@ -250,11 +250,9 @@ impl EncryptionParams {
 					blob.get(..nonce_size)
 						.ok_or_internal_error("invalid encrypted data")?,
 				);
-				let plaintext = cipher
-					.decrypt(nonce, &blob[nonce_size..])
-					.ok_or_bad_request(
-						"Invalid encryption key, could not decrypt object metadata.",
-					)?;
+				let plaintext = cipher.decrypt(nonce, &blob[nonce_size..]).map_err(|_| {
+					Error::forbidden("Invalid encryption key, could not decrypt object metadata.")
+				})?;
 				Ok(Cow::Owned(plaintext))
 			}
 			Self::Plaintext => Ok(Cow::Borrowed(blob)),
@@ -20,7 +20,7 @@ pub enum Error {
 
 	// Category: cannot process
 	/// Authorization Header Malformed
-	#[error(display = "Authorization header malformed, unexpected scope: {}", _0)]
+	#[error(display = "Authorization header malformed, {}", _0)]
 	AuthorizationHeaderMalformed(String),
 
 	/// The object requested don't exists
@@ -31,6 +31,18 @@ pub enum Error {
 	#[error(display = "Upload not found")]
 	NoSuchUpload,
 
+	/// The bucket has no object lock configuration
+	#[error(display = "Object lock configuration does not exist for this bucket")]
+	NoSuchObjectLockConfiguration,
+
+	/// The bucket has no tags
+	#[error(display = "The TagSet does not exist")]
+	NoSuchTagSet,
+
+	/// The bucket has no CORS configuration
+	#[error(display = "The CORS configuration does not exist")]
+	NoSuchCorsConfiguration,
+
 	/// Precondition failed (e.g. x-amz-copy-source-if-match)
 	#[error(display = "At least one of the preconditions you specified did not hold")]
 	PreconditionFailed,
@@ -43,11 +55,36 @@ pub enum Error {
 	#[error(display = "Parts given to CompleteMultipartUpload were not in ascending order")]
 	InvalidPartOrder,
 
-	/// In CompleteMultipartUpload: not enough data
-	/// (here we are more lenient than AWS S3)
+	/// In CompleteMultipartUpload: not enough data, or a part other than the last one
+	/// is smaller than the minimum part size
 	#[error(display = "Proposed upload is smaller than the minimum allowed object size")]
 	EntityTooSmall,
 
+	/// The object or part being uploaded is larger than the maximum allowed size
+	#[error(display = "Your proposed upload exceeds the maximum allowed object size")]
+	EntityTooLarge,
+
+	/// The user metadata (x-amz-meta-* headers) of the object is larger than
+	/// the maximum allowed size
+	#[error(display = "Your metadata headers exceed the maximum allowed metadata size")]
+	MetadataTooLarge,
+
+	/// The storage class requested in the x-amz-storage-class header is not accepted
+	#[error(display = "The storage class you specified is not valid: {}", _0)]
+	InvalidStorageClass(String),
+
+	/// The HTTP method of the request is not supported on the target resource.
+	/// The second field is the value of the `Allow` header sent back to the client.
+	#[error(
+		display = "The specified method is not allowed against this resource: {}",
+		_0
+	)]
+	MethodNotAllowed(String, &'static str),
+
+	/// The request took longer than the configured request timeout
+	#[error(display = "Request took too long to be processed")]
+	RequestTimeout,
+
 	// Category: bad request
 	/// The request contained an invalid UTF-8 sequence in its path or in other parameters
 	#[error(display = "Invalid UTF-8: {}", _0)]
@@ -61,6 +98,10 @@ pub enum Error {
 	#[error(display = "Invalid XML: {}", _0)]
 	InvalidXml(String),
 
+	/// The client sent an invalid tag set
+	#[error(display = "Invalid tag: {}", _0)]
+	InvalidTag(String),
+
 	/// The client sent a range header with invalid value
 	#[error(display = "Invalid HTTP range: {:?}", _0)]
 	InvalidRange(#[error(from)] (http_range::HttpRangeParseError, u64)),
@@ -73,6 +114,14 @@ pub enum Error {
 	#[error(display = "Invalid digest: {}", _0)]
 	InvalidDigest(String),
 
+	/// The Content-MD5 sent by the client does not match the received data
+	#[error(display = "Bad digest: {}", _0)]
+	BadDigest(String),
+
+	/// The x-amz-content-sha256 sent by the client does not match the received data
+	#[error(display = "Content SHA256 mismatch: {}", _0)]
+	XAmzContentSHA256Mismatch(String),
+
 	/// The client sent a request for an action not supported by garage
 	#[error(display = "Unimplemented action: {}", _0)]
 	NotImplemented(String),
@@ -109,6 +158,7 @@ impl From<SignatureError> for Error {
 				Self::AuthorizationHeaderMalformed(c)
 			}
 			SignatureError::InvalidUtf8Str(i) => Self::InvalidUtf8Str(i),
+			SignatureError::BadDigest(m) => Self::BadDigest(m),
 		}
 	}
 }
@@ -125,15 +175,26 @@ impl Error {
 			Error::Common(c) => c.aws_code(),
 			Error::NoSuchKey => "NoSuchKey",
 			Error::NoSuchUpload => "NoSuchUpload",
+			Error::NoSuchObjectLockConfiguration => "ObjectLockConfigurationNotFoundError",
+			Error::NoSuchTagSet => "NoSuchTagSet",
+			Error::NoSuchCorsConfiguration => "NoSuchCORSConfiguration",
 			Error::PreconditionFailed => "PreconditionFailed",
 			Error::InvalidPart => "InvalidPart",
 			Error::InvalidPartOrder => "InvalidPartOrder",
 			Error::EntityTooSmall => "EntityTooSmall",
+			Error::EntityTooLarge => "EntityTooLarge",
+			Error::MetadataTooLarge => "MetadataTooLarge",
+			Error::InvalidStorageClass(_) => "InvalidStorageClass",
+			Error::RequestTimeout => "RequestTimeout",
 			Error::AuthorizationHeaderMalformed(_) => "AuthorizationHeaderMalformed",
 			Error::NotImplemented(_) => "NotImplemented",
+			Error::MethodNotAllowed(..) => "MethodNotAllowed",
 			Error::InvalidXml(_) => "MalformedXML",
+			Error::InvalidTag(_) => "InvalidTag",
 			Error::InvalidRange(_) => "InvalidRange",
 			Error::InvalidDigest(_) => "InvalidDigest",
+			Error::BadDigest(_) => "BadDigest",
+			Error::XAmzContentSHA256Mismatch(_) => "XAmzContentSHA256Mismatch",
 			Error::InvalidUtf8Str(_) | Error::InvalidUtf8String(_) => "InvalidRequest",
 			Error::InvalidEncryptionAlgorithm(_) => "InvalidEncryptionAlgorithmError",
 		}
@@ -145,17 +206,29 @@ impl ApiError for Error {
 	fn http_status_code(&self) -> StatusCode {
 		match self {
 			Error::Common(c) => c.http_status_code(),
-			Error::NoSuchKey | Error::NoSuchUpload => StatusCode::NOT_FOUND,
+			Error::NoSuchKey
+			| Error::NoSuchUpload
+			| Error::NoSuchObjectLockConfiguration
+			| Error::NoSuchTagSet
+			| Error::NoSuchCorsConfiguration => StatusCode::NOT_FOUND,
 			Error::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
 			Error::InvalidRange(_) => StatusCode::RANGE_NOT_SATISFIABLE,
 			Error::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+			Error::MethodNotAllowed(..) => StatusCode::METHOD_NOT_ALLOWED,
+			Error::RequestTimeout => StatusCode::REQUEST_TIMEOUT,
 			Error::AuthorizationHeaderMalformed(_)
 			| Error::InvalidPart
 			| Error::InvalidPartOrder
 			| Error::EntityTooSmall
+			| Error::EntityTooLarge
+			| Error::MetadataTooLarge
+			| Error::InvalidStorageClass(_)
 			| Error::InvalidDigest(_)
+			| Error::BadDigest(_)
+			| Error::XAmzContentSHA256Mismatch(_)
 			| Error::InvalidEncryptionAlgorithm(_)
 			| Error::InvalidXml(_)
+			| Error::InvalidTag(_)
 			| Error::InvalidUtf8Str(_)
 			| Error::InvalidUtf8String(_) => StatusCode::BAD_REQUEST,
 		}
@@ -166,7 +239,6 @@ impl ApiError for Error {
 
 		header_map.append(header::CONTENT_TYPE, "application/xml".parse().unwrap());
 
-		#[allow(clippy::single_match)]
 		match self {
 			Error::InvalidRange((_, len)) => {
 				header_map.append(
@@ -176,6 +248,14 @@ impl ApiError for Error {
 						.expect("header value only contain ascii"),
 				);
 			}
+			Error::MethodNotAllowed(_, allow) => {
+				header_map.append(header::ALLOW, HeaderValue::from_static(*allow));
+			}
+			Error::Common(c) => {
+				if let Some(secs) = c.retry_after() {
+					header_map.append(header::RETRY_AFTER, secs.into());
+				}
+			}
 			_ => (),
 		}
 	}
@@ -200,3 +280,44 @@ impl ApiError for Error {
 		error_body(error_str)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use garage_util::error::Error as GarageError;
+
+	#[test]
+	fn quorum_error_is_slow_down() {
+		let err = Error::from(GarageError::Quorum(
+			2,
+			None,
+			1,
+			3,
+			vec!["timeout".to_string(), "timeout".to_string()],
+		));
+		assert_eq!(err.http_status_code(), StatusCode::SERVICE_UNAVAILABLE);
+		assert_eq!(err.aws_code(), "SlowDown");
+
+		let mut headers = HeaderMap::new();
+		err.add_http_headers(&mut headers);
+		assert_eq!(headers.get(hyper::header::RETRY_AFTER).unwrap(), "1");
+	}
+
+	#[test]
+	fn rate_limited_is_slow_down() {
+		let err = Error::from(CommonError::RateLimited(3));
+		assert_eq!(err.http_status_code(), StatusCode::SERVICE_UNAVAILABLE);
+		assert_eq!(err.aws_code(), "SlowDown");
+
+		let mut headers = HeaderMap::new();
+		err.add_http_headers(&mut headers);
+		assert_eq!(headers.get(hyper::header::RETRY_AFTER).unwrap(), "3");
+	}
+
+	#[test]
+	fn other_internal_error_is_500() {
+		let err = Error::from(GarageError::Message("oops".into()));
+		assert_eq!(err.http_status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+		assert_eq!(err.aws_code(), "InternalError");
+	}
+}
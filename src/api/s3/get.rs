@@ -1,18 +1,20 @@
 //! Function related to GET and HEAD requests
 use std::collections::BTreeMap;
 use std::convert::TryInto;
+use std::future::Future;
 use std::sync::Arc;
 use std::time::{Duration, UNIX_EPOCH};
 
+use base64::prelude::*;
 use bytes::Bytes;
 use futures::future;
 use futures::stream::{self, Stream, StreamExt};
 use http::header::{
-	ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE,
-	CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, EXPIRES, IF_MODIFIED_SINCE, IF_NONE_MATCH,
-	LAST_MODIFIED, RANGE,
+	ACCEPT_ENCODING, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING,
+	CONTENT_LANGUAGE, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, EXPIRES,
+	IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE, VARY,
 };
-use hyper::{body::Body, Request, Response, StatusCode};
+use hyper::{body::Body, header::HeaderValue, HeaderMap, Request, Response, StatusCode};
 use tokio::sync::mpsc;
 
 use garage_net::stream::ByteStream;
@@ -26,12 +28,23 @@ use garage_model::s3::object_table::*;
 use garage_model::s3::version_table::*;
 
 use crate::helpers::*;
+use crate::s3::acl::X_AMZ_ACL;
 use crate::s3::api_server::ResBody;
 use crate::s3::checksum::{add_checksum_response_headers, X_AMZ_CHECKSUM_MODE};
 use crate::s3::encryption::EncryptionParams;
 use crate::s3::error::*;
+use crate::s3::put::{storage_class_of, X_AMZ_STORAGE_CLASS};
+use crate::s3::router::Endpoint;
+use crate::s3::xml as s3_xml;
 
 const X_AMZ_MP_PARTS_COUNT: &str = "x-amz-mp-parts-count";
+const X_AMZ_OBJECT_ATTRIBUTES: &str = "x-amz-object-attributes";
+const X_AMZ_MAX_PARTS: &str = "x-amz-max-parts";
+const X_AMZ_PART_NUMBER_MARKER: &str = "x-amz-part-number-marker";
+
+/// Number of blocks fetched ahead when streaming a full object,
+/// if not set in the configuration
+const DEFAULT_GET_PREFETCH_DEPTH: usize = 2;
 
 #[derive(Default)]
 pub struct GetObjectOverrides {
@@ -43,6 +56,58 @@ pub struct GetObjectOverrides {
 	pub(crate) response_expires: Option<String>,
 }
 
+impl GetObjectOverrides {
+	/// Response header overrides given in the query string of a GetObject
+	/// or HeadObject request (none for other endpoints)
+	pub(crate) fn from_endpoint(endpoint: &Endpoint) -> Self {
+		match endpoint {
+			Endpoint::GetObject {
+				response_cache_control,
+				response_content_disposition,
+				response_content_encoding,
+				response_content_language,
+				response_content_type,
+				response_expires,
+				..
+			}
+			| Endpoint::HeadObject {
+				response_cache_control,
+				response_content_disposition,
+				response_content_encoding,
+				response_content_language,
+				response_content_type,
+				response_expires,
+				..
+			} => Self {
+				response_cache_control: response_cache_control.clone(),
+				response_content_disposition: response_content_disposition.clone(),
+				response_content_encoding: response_content_encoding.clone(),
+				response_content_language: response_content_language.clone(),
+				response_content_type: response_content_type.clone(),
+				response_expires: response_expires.clone(),
+			},
+			_ => Self::default(),
+		}
+	}
+
+	/// Check that no response header is overridden, as this is only allowed
+	/// for signed requests
+	pub(crate) fn check_anonymous(&self) -> Result<(), Error> {
+		let any = self.response_cache_control.is_some()
+			|| self.response_content_disposition.is_some()
+			|| self.response_content_encoding.is_some()
+			|| self.response_content_language.is_some()
+			|| self.response_content_type.is_some()
+			|| self.response_expires.is_some();
+		if any {
+			return Err(Error::bad_request(
+				"Request specific response headers cannot be used for anonymous requests",
+			));
+		}
+		Ok(())
+	}
+}
+
 fn object_headers(
 	version: &ObjectVersion,
 	version_meta: &ObjectVersionMeta,
@@ -62,12 +127,21 @@ fn object_headers(
 	if !version_meta.etag.is_empty() {
 		resp = resp.header(ETAG, format!("\"{}\"", version_meta.etag));
 	}
+	// As in AWS, the storage class is only returned if it is not STANDARD
+	if let Some(storage_class) = &version_meta.storage_class {
+		resp = resp.header(X_AMZ_STORAGE_CLASS, storage_class.to_string());
+	}
 
 	// When metadata is retrieved through the REST API, Amazon S3 combines headers that
 	// have the same name (ignoring case) into a comma-delimited list.
 	// See: https://docs.aws.amazon.com/AmazonS3/latest/userguide/UsingMetadata.html
 	let mut headers_by_name = BTreeMap::new();
 	for (name, value) in meta_inner.headers.iter() {
+		// The canned ACL of the object is stored with its headers,
+		// but it is not part of the object's metadata
+		if name == X_AMZ_ACL {
+			continue;
+		}
 		match headers_by_name.get_mut(name) {
 			None => {
 				headers_by_name.insert(name, vec![value.as_str()]);
@@ -94,12 +168,12 @@ fn object_headers(
 /// Override headers according to specific query parameters, see
 /// section "Overriding response header values through the request" in
 /// https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html
+/// This is used for both GET and HEAD requests. Overrides are only allowed
+/// for signed requests, see `GetObjectOverrides::check_anonymous`.
 fn getobject_override_headers(
 	overrides: GetObjectOverrides,
 	resp: &mut http::response::Builder,
 ) -> Result<(), Error> {
-	// TODO: this only applies for signed requests, so when we support
-	// anonymous access in the future we will have to do a permission check here
 	let overrides = [
 		(CACHE_CONTROL, overrides.response_cache_control),
 		(CONTENT_DISPOSITION, overrides.response_content_disposition),
@@ -117,6 +191,27 @@ fn getobject_override_headers(
 	Ok(())
 }
 
+/// Check if an ETag matches a comma-separated list of entity tags, as given
+/// in If-Match or If-None-Match headers. The `*` wildcard matches any ETag.
+///
+/// As specified by RFC 7232, If-None-Match uses the weak comparison function
+/// (`weak = true`), for which the `W/` prefix of weak entity tags is ignored,
+/// while If-Match uses the strong comparison function, for which weak entity
+/// tags never match.
+pub(crate) fn etag_list_matches(list: &str, etag: &str, weak: bool) -> bool {
+	list.split(',').map(str::trim).any(|tag| {
+		if tag == "*" || tag == "\"*\"" {
+			return true;
+		}
+		let tag = match tag.strip_prefix("W/") {
+			Some(_) if !weak => return false,
+			Some(weak_tag) => weak_tag,
+			None => tag,
+		};
+		tag.trim_matches('"') == etag
+	})
+}
+
 fn try_answer_cached(
 	version: &ObjectVersion,
 	version_meta: &ObjectVersionMeta,
@@ -129,16 +224,14 @@ fn try_answer_cached(
 	// for instance (in case of very fast updates)
 	let cached = if let Some(none_match) = req.headers().get(IF_NONE_MATCH) {
 		let none_match = none_match.to_str().ok()?;
-		let expected = format!("\"{}\"", version_meta.etag);
-		let found = none_match
-			.split(',')
-			.map(str::trim)
-			.any(|etag| etag == expected || etag == "\"*\"");
-		found
+		etag_list_matches(none_match, &version_meta.etag, true)
 	} else if let Some(modified_since) = req.headers().get(IF_MODIFIED_SINCE) {
 		let modified_since = modified_since.to_str().ok()?;
 		let client_date = httpdate::parse_http_date(modified_since).ok()?;
-		let server_date = UNIX_EPOCH + Duration::from_millis(version.timestamp);
+		// HTTP dates only have second precision, whereas version timestamps are
+		// in milliseconds: truncate the timestamp to the second, which is also
+		// what is sent in the Last-Modified header, before comparing.
+		let server_date = UNIX_EPOCH + Duration::from_secs(version.timestamp / 1000);
 		client_date >= server_date
 	} else {
 		false
@@ -156,14 +249,42 @@ fn try_answer_cached(
 	}
 }
 
+/// Request header that clients can set to `read-your-writes` to make sure
+/// that they see all the writes that have been acknowledged to them,
+/// even if the cluster uses a relaxed consistency mode.
+pub const X_GARAGE_CONSISTENCY: &str = "x-garage-consistency";
+
+/// Fetch an object from the object table, with the consistency level
+/// requested by the client
+async fn get_object(
+	garage: &Arc<Garage>,
+	req: &Request<impl Body>,
+	bucket_id: Uuid,
+	key: &str,
+) -> Result<Option<Object>, Error> {
+	let key = key.to_string();
+	match req.headers().get(X_GARAGE_CONSISTENCY) {
+		None => Ok(garage.object_table.get(&bucket_id, &key).await?),
+		Some(v) if v == "read-your-writes" => Ok(garage
+			.object_table
+			.get_read_your_writes(&bucket_id, &key)
+			.await?),
+		Some(v) => Err(Error::bad_request(format!(
+			"Invalid value for {}: {:?}",
+			X_GARAGE_CONSISTENCY, v
+		))),
+	}
+}
+
 /// Handle HEAD request
 pub async fn handle_head(
 	ctx: ReqCtx,
 	req: &Request<impl Body>,
 	key: &str,
 	part_number: Option<u64>,
+	overrides: GetObjectOverrides,
 ) -> Result<Response<ResBody>, Error> {
-	handle_head_without_ctx(ctx.garage, req, ctx.bucket_id, key, part_number).await
+	handle_head_without_ctx(ctx.garage, req, ctx.bucket_id, key, part_number, overrides).await
 }
 
 /// Handle HEAD request for website
@@ -173,10 +294,9 @@ pub async fn handle_head_without_ctx(
 	bucket_id: Uuid,
 	key: &str,
 	part_number: Option<u64>,
+	overrides: GetObjectOverrides,
 ) -> Result<Response<ResBody>, Error> {
-	let object = garage
-		.object_table
-		.get(&bucket_id, &key.to_string())
+	let object = get_object(&garage, req, bucket_id, key)
 		.await?
 		.ok_or(Error::NoSuchKey)?;
 
@@ -195,7 +315,7 @@ pub async fn handle_head_without_ctx(
 	let version_meta = match version_data {
 		ObjectVersionData::Inline(meta, _) => meta,
 		ObjectVersionData::FirstBlock(meta, _) => meta,
-		_ => unreachable!(),
+		ObjectVersionData::DeleteMarker => return Err(Error::NoSuchKey),
 	};
 
 	if let Some(cached) = try_answer_cached(object_version, version_meta, req) {
@@ -207,6 +327,13 @@ pub async fn handle_head_without_ctx(
 
 	let checksum_mode = checksum_mode(&req);
 
+	let range = parse_range_header(req, version_meta.size)?;
+	if part_number.is_some() && range.is_some() {
+		return Err(Error::bad_request(
+			"Cannot specify both partNumber and Range header",
+		));
+	}
+
 	if let Some(pn) = part_number {
 		match version_data {
 			ObjectVersionData::Inline(_, _) => {
@@ -214,7 +341,7 @@ pub async fn handle_head_without_ctx(
 					return Err(Error::InvalidPart);
 				}
 				let bytes_len = version_meta.size;
-				Ok(object_headers(
+				let mut resp_builder = object_headers(
 					object_version,
 					version_meta,
 					&headers,
@@ -222,13 +349,12 @@ pub async fn handle_head_without_ctx(
 					checksum_mode,
 				)
 				.header(CONTENT_LENGTH, format!("{}", bytes_len))
-				.header(
-					CONTENT_RANGE,
-					format!("bytes 0-{}/{}", bytes_len - 1, bytes_len),
-				)
 				.header(X_AMZ_MP_PARTS_COUNT, "1")
-				.status(StatusCode::PARTIAL_CONTENT)
-				.body(empty_body())?)
+				.status(StatusCode::PARTIAL_CONTENT);
+				if let Some(content_range) = part_content_range(0, bytes_len, bytes_len) {
+					resp_builder = resp_builder.header(CONTENT_RANGE, content_range);
+				}
+				Ok(resp_builder.body(empty_body())?)
 			}
 			ObjectVersionData::FirstBlock(_, _) => {
 				let version = garage
@@ -261,9 +387,9 @@ pub async fn handle_head_without_ctx(
 				.status(StatusCode::PARTIAL_CONTENT)
 				.body(empty_body())?)
 			}
-			_ => unreachable!(),
+			ObjectVersionData::DeleteMarker => Err(Error::NoSuchKey),
 		}
-	} else {
+	} else if let Some(range) = range {
 		Ok(object_headers(
 			object_version,
 			version_meta,
@@ -271,9 +397,32 @@ pub async fn handle_head_without_ctx(
 			encryption,
 			checksum_mode,
 		)
-		.header(CONTENT_LENGTH, format!("{}", version_meta.size))
-		.status(StatusCode::OK)
+		.header(CONTENT_LENGTH, format!("{}", range.length))
+		.header(
+			CONTENT_RANGE,
+			format!(
+				"bytes {}-{}/{}",
+				range.start,
+				range.start + range.length - 1,
+				version_meta.size
+			),
+		)
+		.status(StatusCode::PARTIAL_CONTENT)
 		.body(empty_body())?)
+	} else {
+		// As for GET requests, overridden headers are only added to
+		// responses that are not PARTIAL_CONTENT
+		let mut resp_builder = object_headers(
+			object_version,
+			version_meta,
+			&headers,
+			encryption,
+			checksum_mode,
+		)
+		.header(CONTENT_LENGTH, format!("{}", version_meta.size))
+		.status(StatusCode::OK);
+		getobject_override_headers(overrides, &mut resp_builder)?;
+		Ok(resp_builder.body(empty_body())?)
 	}
 }
 
@@ -297,9 +446,7 @@ pub async fn handle_get_without_ctx(
 	part_number: Option<u64>,
 	overrides: GetObjectOverrides,
 ) -> Result<Response<ResBody>, Error> {
-	let object = garage
-		.object_table
-		.get(&bucket_id, &key.to_string())
+	let object = get_object(&garage, req, bucket_id, key)
 		.await?
 		.ok_or(Error::NoSuchKey)?;
 
@@ -328,6 +475,7 @@ pub async fn handle_get_without_ctx(
 		EncryptionParams::check_decrypt(&garage, req.headers(), &last_v_meta.encryption)?;
 
 	let checksum_mode = checksum_mode(&req);
+	let accept_zstd = accepts_zstd(req.headers());
 
 	match (part_number, parse_range_header(req, last_v_meta.size)?) {
 		(Some(_), Some(_)) => Err(Error::bad_request(
@@ -370,6 +518,7 @@ pub async fn handle_get_without_ctx(
 				&headers,
 				overrides,
 				checksum_mode,
+				accept_zstd,
 			)
 			.await
 		}
@@ -385,7 +534,52 @@ async fn handle_get_full(
 	meta_inner: &ObjectVersionMetaInner,
 	overrides: GetObjectOverrides,
 	checksum_mode: ChecksumMode,
+	accept_zstd: bool,
 ) -> Result<Response<ResBody>, Error> {
+	// If enabled, and if blocks are stored compressed, they can be sent as
+	// they are to clients that support it instead of being decompressed.
+	// This is not possible if the object already has its own Content-Encoding.
+	let zstd_negotiated = garage.config.s3_api.zstd_content_encoding
+		&& garage.config.compression_level.is_some()
+		&& matches!(version_data, ObjectVersionData::FirstBlock(..))
+		&& !encryption.is_encrypted()
+		&& overrides.response_content_encoding.is_none()
+		&& !meta_inner
+			.headers
+			.iter()
+			.any(|(name, _)| name.eq_ignore_ascii_case(CONTENT_ENCODING.as_str()));
+
+	if zstd_negotiated && accept_zstd {
+		// Checksums and Content-Length apply to the uncompressed data. The
+		// compressed size is not known in advance, so the response
+		// body is sent with chunked transfer encoding.
+		let mut resp_builder = object_headers(
+			version,
+			version_meta,
+			&meta_inner,
+			encryption,
+			ChecksumMode { enabled: false },
+		)
+		.header(CONTENT_ENCODING, "zstd")
+		.header(VARY, ACCEPT_ENCODING.as_str())
+		.status(StatusCode::OK);
+		getobject_override_headers(overrides, &mut resp_builder)?;
+
+		// The compressed data is not the same byte sequence as the object,
+		// so its ETag is only a weak validator
+		if let Some(headers) = resp_builder.headers_mut() {
+			if headers.contains_key(ETAG) {
+				let etag = HeaderValue::from_str(&format!("W/\"{}\"", version_meta.etag))
+					.ok_or_internal_error("Invalid ETag")?;
+				headers.insert(ETAG, etag);
+			}
+		}
+
+		let stream = full_object_stream(garage, version, version_data, encryption, true);
+
+		return Ok(resp_builder.body(response_body_from_unsized_stream(stream))?);
+	}
+
 	let mut resp_builder = object_headers(
 		version,
 		version_meta,
@@ -395,11 +589,14 @@ async fn handle_get_full(
 	)
 	.header(CONTENT_LENGTH, format!("{}", version_meta.size))
 	.status(StatusCode::OK);
+	if zstd_negotiated {
+		resp_builder = resp_builder.header(VARY, ACCEPT_ENCODING.as_str());
+	}
 	getobject_override_headers(overrides, &mut resp_builder)?;
 
 	let stream = full_object_byte_stream(garage, version, version_data, encryption);
 
-	Ok(resp_builder.body(response_body_from_stream(stream))?)
+	Ok(resp_builder.body(response_body_from_stream(stream, version_meta.size))?)
 }
 
 pub fn full_object_byte_stream(
@@ -407,6 +604,18 @@ pub fn full_object_byte_stream(
 	version: &ObjectVersion,
 	version_data: &ObjectVersionData,
 	encryption: EncryptionParams,
+) -> ByteStream {
+	full_object_stream(garage, version, version_data, encryption, false)
+}
+
+/// Stream the data of an object, as zstd-compressed data if `zstd` is set
+/// (only for objects that are not inline and not encrypted)
+fn full_object_stream(
+	garage: Arc<Garage>,
+	version: &ObjectVersion,
+	version_data: &ObjectVersionData,
+	encryption: EncryptionParams,
+	zstd: bool,
 ) -> ByteStream {
 	match &version_data {
 		ObjectVersionData::DeleteMarker => unreachable!(),
@@ -420,7 +629,15 @@ pub fn full_object_byte_stream(
 			}))
 		}
 		ObjectVersionData::FirstBlock(_, first_block_hash) => {
-			let (tx, rx) = mpsc::channel::<ByteStream>(2);
+			// Block streams are started up to `prefetch_depth` blocks ahead of
+			// the block being sent, so at most about that many blocks are
+			// buffered in memory for this request
+			let prefetch_depth = garage
+				.config
+				.s3_api
+				.get_prefetch_depth
+				.unwrap_or(DEFAULT_GET_PREFETCH_DEPTH);
+			let (tx, rx) = mpsc::channel::<ByteStream>(1);
 
 			let order_stream = OrderTag::stream();
 			let first_block_hash = *first_block_hash;
@@ -433,20 +650,42 @@ pub fn full_object_byte_stream(
 						garage2.version_table.get(&version_uuid, &EmptyKey).await
 					});
 
-					let stream_block_0 = encryption
-						.get_block(&garage, &first_block_hash, Some(order_stream.order(0)))
-						.await?;
+					let stream_block_0 = get_block_stream(
+						&garage,
+						&encryption,
+						zstd,
+						&first_block_hash,
+						Some(order_stream.order(0)),
+					)
+					.await?;
 
 					tx.send(stream_block_0)
 						.await
 						.ok_or_message("channel closed")?;
 
 					let version = version_fut.await.unwrap()?.ok_or(Error::NoSuchKey)?;
-					for (i, (_, vb)) in version.blocks.items().iter().enumerate().skip(1) {
-						let stream_block_i = encryption
-							.get_block(&garage, &vb.hash, Some(order_stream.order(i as u64)))
-							.await?;
-						tx.send(stream_block_i)
+					let block_hashes = version
+						.blocks
+						.items()
+						.iter()
+						.map(|(_, vb)| vb.hash)
+						.collect::<Vec<_>>();
+					let block_futs =
+						block_hashes
+							.into_iter()
+							.enumerate()
+							.skip(1)
+							.map(|(i, hash)| {
+								let (garage, encryption) = (&garage, &encryption);
+								let order = order_stream.order(i as u64);
+								async move {
+									get_block_stream(garage, encryption, zstd, &hash, Some(order))
+										.await
+								}
+							});
+					let mut block_streams = ordered_prefetch(block_futs, prefetch_depth);
+					while let Some(stream_block_i) = block_streams.next().await {
+						tx.send(stream_block_i?)
 							.await
 							.ok_or_message("channel closed")?;
 					}
@@ -467,6 +706,33 @@ pub fn full_object_byte_stream(
 	}
 }
 
+/// Run the futures of `futs` with up to `depth` of them in progress at
+/// the same time, returning their results in the original order
+fn ordered_prefetch<I>(futs: I, depth: usize) -> impl Stream<Item = <I::Item as Future>::Output>
+where
+	I: IntoIterator,
+	I::Item: Future,
+{
+	stream::iter(futs).buffered(std::cmp::max(depth, 1))
+}
+
+async fn get_block_stream(
+	garage: &Garage,
+	encryption: &EncryptionParams,
+	zstd: bool,
+	hash: &Hash,
+	order: Option<OrderTag>,
+) -> Result<ByteStream, garage_util::error::Error> {
+	if zstd {
+		garage
+			.block_manager
+			.rpc_get_block_streaming_zstd(hash, order)
+			.await
+	} else {
+		encryption.get_block(garage, hash, order).await
+	}
+}
+
 async fn handle_get_range(
 	garage: Arc<Garage>,
 	version: &ObjectVersion,
@@ -543,14 +809,14 @@ async fn handle_get_part(
 			}
 			let bytes = encryption.decrypt_blob(&bytes)?;
 			assert_eq!(bytes.len() as u64, version_meta.size);
-			Ok(resp_builder
-				.header(CONTENT_LENGTH, format!("{}", bytes.len()))
-				.header(
-					CONTENT_RANGE,
-					format!("bytes {}-{}/{}", 0, bytes.len() - 1, bytes.len()),
-				)
-				.header(X_AMZ_MP_PARTS_COUNT, "1")
-				.body(bytes_body(bytes.into_owned().into()))?)
+			let len = bytes.len() as u64;
+			let mut resp_builder = resp_builder
+				.header(CONTENT_LENGTH, format!("{}", len))
+				.header(X_AMZ_MP_PARTS_COUNT, "1");
+			if let Some(content_range) = part_content_range(0, len, len) {
+				resp_builder = resp_builder.header(CONTENT_RANGE, content_range);
+			}
+			Ok(resp_builder.body(bytes_body(bytes.into_owned().into()))?)
 		}
 		ObjectVersionData::FirstBlock(_, _) => {
 			let version = garage
@@ -574,30 +840,280 @@ async fn handle_get_part(
 				.header(X_AMZ_MP_PARTS_COUNT, format!("{}", version.n_parts()?))
 				.body(body)?)
 		}
+		ObjectVersionData::DeleteMarker => Err(Error::NoSuchKey),
+	}
+}
+
+/// Handle GetObjectAttributes request
+pub async fn handle_get_object_attributes(
+	ctx: ReqCtx,
+	req: &Request<impl Body>,
+	key: &str,
+	version_id: Option<String>,
+) -> Result<Response<ResBody>, Error> {
+	let ReqCtx {
+		garage, bucket_id, ..
+	} = ctx;
+
+	let attributes = parse_object_attributes(req)?;
+
+	let object = get_object(&garage, req, bucket_id, key)
+		.await?
+		.ok_or(Error::NoSuchKey)?;
+
+	let object_version = object
+		.versions()
+		.iter()
+		.rev()
+		.find(|v| v.is_complete())
+		.ok_or(Error::NoSuchKey)?;
+
+	// Garage only keeps the current version of an object, so a version id
+	// can only designate that one.
+	if let Some(vid) = &version_id {
+		if vid != "null" && *vid != hex::encode(object_version.uuid) {
+			return Err(Error::NoSuchKey);
+		}
+	}
+
+	let version_data = match &object_version.state {
+		ObjectVersionState::Complete(c) => c,
 		_ => unreachable!(),
+	};
+
+	let version_meta = match version_data {
+		ObjectVersionData::Inline(meta, _) => meta,
+		ObjectVersionData::FirstBlock(meta, _) => meta,
+		ObjectVersionData::DeleteMarker => return Err(Error::NoSuchKey),
+	};
+
+	let (_, meta_inner) =
+		EncryptionParams::check_decrypt(&garage, req.headers(), &version_meta.encryption)?;
+
+	let mut result = s3_xml::GetObjectAttributesOutput {
+		xmlns: (),
+		etag: None,
+		checksum: None,
+		object_parts: None,
+		storage_class: None,
+		object_size: None,
+	};
+
+	if attributes.etag {
+		result.etag = Some(s3_xml::Value(version_meta.etag.clone()));
+	}
+	if attributes.checksum {
+		result.checksum = meta_inner.checksum.map(checksum_attribute);
+	}
+	if attributes.storage_class {
+		result.storage_class = Some(s3_xml::Value(storage_class_of(version_meta).to_string()));
+	}
+	if attributes.object_size {
+		result.object_size = Some(s3_xml::IntValue(version_meta.size as i64));
+	}
+
+	// Only multipart objects have an ObjectParts attribute
+	if attributes.object_parts && version_meta.etag.contains('-') {
+		if let ObjectVersionData::FirstBlock(_, _) = version_data {
+			let version = garage
+				.version_table
+				.get(&object_version.uuid, &EmptyKey)
+				.await?
+				.ok_or(Error::NoSuchKey)?;
+			result.object_parts = Some(object_attributes_parts(req, &version)?);
+		}
+	}
+
+	let date = UNIX_EPOCH + Duration::from_millis(object_version.timestamp);
+
+	let xml = s3_xml::to_xml_with_header(&result)?;
+	Ok(Response::builder()
+		.header("Content-Type", "application/xml")
+		.header(LAST_MODIFIED, httpdate::fmt_http_date(date))
+		.header("x-amz-version-id", hex::encode(object_version.uuid))
+		.body(string_body(xml))?)
+}
+
+#[derive(Default)]
+struct ObjectAttributes {
+	etag: bool,
+	checksum: bool,
+	object_parts: bool,
+	storage_class: bool,
+	object_size: bool,
+}
+
+fn parse_object_attributes(req: &Request<impl Body>) -> Result<ObjectAttributes, Error> {
+	let mut attributes = ObjectAttributes::default();
+	for value in req.headers().get_all(X_AMZ_OBJECT_ATTRIBUTES) {
+		for attr in value.to_str()?.split(',').map(str::trim) {
+			match attr {
+				"ETag" => attributes.etag = true,
+				"Checksum" => attributes.checksum = true,
+				"ObjectParts" => attributes.object_parts = true,
+				"StorageClass" => attributes.storage_class = true,
+				"ObjectSize" => attributes.object_size = true,
+				"" => (),
+				_ => {
+					return Err(Error::bad_request(format!(
+						"Invalid object attribute: {}",
+						attr
+					)))
+				}
+			}
+		}
+	}
+	if !(attributes.etag
+		|| attributes.checksum
+		|| attributes.object_parts
+		|| attributes.storage_class
+		|| attributes.object_size)
+	{
+		return Err(Error::bad_request(
+			"Missing or empty x-amz-object-attributes header",
+		));
+	}
+	Ok(attributes)
+}
+
+fn checksum_attribute(checksum: ChecksumValue) -> s3_xml::ObjectAttributesChecksum {
+	let mut ret = s3_xml::ObjectAttributesChecksum::default();
+	match checksum {
+		ChecksumValue::Crc32(x) => {
+			ret.checksum_crc32 = Some(s3_xml::Value(BASE64_STANDARD.encode(&x)))
+		}
+		ChecksumValue::Crc32c(x) => {
+			ret.checksum_crc32c = Some(s3_xml::Value(BASE64_STANDARD.encode(&x)))
+		}
+		ChecksumValue::Sha1(x) => {
+			ret.checksum_sha1 = Some(s3_xml::Value(BASE64_STANDARD.encode(&x)))
+		}
+		ChecksumValue::Sha256(x) => {
+			ret.checksum_sha256 = Some(s3_xml::Value(BASE64_STANDARD.encode(&x)))
+		}
 	}
+	ret
+}
+
+fn object_attributes_parts(
+	req: &Request<impl Body>,
+	version: &Version,
+) -> Result<s3_xml::ObjectAttributesParts, Error> {
+	let max_parts = match req.headers().get(X_AMZ_MAX_PARTS) {
+		Some(v) => v
+			.to_str()?
+			.parse::<u64>()
+			.ok_or_bad_request("Invalid x-amz-max-parts header")?,
+		None => 1000,
+	};
+	let part_number_marker = match req.headers().get(X_AMZ_PART_NUMBER_MARKER) {
+		Some(v) => Some(
+			v.to_str()?
+				.parse::<u64>()
+				.ok_or_bad_request("Invalid x-amz-part-number-marker header")?,
+		),
+		None => None,
+	};
+
+	// Sum the sizes of the blocks of each part
+	let mut all_parts = BTreeMap::<u64, u64>::new();
+	for (bk, bv) in version.blocks.items().iter() {
+		*all_parts.entry(bk.part_number).or_default() += bv.size;
+	}
+	let total_parts_count = all_parts.len();
+
+	let mut parts = all_parts
+		.into_iter()
+		.filter(|(pn, _)| part_number_marker.map(|m| *pn > m).unwrap_or(true))
+		.take((max_parts as usize).saturating_add(1))
+		.collect::<Vec<_>>();
+	let is_truncated = parts.len() > max_parts as usize;
+	parts.truncate(max_parts as usize);
+
+	let next_part_number_marker = match (is_truncated, parts.last()) {
+		(true, Some((pn, _))) => Some(s3_xml::IntValue(*pn as i64)),
+		_ => None,
+	};
+
+	Ok(s3_xml::ObjectAttributesParts {
+		total_parts_count: s3_xml::IntValue(total_parts_count as i64),
+		part_number_marker: part_number_marker.map(|m| s3_xml::IntValue(m as i64)),
+		next_part_number_marker,
+		max_parts: s3_xml::IntValue(max_parts as i64),
+		is_truncated: s3_xml::Value(format!("{}", is_truncated)),
+		parts: parts
+			.into_iter()
+			.map(|(pn, size)| s3_xml::ObjectAttributesPart {
+				part_number: s3_xml::IntValue(pn as i64),
+				size: s3_xml::IntValue(size as i64),
+			})
+			.collect(),
+	})
 }
 
 fn parse_range_header(
 	req: &Request<impl Body>,
 	total_size: u64,
 ) -> Result<Option<http_range::HttpRange>, Error> {
-	let range = match req.headers().get(RANGE) {
-		Some(range) => {
-			let range_str = range.to_str()?;
-			let mut ranges =
-				http_range::HttpRange::parse(range_str, total_size).map_err(|e| (e, total_size))?;
-			if ranges.len() > 1 {
-				// garage does not support multi-range requests yet, so we respond with the entire
-				// object when multiple ranges are requested
-				None
-			} else {
-				ranges.pop()
-			}
+	match req.headers().get(RANGE).map(|r| r.to_str()) {
+		Some(Ok(range_str)) => parse_range(range_str, total_size),
+		// A Range header that is not valid UTF-8 cannot be a valid range
+		Some(Err(_)) => Ok(None),
+		None => Ok(None),
+	}
+}
+
+/// Parse the value of a Range header. As required by RFC 9110, a header that
+/// is syntactically invalid is ignored and the full object is returned, whereas
+/// a valid range that lies entirely outside of the object is an error (416).
+fn parse_range(range_str: &str, total_size: u64) -> Result<Option<http_range::HttpRange>, Error> {
+	let mut ranges = match http_range::HttpRange::parse(range_str, total_size) {
+		Ok(ranges) => ranges,
+		Err(http_range::HttpRangeParseError::InvalidRange) => {
+			debug!("Ignoring invalid Range header: {:?}", range_str);
+			return Ok(None);
 		}
-		None => None,
+		Err(e) => return Err(Error::InvalidRange((e, total_size))),
 	};
-	Ok(range)
+	if ranges.len() > 1 {
+		// garage does not support multi-range requests yet, so we respond with the entire
+		// object when multiple ranges are requested
+		Ok(None)
+	} else {
+		ranges.pop().map(|r| clamp_range(r, total_size)).transpose()
+	}
+}
+
+/// Make sure a parsed range lies within the object. Suffix ranges (`bytes=-N`)
+/// longer than the object cover the whole object, and a range that selects
+/// no bytes at all (e.g. any range on an empty object) cannot be satisfied.
+fn clamp_range(
+	range: http_range::HttpRange,
+	total_size: u64,
+) -> Result<http_range::HttpRange, Error> {
+	let start = std::cmp::min(range.start, total_size);
+	let end = std::cmp::min(range.start.saturating_add(range.length), total_size);
+	if start >= end {
+		return Err(Error::InvalidRange((
+			http_range::HttpRangeParseError::NoOverlap,
+			total_size,
+		)));
+	}
+	Ok(http_range::HttpRange {
+		start,
+		length: end - start,
+	})
+}
+
+/// Content-Range header value for a part spanning `[begin, end)` of an
+/// object of size `size`, or None if the part is empty (the header can't
+/// express an empty range)
+fn part_content_range(begin: u64, end: u64, size: u64) -> Option<String> {
+	if end > begin {
+		Some(format!("bytes {}-{}/{}", begin, end - 1, size))
+	} else {
+		None
+	}
 }
 
 fn calculate_part_bounds(v: &Version, part_number: u64) -> Option<(u64, u64)> {
@@ -616,6 +1132,30 @@ fn calculate_part_bounds(v: &Version, part_number: u64) -> Option<(u64, u64)> {
 	None
 }
 
+/// Whether the client accepts zstd-compressed responses, according to
+/// its Accept-Encoding header
+fn accepts_zstd(headers: &HeaderMap) -> bool {
+	headers
+		.get_all(ACCEPT_ENCODING)
+		.iter()
+		.filter_map(|v| v.to_str().ok())
+		.flat_map(|v| v.split(','))
+		.any(|coding| {
+			let mut params = coding.split(';').map(str::trim);
+			let is_zstd = params
+				.next()
+				.map(|c| c.eq_ignore_ascii_case("zstd"))
+				.unwrap_or(false);
+			let is_refused = params.any(|p| match p.split_once('=') {
+				Some((q, v)) if q.trim().eq_ignore_ascii_case("q") => {
+					v.trim().parse::<f32>().map(|q| q == 0.0).unwrap_or(false)
+				}
+				_ => false,
+			});
+			is_zstd && !is_refused
+		})
+}
+
 struct ChecksumMode {
 	enabled: bool,
 }
@@ -722,15 +1262,28 @@ fn body_from_blocks_range(
 		}
 	});
 
-	response_body_from_block_stream(rx)
+	response_body_from_block_stream(rx, end - begin)
 }
 
-fn response_body_from_block_stream(rx: mpsc::Receiver<ByteStream>) -> ResBody {
+fn response_body_from_block_stream(rx: mpsc::Receiver<ByteStream>, expected_len: u64) -> ResBody {
 	let body_stream = tokio_stream::wrappers::ReceiverStream::new(rx).flatten();
-	response_body_from_stream(body_stream)
+	response_body_from_stream(body_stream, expected_len)
 }
 
-fn response_body_from_stream<S>(stream: S) -> ResBody
+/// Build a response body from a stream of object data. If the stream ends
+/// before `expected_len` bytes were produced (e.g. because the task fetching
+/// the blocks was interrupted), an error is produced at the end of the stream
+/// so that the connection is reset instead of the client receiving a body
+/// that silently looks complete.
+fn response_body_from_stream<S>(stream: S, expected_len: u64) -> ResBody
+where
+	S: Stream<Item = Result<Bytes, std::io::Error>> + Send + Sync + 'static,
+{
+	response_body_from_unsized_stream(check_stream_length(stream, expected_len))
+}
+
+/// Build a response body from a stream whose length is not known in advance
+fn response_body_from_unsized_stream<S>(stream: S) -> ResBody
 where
 	S: Stream<Item = Result<Bytes, std::io::Error>> + Send + Sync + 'static,
 {
@@ -741,6 +1294,34 @@ where
 	ResBody::new(http_body_util::StreamBody::new(body_stream))
 }
 
+fn check_stream_length<S>(
+	stream: S,
+	expected_len: u64,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>>
+where
+	S: Stream<Item = Result<Bytes, std::io::Error>>,
+{
+	let mut len = 0u64;
+	stream
+		.map(Some)
+		.chain(stream::once(future::ready(None)))
+		.filter_map(move |item| {
+			let ret = match item {
+				Some(Ok(bytes)) => {
+					len += bytes.len() as u64;
+					Some(Ok(bytes))
+				}
+				Some(Err(e)) => Some(Err(e)),
+				None if len != expected_len => Some(Err(std_error_from_read_error(format!(
+					"got {} bytes, expected {}",
+					len, expected_len
+				)))),
+				None => None,
+			};
+			future::ready(ret)
+		})
+}
+
 fn error_stream_item<E: std::fmt::Display>(e: E) -> ByteStream {
 	Box::pin(stream::once(future::ready(Err(std_error_from_read_error(
 		e,
@@ -753,3 +1334,167 @@ fn std_error_from_read_error<E: std::fmt::Display>(e: E) -> std::io::Error {
 		format!("Error while reading object data: {}", e),
 	)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn range(s: &str, total_size: u64) -> Result<(u64, u64), Error> {
+		let r = parse_range(s, total_size)?.unwrap();
+		Ok((r.start, r.start + r.length))
+	}
+
+	#[test]
+	fn test_etag_list_matches() {
+		let etag = "b10a8db164e0754105b7a99be72e3fe5";
+
+		for weak in [true, false] {
+			assert!(etag_list_matches(&format!("\"{}\"", etag), etag, weak));
+			assert!(etag_list_matches(etag, etag, weak));
+			assert!(etag_list_matches("*", etag, weak));
+			assert!(etag_list_matches(
+				&format!("\"abc\", \"{}\" ,\"def\"", etag),
+				etag,
+				weak
+			));
+			assert!(!etag_list_matches("\"abc\", \"def\"", etag, weak));
+			assert!(!etag_list_matches("", etag, weak));
+		}
+
+		// Weak entity tags only match with the weak comparison function
+		let weak_tag = format!("W/\"{}\"", etag);
+		assert!(etag_list_matches(&weak_tag, etag, true));
+		assert!(!etag_list_matches(&weak_tag, etag, false));
+		let list = format!("\"abc\", W/\"def\", {}", weak_tag);
+		assert!(etag_list_matches(&list, etag, true));
+		assert!(!etag_list_matches(&list, etag, false));
+		let list = format!("{}, \"{}\"", weak_tag, etag);
+		assert!(etag_list_matches(&list, etag, false));
+	}
+
+	#[test]
+	fn test_suffix_range() {
+		assert_eq!(range("bytes=-100", 1000).unwrap(), (900, 1000));
+		assert_eq!(range("bytes=100-", 1000).unwrap(), (100, 1000));
+		assert_eq!(range("bytes=-1000", 1000).unwrap(), (0, 1000));
+		assert_eq!(range("bytes=-5000", 1000).unwrap(), (0, 1000));
+		assert_eq!(range("bytes=0-5000", 1000).unwrap(), (0, 1000));
+		assert_eq!(range("bytes=999-", 1000).unwrap(), (999, 1000));
+	}
+
+	#[tokio::test]
+	async fn test_ordered_prefetch() {
+		// Later blocks are faster to fetch than earlier ones
+		let fetch = |i: u64| async move {
+			tokio::time::sleep(Duration::from_millis(10 * (10 - i))).await;
+			i
+		};
+
+		let start = std::time::Instant::now();
+		let sequential = ordered_prefetch((0..10).map(fetch), 1)
+			.collect::<Vec<_>>()
+			.await;
+		let sequential_time = start.elapsed();
+
+		let start = std::time::Instant::now();
+		let prefetched = ordered_prefetch((0..10).map(fetch), 4)
+			.collect::<Vec<_>>()
+			.await;
+		let prefetched_time = start.elapsed();
+
+		assert_eq!(sequential, (0..10).collect::<Vec<_>>());
+		assert_eq!(prefetched, sequential);
+		assert!(
+			prefetched_time < sequential_time,
+			"{:?} >= {:?}",
+			prefetched_time,
+			sequential_time
+		);
+
+		// A depth of zero is treated as no prefetching
+		let unbuffered = ordered_prefetch((0..3).map(fetch), 0)
+			.collect::<Vec<_>>()
+			.await;
+		assert_eq!(unbuffered, vec![0, 1, 2]);
+	}
+
+	#[test]
+	fn test_part_content_range() {
+		assert_eq!(
+			part_content_range(0, 100, 100).as_deref(),
+			Some("bytes 0-99/100")
+		);
+		assert_eq!(
+			part_content_range(100, 150, 300).as_deref(),
+			Some("bytes 100-149/300")
+		);
+		assert_eq!(part_content_range(0, 0, 0), None);
+	}
+
+	#[test]
+	fn test_accepts_zstd() {
+		let accepts = |values: &[&str]| {
+			let mut headers = HeaderMap::new();
+			for v in values {
+				headers.append(ACCEPT_ENCODING, v.parse().unwrap());
+			}
+			accepts_zstd(&headers)
+		};
+
+		assert!(!accepts(&[]));
+		assert!(!accepts(&["gzip, br"]));
+		assert!(accepts(&["zstd"]));
+		assert!(accepts(&["gzip, ZSTD;q=0.5"]));
+		assert!(accepts(&["gzip", "br, zstd"]));
+		assert!(!accepts(&["zstd;q=0"]));
+		assert!(!accepts(&["zstd;q=0.0, gzip"]));
+		assert!(!accepts(&["zstdx"]));
+	}
+
+	#[tokio::test]
+	async fn test_check_stream_length() {
+		let chunks = |n: usize| {
+			stream::iter(
+				(0..n)
+					.map(|_| Ok(Bytes::from_static(b"0123456789")))
+					.collect::<Vec<_>>(),
+			)
+		};
+
+		let ok = check_stream_length(chunks(3), 30).collect::<Vec<_>>().await;
+		assert_eq!(ok.len(), 3);
+		assert!(ok.iter().all(|x| x.is_ok()));
+
+		let truncated = check_stream_length(chunks(2), 30).collect::<Vec<_>>().await;
+		assert_eq!(truncated.len(), 3);
+		assert!(truncated[..2].iter().all(|x| x.is_ok()));
+		assert!(truncated[2].is_err());
+	}
+
+	#[test]
+	fn test_unsatisfiable_range() {
+		assert!(matches!(
+			range("bytes=1000-", 1000),
+			Err(Error::InvalidRange(_))
+		));
+		assert!(matches!(range("bytes=-10", 0), Err(Error::InvalidRange(_))));
+		assert!(matches!(
+			range("bytes=5000-6000", 1000),
+			Err(Error::InvalidRange(_))
+		));
+	}
+
+	#[test]
+	fn test_invalid_range_ignored() {
+		for s in [
+			"bytes=abc",
+			"bytes=10-5",
+			"bytes=--5",
+			"items=0-10",
+			"bytes 0-10",
+			"",
+		] {
+			assert!(matches!(parse_range(s, 1000), Ok(None)), "{:?}", s);
+		}
+	}
+}
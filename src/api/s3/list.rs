@@ -16,9 +16,11 @@ use garage_table::EnumerationOrder;
 use crate::encoding::*;
 use crate::helpers::*;
 use crate::s3::api_server::{ReqBody, ResBody};
+use crate::s3::bucket::reported_version_id;
 use crate::s3::encryption::EncryptionParams;
 use crate::s3::error::*;
 use crate::s3::multipart as s3_multipart;
+use crate::s3::put::{storage_class_of, DEFAULT_STORAGE_CLASS};
 use crate::s3::xml as s3_xml;
 
 const DUMMY_NAME: &str = "Dummy Key";
@@ -37,12 +39,20 @@ pub struct ListQueryCommon {
 #[derive(Debug)]
 pub struct ListObjectsQuery {
 	pub is_v2: bool,
+	pub fetch_owner: bool,
 	pub marker: Option<String>,
 	pub continuation_token: Option<String>,
 	pub start_after: Option<String>,
 	pub common: ListQueryCommon,
 }
 
+#[derive(Debug)]
+pub struct ListObjectVersionsQuery {
+	pub key_marker: Option<String>,
+	pub version_id_marker: Option<String>,
+	pub common: ListQueryCommon,
+}
+
 #[derive(Debug)]
 pub struct ListMultipartUploadsQuery {
 	pub key_marker: Option<String>,
@@ -83,6 +93,22 @@ pub async fn handle_list(
 	let mut acc = query.build_accumulator();
 	let pagination = fetch_list_entries(&query.common, query.begin()?, &mut acc, &io).await?;
 
+	// ListObjects (v1) always returns object owners, ListObjectsV2 only if
+	// requested with fetch-owner=true. Objects are owned by the bucket owner,
+	// which we represent by the key making the request.
+	let owner = match query.is_v2 && !query.fetch_owner {
+		true => None,
+		false => {
+			let key_p = ctx.api_key.params().ok_or_internal_error(
+				"Key should not be in deleted state at this point (in handle_list)",
+			)?;
+			Some(s3_xml::Owner {
+				display_name: s3_xml::Value(key_p.name.get().to_string()),
+				id: s3_xml::Value(ctx.api_key.key_id.to_string()),
+			})
+		}
+	};
+
 	let result = s3_xml::ListBucketResult {
 		xmlns: (),
 		// Sending back request information
@@ -148,7 +174,8 @@ pub async fn handle_list(
 				last_modified: s3_xml::Value(msec_to_rfc3339(info.last_modified)),
 				size: s3_xml::IntValue(info.size as i64),
 				etag: s3_xml::Value(format!("\"{}\"", info.etag)),
-				storage_class: s3_xml::Value("STANDARD".to_string()),
+				storage_class: s3_xml::Value(info.storage_class.clone()),
+				owner: owner.clone(),
 			})
 			.collect(),
 		common_prefixes: acc
@@ -166,6 +193,215 @@ pub async fn handle_list(
 		.body(string_body(xml))?)
 }
 
+pub async fn handle_list_object_versions(
+	ctx: ReqCtx,
+	query: &ListObjectVersionsQuery,
+) -> Result<Response<ResBody>, Error> {
+	let ReqCtx {
+		garage, bucket_id, ..
+	} = &ctx;
+
+	debug!("ListObjectVersions {:?}", query);
+
+	// Version ids are reported as in other endpoints, i.e. as "null"
+	// when versioning is suspended, so markers are compared as strings
+	let versioning = *ctx.bucket_params.versioning.get();
+	let version_id_marker = match (&query.key_marker, &query.version_id_marker) {
+		(_, None) => None,
+		(Some(_), Some(v)) => {
+			if v != "null" {
+				hex::decode(v)
+					.ok()
+					.and_then(|b| Uuid::try_from(&b))
+					.ok_or_bad_request("Invalid version-id-marker")?;
+			}
+			Some(v.as_str())
+		}
+		(None, Some(_)) => {
+			return Err(Error::bad_request(
+				"version-id-marker cannot be specified without key-marker",
+			))
+		}
+	};
+
+	let prefix = &query.common.prefix;
+	let mut cursor = match &query.key_marker {
+		Some(km) if km > prefix => km.clone(),
+		_ => prefix.clone(),
+	};
+	let mut last_processed: Option<String> = None;
+
+	let mut versions = vec![];
+	let mut delete_markers = vec![];
+	let mut common_prefixes = BTreeSet::new();
+	let mut count = 0;
+	// Key and version id of the last entry added to the result,
+	// used as markers for the next page if the result is truncated
+	let mut last_entry: Option<(String, Option<String>)> = None;
+	let mut truncated = false;
+
+	'outer: loop {
+		let batch_size = query.common.page_size + 2;
+		let objects = garage
+			.object_table
+			.get_range(
+				bucket_id,
+				Some(cursor.clone()),
+				None,
+				batch_size,
+				EnumerationOrder::Forward,
+			)
+			.await?;
+		let n_objects = objects.len();
+
+		for object in objects.iter() {
+			if last_processed.as_ref() == Some(&object.key) {
+				continue;
+			}
+			if !object.key.starts_with(prefix.as_str()) {
+				break 'outer;
+			}
+			last_processed = Some(object.key.clone());
+
+			let at_key_marker = query.key_marker.as_ref() == Some(&object.key);
+			if at_key_marker && version_id_marker.is_none() {
+				continue;
+			}
+
+			if let Some(pfx) = common_prefix(object, &query.common) {
+				if query.key_marker.as_deref() == Some(pfx) || common_prefixes.contains(pfx) {
+					continue;
+				}
+				if count >= query.common.page_size {
+					truncated = true;
+					break 'outer;
+				}
+				common_prefixes.insert(pfx.to_string());
+				count += 1;
+				last_entry = Some((pfx.to_string(), None));
+				continue;
+			}
+
+			// Complete versions of the object, most recent first
+			let mut object_versions = object
+				.versions()
+				.iter()
+				.rev()
+				.filter(|v| v.is_complete())
+				.enumerate();
+			if let (true, Some(marker)) = (at_key_marker, version_id_marker) {
+				let is_marker =
+					|v: &ObjectVersion| reported_version_id(versioning, v.uuid) == marker;
+				if !object_versions.clone().any(|(_, v)| is_marker(v)) {
+					// Marker version no longer exists, consider that
+					// this key has been fully listed already
+					continue;
+				}
+				for (_, v) in object_versions.by_ref() {
+					if is_marker(v) {
+						break;
+					}
+				}
+			}
+
+			for (i, version) in object_versions {
+				if count >= query.common.page_size {
+					truncated = true;
+					break 'outer;
+				}
+				let key = uriencode_maybe(&object.key, query.common.urlencode_resp);
+				let version_id = reported_version_id(versioning, version.uuid);
+				let is_latest = s3_xml::Value(format!("{}", i == 0));
+				let last_modified = s3_xml::Value(msec_to_rfc3339(version.timestamp));
+				match &version.state {
+					ObjectVersionState::Complete(ObjectVersionData::DeleteMarker) => {
+						delete_markers.push(s3_xml::ListDeleteMarkerItem {
+							key,
+							version_id: s3_xml::Value(version_id.clone()),
+							is_latest,
+							last_modified,
+						});
+					}
+					ObjectVersionState::Complete(ObjectVersionData::Inline(meta, _))
+					| ObjectVersionState::Complete(ObjectVersionData::FirstBlock(meta, _)) => {
+						versions.push(s3_xml::ListVersionsItem {
+							key,
+							version_id: s3_xml::Value(version_id.clone()),
+							is_latest,
+							last_modified,
+							etag: s3_xml::Value(format!("\"{}\"", meta.etag)),
+							size: s3_xml::IntValue(meta.size as i64),
+							storage_class: s3_xml::Value(storage_class_of(meta).to_string()),
+						});
+					}
+					_ => {
+						return Err(GarageError::Message(format!(
+							"Version {:?} of object {} is not complete",
+							version.uuid, object.key
+						))
+						.into())
+					}
+				}
+				count += 1;
+				last_entry = Some((object.key.clone(), Some(version_id)));
+			}
+		}
+
+		match &last_processed {
+			Some(k) if n_objects >= batch_size => cursor = k.clone(),
+			_ => break,
+		}
+	}
+
+	let (next_key_marker, next_version_id_marker) = match (truncated, last_entry) {
+		(true, Some((key, version))) => (
+			Some(uriencode_maybe(&key, query.common.urlencode_resp)),
+			version.map(s3_xml::Value),
+		),
+		_ => (None, None),
+	};
+
+	let result = s3_xml::ListVersionsResult {
+		xmlns: (),
+		name: s3_xml::Value(query.common.bucket_name.to_string()),
+		prefix: uriencode_maybe(prefix, query.common.urlencode_resp),
+		key_marker: query
+			.key_marker
+			.as_ref()
+			.map(|k| uriencode_maybe(k, query.common.urlencode_resp)),
+		version_id_marker: query
+			.version_id_marker
+			.as_ref()
+			.map(|v| s3_xml::Value(v.to_string())),
+		next_key_marker,
+		next_version_id_marker,
+		max_keys: s3_xml::IntValue(query.common.page_size as i64),
+		delimiter: query
+			.common
+			.delimiter
+			.as_ref()
+			.map(|x| uriencode_maybe(x, query.common.urlencode_resp)),
+		encoding_type: match query.common.urlencode_resp {
+			true => Some(s3_xml::Value("url".to_string())),
+			false => None,
+		},
+		is_truncated: s3_xml::Value(format!("{}", truncated)),
+		versions,
+		delete_markers,
+		common_prefixes: common_prefixes
+			.iter()
+			.map(|pfx| s3_xml::CommonPrefix {
+				prefix: uriencode_maybe(pfx, query.common.urlencode_resp),
+			})
+			.collect(),
+	};
+
+	let xml = s3_xml::to_xml_with_header(&result)?;
+	Ok(Response::builder()
+		.header("Content-Type", "application/xml")
+		.body(string_body(xml))?)
+}
+
 pub async fn handle_list_multipart_upload(
 	ctx: ReqCtx,
 	query: &ListMultipartUploadsQuery,
@@ -243,7 +479,12 @@ pub async fn handle_list_multipart_upload(
 				initiated: s3_xml::Value(msec_to_rfc3339(info.timestamp)),
 				key: uriencode_maybe(&info.key, query.common.urlencode_resp),
 				upload_id: s3_xml::Value(hex::encode(uuid)),
-				storage_class: s3_xml::Value("STANDARD".to_string()),
+				storage_class: s3_xml::Value(
+					info.storage_class
+						.as_deref()
+						.unwrap_or(DEFAULT_STORAGE_CLASS)
+						.to_string(),
+				),
 				initiator: s3_xml::Initiator {
 					display_name: s3_xml::Value(DUMMY_NAME.to_string()),
 					id: s3_xml::Value(DUMMY_KEY.to_string()),
@@ -281,8 +522,12 @@ pub async fn handle_list_parts(
 
 	let (_, object_version, mpu) = s3_multipart::get_upload(&ctx, &query.key, &upload_id).await?;
 
-	let object_encryption = match object_version.state {
-		ObjectVersionState::Uploading { encryption, .. } => encryption,
+	let (object_encryption, storage_class) = match object_version.state {
+		ObjectVersionState::Uploading {
+			encryption,
+			storage_class,
+			..
+		} => (encryption, storage_class),
 		_ => unreachable!(),
 	};
 	let encryption_res =
@@ -351,7 +596,12 @@ pub async fn handle_list_parts(
 			display_name: s3_xml::Value(DUMMY_NAME.to_string()),
 			id: s3_xml::Value(DUMMY_KEY.to_string()),
 		},
-		storage_class: s3_xml::Value("STANDARD".to_string()),
+		storage_class: s3_xml::Value(
+			storage_class
+				.as_deref()
+				.unwrap_or(DEFAULT_STORAGE_CLASS)
+				.to_string(),
+		),
 	};
 
 	let xml = s3_xml::to_xml_with_header(&result)?;
@@ -370,12 +620,14 @@ struct ObjectInfo {
 	last_modified: u64,
 	size: u64,
 	etag: String,
+	storage_class: String,
 }
 
 #[derive(Debug, PartialEq)]
 struct UploadInfo {
 	key: String,
 	timestamp: u64,
+	storage_class: Option<String>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -601,9 +853,10 @@ impl ListObjectsQuery {
 
 				// StartAfter has defined semantics in the spec:
 				// start listing at the first key immediately after.
-				(_, Some(key)) => Ok(RangeBegin::AfterKey {
-					key: key.to_string(),
-				}),
+				// It is only used for the first page: subsequent pages
+				// are requested with a continuation token, which takes
+				// precedence over it.
+				(_, Some(key)) => Ok(self.begin_after(key)),
 
 				// In the case where neither is specified, we start
 				// listing at the specified prefix. If an object has this
@@ -617,9 +870,7 @@ impl ListObjectsQuery {
 			match &self.marker {
 				// In V1 mode, the spec defines the Marker value to mean
 				// the same thing as the StartAfter value in V2 mode.
-				Some(key) => Ok(RangeBegin::AfterKey {
-					key: key.to_string(),
-				}),
+				Some(key) => Ok(self.begin_after(key)),
 				_ => Ok(RangeBegin::IncludingKey {
 					key: self.common.prefix.to_string(),
 					fallback_key: None,
@@ -627,6 +878,22 @@ impl ListObjectsQuery {
 			}
 		}
 	}
+
+	/// Start listing after the given key. A key that sorts before the
+	/// prefix does not skip any key of the prefix, so listing then starts
+	/// at the prefix, as the listing stops at the first key that is not in it.
+	fn begin_after(&self, key: &str) -> RangeBegin {
+		if key < self.common.prefix.as_str() {
+			RangeBegin::IncludingKey {
+				key: self.common.prefix.to_string(),
+				fallback_key: None,
+			}
+		} else {
+			RangeBegin::AfterKey {
+				key: key.to_string(),
+			}
+		}
+	}
 }
 
 impl ListMultipartUploadsQuery {
@@ -811,6 +1078,7 @@ impl ExtractAccumulator for ObjectAccumulator {
 			last_modified: version.timestamp,
 			size: meta.size,
 			etag: meta.etag.to_string(),
+			storage_class: storage_class_of(meta).to_string(),
 		};
 
 		match self.try_insert_entry(object.key.clone(), info) {
@@ -822,6 +1090,13 @@ impl ExtractAccumulator for ObjectAccumulator {
 	}
 }
 
+fn upload_storage_class(upload: &ObjectVersion) -> Option<String> {
+	match &upload.state {
+		ObjectVersionState::Uploading { storage_class, .. } => storage_class.clone(),
+		_ => None,
+	}
+}
+
 impl ExtractAccumulator for UploadAccumulator {
 	/// Observe the iterator, process a single key, and try to extract one or more upload entries
 	///
@@ -882,6 +1157,7 @@ impl ExtractAccumulator for UploadAccumulator {
 		let first_up_info = UploadInfo {
 			key: object.key.to_string(),
 			timestamp: first_upload.timestamp,
+			storage_class: upload_storage_class(first_upload),
 		};
 		if !self.try_insert_entry(first_upload.uuid, first_up_info) {
 			return ExtractionResult::Filled;
@@ -893,6 +1169,7 @@ impl ExtractAccumulator for UploadAccumulator {
 			let up_info = UploadInfo {
 				key: object.key.to_string(),
 				timestamp: upload.timestamp,
+				storage_class: upload_storage_class(upload),
 			};
 
 			// Insert data in our accumulator
@@ -991,6 +1268,7 @@ mod tests {
 					},
 				},
 				checksum_algorithm: None,
+				storage_class: None,
 			},
 		}
 	}
@@ -1010,6 +1288,27 @@ mod tests {
 		assert_eq!(common_prefix(objs.get(0).unwrap(), &query.common), None);
 	}
 
+	#[test]
+	fn test_common_prefixes_multichar_delimiter() {
+		let mut query = query();
+		let obj = Object::new(
+			bucket(),
+			"photos->2023->img.jpg".to_string(),
+			vec![objup_version([0x01; 32])],
+		);
+
+		query.common.delimiter = Some("->".to_string());
+		assert_eq!(common_prefix(&obj, &query.common), Some("photos->"));
+
+		query.common.prefix = "photos->".to_string();
+		assert_eq!(common_prefix(&obj, &query.common), Some("photos->2023->"));
+
+		// A single character of the delimiter is not enough
+		query.common.prefix = "".to_string();
+		query.common.delimiter = Some("-/".to_string());
+		assert_eq!(common_prefix(&obj, &query.common), None);
+	}
+
 	#[test]
 	fn test_extract_common_prefix() {
 		let mut query = query();
@@ -1064,14 +1363,16 @@ mod tests {
 			acc.keys.get(&Uuid::from([0x80; 32])).unwrap(),
 			&UploadInfo {
 				timestamp: TS,
-				key: "b".to_string()
+				key: "b".to_string(),
+				storage_class: None,
 			}
 		);
 		assert_eq!(
 			acc.keys.get(&Uuid::from([0x8f; 32])).unwrap(),
 			&UploadInfo {
 				timestamp: TS,
-				key: "b".to_string()
+				key: "b".to_string(),
+				storage_class: None,
 			}
 		);
 
@@ -1350,4 +1651,58 @@ mod tests {
 
 		Ok(())
 	}
+
+	fn list_objects_v2_query(
+		prefix: &str,
+		continuation_token: Option<&str>,
+		start_after: Option<&str>,
+	) -> ListObjectsQuery {
+		ListObjectsQuery {
+			is_v2: true,
+			fetch_owner: false,
+			marker: None,
+			continuation_token: continuation_token.map(String::from),
+			start_after: start_after.map(String::from),
+			common: ListQueryCommon {
+				prefix: prefix.to_string(),
+				..query().common
+			},
+		}
+	}
+
+	#[test]
+	fn test_list_objects_v2_begin() -> Result<(), Error> {
+		let after = |key: &str| RangeBegin::AfterKey {
+			key: key.to_string(),
+		};
+		let token = format!("]{}", BASE64_STANDARD.encode(b"a/c"));
+
+		// StartAfter is used on the first page
+		let q = list_objects_v2_query("", None, Some("a/b"));
+		assert_eq!(q.begin()?, after("a/b"));
+
+		// The continuation token takes precedence over StartAfter
+		let q = list_objects_v2_query("", Some(&token), Some("a/b"));
+		assert_eq!(q.begin()?, after("a/c"));
+		let q = list_objects_v2_query("", Some(&token), None);
+		assert_eq!(q.begin()?, after("a/c"));
+
+		// StartAfter before the prefix starts listing at the prefix
+		let q = list_objects_v2_query("b", None, Some("a/b"));
+		assert_eq!(
+			q.begin()?,
+			RangeBegin::IncludingKey {
+				key: "b".to_string(),
+				fallback_key: None
+			}
+		);
+		let q = list_objects_v2_query("b", None, Some("b/a"));
+		assert_eq!(q.begin()?, after("b/a"));
+
+		// Tokens that were not produced by Garage are rejected
+		let q = list_objects_v2_query("", Some("a/c"), None);
+		assert!(q.begin().is_err());
+
+		Ok(())
+	}
 }
@@ -1,6 +1,9 @@
 pub mod api_server;
 pub mod error;
 
+mod access_log;
+mod acl;
+mod bandwidth;
 mod bucket;
 mod copy;
 pub mod cors;
@@ -9,11 +12,13 @@ pub mod get;
 mod lifecycle;
 mod list;
 mod multipart;
+mod object_lock;
 mod post_object;
 mod put;
+mod tagging;
 mod website;
 
-mod checksum;
+pub(crate) mod checksum;
 mod encryption;
 mod router;
 pub mod xml;
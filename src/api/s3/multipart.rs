@@ -16,10 +16,12 @@ use garage_model::s3::object_table::*;
 use garage_model::s3::version_table::*;
 
 use crate::helpers::*;
+use crate::s3::acl::check_object_acl_allowed;
 use crate::s3::api_server::{ReqBody, ResBody};
 use crate::s3::checksum::*;
 use crate::s3::encryption::EncryptionParams;
 use crate::s3::error::*;
+use crate::s3::object_lock::{check_object_not_locked, new_object_retention};
 use crate::s3::put::*;
 use crate::s3::xml as s3_xml;
 use crate::signature::verify_signed_content;
@@ -42,7 +44,9 @@ pub async fn handle_create_multipart_upload(
 	let upload_id = gen_uuid();
 	let timestamp = next_timestamp(existing_object.as_ref());
 
-	let headers = get_headers(req.headers())?;
+	check_object_acl_allowed(&ctx.api_key, bucket_id, req.headers())?;
+	let headers = get_headers(garage, req.headers())?;
+	let storage_class = get_storage_class(garage, req.headers())?;
 	let meta = ObjectVersionMetaInner {
 		headers,
 		checksum: None,
@@ -62,6 +66,7 @@ pub async fn handle_create_multipart_upload(
 			multipart: true,
 			encryption: object_encryption,
 			checksum_algorithm,
+			storage_class,
 		},
 	};
 	let object = Object::new(*bucket_id, key.to_string(), vec![object_version]);
@@ -133,6 +138,14 @@ pub async fn handle_put_part(
 	// Check object is valid and part can be accepted
 	let first_block = first_block.ok_or_bad_request("Empty body")?;
 
+	let max_size = match garage.config.s3_api.max_object_size {
+		Some(m) => std::cmp::min(m as u64, MAX_PART_SIZE),
+		None => MAX_PART_SIZE,
+	};
+	if first_block.len() as u64 > max_size {
+		return Err(Error::EntityTooLarge);
+	}
+
 	// Calculate part identity: timestamp, version id
 	let version_uuid = gen_uuid();
 	let mpu_part_key = MpuPartKey {
@@ -181,6 +194,7 @@ pub async fn handle_put_part(
 		first_block,
 		&mut chunker,
 		checksummer,
+		Some(max_size),
 	)
 	.await?;
 
@@ -279,16 +293,19 @@ pub async fn handle_complete_multipart_upload(
 	let key = key.to_string();
 	let (object, mut object_version, mpu) = get_upload(&ctx, &key, &upload_id).await?;
 
+	check_object_not_locked(&ctx, Some(&object), false)?;
+
 	if mpu.parts.is_empty() {
 		return Err(Error::bad_request("No data was uploaded"));
 	}
 
-	let (object_encryption, checksum_algorithm) = match object_version.state {
+	let (object_encryption, checksum_algorithm, storage_class) = match object_version.state {
 		ObjectVersionState::Uploading {
 			encryption,
 			checksum_algorithm,
+			storage_class,
 			..
-		} => (encryption, checksum_algorithm),
+		} => (encryption, checksum_algorithm, storage_class),
 		_ => unreachable!(),
 	};
 
@@ -298,6 +315,12 @@ pub async fn handle_complete_multipart_upload(
 	if body_list_of_parts.is_empty() {
 		return Err(Error::EntityTooSmall);
 	}
+	if body_list_of_parts.len() > MAX_PARTS_PER_UPLOAD {
+		return Err(Error::bad_request(format!(
+			"A multipart upload can have at most {} parts",
+			MAX_PARTS_PER_UPLOAD
+		)));
+	}
 	if !body_list_of_parts
 		.iter()
 		.zip(body_list_of_parts.iter().skip(1))
@@ -308,6 +331,10 @@ pub async fn handle_complete_multipart_upload(
 
 	// Check that the list of parts they gave us corresponds to parts we have here
 	debug!("Parts stored in multipart upload: {:?}", mpu.parts.items());
+	// Parts are sorted by (part number, timestamp): when a part was uploaded
+	// several times, the latest upload replaces the previous ones. The versions
+	// of replaced parts are deleted along with the multipart upload, which
+	// releases their blocks.
 	let mut have_parts = HashMap::new();
 	for (pk, pv) in mpu.parts.items().iter() {
 		have_parts.insert(pk.part_number, pv);
@@ -329,6 +356,14 @@ pub async fn handle_complete_multipart_upload(
 		}
 	}
 
+	// Check that all parts except the last one have the minimum part size
+	if parts[..parts.len() - 1]
+		.iter()
+		.any(|p| p.size.unwrap() < MIN_PART_SIZE)
+	{
+		return Err(Error::EntityTooSmall);
+	}
+
 	let grg = &garage;
 	let parts_versions = futures::future::try_join_all(parts.iter().map(|p| async move {
 		grg.version_table
@@ -391,13 +426,25 @@ pub async fn handle_complete_multipart_upload(
 	// Calculate total size of final object
 	let total_size = parts.iter().map(|x| x.size.unwrap()).sum();
 
-	if let Err(e) = check_quotas(&ctx, total_size, Some(&object)).await {
-		object_version.state = ObjectVersionState::Aborted;
-		let final_object = Object::new(*bucket_id, key.clone(), vec![object_version]);
-		garage.object_table.insert(&final_object).await?;
-
-		return Err(e);
-	}
+	let max_size = match garage.config.s3_api.max_object_size {
+		Some(m) => std::cmp::min(m as u64, MAX_MULTIPART_OBJECT_SIZE),
+		None => MAX_MULTIPART_OBJECT_SIZE,
+	};
+	let size_check = if total_size > max_size {
+		Err(Error::EntityTooLarge)
+	} else {
+		check_quotas(&ctx, total_size, Some(&object)).await
+	};
+	let quota_reservation = match size_check {
+		Ok(reservation) => reservation,
+		Err(e) => {
+			object_version.state = ObjectVersionState::Aborted;
+			let final_object = Object::new(*bucket_id, key.clone(), vec![object_version]);
+			garage.object_table.insert(&final_object).await?;
+
+			return Err(e);
+		}
+	};
 
 	// If there is a checksum algorithm, update metadata with checksum
 	let object_encryption = match checksum_algorithm {
@@ -419,12 +466,17 @@ pub async fn handle_complete_multipart_upload(
 			encryption: object_encryption,
 			size: total_size,
 			etag: etag.clone(),
+			storage_class,
+			retention: new_object_retention(&ctx.bucket_params, object_version.timestamp),
 		},
 		final_version.blocks.items()[0].1.hash,
 	));
 
 	let final_object = Object::new(*bucket_id, key.clone(), vec![object_version]);
 	garage.object_table.insert(&final_object).await?;
+	if let Some(r) = quota_reservation {
+		r.commit();
+	}
 
 	// Send response saying ok we're done
 	let result = s3_xml::CompleteMultipartUploadResult {
@@ -0,0 +1,319 @@
+use quick_xml::de::from_reader;
+
+use http_body_util::BodyExt;
+use hyper::{HeaderMap, Request, Response, StatusCode};
+
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::*;
+use crate::s3::api_server::{ReqBody, ResBody};
+use crate::s3::error::*;
+use crate::s3::xml::{to_xml_with_header, xmlns_tag, IntValue, Value};
+use crate::signature::verify_signed_content;
+
+use garage_model::bucket_table::{
+	Bucket, BucketParams, ObjectLockConfig, ObjectLockMode, ObjectLockPeriod, ObjectLockRetention,
+};
+use garage_model::s3::object_table::{Object, ObjectVersionRetention};
+use garage_util::data::*;
+use garage_util::time::*;
+
+pub async fn handle_get_object_lock_config(ctx: ReqCtx) -> Result<Response<ResBody>, Error> {
+	let ReqCtx { bucket_params, .. } = ctx;
+
+	if let Some(config) = bucket_params.object_lock_config.get() {
+		let olc = ObjectLockConfiguration::from_garage_object_lock_config(config);
+		let xml = to_xml_with_header(&olc)?;
+		Ok(Response::builder()
+			.status(StatusCode::OK)
+			.header(http::header::CONTENT_TYPE, "application/xml")
+			.body(string_body(xml))?)
+	} else {
+		Err(Error::NoSuchObjectLockConfiguration)
+	}
+}
+
+pub async fn handle_put_object_lock_config(
+	ctx: ReqCtx,
+	req: Request<ReqBody>,
+	content_sha256: Option<Hash>,
+) -> Result<Response<ResBody>, Error> {
+	let ReqCtx {
+		garage,
+		bucket_id,
+		mut bucket_params,
+		..
+	} = ctx;
+
+	let body = BodyExt::collect(req.into_body()).await?.to_bytes();
+
+	if let Some(content_sha256) = content_sha256 {
+		verify_signed_content(content_sha256, &body[..])?;
+	}
+
+	let conf: ObjectLockConfiguration = from_reader(&body as &[u8])?;
+	let config = conf
+		.validate_into_garage_object_lock_config()
+		.ok_or_bad_request("Invalid object lock configuration")?;
+
+	// Objects under compliance retention must stay protected for the
+	// whole period that was announced when they were written
+	let prev_compliance = bucket_params
+		.object_lock_config
+		.get()
+		.as_ref()
+		.and_then(|c| c.default_retention.as_ref())
+		.filter(|r| r.mode == ObjectLockMode::Compliance);
+	if let Some(prev) = prev_compliance {
+		let still_compliant = config
+			.default_retention
+			.as_ref()
+			.map(|r| {
+				r.mode == ObjectLockMode::Compliance && r.period.as_msec() >= prev.period.as_msec()
+			})
+			.unwrap_or(false);
+		if !still_compliant {
+			return Err(Error::forbidden(
+				"Default retention in COMPLIANCE mode cannot be removed or shortened",
+			));
+		}
+	}
+
+	bucket_params.object_lock_config.update(Some(config));
+	garage
+		.bucket_table
+		.insert(&Bucket::present(bucket_id, bucket_params))
+		.await?;
+
+	Ok(Response::builder()
+		.status(StatusCode::OK)
+		.body(empty_body())?)
+}
+
+/// Check whether the request asks to bypass retention in GOVERNANCE mode
+pub(crate) fn bypass_governance_retention(headers: &HeaderMap) -> bool {
+	headers
+		.get("x-amz-bypass-governance-retention")
+		.map(|v| v == "true")
+		.unwrap_or(false)
+}
+
+/// Object lock retention to record on a new object version of the bucket
+/// written with `timestamp`
+pub(crate) fn new_object_retention(
+	bucket_params: &BucketParams,
+	timestamp: u64,
+) -> Option<ObjectVersionRetention> {
+	bucket_params
+		.object_lock_config
+		.get()
+		.as_ref()
+		.and_then(|c| c.object_retention(timestamp))
+}
+
+/// Check that the current version of `object` is not under retention,
+/// and can therefore be deleted or overwritten. The retention that is checked
+/// is the one recorded on the version when it was written, so that later
+/// changes to the bucket configuration cannot extend or shorten it.
+pub(crate) fn check_object_not_locked(
+	ctx: &ReqCtx,
+	object: Option<&Object>,
+	bypass_governance: bool,
+) -> Result<(), Error> {
+	let retention = match object
+		.and_then(|o| o.versions().iter().rev().find(|v| v.is_data()))
+		.and_then(|v| v.retention())
+	{
+		Some(r) if now_msec() < r.retain_until => r,
+		_ => return Ok(()),
+	};
+
+	if retention.mode == ObjectLockMode::Governance
+		&& bypass_governance
+		&& ctx.api_key.allow_owner(&ctx.bucket_id)
+	{
+		return Ok(());
+	}
+
+	Err(Error::forbidden(format!(
+		"Object is protected by object lock until {}",
+		msec_to_rfc3339(retention.retain_until)
+	)))
+}
+
+/// Same as `check_object_not_locked`, fetching the object stored under `key`
+pub(crate) async fn check_key_not_locked(ctx: &ReqCtx, key: &str) -> Result<(), Error> {
+	let object = ctx
+		.garage
+		.object_table
+		.get(&ctx.bucket_id, &key.to_string())
+		.await?;
+	check_object_not_locked(ctx, object.as_ref(), false)
+}
+
+// ---- SERIALIZATION AND DESERIALIZATION TO/FROM S3 XML ----
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ObjectLockConfiguration {
+	#[serde(serialize_with = "xmlns_tag", skip_deserializing)]
+	pub xmlns: (),
+	#[serde(rename = "ObjectLockEnabled")]
+	pub object_lock_enabled: Option<Value>,
+	#[serde(rename = "Rule")]
+	pub rule: Option<ObjectLockRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ObjectLockRule {
+	#[serde(rename = "DefaultRetention")]
+	pub default_retention: DefaultRetention,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DefaultRetention {
+	#[serde(rename = "Mode")]
+	pub mode: Value,
+	#[serde(rename = "Days")]
+	pub days: Option<IntValue>,
+	#[serde(rename = "Years")]
+	pub years: Option<IntValue>,
+}
+
+impl ObjectLockConfiguration {
+	pub fn validate_into_garage_object_lock_config(self) -> Result<ObjectLockConfig, &'static str> {
+		match self.object_lock_enabled.as_ref().map(|x| x.0.as_str()) {
+			None | Some("Enabled") => (),
+			_ => return Err("invalid value for <ObjectLockEnabled>"),
+		}
+
+		let default_retention = match self.rule {
+			None => None,
+			Some(rule) => Some(rule.default_retention.validate_into_garage_retention()?),
+		};
+
+		Ok(ObjectLockConfig { default_retention })
+	}
+
+	pub fn from_garage_object_lock_config(config: &ObjectLockConfig) -> Self {
+		Self {
+			xmlns: (),
+			object_lock_enabled: Some(Value::from("Enabled")),
+			rule: config.default_retention.as_ref().map(|r| ObjectLockRule {
+				default_retention: DefaultRetention::from_garage_retention(r),
+			}),
+		}
+	}
+}
+
+impl DefaultRetention {
+	pub fn validate_into_garage_retention(self) -> Result<ObjectLockRetention, &'static str> {
+		let mode = match self.mode.0.as_str() {
+			"GOVERNANCE" => ObjectLockMode::Governance,
+			"COMPLIANCE" => ObjectLockMode::Compliance,
+			_ => return Err("invalid value for <Mode>"),
+		};
+
+		let period = match (self.days, self.years) {
+			(Some(_), Some(_)) => return Err("cannot have both <Days> and <Years>"),
+			(None, None) => return Err("<DefaultRetention> must contain either <Days> or <Years>"),
+			(Some(d), None) if d.0 > 0 && d.0 <= u32::MAX as i64 => {
+				ObjectLockPeriod::Days(d.0 as u32)
+			}
+			(None, Some(y)) if y.0 > 0 && y.0 <= u32::MAX as i64 => {
+				ObjectLockPeriod::Years(y.0 as u32)
+			}
+			_ => return Err("retention period must be a positive integer"),
+		};
+
+		Ok(ObjectLockRetention { mode, period })
+	}
+
+	pub fn from_garage_retention(retention: &ObjectLockRetention) -> Self {
+		let mode = match retention.mode {
+			ObjectLockMode::Governance => "GOVERNANCE",
+			ObjectLockMode::Compliance => "COMPLIANCE",
+		};
+		let (days, years) = match retention.period {
+			ObjectLockPeriod::Days(d) => (Some(IntValue(d as i64)), None),
+			ObjectLockPeriod::Years(y) => (None, Some(IntValue(y as i64))),
+		};
+		Self {
+			mode: Value::from(mode),
+			days,
+			years,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use quick_xml::de::from_str;
+
+	#[test]
+	fn test_deserialize_object_lock_config() -> Result<(), Error> {
+		let message = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ObjectLockConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <ObjectLockEnabled>Enabled</ObjectLockEnabled>
+  <Rule>
+    <DefaultRetention>
+      <Mode>GOVERNANCE</Mode>
+      <Days>30</Days>
+    </DefaultRetention>
+  </Rule>
+</ObjectLockConfiguration>"#;
+		let conf: ObjectLockConfiguration = from_str(message).unwrap();
+		let ref_value = ObjectLockConfiguration {
+			xmlns: (),
+			object_lock_enabled: Some("Enabled".into()),
+			rule: Some(ObjectLockRule {
+				default_retention: DefaultRetention {
+					mode: "GOVERNANCE".into(),
+					days: Some(IntValue(30)),
+					years: None,
+				},
+			}),
+		};
+		assert_eq!(ref_value, conf);
+
+		let message2 = to_xml_with_header(&ref_value)?;
+
+		let cleanup = |c: &str| c.replace(char::is_whitespace, "");
+		assert_eq!(cleanup(message), cleanup(&message2));
+
+		let validated = ref_value
+			.validate_into_garage_object_lock_config()
+			.ok_or_bad_request("invalid xml config")?;
+		assert_eq!(
+			validated,
+			ObjectLockConfig {
+				default_retention: Some(ObjectLockRetention {
+					mode: ObjectLockMode::Governance,
+					period: ObjectLockPeriod::Days(30),
+				}),
+			}
+		);
+
+		let message3 = to_xml_with_header(
+			&ObjectLockConfiguration::from_garage_object_lock_config(&validated),
+		)?;
+		assert_eq!(cleanup(message), cleanup(&message3));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_invalid_object_lock_config() {
+		let invalid = [
+			"<ObjectLockConfiguration><Rule><DefaultRetention><Mode>GOVERNANCE</Mode></DefaultRetention></Rule></ObjectLockConfiguration>",
+			"<ObjectLockConfiguration><Rule><DefaultRetention><Mode>COMPLIANCE</Mode><Days>1</Days><Years>1</Years></DefaultRetention></Rule></ObjectLockConfiguration>",
+			"<ObjectLockConfiguration><Rule><DefaultRetention><Mode>LEGAL</Mode><Days>1</Days></DefaultRetention></Rule></ObjectLockConfiguration>",
+			"<ObjectLockConfiguration><Rule><DefaultRetention><Mode>GOVERNANCE</Mode><Days>0</Days></DefaultRetention></Rule></ObjectLockConfiguration>",
+		];
+		for msg in invalid {
+			let conf: ObjectLockConfiguration = from_str(msg).unwrap();
+			assert!(conf.validate_into_garage_object_lock_config().is_err());
+		}
+	}
+}
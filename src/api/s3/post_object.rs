@@ -9,28 +9,35 @@ use bytes::Bytes;
 use chrono::{DateTime, Duration, Utc};
 use futures::{Stream, StreamExt};
 use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
-use hyper::{body::Incoming as IncomingBody, Request, Response, StatusCode};
+use hyper::{Request, Response, StatusCode};
 use multer::{Constraints, Multipart, SizeLimit};
 use serde::Deserialize;
 
 use garage_model::garage::Garage;
+use garage_model::key_table::Key;
 use garage_model::s3::object_table::*;
+use garage_util::forwarded_headers::IpNet;
 
 use crate::helpers::*;
-use crate::s3::api_server::ResBody;
+use crate::s3::acl::check_object_acl_allowed;
+use crate::s3::api_server::{ReqBody, ResBody};
 use crate::s3::checksum::*;
 use crate::s3::cors::*;
 use crate::s3::encryption::EncryptionParams;
 use crate::s3::error::*;
-use crate::s3::put::{get_headers, save_stream, ChecksumMode};
+use crate::s3::put::{get_headers, get_storage_class, save_stream, ChecksumMode};
 use crate::s3::xml as s3_xml;
 use crate::signature::payload::{verify_v4, Authorization};
 
 pub async fn handle_post_object(
 	garage: Arc<Garage>,
-	req: Request<IncomingBody>,
+	req: Request<ReqBody>,
 	bucket_name: String,
+	trusted_proxies: &[IpNet],
+	used_key: &mut Option<Key>,
 ) -> Result<Response<ResBody>, Error> {
+	let client_ip = request_client_ip(&req, trusted_proxies);
+
 	let boundary = req
 		.headers()
 		.get(header::CONTENT_TYPE)
@@ -113,6 +120,10 @@ pub async fn handle_post_object(
 	};
 
 	let api_key = verify_v4(&garage, "s3", &authorization, policy.as_bytes()).await?;
+	*used_key = Some(api_key.clone());
+
+	check_source_ip(&api_key, client_ip)?;
+	check_rate_limit(&garage, &api_key)?;
 
 	let bucket_id = garage
 		.bucket_helper()
@@ -222,7 +233,9 @@ pub async fn handle_post_object(
 		)));
 	}
 
-	let headers = get_headers(&params)?;
+	check_object_acl_allowed(&api_key, &bucket_id, &params)?;
+	let headers = get_headers(&garage, &params)?;
+	let storage_class = get_storage_class(&garage, &params)?;
 
 	let expected_checksums = ExpectedChecksums {
 		md5: params
@@ -241,7 +254,7 @@ pub async fn handle_post_object(
 
 	let encryption = EncryptionParams::new_from_headers(&garage, &params)?;
 
-	let stream = file_field.map(|r| r.map_err(Into::into));
+	let stream = rate_limit_stream(&garage, &api_key, file_field.map(|r| r.map_err(Into::into)));
 	let ctx = ReqCtx {
 		garage,
 		bucket_id,
@@ -253,10 +266,12 @@ pub async fn handle_post_object(
 	let res = save_stream(
 		&ctx,
 		meta,
+		storage_class,
 		encryption,
 		StreamLimiter::new(stream, conditions.content_length),
 		&key,
 		ChecksumMode::Verify(&expected_checksums),
+		None,
 	)
 	.await?;
 
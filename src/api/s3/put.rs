@@ -23,21 +23,34 @@ use garage_util::data::*;
 use garage_util::error::Error as GarageError;
 use garage_util::time::*;
 
-use garage_block::manager::INLINE_THRESHOLD;
 use garage_model::garage::Garage;
 use garage_model::index_counter::CountedItem;
+use garage_model::quota::QuotaReservation;
 use garage_model::s3::block_ref_table::*;
 use garage_model::s3::object_table::*;
 use garage_model::s3::version_table::*;
 
 use crate::helpers::*;
+use crate::s3::acl::{check_object_acl_allowed, parse_canned_acl_header, X_AMZ_ACL};
 use crate::s3::api_server::{ReqBody, ResBody};
+use crate::s3::bucket::reported_version_id;
 use crate::s3::checksum::*;
 use crate::s3::encryption::EncryptionParams;
 use crate::s3::error::*;
+use crate::s3::get::etag_list_matches;
+use crate::s3::object_lock::{check_object_not_locked, new_object_retention};
 
 const PUT_BLOCKS_MAX_PARALLEL: usize = 3;
 
+/// Minimum size of all parts of a multipart upload except the last one, as defined by S3
+pub(crate) const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+/// Maximum size of a part of a multipart upload, as defined by S3
+pub(crate) const MAX_PART_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+/// Maximum size of an object created by a multipart upload, as defined by S3
+pub(crate) const MAX_MULTIPART_OBJECT_SIZE: u64 = 5 * 1024 * 1024 * 1024 * 1024;
+/// Maximum number of parts in a multipart upload, as defined by S3
+pub(crate) const MAX_PARTS_PER_UPLOAD: usize = 10000;
+
 pub(crate) struct SaveStreamResult {
 	pub(crate) version_uuid: Uuid,
 	pub(crate) version_timestamp: u64,
@@ -57,8 +70,10 @@ pub async fn handle_put(
 	content_sha256: Option<Hash>,
 ) -> Result<Response<ResBody>, Error> {
 	// Retrieve interesting headers from request
-	let headers = get_headers(req.headers())?;
+	check_object_acl_allowed(&ctx.api_key, &ctx.bucket_id, req.headers())?;
+	let headers = get_headers(&ctx.garage, req.headers())?;
 	debug!("Object headers: {:?}", headers);
+	let storage_class = get_storage_class(&ctx.garage, req.headers())?;
 
 	let expected_checksums = ExpectedChecksums {
 		md5: match req.headers().get("content-md5") {
@@ -77,33 +92,91 @@ pub async fn handle_put(
 	// Determine whether object should be encrypted, and if so the key
 	let encryption = EncryptionParams::new_from_headers(&ctx.garage, req.headers())?;
 
+	let if_match = IfMatch::from_headers(req.headers())?;
+
+	// If the client waits for a 100 Continue response before sending the body,
+	// refuse uploads that are doomed to fail before the body is read
+	// (the interim response is sent by hyper when the body is first polled)
+	if let Some(size) = continue_expected_size(req.headers()) {
+		check_put_before_body(&ctx, key, size, if_match.as_ref()).await?;
+	}
+
 	let stream = body_stream(req.into_body());
 
 	let res = save_stream(
 		&ctx,
 		meta,
+		storage_class,
 		encryption,
 		stream,
 		key,
 		ChecksumMode::Verify(&expected_checksums),
+		if_match.as_ref(),
 	)
 	.await?;
 
 	let mut resp = Response::builder()
-		.header("x-amz-version-id", hex::encode(res.version_uuid))
+		.header(
+			"x-amz-version-id",
+			reported_version_id(*ctx.bucket_params.versioning.get(), res.version_uuid),
+		)
 		.header("ETag", format!("\"{}\"", res.etag));
 	encryption.add_response_headers(&mut resp);
 	let resp = add_checksum_response_headers(&expected_checksums.extra, resp);
 	Ok(resp.body(empty_body())?)
 }
 
+/// If the request has an `Expect: 100-continue` header, returns the size
+/// of the object announced by the client
+fn continue_expected_size(headers: &HeaderMap<HeaderValue>) -> Option<u64> {
+	let expect = headers.get(hyper::header::EXPECT)?.to_str().ok()?;
+	if !expect.eq_ignore_ascii_case("100-continue") {
+		return None;
+	}
+	headers
+		.get("x-amz-decoded-content-length")
+		.or_else(|| headers.get(hyper::header::CONTENT_LENGTH))?
+		.to_str()
+		.ok()?
+		.parse()
+		.ok()
+}
+
+/// Run the checks of `save_stream` that do not depend on the object data
+async fn check_put_before_body(
+	ctx: &ReqCtx,
+	key: &String,
+	size: u64,
+	if_match: Option<&IfMatch>,
+) -> Result<(), Error> {
+	let ReqCtx {
+		garage, bucket_id, ..
+	} = ctx;
+
+	if let Some(max_size) = garage.config.s3_api.max_object_size {
+		if size > max_size as u64 {
+			return Err(Error::EntityTooLarge);
+		}
+	}
+
+	let existing_object = garage.object_table.get(bucket_id, key).await?;
+	check_object_not_locked(ctx, existing_object.as_ref(), false)?;
+	if let Some(if_match) = if_match {
+		if_match.check(existing_object.as_ref())?;
+	}
+	check_quotas(ctx, size, existing_object.as_ref()).await?;
+	Ok(())
+}
+
 pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 	ctx: &ReqCtx,
 	mut meta: ObjectVersionMetaInner,
+	storage_class: Option<String>,
 	encryption: EncryptionParams,
 	body: S,
 	key: &String,
 	checksum_mode: ChecksumMode<'_>,
+	if_match: Option<&IfMatch>,
 ) -> Result<SaveStreamResult, Error> {
 	let ReqCtx {
 		garage, bucket_id, ..
@@ -117,6 +190,19 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 
 	let first_block = first_block_opt.unwrap_or_default();
 
+	let max_size = garage.config.s3_api.max_object_size.map(|x| x as u64);
+	if max_size
+		.map(|m| first_block.len() as u64 > m)
+		.unwrap_or(false)
+	{
+		return Err(Error::EntityTooLarge);
+	}
+
+	check_object_not_locked(ctx, existing_object.as_ref(), false)?;
+	if let Some(if_match) = if_match {
+		if_match.check(existing_object.as_ref())?;
+	}
+
 	// Generate identity of new version
 	let version_uuid = gen_uuid();
 	let version_timestamp = next_timestamp(existing_object.as_ref());
@@ -130,7 +216,7 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 
 	// If body is small enough, store it directly in the object table
 	// as "inline data". We can then return immediately.
-	if first_block.len() < INLINE_THRESHOLD {
+	if first_block.len() < garage.block_manager.inline_threshold {
 		checksummer.update(&first_block);
 		let checksums = checksummer.finalize();
 
@@ -144,7 +230,7 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 		};
 
 		let size = first_block.len() as u64;
-		check_quotas(ctx, size, existing_object.as_ref()).await?;
+		let quota_reservation = check_quotas(ctx, size, existing_object.as_ref()).await?;
 
 		let etag = encryption.etag_from_md5(&checksums.md5);
 		let inline_data = encryption.encrypt_blob(&first_block)?.to_vec();
@@ -157,6 +243,8 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 					encryption: encryption.encrypt_meta(meta)?,
 					size,
 					etag: etag.clone(),
+					storage_class,
+					retention: new_object_retention(&ctx.bucket_params, version_timestamp),
 				},
 				inline_data,
 			)),
@@ -164,6 +252,13 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 
 		let object = Object::new(*bucket_id, key.into(), vec![object_version]);
 		garage.object_table.insert(&object).await?;
+		if let Some(r) = quota_reservation {
+			r.commit();
+		}
+
+		garage
+			.s3_metrics
+			.record_object_stored(bucket_id, true, size);
 
 		return Ok(SaveStreamResult {
 			version_uuid,
@@ -192,6 +287,7 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 			encryption: encryption.encrypt_meta(meta.clone())?,
 			checksum_algorithm: None, // don't care; overwritten later
 			multipart: false,
+			storage_class: None,
 		},
 	};
 	let object = Object::new(*bucket_id, key.into(), vec![object_version.clone()]);
@@ -220,6 +316,7 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 		first_block,
 		&mut chunker,
 		checksummer,
+		max_size,
 	)
 	.await?;
 
@@ -234,7 +331,7 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 	};
 
 	// Verify quotas are respsected
-	check_quotas(ctx, total_size, existing_object.as_ref()).await?;
+	let quota_reservation = check_quotas(ctx, total_size, existing_object.as_ref()).await?;
 
 	// Save final object state, marked as Complete
 	let etag = encryption.etag_from_md5(&checksums.md5);
@@ -244,16 +341,25 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 			encryption: encryption.encrypt_meta(meta)?,
 			size: total_size,
 			etag: etag.clone(),
+			storage_class,
+			retention: new_object_retention(&ctx.bucket_params, version_timestamp),
 		},
 		first_block_hash,
 	));
 	let object = Object::new(*bucket_id, key.into(), vec![object_version]);
 	garage.object_table.insert(&object).await?;
+	if let Some(r) = quota_reservation {
+		r.commit();
+	}
 
 	// We were not interrupted, everything went fine.
 	// We won't have to clean up on drop.
 	interrupted_cleanup.cancel();
 
+	garage
+		.s3_metrics
+		.record_object_stored(bucket_id, false, total_size);
+
 	Ok(SaveStreamResult {
 		version_uuid,
 		version_timestamp,
@@ -261,12 +367,15 @@ pub(crate) async fn save_stream<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
 	})
 }
 
-/// Check that inserting this object with this size doesn't exceed bucket quotas
+/// Check that inserting this object with this size doesn't exceed bucket quotas.
+/// For buckets with strict quotas, the returned reservation must be kept
+/// until the object has been written, so that concurrent writes on this node
+/// take it into account.
 pub(crate) async fn check_quotas(
 	ctx: &ReqCtx,
 	size: u64,
 	prev_object: Option<&Object>,
-) -> Result<(), Error> {
+) -> Result<Option<QuotaReservation>, Error> {
 	let ReqCtx {
 		garage,
 		bucket_id,
@@ -276,7 +385,7 @@ pub(crate) async fn check_quotas(
 
 	let quotas = bucket_params.quotas.get();
 	if quotas.max_objects.is_none() && quotas.max_size.is_none() {
-		return Ok(());
+		return Ok(None);
 	};
 
 	let counters = garage
@@ -302,27 +411,40 @@ pub(crate) async fn check_quotas(
 	let cnt_obj_diff = 1 - prev_cnt_obj;
 	let cnt_size_diff = size as i64 - prev_cnt_size;
 
-	if let Some(mo) = quotas.max_objects {
-		let current_objects = counters.get(OBJECTS).cloned().unwrap_or_default();
-		if cnt_obj_diff > 0 && current_objects + cnt_obj_diff > mo as i64 {
-			return Err(Error::forbidden(format!(
-				"Object quota is reached, maximum objects for this bucket: {}",
-				mo
-			)));
+	let check = |pending_obj: i64, pending_size: i64| {
+		if let Some(mo) = quotas.max_objects {
+			let current_objects = counters.get(OBJECTS).cloned().unwrap_or_default() + pending_obj;
+			if cnt_obj_diff > 0 && current_objects + cnt_obj_diff > mo as i64 {
+				return Err(Error::forbidden(format!(
+					"Object quota is reached, maximum objects for this bucket: {}",
+					mo
+				)));
+			}
 		}
-	}
 
-	if let Some(ms) = quotas.max_size {
-		let current_size = counters.get(BYTES).cloned().unwrap_or_default();
-		if cnt_size_diff > 0 && current_size + cnt_size_diff > ms as i64 {
-			return Err(Error::forbidden(format!(
-				"Bucket size quota is reached, maximum total size of objects for this bucket: {}. The bucket is already {} bytes, and this object would add {} bytes.",
-				ms, current_size, cnt_size_diff
-			)));
+		if let Some(ms) = quotas.max_size {
+			let current_size = counters.get(BYTES).cloned().unwrap_or_default() + pending_size;
+			if cnt_size_diff > 0 && current_size + cnt_size_diff > ms as i64 {
+				return Err(Error::forbidden(format!(
+					"Bucket size quota is reached, maximum total size of objects for this bucket: {}. The bucket is already {} bytes, and this object would add {} bytes.",
+					ms, current_size, cnt_size_diff
+				)));
+			}
 		}
-	}
 
-	Ok(())
+		Ok(())
+	};
+
+	if quotas.strict {
+		let reservation =
+			garage
+				.quota_reservations
+				.reserve(*bucket_id, cnt_obj_diff, cnt_size_diff, check)?;
+		Ok(Some(reservation))
+	} else {
+		check(0, 0)?;
+		Ok(None)
+	}
 }
 
 pub(crate) async fn read_and_put_blocks<S: Stream<Item = Result<Bytes, Error>> + Unpin>(
@@ -333,11 +455,13 @@ pub(crate) async fn read_and_put_blocks<S: Stream<Item = Result<Bytes, Error>> +
 	first_block: Bytes,
 	chunker: &mut StreamChunker<S>,
 	checksummer: Checksummer,
+	max_size: Option<u64>,
 ) -> Result<(u64, Checksums, Hash), Error> {
 	let tracer = opentelemetry::global::tracer("garage");
 
 	let (block_tx, mut block_rx) = mpsc::channel::<Result<Bytes, Error>>(2);
 	let read_blocks = async {
+		let mut read_bytes = first_block.len() as u64;
 		block_tx.send(Ok(first_block)).await?;
 		loop {
 			let res = chunker
@@ -347,7 +471,16 @@ pub(crate) async fn read_and_put_blocks<S: Stream<Item = Result<Bytes, Error>> +
 				))
 				.await;
 			match res {
-				Ok(Some(block)) => block_tx.send(Ok(block)).await?,
+				Ok(Some(block)) => {
+					// Stop reading as soon as the size limit is exceeded,
+					// the error is propagated to the end of the pipeline
+					read_bytes += block.len() as u64;
+					if max_size.map(|m| read_bytes > m).unwrap_or(false) {
+						block_tx.send(Err(Error::EntityTooLarge)).await?;
+						break;
+					}
+					block_tx.send(Ok(block)).await?
+				}
 				Ok(None) => break,
 				Err(e) => {
 					block_tx.send(Err(e)).await?;
@@ -600,7 +733,16 @@ impl Drop for InterruptedCleanup {
 
 // ============ helpers ============
 
-pub(crate) fn get_headers(headers: &HeaderMap<HeaderValue>) -> Result<HeaderList, Error> {
+/// Maximum total size of the user metadata of an object if it is not set
+/// in the configuration, which is the limit enforced by AWS
+pub(crate) const DEFAULT_MAX_USER_METADATA_SIZE: usize = 2048;
+
+const X_AMZ_META_PREFIX: &str = "x-amz-meta-";
+
+pub(crate) fn get_headers(
+	garage: &Garage,
+	headers: &HeaderMap<HeaderValue>,
+) -> Result<HeaderList, Error> {
 	let mut ret = Vec::new();
 
 	// Preserve standard headers
@@ -608,7 +750,6 @@ pub(crate) fn get_headers(headers: &HeaderMap<HeaderValue>) -> Result<HeaderList
 		hyper::header::CONTENT_TYPE,
 		hyper::header::CACHE_CONTROL,
 		hyper::header::CONTENT_DISPOSITION,
-		hyper::header::CONTENT_ENCODING,
 		hyper::header::CONTENT_LANGUAGE,
 		hyper::header::EXPIRES,
 	];
@@ -618,19 +759,122 @@ pub(crate) fn get_headers(headers: &HeaderMap<HeaderValue>) -> Result<HeaderList
 		}
 	}
 
-	// Preserve x-amz-meta- headers
-	for (name, value) in headers.iter() {
-		if name.as_str().starts_with("x-amz-meta-") {
-			ret.push((
-				name.to_string(),
-				std::str::from_utf8(value.as_bytes())?.to_string(),
-			));
+	// The Content-Encoding declared by the client is an opaque property of
+	// the object, which is unrelated to the compression of data blocks
+	// in storage and is returned as is when the object is read.
+	if let Some(value) = headers.get(hyper::header::CONTENT_ENCODING) {
+		if let Some(encoding) = object_content_encoding(value.to_str()?) {
+			ret.push((hyper::header::CONTENT_ENCODING.to_string(), encoding));
+		}
+	}
+
+	// Preserve the canned ACL of the object
+	if let Some(acl) = parse_canned_acl_header(headers)? {
+		ret.push((X_AMZ_ACL.to_string(), acl.as_str().to_string()));
+	}
+
+	// Preserve x-amz-meta- headers. Headers given several times are stored
+	// once, as a comma-separated list of their values, which is also how
+	// they are returned when the object is read.
+	// The size of the user metadata is the sum of the sizes of the metadata
+	// keys (without the x-amz-meta- prefix) and values, as computed by AWS.
+	let max_meta_size = garage
+		.config
+		.s3_api
+		.max_user_metadata_size
+		.unwrap_or(DEFAULT_MAX_USER_METADATA_SIZE);
+	let mut meta_size = 0;
+	for name in headers.keys() {
+		if let Some(key) = name.as_str().strip_prefix(X_AMZ_META_PREFIX) {
+			let values = headers
+				.get_all(name)
+				.iter()
+				.map(|v| std::str::from_utf8(v.as_bytes()))
+				.collect::<Result<Vec<_>, _>>()?;
+			let value = values.join(",");
+			meta_size += key.len() + value.len();
+			if meta_size > max_meta_size {
+				return Err(Error::MetadataTooLarge);
+			}
+			ret.push((name.to_string(), value));
 		}
 	}
 
 	Ok(ret)
 }
 
+/// Content-Encoding to be stored for an object, given the value of the
+/// Content-Encoding header of the request that uploads it.
+///
+/// `aws-chunked` only describes the framing of the request body when using
+/// streaming signatures, so like AWS we don't store it with the object.
+/// Other encodings are kept verbatim.
+fn object_content_encoding(value: &str) -> Option<String> {
+	let encodings = value
+		.split(',')
+		.map(str::trim)
+		.filter(|e| !e.is_empty() && !e.eq_ignore_ascii_case("aws-chunked"))
+		.collect::<Vec<_>>();
+	if encodings.is_empty() {
+		None
+	} else if encodings.len() == value.split(',').count() {
+		Some(value.to_string())
+	} else {
+		Some(encodings.join(","))
+	}
+}
+
+pub(crate) const X_AMZ_STORAGE_CLASS: &str = "x-amz-storage-class";
+
+/// Storage class of objects uploaded without an x-amz-storage-class header
+pub(crate) const DEFAULT_STORAGE_CLASS: &str = "STANDARD";
+
+/// Storage classes accepted if `storage_classes` is not set in the configuration
+const AWS_STORAGE_CLASSES: &[&str] = &[
+	"STANDARD",
+	"REDUCED_REDUNDANCY",
+	"STANDARD_IA",
+	"ONEZONE_IA",
+	"INTELLIGENT_TIERING",
+	"GLACIER",
+	"DEEP_ARCHIVE",
+	"OUTPOSTS",
+	"GLACIER_IR",
+	"SNOW",
+	"EXPRESS_ONEZONE",
+];
+
+/// Returns the storage class requested in the x-amz-storage-class header,
+/// or None if it is the default one. Garage stores all objects the same way,
+/// so the storage class is only recorded to be returned to clients.
+pub(crate) fn get_storage_class(
+	garage: &Garage,
+	headers: &HeaderMap<HeaderValue>,
+) -> Result<Option<String>, Error> {
+	let class = match headers.get(X_AMZ_STORAGE_CLASS) {
+		Some(v) => v.to_str()?,
+		None => return Ok(None),
+	};
+	if class == DEFAULT_STORAGE_CLASS {
+		return Ok(None);
+	}
+	let accepted = match &garage.config.s3_api.storage_classes {
+		Some(classes) => classes.iter().any(|c| c == class),
+		None => AWS_STORAGE_CLASSES.contains(&class),
+	};
+	if !accepted {
+		return Err(Error::InvalidStorageClass(class.to_string()));
+	}
+	Ok(Some(class.to_string()))
+}
+
+/// Storage class to return to clients for an object version
+pub(crate) fn storage_class_of(meta: &ObjectVersionMeta) -> &str {
+	meta.storage_class
+		.as_deref()
+		.unwrap_or(DEFAULT_STORAGE_CLASS)
+}
+
 pub(crate) fn next_timestamp(existing_object: Option<&Object>) -> u64 {
 	existing_object
 		.as_ref()
@@ -638,3 +882,35 @@ pub(crate) fn next_timestamp(existing_object: Option<&Object>) -> u64 {
 		.map(|t| std::cmp::max(t + 1, now_msec()))
 		.unwrap_or_else(now_msec)
 }
+
+/// Condition given by the If-Match header of a PutObject or DeleteObject
+/// request: the object is only modified if the ETag of its current version
+/// is one of the listed values, or if it exists at all for the `*` wildcard.
+///
+/// This check is best-effort: it is evaluated against the state of the object
+/// read at the beginning of the request, so a concurrent write that is not yet
+/// visible to this node can still be overwritten.
+pub(crate) struct IfMatch(String);
+
+impl IfMatch {
+	pub(crate) fn from_headers(headers: &HeaderMap<HeaderValue>) -> Result<Option<Self>, Error> {
+		match headers.get(hyper::header::IF_MATCH) {
+			Some(x) => Ok(Some(Self(x.to_str()?.to_string()))),
+			None => Ok(None),
+		}
+	}
+
+	pub(crate) fn check(&self, existing_object: Option<&Object>) -> Result<(), Error> {
+		let current_etag = existing_object
+			.and_then(|o| o.versions().iter().rev().find(|v| v.is_data()))
+			.and_then(|v| match &v.state {
+				ObjectVersionState::Complete(ObjectVersionData::Inline(meta, _))
+				| ObjectVersionState::Complete(ObjectVersionData::FirstBlock(meta, _)) => Some(&meta.etag),
+				_ => None,
+			});
+		match current_etag {
+			Some(etag) if etag_list_matches(&self.0, etag, false) => Ok(()),
+			_ => Err(Error::PreconditionFailed),
+		}
+	}
+}
@@ -136,6 +136,10 @@ pub enum Endpoint {
 		key: String,
 		version_id: Option<String>,
 	},
+	GetObjectAttributes {
+		key: String,
+		version_id: Option<String>,
+	},
 	GetObjectLegalHold {
 		key: String,
 		version_id: Option<String>,
@@ -161,6 +165,12 @@ pub enum Endpoint {
 		key: String,
 		part_number: Option<u64>,
 		version_id: Option<String>,
+		response_cache_control: Option<String>,
+		response_content_disposition: Option<String>,
+		response_content_encoding: Option<String>,
+		response_content_language: Option<String>,
+		response_content_type: Option<String>,
+		response_expires: Option<String>,
 	},
 	ListBucketAnalyticsConfigurations {
 		continuation_token: Option<String>,
@@ -205,7 +215,7 @@ pub enum Endpoint {
 		delimiter: Option<String>,
 		encoding_type: Option<String>,
 		key_marker: Option<String>,
-		max_keys: Option<u64>,
+		max_keys: Option<usize>,
 		prefix: Option<String>,
 		version_id_marker: Option<String>,
 	},
@@ -305,6 +315,10 @@ pub enum Endpoint {
 	PostObject,
 }}
 
+/// Methods supported on buckets and objects, as sent in the `Allow` header
+/// of 405 responses
+const ALLOWED_METHODS: &str = "GET, HEAD, PUT, POST, DELETE, OPTIONS";
+
 impl Endpoint {
 	/// Determine which S3 endpoint a request is for using the request, and a bucket which was
 	/// possibly extracted from the Host header.
@@ -348,7 +362,12 @@ impl Endpoint {
 			Method::POST => Self::from_post(key, &mut query)?,
 			Method::PUT => Self::from_put(key, &mut query, req.headers())?,
 			Method::DELETE => Self::from_delete(key, &mut query)?,
-			_ => return Err(Error::bad_request("Unknown method")),
+			_ => {
+				return Err(Error::MethodNotAllowed(
+					req.method().to_string(),
+					ALLOWED_METHODS,
+				))
+			}
 		};
 
 		if let Some(message) = query.nonempty_message() {
@@ -373,6 +392,7 @@ impl Endpoint {
 									query_opt::response_content_type,
 									query_opt::response_expires),
 				ACL => GetObjectAcl (query_opt::version_id),
+				ATTRIBUTES => GetObjectAttributes (query_opt::version_id),
 				LEGAL_HOLD => GetObjectLegalHold (query_opt::version_id),
 				RETENTION => GetObjectRetention (query_opt::version_id),
 				TAGGING => GetObjectTagging (query_opt::version_id),
@@ -427,7 +447,14 @@ impl Endpoint {
 			@gen_parser
 			(query.keyword.take().unwrap_or_default(), key, query, None),
 			key: [
-				EMPTY => HeadObject(opt_parse::part_number, query_opt::version_id),
+				EMPTY => HeadObject(opt_parse::part_number,
+									query_opt::version_id,
+									query_opt::response_cache_control,
+									query_opt::response_content_disposition,
+									query_opt::response_content_encoding,
+									query_opt::response_content_language,
+									query_opt::response_content_type,
+									query_opt::response_expires),
 			],
 			no_key: [
 				EMPTY => HeadBucket,
@@ -544,6 +571,7 @@ impl Endpoint {
 				DeleteObjectTagging,
 				GetObject,
 				GetObjectAcl,
+				GetObjectAttributes,
 				GetObjectLegalHold,
 				GetObjectRetention,
 				GetObjectTagging,
@@ -563,6 +591,14 @@ impl Endpoint {
 		}
 	}
 
+	/// Whether this endpoint reads the content or metadata of an object
+	pub fn is_object_read(&self) -> bool {
+		matches!(
+			self,
+			Endpoint::GetObject { .. } | Endpoint::HeadObject { .. }
+		)
+	}
+
 	/// Get the kind of authorization which is required to perform the operation.
 	pub fn authorization_type(&self) -> Authorization {
 		if let Endpoint::ListBuckets = self {
@@ -592,6 +628,7 @@ impl Endpoint {
 				GetBucketVersioning,
 				GetObject,
 				GetObjectAcl,
+				GetObjectAttributes,
 				GetObjectLegalHold,
 				GetObjectLockConfiguration,
 				GetObjectRetention,
@@ -617,12 +654,16 @@ impl Endpoint {
 			self,
 			[
 				DeleteBucket,
+				PutBucketAcl,
 				GetBucketWebsite,
 				PutBucketWebsite,
 				DeleteBucketWebsite,
 				GetBucketCors,
 				PutBucketCors,
 				DeleteBucketCors,
+				PutObjectLockConfiguration,
+				PutBucketTagging,
+				DeleteBucketTagging,
 			]
 		};
 		if readonly {
@@ -641,6 +682,7 @@ generateQueryParameters! {
 		"accelerate" => ACCELERATE,
 		"acl" => ACL,
 		"analytics" => ANALYTICS,
+		"attributes" => ATTRIBUTES,
 		"cors" => CORS,
 		"delete" => DELETE,
 		"encryption" => ENCRYPTION,
@@ -872,7 +914,7 @@ mod tests {
 			DELETE "/?ownershipControls" => DeleteBucketOwnershipControls
 			DELETE "/?policy" => DeleteBucketPolicy
 			DELETE "/?replication" => DeleteBucketReplication
-			DELETE "/?tagging" => DeleteBucketTagging
+			OWNER_DELETE "/?tagging" => DeleteBucketTagging
 			OWNER_DELETE "/?website" => DeleteBucketWebsite
 			DELETE "/my-second-image.jpg" => DeleteObject
 			DELETE "/my-third-image.jpg?versionId=UIORUnfndfiufdisojhr398493jfdkjFJjkndnqUifhnw89493jJFJ" => DeleteObject
@@ -910,6 +952,7 @@ mod tests {
 			GET "/my-image.jpg?acl" => GetObjectAcl
 			GET "/my-image.jpg?versionId=3/L4kqtJlcpXroDVBH40Nr8X8gdRQBpUMLUo&acl" => GetObjectAcl
 			GET "/{Key+}?acl&versionId=VersionId" => GetObjectAcl
+			GET "/{Key+}?attributes&versionId=VersionId" => GetObjectAttributes
 			GET "/{Key+}?legal-hold&versionId=VersionId" => GetObjectLegalHold
 			GET "/?object-lock" => GetObjectLockConfiguration
 			GET "/{Key+}?retention&versionId=VersionId" => GetObjectRetention
@@ -957,7 +1000,7 @@ mod tests {
 			GET "/example-object?uploadId=XXBsb2FkIElEIGZvciBlbHZpbmcncyVcdS1tb3ZpZS5tMnRzEEEwbG9hZA&max-parts=2&part-number-marker=1" => ListParts
 			GET "/Key+?max-parts=2&part-number-marker=2&uploadId=UploadId" => ListParts
 			PUT "/?accelerate" => PutBucketAccelerateConfiguration
-			PUT "/?acl" => PutBucketAcl
+			OWNER_PUT "/?acl" => PutBucketAcl
 			PUT "/?analytics&id=report1" => PutBucketAnalyticsConfiguration
 			PUT "/?analytics&id=Id" => PutBucketAnalyticsConfiguration
 			OWNER_PUT "/?cors" => PutBucketCors
@@ -974,7 +1017,7 @@ mod tests {
 			PUT "/?policy" => PutBucketPolicy
 			PUT "/?replication" => PutBucketReplication
 			PUT "/?requestPayment" => PutBucketRequestPayment
-			PUT "/?tagging" => PutBucketTagging
+			OWNER_PUT "/?tagging" => PutBucketTagging
 			PUT "/?versioning" => PutBucketVersioning
 			OWNER_PUT "/?website" => PutBucketWebsite
 			PUT "/my-image.jpg" => PutObject
@@ -983,7 +1026,7 @@ mod tests {
 			PUT "/my-image.jpg?acl&versionId=3HL4kqtJlcpXroDTDmJ+rmSpXd3dIbrHY+MTRCxf3vjVBH40Nrjfkd" => PutObjectAcl
 			PUT "/{Key+}?acl&versionId=VersionId" => PutObjectAcl
 			PUT "/{Key+}?legal-hold&versionId=VersionId" => PutObjectLegalHold
-			PUT "/?object-lock" => PutObjectLockConfiguration
+			OWNER_PUT "/?object-lock" => PutObjectLockConfiguration
 			PUT "/{Key+}?retention&versionId=VersionId" => PutObjectRetention
 			PUT "/object-key?tagging" => PutObjectTagging
 			PUT "/{Key+}?tagging&versionId=VersionId" => PutObjectTagging
@@ -1092,4 +1135,21 @@ mod tests {
 			Authorization::Read
 		));
 	}
+
+	#[test]
+	fn test_method_not_allowed() {
+		for uri in ["/my_bucket", "/my_bucket/my/key"] {
+			let req = Request::builder()
+				.method("PATCH")
+				.uri(uri)
+				.body(())
+				.unwrap();
+			let err = Endpoint::from_request(&req, None).unwrap_err();
+			assert!(
+				matches!(&err, Error::MethodNotAllowed(method, ALLOWED_METHODS) if method == "PATCH"),
+				"{}",
+				err
+			);
+		}
+	}
 }
@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+
+use quick_xml::de::from_reader;
+
+use http_body_util::BodyExt;
+use hyper::{Request, Response, StatusCode};
+
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::*;
+use crate::s3::api_server::{ReqBody, ResBody};
+use crate::s3::error::*;
+use crate::s3::xml::{to_xml_with_header, xmlns_tag, Value};
+use crate::signature::verify_signed_content;
+
+use garage_model::bucket_table::Bucket;
+use garage_util::data::*;
+
+/// Maximum number of tags on a bucket
+pub const MAX_BUCKET_TAGS: usize = 50;
+/// Maximum length of a tag key, in unicode characters
+pub const MAX_TAG_KEY_LENGTH: usize = 128;
+/// Maximum length of a tag value, in unicode characters
+pub const MAX_TAG_VALUE_LENGTH: usize = 256;
+
+pub async fn handle_get_bucket_tagging(ctx: ReqCtx) -> Result<Response<ResBody>, Error> {
+	let ReqCtx { bucket_params, .. } = ctx;
+
+	let tags = bucket_params.tags.get();
+	if tags.is_empty() {
+		return Err(Error::NoSuchTagSet);
+	}
+
+	let xml = to_xml_with_header(&Tagging::from_garage_tags(tags))?;
+	Ok(Response::builder()
+		.status(StatusCode::OK)
+		.header(http::header::CONTENT_TYPE, "application/xml")
+		.body(string_body(xml))?)
+}
+
+pub async fn handle_delete_bucket_tagging(ctx: ReqCtx) -> Result<Response<ResBody>, Error> {
+	let ReqCtx {
+		garage,
+		bucket_id,
+		mut bucket_params,
+		..
+	} = ctx;
+	bucket_params.tags.update(BTreeMap::new());
+	garage
+		.bucket_table
+		.insert(&Bucket::present(bucket_id, bucket_params))
+		.await?;
+
+	Ok(Response::builder()
+		.status(StatusCode::NO_CONTENT)
+		.body(empty_body())?)
+}
+
+pub async fn handle_put_bucket_tagging(
+	ctx: ReqCtx,
+	req: Request<ReqBody>,
+	content_sha256: Option<Hash>,
+) -> Result<Response<ResBody>, Error> {
+	let ReqCtx {
+		garage,
+		bucket_id,
+		mut bucket_params,
+		..
+	} = ctx;
+
+	let body = BodyExt::collect(req.into_body()).await?.to_bytes();
+
+	if let Some(content_sha256) = content_sha256 {
+		verify_signed_content(content_sha256, &body[..])?;
+	}
+
+	let conf: Tagging = from_reader(&body as &[u8])?;
+	let tags = conf.validate_into_garage_tags()?;
+
+	bucket_params.tags.update(tags);
+	garage
+		.bucket_table
+		.insert(&Bucket::present(bucket_id, bucket_params))
+		.await?;
+
+	Ok(Response::builder()
+		.status(StatusCode::NO_CONTENT)
+		.body(empty_body())?)
+}
+
+// ---- SERIALIZATION AND DESERIALIZATION TO/FROM S3 XML ----
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Tagging {
+	#[serde(serialize_with = "xmlns_tag", skip_deserializing)]
+	pub xmlns: (),
+	#[serde(rename = "TagSet")]
+	pub tag_set: TagSet,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TagSet {
+	#[serde(rename = "Tag", default)]
+	pub tags: Vec<Tag>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Tag {
+	#[serde(rename = "Key")]
+	pub key: Value,
+	#[serde(rename = "Value")]
+	pub value: Value,
+}
+
+impl Tagging {
+	pub fn validate_into_garage_tags(self) -> Result<BTreeMap<String, String>, Error> {
+		if self.tag_set.tags.len() > MAX_BUCKET_TAGS {
+			return Err(Error::InvalidTag(format!(
+				"a bucket can have at most {} tags",
+				MAX_BUCKET_TAGS
+			)));
+		}
+
+		let mut ret = BTreeMap::new();
+		for Tag { key, value } in self.tag_set.tags {
+			let (key, value) = (key.0, value.0);
+			if key.is_empty() || key.chars().count() > MAX_TAG_KEY_LENGTH {
+				return Err(Error::InvalidTag(format!(
+					"tag keys must be between 1 and {} characters long",
+					MAX_TAG_KEY_LENGTH
+				)));
+			}
+			if value.chars().count() > MAX_TAG_VALUE_LENGTH {
+				return Err(Error::InvalidTag(format!(
+					"tag values must be at most {} characters long",
+					MAX_TAG_VALUE_LENGTH
+				)));
+			}
+			if key.starts_with("aws:") {
+				return Err(Error::InvalidTag(
+					"tag keys starting with `aws:` are reserved".into(),
+				));
+			}
+			if ret.insert(key.clone(), value).is_some() {
+				return Err(Error::InvalidTag(format!("duplicate tag key: {}", key)));
+			}
+		}
+		Ok(ret)
+	}
+
+	pub fn from_garage_tags(tags: &BTreeMap<String, String>) -> Self {
+		Self {
+			xmlns: (),
+			tag_set: TagSet {
+				tags: tags
+					.iter()
+					.map(|(k, v)| Tag {
+						key: Value(k.clone()),
+						value: Value(v.clone()),
+					})
+					.collect(),
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use quick_xml::de::from_str;
+
+	#[test]
+	fn test_deserialize_tagging() -> Result<(), Error> {
+		let message = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Tagging xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <TagSet>
+    <Tag>
+      <Key>project</Key>
+      <Value>garage</Value>
+    </Tag>
+    <Tag>
+      <Key>cost-center</Key>
+      <Value>1234</Value>
+    </Tag>
+  </TagSet>
+</Tagging>"#;
+		let conf: Tagging = from_str(message).unwrap();
+		let ref_value = Tagging {
+			xmlns: (),
+			tag_set: TagSet {
+				tags: vec![
+					Tag {
+						key: "project".into(),
+						value: "garage".into(),
+					},
+					Tag {
+						key: "cost-center".into(),
+						value: "1234".into(),
+					},
+				],
+			},
+		};
+		assert_eq!(ref_value, conf);
+
+		let tags = conf.validate_into_garage_tags()?;
+		assert_eq!(tags.len(), 2);
+		assert_eq!(tags.get("project").map(String::as_str), Some("garage"));
+
+		let message2 = to_xml_with_header(&Tagging::from_garage_tags(&tags))?;
+		let conf2: Tagging = from_str(&message2).unwrap();
+		assert_eq!(conf2.validate_into_garage_tags()?, tags);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_validate_tagging() {
+		let tagging = |tags: Vec<(String, String)>| Tagging {
+			xmlns: (),
+			tag_set: TagSet {
+				tags: tags
+					.into_iter()
+					.map(|(k, v)| Tag {
+						key: Value(k),
+						value: Value(v),
+					})
+					.collect(),
+			},
+		};
+
+		let max = (0..MAX_BUCKET_TAGS)
+			.map(|i| (format!("key{}", i), "v".to_string()))
+			.collect::<Vec<_>>();
+		assert!(tagging(max.clone()).validate_into_garage_tags().is_ok());
+
+		let mut too_many = max;
+		too_many.push(("one-more".into(), "v".into()));
+		assert!(matches!(
+			tagging(too_many).validate_into_garage_tags(),
+			Err(Error::InvalidTag(_))
+		));
+
+		let invalid = [
+			("".to_string(), "v".to_string()),
+			("k".repeat(MAX_TAG_KEY_LENGTH + 1), "v".to_string()),
+			("k".to_string(), "v".repeat(MAX_TAG_VALUE_LENGTH + 1)),
+			("aws:createdBy".to_string(), "v".to_string()),
+		];
+		for tag in invalid {
+			assert!(matches!(
+				tagging(vec![tag]).validate_into_garage_tags(),
+				Err(Error::InvalidTag(_))
+			));
+		}
+
+		let duplicate = vec![("k".into(), "a".into()), ("k".into(), "b".into())];
+		assert!(matches!(
+			tagging(duplicate).validate_into_garage_tags(),
+			Err(Error::InvalidTag(_))
+		));
+	}
+}
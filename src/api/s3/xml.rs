@@ -13,7 +13,7 @@ pub fn xmlns_tag<S: Serializer>(_v: &(), s: S) -> Result<S::Ok, S::Error> {
 	s.serialize_str("http://s3.amazonaws.com/doc/2006-03-01/")
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Value(#[serde(rename = "$value")] pub String);
 
 impl From<&str> for Value {
@@ -33,7 +33,7 @@ pub struct Bucket {
 	pub name: Value,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
 pub struct Owner {
 	#[serde(rename = "DisplayName")]
 	pub display_name: Value,
@@ -255,6 +255,8 @@ pub struct ListBucketItem {
 	pub size: IntValue,
 	#[serde(rename = "StorageClass")]
 	pub storage_class: Value,
+	#[serde(rename = "Owner")]
+	pub owner: Option<Owner>,
 }
 
 #[derive(Debug, Serialize, PartialEq, Eq)]
@@ -298,13 +300,127 @@ pub struct ListBucketResult {
 }
 
 #[derive(Debug, Serialize, PartialEq, Eq)]
-pub struct VersioningConfiguration {
+pub struct ListVersionsItem {
+	#[serde(rename = "Key")]
+	pub key: Value,
+	#[serde(rename = "VersionId")]
+	pub version_id: Value,
+	#[serde(rename = "IsLatest")]
+	pub is_latest: Value,
+	#[serde(rename = "LastModified")]
+	pub last_modified: Value,
+	#[serde(rename = "ETag")]
+	pub etag: Value,
+	#[serde(rename = "Size")]
+	pub size: IntValue,
+	#[serde(rename = "StorageClass")]
+	pub storage_class: Value,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ListDeleteMarkerItem {
+	#[serde(rename = "Key")]
+	pub key: Value,
+	#[serde(rename = "VersionId")]
+	pub version_id: Value,
+	#[serde(rename = "IsLatest")]
+	pub is_latest: Value,
+	#[serde(rename = "LastModified")]
+	pub last_modified: Value,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ListVersionsResult {
 	#[serde(serialize_with = "xmlns_tag")]
 	pub xmlns: (),
+	#[serde(rename = "Name")]
+	pub name: Value,
+	#[serde(rename = "Prefix")]
+	pub prefix: Value,
+	#[serde(rename = "KeyMarker")]
+	pub key_marker: Option<Value>,
+	#[serde(rename = "VersionIdMarker")]
+	pub version_id_marker: Option<Value>,
+	#[serde(rename = "NextKeyMarker")]
+	pub next_key_marker: Option<Value>,
+	#[serde(rename = "NextVersionIdMarker")]
+	pub next_version_id_marker: Option<Value>,
+	#[serde(rename = "MaxKeys")]
+	pub max_keys: IntValue,
+	#[serde(rename = "Delimiter")]
+	pub delimiter: Option<Value>,
+	#[serde(rename = "EncodingType")]
+	pub encoding_type: Option<Value>,
+	#[serde(rename = "IsTruncated")]
+	pub is_truncated: Value,
+	#[serde(rename = "Version")]
+	pub versions: Vec<ListVersionsItem>,
+	#[serde(rename = "DeleteMarker")]
+	pub delete_markers: Vec<ListDeleteMarkerItem>,
+	#[serde(rename = "CommonPrefixes")]
+	pub common_prefixes: Vec<CommonPrefix>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VersioningConfiguration {
+	#[serde(serialize_with = "xmlns_tag", skip_deserializing)]
+	pub xmlns: (),
 	#[serde(rename = "Status")]
 	pub status: Option<Value>,
 }
 
+#[derive(Debug, Serialize, PartialEq, Eq, Default)]
+pub struct ObjectAttributesChecksum {
+	#[serde(rename = "ChecksumCRC32")]
+	pub checksum_crc32: Option<Value>,
+	#[serde(rename = "ChecksumCRC32C")]
+	pub checksum_crc32c: Option<Value>,
+	#[serde(rename = "ChecksumSHA1")]
+	pub checksum_sha1: Option<Value>,
+	#[serde(rename = "ChecksumSHA256")]
+	pub checksum_sha256: Option<Value>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ObjectAttributesPart {
+	#[serde(rename = "PartNumber")]
+	pub part_number: IntValue,
+	#[serde(rename = "Size")]
+	pub size: IntValue,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ObjectAttributesParts {
+	#[serde(rename = "TotalPartsCount")]
+	pub total_parts_count: IntValue,
+	#[serde(rename = "PartNumberMarker")]
+	pub part_number_marker: Option<IntValue>,
+	#[serde(rename = "NextPartNumberMarker")]
+	pub next_part_number_marker: Option<IntValue>,
+	#[serde(rename = "MaxParts")]
+	pub max_parts: IntValue,
+	#[serde(rename = "IsTruncated")]
+	pub is_truncated: Value,
+	#[serde(rename = "Part", default)]
+	pub parts: Vec<ObjectAttributesPart>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct GetObjectAttributesOutput {
+	#[serde(serialize_with = "xmlns_tag")]
+	pub xmlns: (),
+	#[serde(rename = "ETag")]
+	pub etag: Option<Value>,
+	#[serde(rename = "Checksum")]
+	pub checksum: Option<ObjectAttributesChecksum>,
+	#[serde(rename = "ObjectParts")]
+	pub object_parts: Option<ObjectAttributesParts>,
+	#[serde(rename = "StorageClass")]
+	pub storage_class: Option<Value>,
+	#[serde(rename = "ObjectSize")]
+	pub object_size: Option<IntValue>,
+}
+
 #[derive(Debug, Serialize, PartialEq, Eq)]
 pub struct PostObject {
 	#[serde(serialize_with = "xmlns_tag")]
@@ -609,6 +725,10 @@ mod tests {
 				etag: Value("\"bf1d737a4d46a19f3bced6905cc8b902\"".to_string()),
 				size: IntValue(142863),
 				storage_class: Value("STANDARD".to_string()),
+				owner: Some(Owner {
+					display_name: Value("owner_name".to_string()),
+					id: Value("qsdfjklm".to_string()),
+				}),
 			}],
 			common_prefixes: vec![CommonPrefix {
 				prefix: Value("photos/".to_string()),
@@ -630,6 +750,10 @@ mod tests {
     <ETag>&quot;bf1d737a4d46a19f3bced6905cc8b902&quot;</ETag>\
     <Size>142863</Size>\
     <StorageClass>STANDARD</StorageClass>\
+    <Owner>\
+      <DisplayName>owner_name</DisplayName>\
+      <ID>qsdfjklm</ID>\
+    </Owner>\
   </Contents>\
   <CommonPrefixes>\
     <Prefix>photos/</Prefix>\
@@ -708,6 +832,7 @@ mod tests {
 				etag: Value("\"599bab3ed2c697f1d26842727561fd94\"".to_string()),
 				size: IntValue(857),
 				storage_class: Value("REDUCED_REDUNDANCY".to_string()),
+				owner: None,
 			}],
 			common_prefixes: vec![],
 		};
@@ -756,6 +881,7 @@ mod tests {
 				etag: Value("\"70ee1738b6b21e2c8a43f3a5ab0eee71\"".to_string()),
 				size: IntValue(1111),
 				storage_class: Value("STANDARD".to_string()),
+				owner: None,
 			}],
 			common_prefixes: vec![],
 		};
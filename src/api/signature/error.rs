@@ -11,13 +11,17 @@ pub enum Error {
 	Common(CommonError),
 
 	/// Authorization Header Malformed
-	#[error(display = "Authorization header malformed, unexpected scope: {}", _0)]
+	#[error(display = "Authorization header malformed, {}", _0)]
 	AuthorizationHeaderMalformed(String),
 
 	// Category: bad request
 	/// The request contained an invalid UTF-8 sequence in its path or in other parameters
 	#[error(display = "Invalid UTF-8: {}", _0)]
 	InvalidUtf8Str(#[error(source)] std::str::Utf8Error),
+
+	/// The checksum sent in the payload trailer does not match the received data
+	#[error(display = "Bad digest: {}", _0)]
+	BadDigest(String),
 }
 
 impl<T> From<T> for Error
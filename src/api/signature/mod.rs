@@ -21,21 +21,36 @@ type HmacSha256 = Hmac<Sha256>;
 
 pub async fn verify_request(
 	garage: &Garage,
-	mut req: Request<IncomingBody>,
+	req: Request<IncomingBody>,
 	service: &'static str,
 ) -> Result<(Request<streaming::ReqBody>, Key, Option<Hash>), Error> {
-	let (api_key, mut content_sha256) =
-		payload::check_payload_signature(&garage, &mut req, service).await?;
+	let (req, api_key, content_sha256) =
+		verify_request_allow_anonymous(garage, req, service).await?;
 	let api_key =
 		api_key.ok_or_else(|| Error::forbidden("Garage does not support anonymous access yet"))?;
+	Ok((req, api_key, content_sha256))
+}
+
+/// Same as `verify_request`, but unsigned requests are accepted and
+/// returned without an access key
+pub async fn verify_request_allow_anonymous(
+	garage: &Garage,
+	mut req: Request<IncomingBody>,
+	service: &'static str,
+) -> Result<(Request<streaming::ReqBody>, Option<Key>, Option<Hash>), Error> {
+	let (api_key, mut content_sha256) =
+		payload::check_payload_signature(&garage, &mut req, service).await?;
 
-	let req = streaming::parse_streaming_body(
-		&api_key,
-		req,
-		&mut content_sha256,
-		&garage.config.s3_api.s3_region,
-		service,
-	)?;
+	let req = match &api_key {
+		Some(api_key) => streaming::parse_streaming_body(
+			api_key,
+			req,
+			&mut content_sha256,
+			&garage.config.s3_api.s3_region,
+			service,
+		)?,
+		None => streaming::parse_unsigned_body(req)?,
+	};
 
 	Ok((req, api_key, content_sha256))
 }
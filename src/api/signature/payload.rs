@@ -26,10 +26,14 @@ pub const X_AMZ_EXPIRES: HeaderName = HeaderName::from_static("x-amz-expires");
 pub const X_AMZ_SIGNEDHEADERS: HeaderName = HeaderName::from_static("x-amz-signedheaders");
 pub const X_AMZ_SIGNATURE: HeaderName = HeaderName::from_static("x-amz-signature");
 pub const X_AMZ_CONTENT_SH256: HeaderName = HeaderName::from_static("x-amz-content-sha256");
+pub const X_AMZ_TRAILER: HeaderName = HeaderName::from_static("x-amz-trailer");
 
 pub const AWS4_HMAC_SHA256: &str = "AWS4-HMAC-SHA256";
 pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
 pub const STREAMING_AWS4_HMAC_SHA256_PAYLOAD: &str = "STREAMING-AWS4-HMAC-SHA256-PAYLOAD";
+pub const STREAMING_AWS4_HMAC_SHA256_PAYLOAD_TRAILER: &str =
+	"STREAMING-AWS4-HMAC-SHA256-PAYLOAD-TRAILER";
+pub const STREAMING_UNSIGNED_PAYLOAD_TRAILER: &str = "STREAMING-UNSIGNED-PAYLOAD-TRAILER";
 
 pub type QueryMap = HeaderMap<QueryValue>;
 pub struct QueryValue {
@@ -55,10 +59,10 @@ pub async fn check_payload_signature(
 		check_standard_signature(garage, service, request, query).await
 	} else {
 		// Unsigned (anonymous) request
-		let content_sha256 = request
-			.headers()
-			.get("x-amz-content-sha256")
-			.filter(|c| c.as_bytes() != UNSIGNED_PAYLOAD.as_bytes());
+		let content_sha256 = request.headers().get("x-amz-content-sha256").filter(|c| {
+			c.as_bytes() != UNSIGNED_PAYLOAD.as_bytes()
+				&& c.as_bytes() != STREAMING_UNSIGNED_PAYLOAD_TRAILER.as_bytes()
+		});
 		if let Some(content_sha256) = content_sha256 {
 			let sha256 = hex::decode(content_sha256)
 				.ok()
@@ -108,9 +112,13 @@ async fn check_standard_signature(
 
 	let key = verify_v4(garage, service, &authorization, string_to_sign.as_bytes()).await?;
 
-	let content_sha256 = if authorization.content_sha256 == UNSIGNED_PAYLOAD {
+	let content_sha256 = if authorization.content_sha256 == UNSIGNED_PAYLOAD
+		|| authorization.content_sha256 == STREAMING_UNSIGNED_PAYLOAD_TRAILER
+	{
 		None
-	} else if authorization.content_sha256 == STREAMING_AWS4_HMAC_SHA256_PAYLOAD {
+	} else if authorization.content_sha256 == STREAMING_AWS4_HMAC_SHA256_PAYLOAD
+		|| authorization.content_sha256 == STREAMING_AWS4_HMAC_SHA256_PAYLOAD_TRAILER
+	{
 		let bytes = hex::decode(authorization.signature).ok_or_bad_request("Invalid signature")?;
 		Some(Hash::try_from(&bytes).ok_or_bad_request("Invalid signature")?)
 	} else {
@@ -357,6 +365,42 @@ pub fn parse_date(date: &str) -> Result<DateTime<Utc>, Error> {
 	Ok(Utc.from_utc_datetime(&date))
 }
 
+/// Describe how the credential scope sent by a client differs from the one
+/// expected by Garage, so that a misconfigured client (most often, a wrong
+/// region) can be fixed from the error message alone
+fn scope_mismatch(received: &str, expected: &str) -> String {
+	const COMPONENTS: [&str; 4] = ["date", "region", "service", "terminator"];
+
+	let received_parts = received.split('/').collect::<Vec<_>>();
+	let expected_parts = expected.split('/').collect::<Vec<_>>();
+	if received_parts.len() != expected_parts.len() {
+		return format!(
+			"invalid credential scope `{}`, expected `{}`",
+			received, expected
+		);
+	}
+
+	let mut msg = format!(
+		"credential scope `{}` does not match `{}`:",
+		received, expected
+	);
+	for ((name, r), e) in COMPONENTS
+		.iter()
+		.zip(received_parts.iter())
+		.zip(expected_parts.iter())
+	{
+		if r != e {
+			msg.push_str(&format!(" {} is `{}` but `{}` was expected;", name, r, e));
+		}
+	}
+	if received_parts[1] != expected_parts[1] {
+		msg.push_str(" check the region configured in your client");
+	} else {
+		msg.pop();
+	}
+	msg
+}
+
 pub async fn verify_v4(
 	garage: &Garage,
 	service: &str,
@@ -365,7 +409,10 @@ pub async fn verify_v4(
 ) -> Result<Key, Error> {
 	let scope_expected = compute_scope(&auth.date, &garage.config.s3_api.s3_region, service);
 	if auth.scope != scope_expected {
-		return Err(Error::AuthorizationHeaderMalformed(auth.scope.to_string()));
+		return Err(Error::AuthorizationHeaderMalformed(scope_mismatch(
+			&auth.scope,
+			&scope_expected,
+		)));
 	}
 
 	let key = garage
@@ -560,3 +607,31 @@ impl Authorization {
 		Ok(auth)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_scope_mismatch() {
+		let msg = scope_mismatch(
+			"20240101/us-east-1/s3/aws4_request",
+			"20240101/garage/s3/aws4_request",
+		);
+		assert!(msg.contains("region is `us-east-1` but `garage` was expected"));
+		assert!(msg.contains("check the region configured in your client"));
+		assert!(!msg.contains("date is"));
+
+		let msg = scope_mismatch(
+			"20240101/garage/k2v/aws4_request",
+			"20240102/garage/s3/aws4_request",
+		);
+		assert!(msg.contains("date is `20240101` but `20240102` was expected"));
+		assert!(msg.contains("service is `k2v` but `s3` was expected"));
+		assert!(!msg.contains("region"));
+		assert!(!msg.ends_with(';'));
+
+		let msg = scope_mismatch("garage", "20240101/garage/s3/aws4_request");
+		assert!(msg.starts_with("invalid credential scope `garage`"));
+	}
+}
@@ -1,9 +1,11 @@
 use std::pin::Pin;
 
+use base64::prelude::*;
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use futures::prelude::*;
 use futures::task;
 use garage_model::key_table::Key;
+use garage_model::s3::object_table::{ChecksumAlgorithm, ChecksumValue};
 use hmac::Mac;
 use http_body_util::StreamBody;
 use hyper::body::{Bytes, Incoming as IncomingBody};
@@ -14,12 +16,17 @@ use garage_util::data::Hash;
 use super::{compute_scope, sha256sum, HmacSha256, LONG_DATETIME};
 
 use crate::helpers::*;
+use crate::s3::checksum::{Checksummer, ExpectedChecksums};
 use crate::signature::error::*;
 use crate::signature::payload::{
-	STREAMING_AWS4_HMAC_SHA256_PAYLOAD, X_AMZ_CONTENT_SH256, X_AMZ_DATE,
+	STREAMING_AWS4_HMAC_SHA256_PAYLOAD, STREAMING_AWS4_HMAC_SHA256_PAYLOAD_TRAILER,
+	STREAMING_UNSIGNED_PAYLOAD_TRAILER, X_AMZ_CONTENT_SH256, X_AMZ_DATE, X_AMZ_TRAILER,
 };
 
 pub const AWS4_HMAC_SHA256_PAYLOAD: &str = "AWS4-HMAC-SHA256-PAYLOAD";
+pub const AWS4_HMAC_SHA256_TRAILER: &str = "AWS4-HMAC-SHA256-TRAILER";
+
+const X_AMZ_TRAILER_SIGNATURE: &str = "x-amz-trailer-signature";
 
 pub type ReqBody = BoxBody<Error>;
 
@@ -30,44 +37,138 @@ pub fn parse_streaming_body(
 	region: &str,
 	service: &str,
 ) -> Result<Request<ReqBody>, Error> {
+	let with_trailer = match req.headers().get(X_AMZ_CONTENT_SH256) {
+		Some(header) if header == STREAMING_AWS4_HMAC_SHA256_PAYLOAD => false,
+		Some(header) if header == STREAMING_AWS4_HMAC_SHA256_PAYLOAD_TRAILER => true,
+		Some(header) if header == STREAMING_UNSIGNED_PAYLOAD_TRAILER => {
+			return parse_unsigned_trailer_body(req);
+		}
+		_ => {
+			return Ok(
+				req.map(|body| ReqBody::new(http_body_util::BodyExt::map_err(body, Error::from)))
+			)
+		}
+	};
+
+	let signature = content_sha256
+		.take()
+		.ok_or_bad_request("No signature provided")?;
+
+	let secret_key = &api_key
+		.state
+		.as_option()
+		.ok_or_internal_error("Deleted key state")?
+		.secret_key;
+
+	let date = req
+		.headers()
+		.get(X_AMZ_DATE)
+		.ok_or_bad_request("Missing X-Amz-Date field")?
+		.to_str()?;
+	let date: NaiveDateTime =
+		NaiveDateTime::parse_from_str(date, LONG_DATETIME).ok_or_bad_request("Invalid date")?;
+	let date: DateTime<Utc> = Utc.from_utc_datetime(&date);
+
+	let trailer_algorithm = trailer_algorithm(&req, with_trailer)?;
+
+	let scope = compute_scope(&date, region, service);
+	let signing_hmac = crate::signature::signing_hmac(&date, secret_key, region, service)
+		.ok_or_internal_error("Unable to build signing HMAC")?;
+
+	Ok(req.map(move |body| {
+		let stream = body_stream::<_, Error>(body);
+		let signed_payload_stream =
+			SignedPayloadStream::new(stream, signing_hmac, date, &scope, signature);
+		payload_stream_body(signed_payload_stream, trailer_algorithm)
+	}))
+}
+
+/// Body of a request made without an access key, which cannot use a
+/// streaming signature
+pub fn parse_unsigned_body(req: Request<IncomingBody>) -> Result<Request<ReqBody>, Error> {
 	match req.headers().get(X_AMZ_CONTENT_SH256) {
-		Some(header) if header == STREAMING_AWS4_HMAC_SHA256_PAYLOAD => {
-			let signature = content_sha256
-				.take()
-				.ok_or_bad_request("No signature provided")?;
-
-			let secret_key = &api_key
-				.state
-				.as_option()
-				.ok_or_internal_error("Deleted key state")?
-				.secret_key;
-
-			let date = req
-				.headers()
-				.get(X_AMZ_DATE)
-				.ok_or_bad_request("Missing X-Amz-Date field")?
-				.to_str()?;
-			let date: NaiveDateTime = NaiveDateTime::parse_from_str(date, LONG_DATETIME)
-				.ok_or_bad_request("Invalid date")?;
-			let date: DateTime<Utc> = Utc.from_utc_datetime(&date);
-
-			let scope = compute_scope(&date, region, service);
-			let signing_hmac = crate::signature::signing_hmac(&date, secret_key, region, service)
-				.ok_or_internal_error("Unable to build signing HMAC")?;
-
-			Ok(req.map(move |body| {
-				let stream = body_stream::<_, Error>(body);
-				let signed_payload_stream =
-					SignedPayloadStream::new(stream, signing_hmac, date, &scope, signature)
-						.map(|x| x.map(hyper::body::Frame::data))
-						.map_err(Error::from);
-				ReqBody::new(StreamBody::new(signed_payload_stream))
-			}))
+		Some(header)
+			if header == STREAMING_AWS4_HMAC_SHA256_PAYLOAD
+				|| header == STREAMING_AWS4_HMAC_SHA256_PAYLOAD_TRAILER =>
+		{
+			Err(Error::bad_request(
+				"Streaming signature requires an access key",
+			))
+		}
+		Some(header) if header == STREAMING_UNSIGNED_PAYLOAD_TRAILER => {
+			parse_unsigned_trailer_body(req)
 		}
 		_ => Ok(req.map(|body| ReqBody::new(http_body_util::BodyExt::map_err(body, Error::from)))),
 	}
 }
 
+/// Body sent with STREAMING-UNSIGNED-PAYLOAD-TRAILER: chunks are not signed,
+/// but the checksum sent in the trailer is checked against the data
+fn parse_unsigned_trailer_body(req: Request<IncomingBody>) -> Result<Request<ReqBody>, Error> {
+	let trailer_algorithm = trailer_algorithm(&req, true)?;
+	Ok(req.map(move |body| {
+		let stream = body_stream::<_, Error>(body);
+		payload_stream_body(SignedPayloadStream::new_unsigned(stream), trailer_algorithm)
+	}))
+}
+
+fn payload_stream_body<S>(
+	mut stream: SignedPayloadStream<S>,
+	trailer_algorithm: Option<ChecksumAlgorithm>,
+) -> ReqBody
+where
+	S: Stream<Item = Result<Bytes, Error>> + Unpin + Send + Sync + 'static,
+{
+	if let Some(algo) = trailer_algorithm {
+		stream = stream.with_trailer_checksum(algo);
+	}
+	let stream = stream
+		.map(|x| x.map(hyper::body::Frame::data))
+		.map_err(Error::from);
+	ReqBody::new(StreamBody::new(stream))
+}
+
+/// Checksum algorithm announced in the x-amz-trailer header, which is
+/// mandatory for the streaming modes that send a trailer
+fn trailer_algorithm(
+	req: &Request<IncomingBody>,
+	with_trailer: bool,
+) -> Result<Option<ChecksumAlgorithm>, Error> {
+	if !with_trailer {
+		return Ok(None);
+	}
+	let trailer = req
+		.headers()
+		.get(X_AMZ_TRAILER)
+		.ok_or_bad_request("Missing x-amz-trailer header")?
+		.to_str()?;
+	Ok(Some(parse_trailer_algorithm(trailer)?))
+}
+
+/// Parse the value of the x-amz-trailer header, which announces which
+/// checksum header will be sent after the last chunk of the payload
+fn parse_trailer_algorithm(trailer: &str) -> Result<ChecksumAlgorithm, Error> {
+	match trailer.trim() {
+		"x-amz-checksum-crc32" => Ok(ChecksumAlgorithm::Crc32),
+		"x-amz-checksum-crc32c" => Ok(ChecksumAlgorithm::Crc32c),
+		"x-amz-checksum-sha1" => Ok(ChecksumAlgorithm::Sha1),
+		"x-amz-checksum-sha256" => Ok(ChecksumAlgorithm::Sha256),
+		_ => Err(Error::bad_request(format!(
+			"Unsupported x-amz-trailer: {}",
+			trailer
+		))),
+	}
+}
+
+fn trailer_checksum_name(algo: ChecksumAlgorithm) -> &'static str {
+	match algo {
+		ChecksumAlgorithm::Crc32 => "x-amz-checksum-crc32",
+		ChecksumAlgorithm::Crc32c => "x-amz-checksum-crc32c",
+		ChecksumAlgorithm::Sha1 => "x-amz-checksum-sha1",
+		ChecksumAlgorithm::Sha256 => "x-amz-checksum-sha256",
+	}
+}
+
 /// Result of `sha256("")`
 const EMPTY_STRING_HEX_DIGEST: &str =
 	"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
@@ -95,12 +196,35 @@ fn compute_streaming_payload_signature(
 	Ok(Hash::try_from(&hmac.finalize().into_bytes()).ok_or_internal_error("Invalid signature")?)
 }
 
+fn compute_streaming_trailer_signature(
+	signing_hmac: &HmacSha256,
+	date: DateTime<Utc>,
+	scope: &str,
+	previous_signature: Hash,
+	trailer_sha256: Hash,
+) -> Result<Hash, Error> {
+	let string_to_sign = [
+		AWS4_HMAC_SHA256_TRAILER,
+		&date.format(LONG_DATETIME).to_string(),
+		scope,
+		&hex::encode(previous_signature),
+		&hex::encode(trailer_sha256),
+	]
+	.join("\n");
+
+	let mut hmac = signing_hmac.clone();
+	hmac.update(string_to_sign.as_bytes());
+
+	Ok(Hash::try_from(&hmac.finalize().into_bytes()).ok_or_internal_error("Invalid signature")?)
+}
+
 mod payload {
 	use garage_util::data::Hash;
 
 	pub enum Error<I> {
 		Parser(nom::error::Error<I>),
 		BadSignature,
+		BadTrailer,
 	}
 
 	impl<I> Error<I> {
@@ -108,6 +232,7 @@ mod payload {
 			match *self {
 				Error::Parser(ref e) => e.code.description(),
 				Error::BadSignature => "Bad signature",
+				Error::BadTrailer => "Bad trailer",
 			}
 		}
 	}
@@ -115,11 +240,13 @@ mod payload {
 	#[derive(Debug, Clone)]
 	pub struct Header {
 		pub size: usize,
-		pub signature: Hash,
+		pub signature: Option<Hash>,
 	}
 
 	impl Header {
-		pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Self, Error<&[u8]>> {
+		/// Parse a chunk header, which carries a chunk signature
+		/// only if the payload is signed
+		pub fn parse(input: &[u8], signed: bool) -> nom::IResult<&[u8], Self, Error<&[u8]>> {
 			use nom::bytes::streaming::tag;
 			use nom::character::streaming::hex_digit1;
 			use nom::combinator::map_res;
@@ -132,11 +259,17 @@ mod payload {
 			}
 
 			let (input, size) = try_parse!(hex_u32(input));
-			let (input, _) = try_parse!(tag(";")(input));
 
-			let (input, _) = try_parse!(tag("chunk-signature=")(input));
-			let (input, data) = try_parse!(map_res(hex_digit1, hex::decode)(input));
-			let signature = Hash::try_from(&data).ok_or(nom::Err::Failure(Error::BadSignature))?;
+			let (input, signature) = if signed {
+				let (input, _) = try_parse!(tag(";")(input));
+				let (input, _) = try_parse!(tag("chunk-signature=")(input));
+				let (input, data) = try_parse!(map_res(hex_digit1, hex::decode)(input));
+				let signature =
+					Hash::try_from(&data).ok_or(nom::Err::Failure(Error::BadSignature))?;
+				(input, Some(signature))
+			} else {
+				(input, None)
+			};
 
 			let (input, _) = try_parse!(tag("\r\n")(input));
 
@@ -148,12 +281,59 @@ mod payload {
 			Ok((input, header))
 		}
 	}
+
+	#[derive(Debug, Clone)]
+	pub struct Trailer {
+		pub fields: Vec<(String, String)>,
+	}
+
+	impl Trailer {
+		/// Parse the trailing header lines sent after the last chunk,
+		/// up to and including the final empty line
+		pub fn parse(mut input: &[u8]) -> nom::IResult<&[u8], Self, Error<&[u8]>> {
+			use nom::bytes::streaming::{tag, take_until};
+
+			macro_rules! try_parse {
+				($expr:expr) => {
+					$expr.map_err(|e| e.map(Error::Parser))?
+				};
+			}
+
+			let mut fields = vec![];
+			loop {
+				let (rest, line) = try_parse!(take_until("\r\n")(input));
+				let (rest, _) = try_parse!(tag("\r\n")(rest));
+				input = rest;
+
+				if line.is_empty() {
+					break;
+				}
+
+				let line =
+					std::str::from_utf8(line).map_err(|_| nom::Err::Failure(Error::BadTrailer))?;
+				let (name, value) = line
+					.split_once(':')
+					.ok_or(nom::Err::Failure(Error::BadTrailer))?;
+				fields.push((name.trim().to_lowercase(), value.trim().to_string()));
+			}
+
+			Ok((input, Trailer { fields }))
+		}
+
+		pub fn get(&self, name: &str) -> Option<&str> {
+			self.fields
+				.iter()
+				.find(|(k, _)| k == name)
+				.map(|(_, v)| v.as_str())
+		}
+	}
 }
 
 #[derive(Debug)]
 pub enum SignedPayloadStreamError {
 	Stream(Error),
 	InvalidSignature,
+	InvalidChecksum(String),
 	Message(String),
 }
 
@@ -170,6 +350,7 @@ impl From<SignedPayloadStreamError> for Error {
 			SignedPayloadStreamError::InvalidSignature => {
 				Error::bad_request("Invalid payload signature")
 			}
+			SignedPayloadStreamError::InvalidChecksum(e) => Error::BadDigest(e),
 			SignedPayloadStreamError::Message(e) => {
 				Error::bad_request(format!("Chunk format error: {}", e))
 			}
@@ -192,6 +373,81 @@ impl<I> From<nom::error::Error<I>> for SignedPayloadStreamError {
 struct SignedPayload {
 	header: payload::Header,
 	data: Bytes,
+	trailer: Option<payload::Trailer>,
+}
+
+/// State needed to check the signature chain of a signed payload
+struct StreamSigning {
+	datetime: DateTime<Utc>,
+	scope: String,
+	signing_hmac: HmacSha256,
+	previous_signature: Hash,
+}
+
+impl StreamSigning {
+	fn check_chunk(
+		&mut self,
+		signature: Option<Hash>,
+		data: &[u8],
+	) -> Result<(), SignedPayloadStreamError> {
+		let signature = signature.ok_or(SignedPayloadStreamError::InvalidSignature)?;
+
+		let expected_signature = compute_streaming_payload_signature(
+			&self.signing_hmac,
+			self.datetime,
+			&self.scope,
+			self.previous_signature,
+			sha256sum(data),
+		)
+		.map_err(|e| {
+			SignedPayloadStreamError::Message(format!("Could not build signature: {}", e))
+		})?;
+
+		if signature != expected_signature {
+			return Err(SignedPayloadStreamError::InvalidSignature);
+		}
+
+		self.previous_signature = signature;
+		Ok(())
+	}
+
+	/// Check the x-amz-trailer-signature field, which signs all the other
+	/// trailing fields and is chained to the signature of the last chunk
+	fn check_trailer(&self, trailer: &payload::Trailer) -> Result<(), SignedPayloadStreamError> {
+		let signature = trailer
+			.get(X_AMZ_TRAILER_SIGNATURE)
+			.and_then(|sig| hex::decode(sig).ok())
+			.and_then(|sig| Hash::try_from(&sig))
+			.ok_or_else(|| {
+				SignedPayloadStreamError::Message(format!(
+					"Missing or invalid {} in trailer",
+					X_AMZ_TRAILER_SIGNATURE
+				))
+			})?;
+
+		let canonical_trailer = trailer
+			.fields
+			.iter()
+			.filter(|(k, _)| k != X_AMZ_TRAILER_SIGNATURE)
+			.map(|(k, v)| format!("{}:{}\n", k, v))
+			.collect::<String>();
+
+		let expected_signature = compute_streaming_trailer_signature(
+			&self.signing_hmac,
+			self.datetime,
+			&self.scope,
+			self.previous_signature,
+			sha256sum(canonical_trailer.as_bytes()),
+		)
+		.map_err(|e| {
+			SignedPayloadStreamError::Message(format!("Could not build signature: {}", e))
+		})?;
+
+		if signature != expected_signature {
+			return Err(SignedPayloadStreamError::InvalidSignature);
+		}
+		Ok(())
+	}
 }
 
 #[pin_project::pin_project]
@@ -202,10 +458,8 @@ where
 	#[pin]
 	stream: S,
 	buf: bytes::BytesMut,
-	datetime: DateTime<Utc>,
-	scope: String,
-	signing_hmac: HmacSha256,
-	previous_signature: Hash,
+	signing: Option<StreamSigning>,
+	trailer_checksum: Option<(ChecksumAlgorithm, Checksummer)>,
 }
 
 impl<S> SignedPayloadStream<S>
@@ -222,14 +476,40 @@ where
 		Self {
 			stream,
 			buf: bytes::BytesMut::new(),
-			datetime,
-			scope: scope.into(),
-			signing_hmac,
-			previous_signature: seed_signature,
+			signing: Some(StreamSigning {
+				datetime,
+				scope: scope.into(),
+				signing_hmac,
+				previous_signature: seed_signature,
+			}),
+			trailer_checksum: None,
 		}
 	}
 
-	fn parse_next(input: &[u8]) -> nom::IResult<&[u8], SignedPayload, SignedPayloadStreamError> {
+	/// Stream of chunks without chunk signatures, as sent with
+	/// STREAMING-UNSIGNED-PAYLOAD-TRAILER
+	pub fn new_unsigned(stream: S) -> Self {
+		Self {
+			stream,
+			buf: bytes::BytesMut::new(),
+			signing: None,
+			trailer_checksum: None,
+		}
+	}
+
+	/// Expect a trailer containing a checksum of the given algorithm after
+	/// the last chunk, and check it against the streamed data
+	pub fn with_trailer_checksum(mut self, algo: ChecksumAlgorithm) -> Self {
+		let checksummer = Checksummer::init(&ExpectedChecksums::default(), false).add(Some(algo));
+		self.trailer_checksum = Some((algo, checksummer));
+		self
+	}
+
+	fn parse_next(
+		input: &[u8],
+		signed: bool,
+		with_trailer: bool,
+	) -> nom::IResult<&[u8], SignedPayload, SignedPayloadStreamError> {
 		use nom::bytes::streaming::{tag, take};
 
 		macro_rules! try_parse {
@@ -238,15 +518,22 @@ where
 			};
 		}
 
-		let (input, header) = try_parse!(payload::Header::parse(input));
+		let (input, header) = try_parse!(payload::Header::parse(input, signed));
 
 		// 0-sized chunk is the last
 		if header.size == 0 {
+			let (input, trailer) = if with_trailer {
+				let (input, trailer) = try_parse!(payload::Trailer::parse(input));
+				(input, Some(trailer))
+			} else {
+				(input, None)
+			};
 			return Ok((
 				input,
 				SignedPayload {
 					header,
 					data: Bytes::new(),
+					trailer,
 				},
 			));
 		}
@@ -256,7 +543,14 @@ where
 
 		let data = Bytes::from(data.to_vec());
 
-		Ok((input, SignedPayload { header, data }))
+		Ok((
+			input,
+			SignedPayload {
+				header,
+				data,
+				trailer: None,
+			},
+		))
 	}
 }
 
@@ -275,7 +569,9 @@ where
 		let mut this = self.project();
 
 		loop {
-			let (input, payload) = match Self::parse_next(this.buf) {
+			let signed = this.signing.is_some();
+			let with_trailer = this.trailer_checksum.is_some();
+			let (input, payload) = match Self::parse_next(this.buf, signed, with_trailer) {
 				Ok(res) => res,
 				Err(nom::Err::Incomplete(_)) => {
 					match futures::ready!(this.stream.as_mut().poll_next(cx)) {
@@ -300,28 +596,38 @@ where
 
 			// 0-sized chunk is the last
 			if payload.data.is_empty() {
+				if let Some((algo, checksummer)) = this.trailer_checksum.take() {
+					let trailer = payload
+						.trailer
+						.unwrap_or(payload::Trailer { fields: vec![] });
+					if let Some(signing) = this.signing.as_mut() {
+						// The trailer signature is chained to the one of the
+						// final chunk, so both must be checked
+						let res = signing
+							.check_chunk(payload.header.signature, &[])
+							.and_then(|()| signing.check_trailer(&trailer));
+						if let Err(e) = res {
+							return Poll::Ready(Some(Err(e)));
+						}
+					}
+					if let Err(e) = check_trailer_checksum(algo, checksummer, &trailer) {
+						return Poll::Ready(Some(Err(e)));
+					}
+				}
 				return Poll::Ready(None);
 			}
 
-			let data_sha256sum = sha256sum(&payload.data);
-
-			let expected_signature = compute_streaming_payload_signature(
-				this.signing_hmac,
-				*this.datetime,
-				this.scope,
-				*this.previous_signature,
-				data_sha256sum,
-			)
-			.map_err(|e| {
-				SignedPayloadStreamError::Message(format!("Could not build signature: {}", e))
-			})?;
-
-			if payload.header.signature != expected_signature {
-				return Poll::Ready(Some(Err(SignedPayloadStreamError::InvalidSignature)));
+			if let Some(signing) = this.signing.as_mut() {
+				if let Err(e) = signing.check_chunk(payload.header.signature, &payload.data) {
+					return Poll::Ready(Some(Err(e)));
+				}
 			}
 
 			*this.buf = input.into();
-			*this.previous_signature = payload.header.signature;
+
+			if let Some((_, checksummer)) = this.trailer_checksum.as_mut() {
+				checksummer.update(&payload.data);
+			}
 
 			return Poll::Ready(Some(Ok(payload.data)));
 		}
@@ -332,12 +638,200 @@ where
 	}
 }
 
+fn check_trailer_checksum(
+	algo: ChecksumAlgorithm,
+	checksummer: Checksummer,
+	trailer: &payload::Trailer,
+) -> Result<(), SignedPayloadStreamError> {
+	let name = trailer_checksum_name(algo);
+	let value = trailer
+		.get(name)
+		.ok_or_else(|| SignedPayloadStreamError::Message(format!("Missing {} in trailer", name)))?;
+	let value = BASE64_STANDARD
+		.decode(value)
+		.map_err(|_| SignedPayloadStreamError::Message(format!("Invalid {} in trailer", name)))?;
+
+	let expected = match checksummer.finalize().extract(Some(algo)) {
+		Some(ChecksumValue::Crc32(x)) => x.to_vec(),
+		Some(ChecksumValue::Crc32c(x)) => x.to_vec(),
+		Some(ChecksumValue::Sha1(x)) => x.to_vec(),
+		Some(ChecksumValue::Sha256(x)) => x.to_vec(),
+		None => unreachable!(),
+	};
+
+	if value != expected {
+		return Err(SignedPayloadStreamError::InvalidChecksum(format!(
+			"{} from trailer does not match the received data",
+			name
+		)));
+	}
+	Ok(())
+}
+
 #[cfg(test)]
 mod tests {
 	use futures::prelude::*;
 
 	use super::{SignedPayloadStream, SignedPayloadStreamError};
 
+	async fn stream_with_trailer(
+		trailer: &str,
+		bad_trailer_signature: bool,
+	) -> Result<Vec<u8>, SignedPayloadStreamError> {
+		use chrono::{DateTime, Utc};
+
+		use garage_model::s3::object_table::ChecksumAlgorithm;
+		use garage_util::data::Hash;
+
+		let datetime = DateTime::parse_from_rfc3339("2021-12-13T13:12:42+01:00")
+			.unwrap()
+			.with_timezone(&Utc);
+		let secret_key = "test";
+		let region = "test";
+		let scope = crate::signature::compute_scope(&datetime, region, "s3");
+		let signing_hmac =
+			crate::signature::signing_hmac(&datetime, secret_key, region, "s3").unwrap();
+
+		let data = b"hello world";
+		let seed_signature = Hash::default();
+		let chunk_signature = super::compute_streaming_payload_signature(
+			&signing_hmac,
+			datetime,
+			&scope,
+			seed_signature,
+			crate::signature::sha256sum(data),
+		)
+		.unwrap();
+		let final_signature = super::compute_streaming_payload_signature(
+			&signing_hmac,
+			datetime,
+			&scope,
+			chunk_signature,
+			crate::signature::sha256sum(b""),
+		)
+		.unwrap();
+
+		let trailer = with_crc32(trailer, data);
+		let trailer_signature = super::compute_streaming_trailer_signature(
+			&signing_hmac,
+			datetime,
+			&scope,
+			if bad_trailer_signature {
+				chunk_signature
+			} else {
+				final_signature
+			},
+			crate::signature::sha256sum(format!("{}\n", trailer).as_bytes()),
+		)
+		.unwrap();
+
+		let mut body = format!(
+			"{:x};chunk-signature={}\r\n",
+			data.len(),
+			hex::encode(chunk_signature)
+		)
+		.into_bytes();
+		body.extend_from_slice(data);
+		body.extend_from_slice(
+			format!(
+				"\r\n0;chunk-signature={}\r\n{}\r\nx-amz-trailer-signature:{}\r\n\r\n",
+				hex::encode(final_signature),
+				trailer,
+				hex::encode(trailer_signature)
+			)
+			.as_bytes(),
+		);
+
+		let stream = SignedPayloadStream::new(
+			body_pieces(body),
+			signing_hmac,
+			datetime,
+			&scope,
+			seed_signature,
+		)
+		.with_trailer_checksum(ChecksumAlgorithm::Crc32);
+
+		let chunks = stream.try_collect::<Vec<_>>().await?;
+		Ok(chunks.concat())
+	}
+
+	async fn unsigned_stream_with_trailer(
+		trailer: &str,
+	) -> Result<Vec<u8>, SignedPayloadStreamError> {
+		use garage_model::s3::object_table::ChecksumAlgorithm;
+
+		let data = b"hello world";
+		let body = format!(
+			"{:x}\r\n{}\r\n0\r\n{}\r\n\r\n",
+			data.len(),
+			std::str::from_utf8(data).unwrap(),
+			with_crc32(trailer, data)
+		)
+		.into_bytes();
+
+		let stream = SignedPayloadStream::new_unsigned(body_pieces(body))
+			.with_trailer_checksum(ChecksumAlgorithm::Crc32);
+
+		let chunks = stream.try_collect::<Vec<_>>().await?;
+		Ok(chunks.concat())
+	}
+
+	fn with_crc32(trailer: &str, data: &[u8]) -> String {
+		use base64::prelude::*;
+
+		trailer.replace(
+			"$CRC32",
+			&BASE64_STANDARD.encode(crc32fast::hash(data).to_be_bytes()),
+		)
+	}
+
+	// feed the body in small pieces to exercise incomplete parses
+	fn body_pieces(
+		body: Vec<u8>,
+	) -> impl Stream<Item = Result<hyper::body::Bytes, crate::signature::error::Error>> {
+		let pieces = body
+			.chunks(7)
+			.map(|x| Ok(x.to_vec().into()))
+			.collect::<Vec<_>>();
+		futures::stream::iter(pieces)
+	}
+
+	#[tokio::test]
+	async fn test_signed_payload_stream_trailer() {
+		let data = stream_with_trailer("x-amz-checksum-crc32:$CRC32", false)
+			.await
+			.unwrap();
+		assert_eq!(data, b"hello world");
+
+		match stream_with_trailer("x-amz-checksum-crc32:AAAAAA==", false).await {
+			Err(SignedPayloadStreamError::InvalidChecksum(_)) => (),
+			r => panic!("Unexpected result, expected invalid checksum, got {:?}", r),
+		}
+
+		match stream_with_trailer("x-amz-checksum-sha1:AAAAAA==", false).await {
+			Err(SignedPayloadStreamError::Message(_)) => (),
+			r => panic!("Unexpected result, expected missing checksum, got {:?}", r),
+		}
+
+		match stream_with_trailer("x-amz-checksum-crc32:$CRC32", true).await {
+			Err(SignedPayloadStreamError::InvalidSignature) => (),
+			r => panic!("Unexpected result, expected invalid signature, got {:?}", r),
+		}
+	}
+
+	#[tokio::test]
+	async fn test_unsigned_payload_stream_trailer() {
+		let data = unsigned_stream_with_trailer("x-amz-checksum-crc32:$CRC32")
+			.await
+			.unwrap();
+		assert_eq!(data, b"hello world");
+
+		match unsigned_stream_with_trailer("x-amz-checksum-crc32:AAAAAA==").await {
+			Err(SignedPayloadStreamError::InvalidChecksum(_)) => (),
+			r => panic!("Unexpected result, expected invalid checksum, got {:?}", r),
+		}
+	}
+
 	#[tokio::test]
 	async fn test_interrupted_signed_payload_stream() {
 		use chrono::{DateTime, Utc};
@@ -94,6 +94,52 @@ impl DataBlock {
 		.await
 		.unwrap()
 	}
+
+	/// Re-encode a block with the given compression level, if the form in which
+	/// it is currently stored is not the one we would produce with that level.
+	/// The content is checked against `hash` both before and after re-encoding.
+	/// Returns `None` if the block does not need to be rewritten.
+	pub async fn recompress(
+		&self,
+		hash: Hash,
+		level: Option<i32>,
+	) -> Result<Option<DataBlock>, Error> {
+		let header = self.header;
+		let data = self.elem.clone();
+		tokio::task::spawn_blocking(move || {
+			let plain: Bytes = match header {
+				DataBlockHeader::Plain => data,
+				DataBlockHeader::Compressed => zstd::stream::decode_all(&data[..])
+					.map_err(|_| Error::CorruptData(hash))?
+					.into(),
+			};
+			if blake2sum(&plain) != hash {
+				return Err(Error::CorruptData(hash));
+			}
+
+			if header.is_compressed() == level.is_some() {
+				return Ok(None);
+			}
+
+			let new_block = match level {
+				Some(level) => {
+					let compressed = zstd_encode(&plain[..], level)?;
+					let check = zstd::stream::decode_all(&compressed[..])?;
+					if blake2sum(&check) != hash {
+						return Err(Error::Message(format!(
+							"block {:?} does not match its hash after compression",
+							hash
+						)));
+					}
+					DataBlock::compressed(compressed.into())
+				}
+				None => DataBlock::plain(plain),
+			};
+			Ok(Some(new_block))
+		})
+		.await
+		.unwrap()
+	}
 }
 
 pub fn zstd_encode<R: std::io::Read>(mut source: R, level: i32) -> std::io::Result<Vec<u8>> {
@@ -104,3 +150,68 @@ pub fn zstd_encode<R: std::io::Read>(mut source: R, level: i32) -> std::io::Resu
 	encoder.finish()?;
 	Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+	use rand::Rng;
+
+	use super::*;
+	use crate::metrics::CompressionStats;
+
+	#[tokio::test]
+	async fn test_recompress() {
+		let data = Bytes::from(vec![42u8; 10_000]);
+		let hash = blake2sum(&data);
+
+		let plain = DataBlock::plain(data.clone());
+		assert!(plain.recompress(hash, None).await.unwrap().is_none());
+
+		let compressed = plain.recompress(hash, Some(1)).await.unwrap().unwrap();
+		assert!(compressed.header.is_compressed());
+		assert!(compressed.elem.len() < data.len());
+		assert!(compressed
+			.recompress(hash, Some(3))
+			.await
+			.unwrap()
+			.is_none());
+
+		let plain_again = compressed.recompress(hash, None).await.unwrap().unwrap();
+		assert!(!plain_again.header.is_compressed());
+		assert_eq!(plain_again.elem, data);
+
+		let corrupted = DataBlock::plain(Bytes::from(vec![43u8; 10_000]));
+		assert!(matches!(
+			corrupted.recompress(hash, Some(1)).await,
+			Err(Error::CorruptData(_))
+		));
+	}
+
+	#[tokio::test]
+	async fn test_compression_ratio() {
+		let compressible = Bytes::from(vec![42u8; 100_000]);
+		let mut incompressible = vec![0u8; 100_000];
+		rand::thread_rng().fill(&mut incompressible[..]);
+		let incompressible = Bytes::from(incompressible);
+
+		let stats = CompressionStats::default();
+		assert!(stats.ratio().is_none());
+		let block = DataBlock::from_buffer(compressible.clone(), Some(1)).await;
+		stats.record(compressible.len() as u64, block.elem.len() as u64);
+		assert!(stats.ratio().unwrap() > 10.);
+
+		let stats = CompressionStats::default();
+		let block = DataBlock::from_buffer(incompressible.clone(), Some(1)).await;
+		stats.record(incompressible.len() as u64, block.elem.len() as u64);
+		let ratio = stats.ratio().unwrap();
+		assert!(ratio > 0.9 && ratio <= 1.0, "{}", ratio);
+
+		// Mixing both kinds of data gives a ratio in between
+		let stats = CompressionStats::default();
+		for data in [compressible, incompressible] {
+			let block = DataBlock::from_buffer(data.clone(), Some(1)).await;
+			stats.record(data.len() as u64, block.elem.len() as u64);
+		}
+		let ratio = stats.ratio().unwrap();
+		assert!(ratio > 1.5 && ratio < 2.1, "{}", ratio);
+	}
+}
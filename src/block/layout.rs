@@ -371,3 +371,96 @@ fn dir_not_empty(path: &PathBuf) -> Result<bool, Error> {
 	}
 	Ok(false)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use garage_util::config::DataDir as ConfigDataDir;
+	use garage_util::data::{blake2sum, gen_uuid};
+
+	fn make_dirs(n: usize) -> Vec<PathBuf> {
+		let base = std::env::temp_dir().join(format!(
+			"garage-layout-test-{}",
+			hex::encode(&gen_uuid().as_slice()[..8])
+		));
+		(0..n)
+			.map(|i| {
+				let path = base.join(format!("dir{}", i));
+				std::fs::create_dir_all(&path).unwrap();
+				path
+			})
+			.collect()
+	}
+
+	fn config(dirs: &[(&PathBuf, &str)]) -> DataDirEnum {
+		DataDirEnum::Multiple(
+			dirs.iter()
+				.map(|(path, cap)| ConfigDataDir {
+					path: (*path).clone(),
+					capacity: Some(cap.to_string()),
+					read_only: false,
+				})
+				.collect(),
+		)
+	}
+
+	fn hashes() -> Vec<Hash> {
+		(0u32..4096).map(|i| blake2sum(&i.to_be_bytes())).collect()
+	}
+
+	#[test]
+	fn blocks_are_spread_by_capacity() {
+		let dirs = make_dirs(2);
+		let layout =
+			DataLayout::initialize(&config(&[(&dirs[0], "1G"), (&dirs[1], "3G")])).unwrap();
+
+		let parts_in_first = layout.part_prim.iter().filter(|x| **x == 0).count();
+		assert_eq!(parts_in_first, DRIVE_NPART / 4);
+
+		let hashes = hashes();
+		let in_first = hashes
+			.iter()
+			.filter(|h| layout.primary_block_dir(h).starts_with(&dirs[0]))
+			.count();
+		assert!(in_first > hashes.len() / 8 && in_first < hashes.len() * 3 / 8);
+
+		// Directory choice only depends on the block hash and the configuration
+		let layout2 =
+			DataLayout::initialize(&config(&[(&dirs[0], "1G"), (&dirs[1], "3G")])).unwrap();
+		for h in hashes.iter() {
+			assert_eq!(layout.primary_block_dir(h), layout2.primary_block_dir(h));
+		}
+
+		std::fs::remove_dir_all(dirs[0].parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn blocks_remain_reachable_after_adding_dir() {
+		let dirs = make_dirs(3);
+		let layout =
+			DataLayout::initialize(&config(&[(&dirs[0], "1G"), (&dirs[1], "1G")])).unwrap();
+		let new_layout = layout
+			.clone()
+			.update(&config(&[
+				(&dirs[0], "1G"),
+				(&dirs[1], "1G"),
+				(&dirs[2], "2G"),
+			]))
+			.unwrap();
+
+		let mut moved = 0;
+		for h in hashes().iter() {
+			let old_dir = layout.primary_block_dir(h);
+			let new_dir = new_layout.primary_block_dir(h);
+			if old_dir != new_dir {
+				moved += 1;
+				assert!(new_dir.starts_with(&dirs[2]));
+				// Blocks not yet moved must still be found when reading
+				assert!(new_layout.secondary_block_dirs(h).any(|d| d == old_dir));
+			}
+		}
+		assert!(moved > 0);
+
+		std::fs::remove_dir_all(dirs[0].parent().unwrap()).unwrap();
+	}
+}
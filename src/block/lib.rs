@@ -11,4 +11,5 @@ mod metrics;
 mod rc;
 
 pub use block::zstd_encode;
+pub use metrics::CompressionStats;
 pub use rc::CalculateRefcount;
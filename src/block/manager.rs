@@ -1,5 +1,7 @@
+use std::collections::HashSet;
 use std::convert::TryInto;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -23,7 +25,7 @@ use garage_net::stream::{read_stream_to_end, stream_asyncread, ByteStream};
 use garage_db as db;
 
 use garage_util::background::{vars, BackgroundRunner};
-use garage_util::config::Config;
+use garage_util::config::{BlockReadStrategy, Config};
 use garage_util::data::*;
 use garage_util::error::*;
 use garage_util::metrics::RecordDuration;
@@ -43,8 +45,9 @@ use crate::rc::*;
 use crate::repair::*;
 use crate::resync::*;
 
-/// Size under which data will be stored inlined in database instead of as files
-pub const INLINE_THRESHOLD: usize = 3072;
+/// Maximum value allowed for the `inline_threshold` configuration parameter,
+/// to avoid storing too much data in the metadata store
+pub const MAX_INLINE_THRESHOLD: usize = 32 * 1024;
 
 // The delay between the moment when the reference counter
 // drops to zero, and the moment where we allow ourselves
@@ -86,6 +89,12 @@ pub struct BlockManager {
 	data_fsync: bool,
 	compression_level: Option<i32>,
 	disable_scrub: bool,
+	read_strategy: BlockReadStrategy,
+	/// Counter used to rotate the nodes to read from with `BlockReadStrategy::RoundRobin`
+	read_round_robin: AtomicUsize,
+
+	/// Size under which data will be stored inlined in database instead of as files
+	pub inline_threshold: usize,
 
 	mutation_lock: Vec<Mutex<BlockManagerLocked>>,
 
@@ -100,6 +109,7 @@ pub struct BlockManager {
 
 	pub scrub_persister: PersisterShared<ScrubWorkerPersisted>,
 	tx_scrub_command: ArcSwapOption<mpsc::Sender<ScrubWorkerCommand>>,
+	pub(crate) recompress_cancel: ArcSwapOption<AtomicBool>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -126,6 +136,25 @@ impl BlockManager {
 		replication: TableShardedReplication,
 		system: Arc<System>,
 	) -> Result<Arc<Self>, Error> {
+		if config.inline_threshold > MAX_INLINE_THRESHOLD {
+			return Err(Error::Message(format!(
+				"inline_threshold cannot be larger than {} bytes",
+				MAX_INLINE_THRESHOLD
+			)));
+		}
+		if config.inline_threshold >= config.block_size {
+			return Err(Error::Message(
+				"inline_threshold must be smaller than block_size".into(),
+			));
+		}
+		info!(
+			"Objects smaller than {} bytes will be stored inline in the metadata store",
+			config.inline_threshold
+		);
+		if !config.data_fsync {
+			info!("data_fsync is disabled: data blocks are not fsynced after being written, recently written blocks could be lost if this node loses power");
+		}
+
 		// Load or compute layout, i.e. assignment of data blocks to the different data directories
 		let data_layout_persister: Persister<DataLayout> =
 			Persister::new(&system.metadata_dir, "data_layout");
@@ -148,7 +177,18 @@ impl BlockManager {
 			.expect("Unable to open block_local_rc tree");
 		let rc = BlockRc::new(rc);
 
-		let resync = BlockResyncManager::new(db, &system);
+		if !(1..=MAX_RESYNC_WORKERS).contains(&config.block_resync_workers) {
+			return Err(Error::Message(format!(
+				"block_resync_workers must be between 1 and {}",
+				MAX_RESYNC_WORKERS
+			)));
+		}
+		let resync = BlockResyncManager::new(
+			db,
+			rc.rc_table.clone(),
+			&system,
+			config.block_resync_workers,
+		);
 
 		let endpoint = system
 			.netapp
@@ -172,7 +212,10 @@ impl BlockManager {
 			data_layout_persister,
 			data_fsync: config.data_fsync,
 			disable_scrub: config.disable_scrub,
+			read_strategy: config.block_read_strategy,
+			read_round_robin: AtomicUsize::new(0),
 			compression_level: config.compression_level,
+			inline_threshold: config.inline_threshold,
 			mutation_lock: vec![(); MUTEX_COUNT]
 				.iter()
 				.map(|_| Mutex::new(BlockManagerLocked()))
@@ -185,6 +228,7 @@ impl BlockManager {
 			metrics,
 			scrub_persister,
 			tx_scrub_command: ArcSwapOption::new(None),
+			recompress_cancel: ArcSwapOption::new(None),
 		});
 		block_manager.endpoint.set_handler(block_manager.clone());
 		block_manager.scrub_persister.set_with(|_| ()).unwrap();
@@ -194,7 +238,7 @@ impl BlockManager {
 
 	pub fn spawn_workers(self: &Arc<Self>, bg: &BackgroundRunner) {
 		// Spawn a bunch of resync workers
-		for index in 0..MAX_RESYNC_WORKERS {
+		for index in 0..self.resync.spawned_workers {
 			let worker = ResyncWorker::new(index, self.clone());
 			bg.spawn_worker(worker);
 		}
@@ -285,12 +329,49 @@ impl BlockManager {
 		F: Fn(DataBlockStream) -> Fut,
 		Fut: futures::Future<Output = Result<T, Error>>,
 	{
-		let who = self
+		let mut who = self
 			.system
 			.rpc_helper()
 			.block_read_nodes_of(hash, self.system.rpc_helper());
 
+		match self.read_strategy {
+			BlockReadStrategy::Latency => (),
+			BlockReadStrategy::PreferLocal => {
+				if self.find_block(hash).await.is_some() {
+					match self.read_block(hash).await {
+						Ok(block) => {
+							let (header, data) = block.into_parts();
+							let stream: ByteStream = Box::pin(futures::stream::once(async move {
+								Ok::<_, std::io::Error>(data)
+							}));
+							match f(DataBlockStream::from_parts(header, stream)).await {
+								Ok(ret) => return Ok(ret),
+								Err(e) => {
+									debug!(
+										"Get block {:?}: error reading local block: {}",
+										hash, e
+									);
+								}
+							}
+						}
+						Err(e) => {
+							debug!("Get block {:?}: could not read local block: {}", hash, e);
+						}
+					}
+					// No need to ask ourself again through RPC
+					who.retain(|node| *node != self.system.id);
+				}
+			}
+			BlockReadStrategy::RoundRobin => {
+				if !who.is_empty() {
+					let first = self.read_round_robin.fetch_add(1, Ordering::Relaxed) % who.len();
+					who.rotate_left(first);
+				}
+			}
+		}
+
 		for node in who.iter() {
+			self.metrics.rpc_get_counter.add(1);
 			let node_id = NodeID::from(*node);
 			let rpc = self.endpoint.call_streaming(
 				&node_id,
@@ -328,7 +409,7 @@ impl BlockManager {
 				// if the first one doesn't succeed rapidly
 				// TODO: keep first request running when initiating a new one and take the
 				// one that finishes earlier
-				_ = tokio::time::sleep(self.system.rpc_helper().rpc_timeout()) => {
+				_ = tokio::time::sleep(self.system.rpc_helper().rpc_timeout_for(priority)) => {
 					debug!("Get block {:?}: node {:?} didn't return block in time, trying next.", hash, node);
 				}
 			};
@@ -363,6 +444,34 @@ impl BlockManager {
 		}
 	}
 
+	/// Ask nodes that might have a block for it, return it as a stream
+	/// of zstd-compressed data. Blocks that are stored compressed are
+	/// returned as they are, other blocks are compressed on the fly.
+	pub async fn rpc_get_block_streaming_zstd(
+		&self,
+		hash: &Hash,
+		order_tag: Option<OrderTag>,
+	) -> Result<ByteStream, Error> {
+		let block_stream = self
+			.rpc_get_raw_block_streaming(hash, PRIO_NORMAL | PRIO_SECONDARY, order_tag)
+			.await?;
+		let (header, stream) = block_stream.into_parts();
+		match header {
+			DataBlockHeader::Compressed => Ok(stream),
+			DataBlockHeader::Plain => {
+				// Blocks are stored uncompressed when compression does not
+				// make them smaller, so don't spend time trying hard here
+				let reader = stream_asyncread(stream);
+				let reader = BufReader::new(reader);
+				let reader = async_compression::tokio::bufread::ZstdEncoder::with_quality(
+					reader,
+					async_compression::Level::Fastest,
+				);
+				Ok(Box::pin(tokio_util::io::ReaderStream::new(reader)))
+			}
+		}
+	}
+
 	/// Send block to nodes that should have it
 	pub async fn rpc_put_block(
 		&self,
@@ -374,9 +483,14 @@ impl BlockManager {
 		let who = self.replication.write_sets(&hash);
 
 		let compression_level = self.compression_level.filter(|_| !prevent_compression);
+		let data_len = data.len();
 		let (header, bytes) = DataBlock::from_buffer(data, compression_level)
 			.await
 			.into_parts();
+		if compression_level.is_some() {
+			self.metrics
+				.record_compression(data_len as u64, bytes.len() as u64);
+		}
 
 		let permit = self
 			.buffer_kb_semaphore
@@ -462,12 +576,15 @@ impl BlockManager {
 			// so in all cases we add the block here to the todo list
 			// to check later that it arrived correctly, and if not
 			// we will fecth it from someone.
+			// The transaction might not be committed when the block is
+			// put in the queue, so its priority is given here.
 			let this = self.clone();
 			tokio::spawn(async move {
-				if let Err(e) = this
-					.resync
-					.put_to_resync(&hash, 2 * this.system.rpc_helper().rpc_timeout())
-				{
+				if let Err(e) = this.resync.put_to_resync_with_priority(
+					&hash,
+					2 * this.system.rpc_helper().rpc_timeout(),
+					ResyncPriority::Needed,
+				) {
 					error!("Block {:?} could not be put in resync queue: {}.", hash, e);
 				}
 			});
@@ -486,13 +603,15 @@ impl BlockManager {
 			// indicating that we don't need the block.
 			// There is a delay before we garbage collect it;
 			// make sure that it is handled in the resync loop
-			// after that delay has passed.
+			// after that delay has passed, with the priority of
+			// deletable blocks as the transaction is not committed yet.
 			let this = self.clone();
 			tokio::spawn(async move {
-				if let Err(e) = this
-					.resync
-					.put_to_resync(&hash, BLOCK_GC_DELAY + Duration::from_secs(10))
-				{
+				if let Err(e) = this.resync.put_to_resync_with_priority(
+					&hash,
+					BLOCK_GC_DELAY + Duration::from_secs(10),
+					ResyncPriority::Unneeded,
+				) {
 					error!("Block {:?} could not be put in resync queue: {}.", hash, e);
 				}
 			});
@@ -616,6 +735,74 @@ impl BlockManager {
 		Ok(rc.is_nonzero() && !exists)
 	}
 
+	/// Send a locally stored block to those of the given nodes that need it,
+	/// i.e. that have a non-zero reference count for it but don't store it.
+	/// Returns the list of nodes to which the block was sent.
+	pub(crate) async fn send_block_to_needing_nodes(
+		&self,
+		hash: &Hash,
+		block_path: &DataBlockPath,
+		who: &[Uuid],
+	) -> Result<Vec<Uuid>, Error> {
+		if who.is_empty() {
+			return Ok(vec![]);
+		}
+
+		let who_needs_resps = self
+			.system
+			.rpc_helper()
+			.call_many(
+				&self.endpoint,
+				who,
+				BlockRpc::NeedBlockQuery(*hash),
+				RequestStrategy::with_priority(PRIO_BACKGROUND),
+			)
+			.await?;
+
+		let mut need_nodes = vec![];
+		for (node, needed) in who_needs_resps {
+			match needed.err_context("NeedBlockQuery RPC")? {
+				BlockRpc::NeedBlockReply(needed) => {
+					if needed {
+						need_nodes.push(node);
+					}
+				}
+				m => {
+					return Err(Error::unexpected_rpc_message(m));
+				}
+			}
+		}
+
+		if !need_nodes.is_empty() {
+			trace!(
+				"Block {:?} needed by {} nodes, sending",
+				hash,
+				need_nodes.len()
+			);
+
+			let block = self.read_block_from(hash, block_path).await?;
+			let (header, bytes) = block.into_parts();
+			let put_block_message = Req::new(BlockRpc::PutBlock {
+				hash: *hash,
+				header,
+			})?
+			.with_stream_from_buffer(bytes);
+			self.system
+				.rpc_helper()
+				.try_call_many(
+					&self.endpoint,
+					&need_nodes,
+					put_block_message,
+					RequestStrategy::with_priority(PRIO_BACKGROUND | PRIO_SECONDARY)
+						.with_quorum(need_nodes.len()),
+				)
+				.await
+				.err_context("PutBlock RPC")?;
+		}
+
+		Ok(need_nodes)
+	}
+
 	/// Delete block if it is not needed anymore
 	pub(crate) async fn delete_if_unneeded(&self, hash: &Hash) -> Result<(), Error> {
 		self.lock_mutate(hash)
@@ -675,6 +862,51 @@ impl BlockManager {
 			.await
 	}
 
+	/// Rewrite a block using the currently configured compression level,
+	/// if it is stored in a different form. Returns the number of bytes
+	/// written, or `None` if the block was left untouched.
+	pub(crate) async fn recompress_block(
+		&self,
+		hash: &Hash,
+		path: DataBlockPath,
+	) -> Result<Option<usize>, Error> {
+		self.lock_mutate(hash)
+			.await
+			.recompress_block(hash, path, self)
+			.await
+	}
+
+	/// Statistics about the compression of data blocks written by this node
+	pub fn compression_stats(&self) -> &CompressionStats {
+		&self.metrics.compression
+	}
+
+	/// Total size of the data blocks stored on this node, as found on disk.
+	/// Blocks present several times (e.g. in both compressed and plain form,
+	/// or in two data directories during a rebalance) are counted only once.
+	/// This walks all data directories and can therefore be slow.
+	pub async fn stored_bytes(&self) -> Result<u64, Error> {
+		let mut seen = HashSet::new();
+		let mut total = 0;
+		let mut iter = BlockStoreIterator::new(self);
+		while let Some((path, hash)) = iter.next().await? {
+			// the block might have been deleted since it was listed
+			if let Ok(meta) = fs::metadata(&path).await {
+				if seen.insert(hash) {
+					total += meta.len();
+				}
+			}
+		}
+		Ok(total)
+	}
+
+	/// Cancel the block recompression worker, if one is running
+	pub fn cancel_recompress(&self) {
+		if let Some(cancel) = self.recompress_cancel.swap(None) {
+			cancel.store(true, Ordering::Relaxed);
+		}
+	}
+
 	async fn lock_mutate(&self, hash: &Hash) -> MutexGuard<'_, BlockManagerLocked> {
 		let tracer = opentelemetry::global::tracer("garage");
 		let ilock = u16::from_be_bytes([hash.as_slice()[0], hash.as_slice()[1]]) as usize
@@ -841,6 +1073,37 @@ impl BlockManagerLocked {
 			.await?;
 		Ok(data.as_parts_ref().1.len())
 	}
+
+	async fn recompress_block(
+		&self,
+		hash: &Hash,
+		path: DataBlockPath,
+		mgr: &BlockManager,
+	) -> Result<Option<usize>, Error> {
+		let (header, path) = path.into_parts();
+
+		// The block might have been deleted or moved since it was listed,
+		// in which case there is nothing left for us to do.
+		let data = match fs::read(&path).await {
+			Ok(data) => data,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(e.into()),
+		};
+		mgr.metrics.bytes_read.add(data.len() as u64);
+
+		let block = DataBlock::from_parts(header, data.into());
+		let new_block = match block.recompress(*hash, mgr.compression_level).await? {
+			Some(b) => b,
+			None => return Ok(None),
+		};
+
+		// Write the new form of the block at its primary location,
+		// then delete the old file which has a different extension.
+		self.write_block_inner(hash, &new_block, mgr, None).await?;
+		fs::remove_file(&path).await?;
+
+		Ok(Some(new_block.as_parts_ref().1.len()))
+	}
 }
 
 struct DeleteOnDrop(Option<PathBuf>);
@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use tokio::sync::Semaphore;
@@ -13,6 +14,7 @@ pub struct BlockManagerMetrics {
 	pub(crate) _resync_queue_len: ValueObserver<u64>,
 	pub(crate) _resync_errored_blocks: ValueObserver<u64>,
 	pub(crate) _buffer_free_kb: ValueObserver<u64>,
+	pub(crate) _compression_ratio: ValueObserver<f64>,
 
 	pub(crate) resync_counter: BoundCounter<u64>,
 	pub(crate) resync_error_counter: BoundCounter<u64>,
@@ -21,12 +23,51 @@ pub struct BlockManagerMetrics {
 	pub(crate) resync_recv_counter: BoundCounter<u64>,
 
 	pub(crate) bytes_read: BoundCounter<u64>,
+	pub(crate) rpc_get_counter: BoundCounter<u64>,
 	pub(crate) block_read_duration: BoundValueRecorder<f64>,
 	pub(crate) bytes_written: BoundCounter<u64>,
 	pub(crate) block_write_duration: BoundValueRecorder<f64>,
 	pub(crate) delete_counter: BoundCounter<u64>,
 
 	pub(crate) corruption_counter: BoundCounter<u64>,
+
+	pub(crate) compression: Arc<CompressionStats>,
+	pub(crate) compression_bytes_in: BoundCounter<u64>,
+	pub(crate) compression_bytes_out: BoundCounter<u64>,
+}
+
+/// Amount of data that went through compression on this node
+#[derive(Default)]
+pub struct CompressionStats {
+	bytes_in: AtomicU64,
+	bytes_out: AtomicU64,
+}
+
+impl CompressionStats {
+	pub(crate) fn record(&self, bytes_in: u64, bytes_out: u64) {
+		self.bytes_in.fetch_add(bytes_in, Ordering::Relaxed);
+		self.bytes_out.fetch_add(bytes_out, Ordering::Relaxed);
+	}
+
+	/// Number of bytes of data given to the compressor
+	pub fn bytes_in(&self) -> u64 {
+		self.bytes_in.load(Ordering::Relaxed)
+	}
+
+	/// Number of bytes of data produced by the compressor
+	/// (or of uncompressed data if it could not be compressed)
+	pub fn bytes_out(&self) -> u64 {
+		self.bytes_out.load(Ordering::Relaxed)
+	}
+
+	/// Size of data before compression divided by size after compression,
+	/// or None if no data was compressed yet
+	pub fn ratio(&self) -> Option<f64> {
+		match (self.bytes_in(), self.bytes_out()) {
+			(_, 0) => None,
+			(bytes_in, bytes_out) => Some(bytes_in as f64 / bytes_out as f64),
+		}
+	}
 }
 
 impl BlockManagerMetrics {
@@ -38,6 +79,8 @@ impl BlockManagerMetrics {
 		buffer_semaphore: Arc<Semaphore>,
 	) -> Self {
 		let meter = global::meter("garage_model/block");
+		let compression = Arc::new(CompressionStats::default());
+		let compression2 = compression.clone();
 		Self {
 			_compression_level: meter
 				.u64_value_observer("block.compression_level", move |observer| {
@@ -83,6 +126,16 @@ impl BlockManagerMetrics {
 					"Available RAM in KiB to use for buffering data blocks to be written to remote nodes",
 				)
 				.init(),
+			_compression_ratio: meter
+				.f64_value_observer("block.compression_ratio", move |observer| {
+					if let Some(ratio) = compression2.ratio() {
+						observer.observe(ratio, &[]);
+					}
+				})
+				.with_description(
+					"Size of data blocks before compression divided by their size after compression",
+				)
+				.init(),
 
 			resync_counter: meter
 				.u64_counter("block.resync_counter")
@@ -114,6 +167,11 @@ impl BlockManagerMetrics {
 				.with_description("Number of bytes read from disk")
 				.init()
 				.bind(&[]),
+			rpc_get_counter: meter
+				.u64_counter("block.rpc_get_counter")
+				.with_description("Number of requests for a block sent to a node through RPC")
+				.init()
+				.bind(&[]),
 			block_read_duration: meter
 				.f64_value_recorder("block.read_duration")
 				.with_description("Duration of block read operations")
@@ -140,6 +198,24 @@ impl BlockManagerMetrics {
 				.with_description("Data corruptions detected on block reads")
 				.init()
 				.bind(&[]),
+
+			compression,
+			compression_bytes_in: meter
+				.u64_counter("block.compression_bytes_in")
+				.with_description("Number of bytes of data blocks given to the compressor")
+				.init()
+				.bind(&[]),
+			compression_bytes_out: meter
+				.u64_counter("block.compression_bytes_out")
+				.with_description("Number of bytes of data blocks after compression")
+				.init()
+				.bind(&[]),
 		}
 	}
+
+	pub(crate) fn record_compression(&self, bytes_in: u64, bytes_out: u64) {
+		self.compression.record(bytes_in, bytes_out);
+		self.compression_bytes_in.add(bytes_in);
+		self.compression_bytes_out.add(bytes_out);
+	}
 }
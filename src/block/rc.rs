@@ -170,7 +170,7 @@ impl RcEntry {
 		}
 	}
 
-	fn parse_opt<V: AsRef<[u8]>>(bytes: Option<V>) -> Self {
+	pub(crate) fn parse_opt<V: AsRef<[u8]>>(bytes: Option<V>) -> Self {
 		bytes
 			.map(|b| Self::parse(b.as_ref()))
 			.unwrap_or(Self::Absent)
@@ -1,5 +1,6 @@
 use core::ops::Bound;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -54,6 +55,10 @@ impl Worker for RepairWorker {
 		"Block repair worker".into()
 	}
 
+	fn kind(&self) -> WorkerKind {
+		WorkerKind::Repair
+	}
+
 	fn status(&self) -> WorkerStatus {
 		match self.block_iter.as_ref() {
 			None => {
@@ -557,6 +562,10 @@ impl Worker for RebalanceWorker {
 		"Block rebalance worker".into()
 	}
 
+	fn kind(&self) -> WorkerKind {
+		WorkerKind::Repair
+	}
+
 	fn status(&self) -> WorkerStatus {
 		let t_cur = self.t_finished.unwrap_or_else(|| now_msec());
 		let rate = self.moved_bytes / std::cmp::max(1, (t_cur - self.t_started) / 1000);
@@ -625,6 +634,289 @@ impl Worker for RebalanceWorker {
 	}
 }
 
+// ---- ---- ----
+// FOURTH KIND OF REPAIR: RECOMPRESSING DATA BLOCKS
+// with the currently configured compression level.
+// This is a one-shot repair operation that can be launched,
+// rewrites everything that needs it, and then exits.
+// ---- ---- ----
+
+pub struct RecompressWorker {
+	manager: Arc<BlockManager>,
+	block_iter: BlockStoreIterator,
+	tranquilizer: Tranquilizer,
+	tranquility: u32,
+	cancel: Arc<AtomicBool>,
+	t_started: u64,
+	t_finished: Option<u64>,
+	checked: usize,
+	recompressed: usize,
+	corrupted: usize,
+}
+
+impl RecompressWorker {
+	pub fn new(manager: Arc<BlockManager>, tranquility: u32) -> Self {
+		// Only one recompression may run at a time: cancel any previous one
+		let cancel = Arc::new(AtomicBool::new(false));
+		if let Some(prev) = manager.recompress_cancel.swap(Some(cancel.clone())) {
+			prev.store(true, Ordering::Relaxed);
+		}
+
+		let block_iter = BlockStoreIterator::new(&manager);
+		Self {
+			manager,
+			block_iter,
+			tranquilizer: Tranquilizer::new(30),
+			tranquility,
+			cancel,
+			t_started: now_msec(),
+			t_finished: None,
+			checked: 0,
+			recompressed: 0,
+			corrupted: 0,
+		}
+	}
+}
+
+#[async_trait]
+impl Worker for RecompressWorker {
+	fn name(&self) -> String {
+		"Block recompression worker".into()
+	}
+
+	fn status(&self) -> WorkerStatus {
+		let mut freeform = vec![
+			format!("Blocks checked: {}", self.checked),
+			format!("Blocks recompressed: {}", self.recompressed),
+			format!("Corrupted blocks skipped: {}", self.corrupted),
+			format!("Started: {}", msec_to_rfc3339(self.t_started)),
+		];
+		if let Some(t_fin) = self.t_finished {
+			freeform.push(format!("Finished: {}", msec_to_rfc3339(t_fin)))
+		}
+		WorkerStatus {
+			progress: Some(format!("{:.2}%", self.block_iter.progress() * 100.)),
+			tranquility: Some(self.tranquility),
+			freeform,
+			..Default::default()
+		}
+	}
+
+	async fn work(&mut self, _must_exit: &mut watch::Receiver<bool>) -> Result<WorkerState, Error> {
+		if self.cancel.load(Ordering::Relaxed) {
+			info!("Block recompression cancelled");
+			self.t_finished = Some(now_msec());
+			return Ok(WorkerState::Done);
+		}
+
+		self.tranquilizer.reset();
+		if let Some((path, hash)) = self.block_iter.next().await? {
+			let block_path = match path.extension() {
+				None => DataBlockPath::plain(path.clone()),
+				Some(x) if x.to_str() == Some("zst") => DataBlockPath::compressed(path.clone()),
+				_ => {
+					warn!("not recompressing file: {}", path.to_string_lossy());
+					return Ok(WorkerState::Busy);
+				}
+			};
+
+			self.checked += 1;
+			match self.manager.recompress_block(&hash, block_path).await {
+				Ok(Some(_)) => self.recompressed += 1,
+				Ok(None) => (),
+				Err(Error::CorruptData(_)) => {
+					// leave it to the scrub worker to handle corrupted blocks
+					warn!("recompress: block {:?} is corrupted, skipping", hash);
+					self.corrupted += 1;
+				}
+				Err(e) => return Err(e),
+			}
+
+			Ok(self.tranquilizer.tranquilize_worker(self.tranquility))
+		} else {
+			info!(
+				"Block recompression finished: {} blocks checked, {} rewritten",
+				self.checked, self.recompressed
+			);
+			self.t_finished = Some(now_msec());
+			Ok(WorkerState::Done)
+		}
+	}
+
+	async fn wait_for_work(&mut self) -> WorkerState {
+		unreachable!()
+	}
+}
+
+// ---- ---- ----
+// FIFTH KIND OF REPAIR: REBALANCING DATA BLOCKS BETWEEN NODES
+// after the cluster layout has changed (e.g. node capacities were updated).
+// Blocks are otherwise moved lazily by the resync worker; this is a
+// one-shot repair operation that can be launched, sends every locally
+// stored block to its new owners, and then exits.
+// ---- ---- ----
+
+pub struct ClusterRebalanceWorker {
+	manager: Arc<BlockManager>,
+	block_iter: BlockStoreIterator,
+	tranquilizer: Tranquilizer,
+	tranquility: u32,
+	t_started: u64,
+	t_finished: Option<u64>,
+	checked: usize,
+	sent: usize,
+	sent_copies: usize,
+	offloaded: usize,
+	errors: usize,
+}
+
+impl ClusterRebalanceWorker {
+	pub fn new(manager: Arc<BlockManager>, tranquility: u32) -> Self {
+		let block_iter = BlockStoreIterator::new(&manager);
+		Self {
+			manager,
+			block_iter,
+			tranquilizer: Tranquilizer::new(30),
+			tranquility,
+			t_started: now_msec(),
+			t_finished: None,
+			checked: 0,
+			sent: 0,
+			sent_copies: 0,
+			offloaded: 0,
+			errors: 0,
+		}
+	}
+}
+
+#[async_trait]
+impl Worker for ClusterRebalanceWorker {
+	fn name(&self) -> String {
+		"Cluster rebalance worker".into()
+	}
+
+	fn kind(&self) -> WorkerKind {
+		WorkerKind::Repair
+	}
+
+	fn status(&self) -> WorkerStatus {
+		let mut freeform = vec![
+			format!(
+				"{} blocks checked, {} sent to new owners, {} queued for deletion",
+				self.checked, self.sent, self.offloaded
+			),
+			format!("Copies sent: {}", self.sent_copies),
+			format!("Started: {}", msec_to_rfc3339(self.t_started)),
+		];
+		if let Some(t_fin) = self.t_finished {
+			freeform.push(format!("Finished: {}", msec_to_rfc3339(t_fin)))
+		}
+		WorkerStatus {
+			progress: Some(format!("{:.2}%", self.block_iter.progress() * 100.)),
+			tranquility: Some(self.tranquility),
+			persistent_errors: Some(self.errors as u64),
+			freeform,
+			..Default::default()
+		}
+	}
+
+	async fn work(&mut self, _must_exit: &mut watch::Receiver<bool>) -> Result<WorkerState, Error> {
+		self.tranquilizer.reset();
+		if let Some((path, hash)) = self.block_iter.next().await? {
+			let block_path = match path.extension() {
+				None => DataBlockPath::plain(path.clone()),
+				Some(x) if x.to_str() == Some("zst") => DataBlockPath::compressed(path.clone()),
+				_ => {
+					warn!("not rebalancing file: {}", path.to_string_lossy());
+					return Ok(WorkerState::Busy);
+				}
+			};
+
+			self.checked += 1;
+			let plan = {
+				let layout = self.manager.system.cluster_layout();
+				let current = layout.current();
+				let current_nodes = current
+					.nodes_of(&hash, current.replication_factor)
+					.collect::<Vec<_>>();
+				rebalance_plan(
+					self.manager.system.id,
+					&current_nodes,
+					&layout.storage_nodes_of(&hash),
+				)
+			};
+
+			match self
+				.manager
+				.send_block_to_needing_nodes(&hash, &block_path, &plan.push_to)
+				.await
+			{
+				Ok(sent_to) if !sent_to.is_empty() => {
+					debug!("rebalance: sent block {:?} to {:?}", hash, sent_to);
+					self.sent += 1;
+					self.sent_copies += sent_to.len();
+				}
+				Ok(_) => (),
+				Err(e) => {
+					// the resync worker will take care of this block eventually
+					warn!("rebalance: could not send block {:?}: {}", hash, e);
+					self.errors += 1;
+					return Ok(self.tranquilizer.tranquilize_worker(self.tranquility));
+				}
+			}
+
+			if plan.offload {
+				// We are not a storage node for this block anymore: let the resync
+				// worker check its reference counter and delete it when it is safe
+				self.manager
+					.resync
+					.put_to_resync(&hash, Duration::from_secs(0))?;
+				self.offloaded += 1;
+			}
+
+			Ok(self.tranquilizer.tranquilize_worker(self.tranquility))
+		} else {
+			info!(
+				"Cluster rebalance finished: {} blocks checked, {} sent to new owners, {} queued for deletion",
+				self.checked, self.sent, self.offloaded
+			);
+			self.t_finished = Some(now_msec());
+			Ok(WorkerState::Done)
+		}
+	}
+
+	async fn wait_for_work(&mut self) -> WorkerState {
+		unreachable!()
+	}
+}
+
+/// What the cluster rebalance worker does with a block stored on this node
+#[derive(Debug, PartialEq, Eq)]
+struct RebalancePlan {
+	/// Nodes of the current layout version that should be offered a copy
+	push_to: Vec<Uuid>,
+	/// Whether this node is not a storage node for the block anymore
+	offload: bool,
+}
+
+/// `current_nodes` are the nodes storing the block in the current layout
+/// version, `storage_nodes` are those storing it in any of the layout versions
+/// still in use (blocks are kept until older versions are no longer needed)
+fn rebalance_plan(
+	this_node: Uuid,
+	current_nodes: &[Uuid],
+	storage_nodes: &[Uuid],
+) -> RebalancePlan {
+	RebalancePlan {
+		push_to: current_nodes
+			.iter()
+			.filter(|n| **n != this_node)
+			.copied()
+			.collect(),
+		offload: !storage_nodes.contains(&this_node),
+	}
+}
+
 // ---- ---- ----
 // UTILITY FOR ENUMERATING THE BLOCK STORE
 // ---- ---- ----
@@ -632,7 +924,7 @@ impl Worker for RebalanceWorker {
 const PROGRESS_FP: u64 = 1_000_000_000;
 
 impl BlockStoreIterator {
-	fn new(manager: &BlockManager) -> Self {
+	pub(crate) fn new(manager: &BlockManager) -> Self {
 		let data_layout = manager.data_layout.load_full();
 
 		let mut dir_cap = vec![0; data_layout.data_dirs.len()];
@@ -681,7 +973,7 @@ impl BlockStoreIterator {
 			.unwrap_or(1.0)
 	}
 
-	async fn next(&mut self) -> Result<Option<(PathBuf, Hash)>, Error> {
+	pub(crate) async fn next(&mut self) -> Result<Option<(PathBuf, Hash)>, Error> {
 		loop {
 			match self.todo.pop() {
 				None => return Ok(None),
@@ -762,3 +1054,85 @@ impl BlockStoreIterator {
 		iter.zip(iter_1).all(|(prev, next)| prev >= next)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use garage_rpc::layout::*;
+	use garage_rpc::replication_mode::ReplicationFactor;
+	use garage_util::crdt::Crdt;
+
+	fn set_capacities(cl: &mut LayoutHistory, capacities: &[u64]) {
+		let staging = cl.staging.get_mut();
+		for (i, capacity) in capacities.iter().enumerate() {
+			let update = staging.roles.update_mutator(
+				[i as u8; 32].into(),
+				NodeRoleV(Some(NodeRole {
+					zone: "A".to_string(),
+					capacity: Some(*capacity),
+					tags: vec![],
+				})),
+			);
+			staging.roles.merge(&update);
+		}
+		staging.parameters.update(LayoutParameters {
+			zone_redundancy: ZoneRedundancy::AtLeast(1),
+		});
+	}
+
+	#[test]
+	fn test_rebalance_after_capacity_change() {
+		let nodes = (0..3u8).map(|i| Uuid::from([i; 32])).collect::<Vec<_>>();
+
+		let mut cl = LayoutHistory::new(ReplicationFactor::new(1).unwrap());
+		set_capacities(&mut cl, &[1000, 1000, 1000]);
+		let v = cl.current().version;
+		let (mut cl, _) = cl.apply_staged_changes(Some(v + 1)).unwrap();
+		let old = cl.current().clone();
+
+		// Give most of the capacity to the last node
+		set_capacities(&mut cl, &[1000, 1000, 8000]);
+		let v = cl.current().version;
+		let (cl, _) = cl.apply_staged_changes(Some(v + 1)).unwrap();
+		let new = cl.current();
+
+		let mut migrated = 0;
+		for i in 0..1000u32 {
+			let hash = blake2sum(&i.to_be_bytes());
+			let old_owner = old.nodes_of(&hash, 1).next().unwrap();
+			let new_owner = new.nodes_of(&hash, 1).next().unwrap();
+
+			let mut storage_nodes = vec![old_owner, new_owner];
+			storage_nodes.dedup();
+
+			for node in nodes.iter() {
+				// While both layout versions are in use, blocks are sent
+				// to the new owner but not deleted from the old one
+				let plan = rebalance_plan(*node, &[new_owner], &storage_nodes);
+				if *node == new_owner {
+					assert!(plan.push_to.is_empty());
+				} else {
+					assert_eq!(plan.push_to, vec![new_owner]);
+				}
+				assert_eq!(plan.offload, !storage_nodes.contains(node));
+
+				// Once the old version is no longer used, the old owner can
+				// hand the block over to garbage collection
+				let plan = rebalance_plan(*node, &[new_owner], &[new_owner]);
+				assert_eq!(plan.offload, *node != new_owner);
+			}
+
+			if old_owner != new_owner {
+				migrated += 1;
+				let plan = rebalance_plan(old_owner, &[new_owner], &[new_owner]);
+				assert_eq!(plan.push_to, vec![new_owner]);
+				assert!(plan.offload);
+			}
+		}
+
+		// The node whose capacity increased receives blocks from the others
+		assert!(migrated > 0);
+		assert!(new.get_node_usage(&nodes[2]).unwrap() > old.get_node_usage(&nodes[2]).unwrap());
+	}
+}
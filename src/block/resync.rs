@@ -1,5 +1,6 @@
 use std::collections::HashSet;
 use std::convert::TryInto;
+use std::ops::Bound;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -30,6 +31,7 @@ use garage_rpc::*;
 use garage_table::replication::TableReplication;
 
 use crate::manager::*;
+use crate::rc::RcEntry;
 
 // The delay between the time where a resync operation fails
 // and the time when it is retried, with exponential backoff
@@ -39,19 +41,29 @@ pub(crate) const RESYNC_RETRY_DELAY: Duration = Duration::from_secs(60);
 // The maximum retry delay is 60 seconds * 2^6 = 60 seconds << 6 = 64 minutes (~1 hour)
 pub(crate) const RESYNC_RETRY_DELAY_MAX_BACKOFF_POWER: u64 = 6;
 
-// No more than 4 resync workers can be running in the system
-pub(crate) const MAX_RESYNC_WORKERS: usize = 8;
+// No more than 64 resync workers can be spawned, the number of workers
+// spawned is given by the block_resync_workers config option (default 8)
+pub(crate) const MAX_RESYNC_WORKERS: usize = 64;
 // Resync tranquility is initially set to 2, but can be changed in the CLI
 // and the updated version is persisted over Garage restarts
 const INITIAL_RESYNC_TRANQUILITY: u32 = 2;
 
+// Length of keys in the resync queue written by previous Garage versions,
+// which did not have a priority byte: concat(timestamp, hash)
+const OLD_QUEUE_KEY_LEN: usize = 8 + 32;
+// Number of old-format queue entries that are re-keyed at once at startup
+const QUEUE_MIGRATION_BATCH_SIZE: usize = 1000;
+
 pub struct BlockResyncManager {
 	pub(crate) queue: db::Tree,
 	pub(crate) notify: Arc<Notify>,
 	pub(crate) errors: db::Tree,
+	rc_table: db::Tree,
 
 	busy_set: BusySet,
 
+	/// Number of resync workers spawned at startup
+	pub(crate) spawned_workers: usize,
 	persister: PersisterShared<ResyncPersistedConfig>,
 }
 
@@ -79,28 +91,80 @@ enum ResyncIterResult {
 type BusySet = Arc<Mutex<HashSet<Vec<u8>>>>;
 
 struct BusyBlock {
-	time_bytes: Vec<u8>,
+	key_bytes: Vec<u8>,
 	hash_bytes: Vec<u8>,
 	busy_set: BusySet,
 }
 
+/// Priority class of an entry in the resync queue. Entries of a
+/// higher priority class (lower value) that are ready to be processed
+/// are always dequeued before entries of a lower priority class.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum ResyncPriority {
+	/// The block has a nonzero refcount: it might have to be fetched
+	Needed = 0,
+	/// The block has a zero refcount: it might have to be deleted
+	Unneeded = 1,
+}
+
+impl ResyncPriority {
+	const ALL: [Self; 2] = [Self::Needed, Self::Unneeded];
+
+	fn of(rc_table: &db::Tree, hash: &Hash) -> db::Result<Self> {
+		if RcEntry::parse_opt(rc_table.get(hash)?).is_nonzero() {
+			Ok(Self::Needed)
+		} else {
+			Ok(Self::Unneeded)
+		}
+	}
+
+	fn range(self) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+		(
+			Bound::Included(vec![self as u8]),
+			Bound::Excluded(vec![self as u8 + 1]),
+		)
+	}
+}
+
 impl BlockResyncManager {
-	pub(crate) fn new(db: &db::Db, system: &System) -> Self {
+	pub(crate) fn new(
+		db: &db::Db,
+		rc_table: db::Tree,
+		system: &System,
+		spawned_workers: usize,
+	) -> Self {
 		let queue = db
-			.open_tree("block_local_resync_queue")
+			.open_tree_counted("block_local_resync_queue")
 			.expect("Unable to open block_local_resync_queue tree");
+		match migrate_queue_keys(&queue, &rc_table) {
+			Ok(0) => (),
+			Ok(n) => info!(
+				"Migrated {} entries of the resync queue to new key format",
+				n
+			),
+			Err(e) => error!("Could not migrate resync queue to new key format: {}", e),
+		}
 
 		let errors = db
-			.open_tree("block_local_resync_errors")
+			.open_tree_counted("block_local_resync_errors")
 			.expect("Unable to open block_local_resync_errors tree");
 
-		let persister = PersisterShared::new(&system.metadata_dir, "resync_cfg");
+		let persister: PersisterShared<ResyncPersistedConfig> =
+			PersisterShared::new(&system.metadata_dir, "resync_cfg");
+		if persister.get_with(|x| x.n_workers) > spawned_workers {
+			if let Err(e) = persister.set_with(|x| x.n_workers = spawned_workers) {
+				warn!("Could not update resync worker count: {}", e);
+			}
+		}
 
 		Self {
 			queue,
 			notify: Arc::new(Notify::new()),
 			errors,
+			rc_table,
 			busy_set: Arc::new(Mutex::new(HashSet::new())),
+			spawned_workers,
 			persister,
 		}
 	}
@@ -110,6 +174,19 @@ impl BlockResyncManager {
 		Ok(self.queue.len()?)
 	}
 
+	/// Get number of entries in the resync queue for blocks that are needed
+	/// and for blocks that are not needed anymore, in this order
+	pub fn queue_composition(&self) -> Result<(usize, usize), Error> {
+		let mut counts = [0usize; 2];
+		for prio in ResyncPriority::ALL {
+			for ent in self.queue.range(prio.range())? {
+				ent?;
+				counts[prio as usize] += 1;
+			}
+		}
+		Ok((counts[0], counts[1]))
+	}
+
 	/// Get number of blocks that have an error
 	pub fn errors_len(&self) -> Result<usize, Error> {
 		Ok(self.errors.len()?)
@@ -123,7 +200,8 @@ impl BlockResyncManager {
 			if ec.errors > 0 {
 				ec.last_try = now - ec.delay_msec();
 				self.errors.insert(hash, ec.encode())?;
-				self.put_to_resync_at(hash, now)?;
+				let prio = ResyncPriority::of(&self.rc_table, hash)?;
+				self.put_to_resync_at(hash, now, prio)?;
 				return Ok(());
 			}
 		}
@@ -135,15 +213,16 @@ impl BlockResyncManager {
 
 	pub fn register_bg_vars(&self, vars: &mut vars::BgVars) {
 		let notify = self.notify.clone();
+		let spawned_workers = self.spawned_workers;
 		vars.register_rw(
 			&self.persister,
 			"resync-worker-count",
 			|p| p.get_with(|x| x.n_workers),
 			move |p, n_workers| {
-				if !(1..=MAX_RESYNC_WORKERS).contains(&n_workers) {
+				if !(1..=spawned_workers).contains(&n_workers) {
 					return Err(Error::Message(format!(
-						"Invalid number of resync workers, must be between 1 and {}",
-						MAX_RESYNC_WORKERS
+						"Invalid number of resync workers, must be between 1 and {} (see block_resync_workers config option)",
+						spawned_workers
 					)));
 				}
 				p.set_with(|x| x.n_workers = n_workers)?;
@@ -176,12 +255,17 @@ impl BlockResyncManager {
 	// There are two db trees that are used to have information
 	// about the status of blocks that need to be resynchronized:
 	//
-	// - resync.queue: a tree that is ordered first by a timestamp
+	// - resync.queue: a tree that is ordered first by a priority class
+	//   (see ResyncPriority: blocks that are needed come before blocks
+	//   that might have to be deleted), second by a timestamp
 	//   (in milliseconds since Unix epoch) that is the time at which
-	//   the resync must be done, and second by block hash.
+	//   the resync must be done, and third by block hash.
 	//   The key in this tree is just:
-	//       concat(timestamp (8 bytes), hash (32 bytes))
+	//       concat(priority (1 byte), timestamp (8 bytes), hash (32 bytes))
 	//   The value is the same 32-byte hash.
+	//   The worker takes the first entry of the highest priority class
+	//   that is ready to be processed, so that fetching missing blocks
+	//   is not delayed by a large backlog of blocks to delete.
 	//
 	// - resync.errors: a tree that indicates for each block
 	//   if the last resync resulted in an error, and if so,
@@ -238,23 +322,35 @@ impl BlockResyncManager {
 	// for times that are earlier than the exponential back-off delay
 	// is a natural condition that is handled properly).
 
+	/// Put a block in the resync queue, with a priority given by its current
+	/// reference counter. Changes to the reference counter that are not yet
+	/// committed must use `put_to_resync_with_priority` instead.
 	pub(crate) fn put_to_resync(&self, hash: &Hash, delay: Duration) -> db::Result<()> {
+		let prio = ResyncPriority::of(&self.rc_table, hash)?;
+		self.put_to_resync_with_priority(hash, delay, prio)
+	}
+
+	pub(crate) fn put_to_resync_with_priority(
+		&self,
+		hash: &Hash,
+		delay: Duration,
+		prio: ResyncPriority,
+	) -> db::Result<()> {
 		let when = now_msec() + delay.as_millis() as u64;
-		self.put_to_resync_at(hash, when)
+		self.put_to_resync_at(hash, when, prio)
 	}
 
-	pub(crate) fn put_to_resync_at(&self, hash: &Hash, when: u64) -> db::Result<()> {
-		trace!("Put resync_queue: {} {:?}", when, hash);
-		let mut key = u64::to_be_bytes(when).to_vec();
-		key.extend(hash.as_ref());
-		self.queue.insert(key, hash.as_ref())?;
+	fn put_to_resync_at(&self, hash: &Hash, when: u64, prio: ResyncPriority) -> db::Result<()> {
+		trace!("Put resync_queue: {:?} {} {:?}", prio, when, hash);
+		self.queue
+			.insert(queue_key(prio, when, hash), hash.as_ref())?;
 		self.notify.notify_waiters();
 		Ok(())
 	}
 
 	async fn resync_iter(&self, manager: &BlockManager) -> Result<ResyncIterResult, db::Error> {
 		if let Some(block) = self.get_block_to_resync()? {
-			let time_msec = u64::from_be_bytes(block.time_bytes[0..8].try_into().unwrap());
+			let time_msec = queue_key_time(&block.key_bytes);
 			let now = now_msec();
 
 			if now >= time_msec {
@@ -266,12 +362,13 @@ impl BlockResyncManager {
 						// if next retry after an error is not yet,
 						// don't do resync and return early, but still
 						// make sure the item is still in queue at expected time
-						self.put_to_resync_at(&hash, ec.next_try())?;
+						let prio = ResyncPriority::of(&self.rc_table, &hash)?;
+						self.put_to_resync_at(&hash, ec.next_try(), prio)?;
 						// ec.next_try() > now >= time_msec, so this remove
 						// is not removing the one we added just above
 						// (we want to do the remove after the insert to ensure
 						// that the item is not lost if we crash in-between)
-						self.queue.remove(&block.time_bytes)?;
+						self.queue.remove(&block.key_bytes)?;
 						return Ok(ResyncIterResult::BusyDidNothing);
 					}
 				}
@@ -308,14 +405,15 @@ impl BlockResyncManager {
 
 					self.errors.insert(hash.as_slice(), err_counter.encode())?;
 
-					self.put_to_resync_at(&hash, err_counter.next_try())?;
+					let prio = ResyncPriority::of(&self.rc_table, &hash)?;
+					self.put_to_resync_at(&hash, err_counter.next_try(), prio)?;
 					// err_counter.next_try() >= now + 1 > now,
 					// the entry we remove from the queue is not
 					// the entry we inserted with put_to_resync_at
-					self.queue.remove(&block.time_bytes)?;
+					self.queue.remove(&block.key_bytes)?;
 				} else {
 					self.errors.remove(hash.as_slice())?;
-					self.queue.remove(&block.time_bytes)?;
+					self.queue.remove(&block.key_bytes)?;
 				}
 
 				Ok(ResyncIterResult::BusyDidSomething)
@@ -337,18 +435,17 @@ impl BlockResyncManager {
 
 	fn get_block_to_resync(&self) -> Result<Option<BusyBlock>, db::Error> {
 		let mut busy = self.busy_set.lock().unwrap();
-		for it in self.queue.iter()? {
-			let (time_bytes, hash_bytes) = it?;
-			if !busy.contains(&time_bytes) {
-				busy.insert(time_bytes.clone());
-				return Ok(Some(BusyBlock {
-					time_bytes,
+		match next_queue_entry(&self.queue, &busy, now_msec())? {
+			Some((key_bytes, hash_bytes)) => {
+				busy.insert(key_bytes.clone());
+				Ok(Some(BusyBlock {
+					key_bytes,
 					hash_bytes,
 					busy_set: self.busy_set.clone(),
-				}));
+				}))
 			}
+			None => Ok(None),
 		}
-		Ok(None)
 	}
 
 	async fn resync_block(&self, manager: &BlockManager, hash: &Hash) -> Result<(), Error> {
@@ -383,64 +480,14 @@ impl BlockResyncManager {
 			}
 			who.retain(|id| *id != manager.system.id);
 
-			let who_needs_resps = manager
-				.system
-				.rpc_helper()
-				.call_many(
-					&manager.endpoint,
-					&who,
-					BlockRpc::NeedBlockQuery(*hash),
-					RequestStrategy::with_priority(PRIO_BACKGROUND),
-				)
+			let need_nodes = manager
+				.send_block_to_needing_nodes(hash, &existing_path, &who)
 				.await?;
-
-			let mut need_nodes = vec![];
-			for (node, needed) in who_needs_resps {
-				match needed.err_context("NeedBlockQuery RPC")? {
-					BlockRpc::NeedBlockReply(needed) => {
-						if needed {
-							need_nodes.push(node);
-						}
-					}
-					m => {
-						return Err(Error::unexpected_rpc_message(m));
-					}
-				}
-			}
-
-			if !need_nodes.is_empty() {
-				trace!(
-					"Block {:?} needed by {} nodes, sending",
-					hash,
-					need_nodes.len()
-				);
-
-				for node in need_nodes.iter() {
-					manager
-						.metrics
-						.resync_send_counter
-						.add(1, &[KeyValue::new("to", format!("{:?}", node))]);
-				}
-
-				let block = manager.read_block_from(hash, &existing_path).await?;
-				let (header, bytes) = block.into_parts();
-				let put_block_message = Req::new(BlockRpc::PutBlock {
-					hash: *hash,
-					header,
-				})?
-				.with_stream_from_buffer(bytes);
+			for node in need_nodes.iter() {
 				manager
-					.system
-					.rpc_helper()
-					.try_call_many(
-						&manager.endpoint,
-						&need_nodes,
-						put_block_message,
-						RequestStrategy::with_priority(PRIO_BACKGROUND | PRIO_SECONDARY)
-							.with_quorum(need_nodes.len()),
-					)
-					.await
-					.err_context("PutBlock RPC")?;
+					.metrics
+					.resync_send_counter
+					.add(1, &[KeyValue::new("to", format!("{:?}", node))]);
 			}
 			info!(
 				"Deleting unneeded block {:?}, offload finished ({} / {})",
@@ -484,10 +531,92 @@ impl BlockResyncManager {
 impl Drop for BusyBlock {
 	fn drop(&mut self) {
 		let mut busy = self.busy_set.lock().unwrap();
-		busy.remove(&self.time_bytes);
+		busy.remove(&self.key_bytes);
 	}
 }
 
+// ---- Resync queue keys ----
+
+fn queue_key(prio: ResyncPriority, when: u64, hash: &Hash) -> Vec<u8> {
+	let mut key = Vec::with_capacity(1 + 8 + 32);
+	key.push(prio as u8);
+	key.extend(u64::to_be_bytes(when));
+	key.extend(hash.as_slice());
+	key
+}
+
+fn queue_key_time(key: &[u8]) -> u64 {
+	u64::from_be_bytes(key[1..9].try_into().unwrap())
+}
+
+/// Find the entry of the resync queue that should be processed next:
+/// the first non-busy entry that is ready to be processed in the highest
+/// priority class that has one, or if no entry is ready yet,
+/// the entry that will be ready the soonest.
+fn next_queue_entry(
+	queue: &db::Tree,
+	busy: &HashSet<Vec<u8>>,
+	now: u64,
+) -> db::Result<Option<(Vec<u8>, Vec<u8>)>> {
+	let mut earliest: Option<(Vec<u8>, Vec<u8>)> = None;
+	for prio in ResyncPriority::ALL {
+		for it in queue.range(prio.range())? {
+			let (key, hash) = it?;
+			if busy.contains(&key) {
+				continue;
+			}
+			let time = queue_key_time(&key);
+			if time <= now {
+				return Ok(Some((key, hash)));
+			}
+			if earliest
+				.as_ref()
+				.map(|(k, _)| time < queue_key_time(k))
+				.unwrap_or(true)
+			{
+				earliest = Some((key, hash));
+			}
+			break;
+		}
+	}
+	Ok(earliest)
+}
+
+/// Re-key the entries of the resync queue written by previous Garage
+/// versions, that did not include a priority byte. Returns the number
+/// of entries that were migrated.
+fn migrate_queue_keys(queue: &db::Tree, rc_table: &db::Tree) -> db::Result<usize> {
+	let mut migrated = 0;
+	let mut pos = Bound::Unbounded;
+	loop {
+		let mut batch = Vec::with_capacity(QUEUE_MIGRATION_BATCH_SIZE);
+		for it in queue.range((pos.clone(), Bound::Unbounded))? {
+			let (key, hash) = it?;
+			if key.len() == OLD_QUEUE_KEY_LEN {
+				batch.push((key, hash));
+				if batch.len() >= QUEUE_MIGRATION_BATCH_SIZE {
+					break;
+				}
+			}
+		}
+		let last_key = match batch.last() {
+			Some((key, _)) => key.clone(),
+			None => break,
+		};
+
+		for (key, hash_bytes) in batch {
+			let hash = Hash::try_from(&hash_bytes[..]).unwrap();
+			let when = u64::from_be_bytes(key[0..8].try_into().unwrap());
+			let prio = ResyncPriority::of(rc_table, &hash)?;
+			queue.insert(queue_key(prio, when, &hash), hash.as_slice())?;
+			queue.remove(&key)?;
+			migrated += 1;
+		}
+		pos = Bound::Excluded(last_key);
+	}
+	Ok(migrated)
+}
+
 pub(crate) struct ResyncWorker {
 	index: usize,
 	manager: Arc<BlockManager>,
@@ -624,3 +753,98 @@ impl ErrorCounter {
 		self.last_try + self.delay_msec()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn open_test_db() -> (db::Db, std::path::PathBuf) {
+		let path = std::env::temp_dir().join(format!(
+			"garage-resync-test-{}",
+			hex::encode(&gen_uuid().as_slice()[..8])
+		));
+		std::fs::create_dir_all(&path).unwrap();
+		let db = db::open_db(
+			&path.join("db.sqlite"),
+			db::Engine::Sqlite,
+			&db::OpenOpt::default(),
+		)
+		.unwrap();
+		(db, path)
+	}
+
+	#[test]
+	fn test_needed_block_dequeued_first() {
+		let (db, path) = open_test_db();
+		let queue = db.open_tree("queue").unwrap();
+		let rc_table = db.open_tree("rc").unwrap();
+
+		let needed = blake2sum(b"needed");
+		let unneeded = blake2sum(b"unneeded");
+		rc_table.insert(needed, u64::to_be_bytes(1)).unwrap();
+
+		// The deletable block is scheduled earlier, but the needed block
+		// must be processed first once both are ready
+		for (hash, when) in [(unneeded, 1000), (needed, 2000)] {
+			let prio = ResyncPriority::of(&rc_table, &hash).unwrap();
+			queue
+				.insert(queue_key(prio, when, &hash), hash.as_slice())
+				.unwrap();
+		}
+
+		let mut busy = HashSet::new();
+		let (key, hash) = next_queue_entry(&queue, &busy, 3000).unwrap().unwrap();
+		assert_eq!(hash, needed.as_slice());
+		busy.insert(key);
+		let (_, hash) = next_queue_entry(&queue, &busy, 3000).unwrap().unwrap();
+		assert_eq!(hash, unneeded.as_slice());
+
+		// When only the deletable block is ready, it is not held back
+		let (key, hash) = next_queue_entry(&queue, &HashSet::new(), 1500)
+			.unwrap()
+			.unwrap();
+		assert_eq!(hash, unneeded.as_slice());
+		assert_eq!(queue_key_time(&key), 1000);
+
+		// When nothing is ready, the soonest entry is returned
+		let (key, _) = next_queue_entry(&queue, &HashSet::new(), 500)
+			.unwrap()
+			.unwrap();
+		assert_eq!(queue_key_time(&key), 1000);
+
+		drop(db);
+		std::fs::remove_dir_all(path).unwrap();
+	}
+
+	#[test]
+	fn test_migrate_queue_keys() {
+		let (db, path) = open_test_db();
+		let queue = db.open_tree("queue").unwrap();
+		let rc_table = db.open_tree("rc").unwrap();
+
+		let needed = blake2sum(b"needed");
+		let unneeded = blake2sum(b"unneeded");
+		rc_table.insert(needed, u64::to_be_bytes(1)).unwrap();
+
+		for (hash, when) in [(unneeded, 1000u64), (needed, 2000u64)] {
+			let mut key = u64::to_be_bytes(when).to_vec();
+			key.extend(hash.as_slice());
+			queue.insert(key, hash.as_slice()).unwrap();
+		}
+
+		assert_eq!(migrate_queue_keys(&queue, &rc_table).unwrap(), 2);
+		assert_eq!(migrate_queue_keys(&queue, &rc_table).unwrap(), 0);
+		assert_eq!(queue.len().unwrap(), 2);
+		assert!(queue
+			.get(queue_key(ResyncPriority::Needed, 2000, &needed))
+			.unwrap()
+			.is_some());
+		assert!(queue
+			.get(queue_key(ResyncPriority::Unneeded, 1000, &unneeded))
+			.unwrap()
+			.is_some());
+
+		drop(db);
+		std::fs::remove_dir_all(path).unwrap();
+	}
+}
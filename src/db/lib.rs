@@ -91,6 +91,16 @@ impl Db {
 		Ok(Tree(self.0.clone(), tree_id))
 	}
 
+	/// Open a tree whose number of items is maintained by the database engine,
+	/// so that `len` is O(1) even for engines that would otherwise count items
+	/// by scanning the tree (Sqlite). The counter is updated in the same
+	/// transaction as the modifications of the tree. Use this for trees whose
+	/// length is queried frequently, such as work queues.
+	pub fn open_tree_counted<S: AsRef<str>>(&self, name: S) -> Result<Tree> {
+		let tree_id = self.0.open_tree_counted(name.as_ref())?;
+		Ok(Tree(self.0.clone(), tree_id))
+	}
+
 	pub fn list_trees(&self) -> Result<Vec<String>> {
 		self.0.list_trees()
 	}
@@ -333,6 +343,10 @@ impl<'a> Transaction<'a> {
 pub(crate) trait IDb: Send + Sync {
 	fn engine(&self) -> String;
 	fn open_tree(&self, name: &str) -> Result<usize>;
+	/// Engines whose `len` is already O(1) don't need to do anything special
+	fn open_tree_counted(&self, name: &str) -> Result<usize> {
+		self.open_tree(name)
+	}
 	fn list_trees(&self) -> Result<Vec<String>>;
 	fn snapshot(&self, path: &PathBuf) -> Result<()>;
 
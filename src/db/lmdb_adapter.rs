@@ -21,7 +21,15 @@ pub use heed;
 
 impl From<heed::Error> for Error {
 	fn from(e: heed::Error) -> Error {
-		Error(format!("LMDB: {}", e).into())
+		match e {
+			heed::Error::Mdb(heed::MdbError::MapFull) => Error(
+				"LMDB: the metadata database is full, as it reached the maximum size set by \
+				`lmdb_map_size`. Increase `lmdb_map_size` in the configuration file and \
+				restart Garage."
+					.into(),
+			),
+			e => Error(format!("LMDB: {}", e).into()),
+		}
 	}
 }
 
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{Db, Error, Result};
 
@@ -21,6 +21,15 @@ impl Engine {
 			Self::Sqlite => "sqlite",
 		}
 	}
+
+	/// Name of the file or directory in which this engine stores its data,
+	/// within Garage's metadata directory
+	pub fn db_file_name(&self) -> &'static str {
+		match self {
+			Self::Lmdb => "db.lmdb",
+			Self::Sqlite => "db.sqlite",
+		}
+	}
 }
 
 impl std::fmt::Display for Engine {
@@ -48,9 +57,72 @@ impl std::str::FromStr for Engine {
 	}
 }
 
+/// Inspect a Garage metadata directory and return the names of the database
+/// engines that have data in it: `lmdb`, `sqlite`, or `sled` for databases
+/// created by Garage versions older than v1.0.
+pub fn detect_engines(metadata_dir: &Path) -> Vec<&'static str> {
+	let mut ret = vec![];
+	if metadata_dir
+		.join(Engine::Lmdb.db_file_name())
+		.join("data.mdb")
+		.is_file()
+	{
+		ret.push(Engine::Lmdb.as_str());
+	}
+	if metadata_dir.join(Engine::Sqlite.db_file_name()).is_file() {
+		ret.push(Engine::Sqlite.as_str());
+	}
+	if metadata_dir.join("db").join("conf").is_file() {
+		ret.push("sled");
+	}
+	ret
+}
+
+/// Check that the configured engine matches the database found in the
+/// metadata directory, if any, so that we don't silently create a second
+/// empty database next to an existing one.
+pub fn check_engine(metadata_dir: &Path, engine: Engine) -> Result<()> {
+	let detected = detect_engines(metadata_dir);
+	if detected.is_empty() || detected.contains(&engine.as_str()) {
+		return Ok(());
+	}
+
+	match detected.iter().find_map(|e| e.parse::<Engine>().ok()) {
+		Some(found) => Err(Error(
+			format!(
+				"Metadata directory {} contains a database for engine `{}`, but `db_engine` is set to `{}`. \
+				Set `db_engine = \"{}\"` in your configuration file, or convert the existing database \
+				with `garage convert-db -a {} -i {} -b {} -o {}`.",
+				metadata_dir.display(),
+				found,
+				engine,
+				found,
+				found,
+				metadata_dir.join(found.db_file_name()).display(),
+				engine,
+				metadata_dir.join(engine.db_file_name()).display(),
+			)
+			.into(),
+		)),
+		None => Err(Error(
+			format!(
+				"Metadata directory {} contains a Sled database, which is no longer supported. \
+				Convert it to `{}` using an older Garage binary (e.g. v0.9.4) with `garage convert-db`.",
+				metadata_dir.display(),
+				engine,
+			)
+			.into(),
+		)),
+	}
+}
+
 pub struct OpenOpt {
 	pub fsync: bool,
 	pub lmdb_map_size: Option<usize>,
+	/// Page size of newly created Sqlite databases, in bytes
+	pub sqlite_page_size: Option<usize>,
+	/// Size of the Sqlite page cache of each connection, in bytes
+	pub sqlite_cache_size: Option<usize>,
 }
 
 impl Default for OpenOpt {
@@ -58,6 +130,8 @@ impl Default for OpenOpt {
 		Self {
 			fsync: false,
 			lmdb_map_size: None,
+			sqlite_page_size: None,
+			sqlite_cache_size: None,
 		}
 	}
 }
@@ -68,8 +142,24 @@ pub fn open_db(path: &PathBuf, engine: Engine, opt: &OpenOpt) -> Result<Db> {
 		#[cfg(feature = "sqlite")]
 		Engine::Sqlite => {
 			info!("Opening Sqlite database at: {}", path.display());
+			if let Some(page_size) = opt.sqlite_page_size {
+				if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+					return Err(Error(
+						format!(
+							"Invalid Sqlite page size {}: it must be a power of two between 512 and 65536",
+							page_size
+						)
+						.into(),
+					));
+				}
+			}
 			let manager = r2d2_sqlite::SqliteConnectionManager::file(path);
-			Ok(crate::sqlite_adapter::SqliteDb::new(manager, opt.fsync)?)
+			Ok(crate::sqlite_adapter::SqliteDb::new_with_tuning(
+				manager,
+				opt.fsync,
+				opt.sqlite_page_size,
+				opt.sqlite_cache_size,
+			)?)
 		}
 
 		// ---- LMDB DB ----
@@ -84,8 +174,32 @@ pub fn open_db(path: &PathBuf, engine: Engine, opt: &OpenOpt) -> Result<Db> {
 
 			let map_size = match opt.lmdb_map_size {
 				None => crate::lmdb_adapter::recommended_map_size(),
-				Some(v) => v - (v % 4096),
+				Some(v) => {
+					// The map size must be a multiple of the OS page size
+					let page_size = page_size::get();
+					let aligned = v - (v % page_size);
+					if aligned == 0 {
+						return Err(Error(
+							format!(
+								"Invalid LMDB map size {}: it must be at least the OS page size ({} bytes)",
+								v, page_size
+							)
+							.into(),
+						));
+					}
+					if aligned != v {
+						warn!(
+							"LMDB map size {} is not a multiple of the OS page size ({} bytes), rounding it down to {}",
+							v, page_size, aligned
+						);
+					}
+					aligned
+				}
 			};
+			info!(
+				"LMDB map size: {} MiB (maximum size of the metadata database, see `lmdb_map_size`)",
+				map_size >> 20
+			);
 
 			let mut env_builder = heed::EnvOpenOptions::new();
 			env_builder.max_dbs(100);
@@ -122,3 +236,150 @@ pub fn open_db(path: &PathBuf, engine: Engine, opt: &OpenOpt) -> Result<Db> {
 		)),
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_detect_engines() {
+		let dir = mktemp::Temp::new_dir().unwrap();
+		let dir = dir.as_path();
+
+		assert!(detect_engines(dir).is_empty());
+		assert!(check_engine(dir, Engine::Lmdb).is_ok());
+		assert!(check_engine(dir, Engine::Sqlite).is_ok());
+
+		std::fs::create_dir_all(dir.join("db")).unwrap();
+		std::fs::write(dir.join("db").join("conf"), b"").unwrap();
+		assert_eq!(detect_engines(dir), vec!["sled"]);
+		assert!(check_engine(dir, Engine::Lmdb).is_err());
+		assert!(check_engine(dir, Engine::Sqlite).is_err());
+	}
+
+	#[test]
+	fn test_sled_engine_rejected() {
+		// Sled (and its tuning options) were removed: selecting it must fail
+		// with a message that tells operators how to convert their database
+		let err = "sled".parse::<Engine>().unwrap_err();
+		assert!(err.0.contains("no longer supported"));
+		assert!(err.0.contains("older Garage binary"));
+	}
+
+	#[cfg(feature = "lmdb")]
+	#[test]
+	fn test_detect_lmdb() {
+		let dir = mktemp::Temp::new_dir().unwrap();
+		let dir = dir.as_path();
+
+		let db = open_db(
+			&dir.join(Engine::Lmdb.db_file_name()),
+			Engine::Lmdb,
+			&OpenOpt {
+				lmdb_map_size: Some(1 << 20),
+				..Default::default()
+			},
+		)
+		.unwrap();
+		drop(db);
+
+		assert_eq!(detect_engines(dir), vec!["lmdb"]);
+		assert!(check_engine(dir, Engine::Lmdb).is_ok());
+		let err = check_engine(dir, Engine::Sqlite).unwrap_err();
+		assert!(err.to_string().contains("db_engine = \"lmdb\""));
+	}
+
+	#[cfg(feature = "lmdb")]
+	#[test]
+	fn test_lmdb_map_size() {
+		let dir = mktemp::Temp::new_dir().unwrap();
+		let path = dir.join(Engine::Lmdb.db_file_name());
+		let open = |map_size| {
+			open_db(
+				&path,
+				Engine::Lmdb,
+				&OpenOpt {
+					lmdb_map_size: Some(map_size),
+					..Default::default()
+				},
+			)
+		};
+
+		// A small map size is enough for a small amount of data
+		let db = open(1 << 20).unwrap();
+		let tree = db.open_tree("test").unwrap();
+		for i in 0u32..100 {
+			tree.insert(i.to_be_bytes(), [0u8; 100]).unwrap();
+		}
+		assert_eq!(tree.len().unwrap(), 100);
+
+		// Writing past the map size fails with an actionable error
+		let err = (0u32..10000)
+			.map(|i| tree.insert(i.to_be_bytes(), [0u8; 1000]))
+			.find_map(|r| r.err())
+			.unwrap();
+		assert!(err.to_string().contains("lmdb_map_size"));
+		// The inserts that fit before the map was full are kept
+		let len = tree.len().unwrap();
+		assert!(len > 100);
+		drop(tree);
+		drop(db);
+
+		// Map sizes that are not a multiple of the page size are rounded down
+		let db = open((1 << 20) + 1).unwrap();
+		assert_eq!(db.open_tree("test").unwrap().len().unwrap(), len);
+		drop(db);
+
+		// Map sizes smaller than a page are rejected
+		let err = open(1).err().unwrap();
+		assert!(err.to_string().contains("OS page size"));
+		assert!(open(0).is_err());
+	}
+
+	#[cfg(feature = "sqlite")]
+	#[test]
+	fn test_sqlite_tuning() {
+		let dir = mktemp::Temp::new_dir().unwrap();
+		let path = dir.join(Engine::Sqlite.db_file_name());
+		let open = |page_size| {
+			open_db(
+				&path,
+				Engine::Sqlite,
+				&OpenOpt {
+					sqlite_page_size: page_size,
+					sqlite_cache_size: Some(1 << 20),
+					..Default::default()
+				},
+			)
+		};
+
+		assert!(open(Some(1000)).is_err());
+		assert!(open(Some(1 << 17)).is_err());
+
+		let db = open(Some(8192)).unwrap();
+		let tree = db.open_tree("test").unwrap();
+		tree.insert(b"k", b"v").unwrap();
+		assert_eq!(tree.get(b"k").unwrap().unwrap(), b"v");
+	}
+
+	#[cfg(feature = "sqlite")]
+	#[test]
+	fn test_detect_sqlite() {
+		let dir = mktemp::Temp::new_dir().unwrap();
+		let dir = dir.as_path();
+
+		let db = open_db(
+			&dir.join(Engine::Sqlite.db_file_name()),
+			Engine::Sqlite,
+			&OpenOpt::default(),
+		)
+		.unwrap();
+		db.open_tree("test").unwrap();
+		drop(db);
+
+		assert_eq!(detect_engines(dir), vec!["sqlite"]);
+		assert!(check_engine(dir, Engine::Sqlite).is_ok());
+		let err = check_engine(dir, Engine::Lmdb).unwrap_err();
+		assert!(err.to_string().contains("db_engine = \"sqlite\""));
+	}
+}
@@ -19,6 +19,10 @@ pub use rusqlite;
 
 type Connection = r2d2::PooledConnection<SqliteConnectionManager>;
 
+/// Table in which the number of items of counted trees is maintained
+/// (its name must not start with `tree_`, see `list_trees`)
+const COUNTS_TABLE: &str = "garage_tree_counts";
+
 // --- err
 
 impl From<rusqlite::Error> for Error {
@@ -43,16 +47,46 @@ impl From<rusqlite::Error> for TxOpError {
 
 pub struct SqliteDb {
 	db: Pool<SqliteConnectionManager>,
-	trees: RwLock<Vec<Arc<str>>>,
+	trees: RwLock<Vec<SqliteTree>>,
 	// All operations that might write on the DB must take this lock first.
 	// This emulates LMDB's approach where a single writer can be
-	// active at once.
+	// active at once. Operations that also lock `trees` must do so
+	// before taking this lock, to avoid deadlocks.
 	write_lock: Mutex<()>,
 }
 
+#[derive(Clone)]
+struct SqliteTree {
+	name: Arc<str>,
+	/// Whether the number of items of this tree is maintained in `COUNTS_TABLE`,
+	/// which makes `len` O(1) instead of a full table scan
+	counted: bool,
+}
+
 impl SqliteDb {
 	pub fn new(manager: SqliteConnectionManager, sync_mode: bool) -> Result<Db> {
+		Self::new_with_tuning(manager, sync_mode, None, None)
+	}
+
+	/// Open a Sqlite database, setting its page size (only effective if the
+	/// database is being created) and the size of the page cache of each
+	/// connection, both in bytes
+	pub fn new_with_tuning(
+		manager: SqliteConnectionManager,
+		sync_mode: bool,
+		page_size: Option<usize>,
+		cache_size: Option<usize>,
+	) -> Result<Db> {
 		let manager = manager.with_init(move |db| {
+			if let Some(page_size) = page_size {
+				// Must be set before switching to WAL mode, after which
+				// the page size of the database can no longer change
+				db.pragma_update(None, "page_size", page_size)?;
+			}
+			if let Some(cache_size) = cache_size {
+				// Negative values are interpreted as a number of KiB
+				db.pragma_update(None, "cache_size", -((cache_size / 1024) as i64))?;
+			}
 			db.pragma_update(None, "journal_mode", "WAL")?;
 			if sync_mode {
 				db.pragma_update(None, "synchronous", "NORMAL")?;
@@ -71,16 +105,16 @@ impl SqliteDb {
 }
 
 impl SqliteDb {
-	fn get_tree(&self, i: usize) -> Result<Arc<str>> {
-		self.trees
-			.read()
-			.unwrap()
-			.get(i)
-			.cloned()
-			.ok_or_else(|| Error("invalid tree id".into()))
+	fn get_tree(&self, i: usize) -> Result<SqliteTree> {
+		tree_at(&self.trees.read().unwrap(), i).cloned()
 	}
 
-	fn internal_get(&self, db: &Connection, tree: &str, key: &[u8]) -> Result<Option<Value>> {
+	fn internal_get(
+		&self,
+		db: &rusqlite::Connection,
+		tree: &str,
+		key: &[u8],
+	) -> Result<Option<Value>> {
 		let mut stmt = db.prepare(&format!("SELECT v FROM {} WHERE k = ?1", tree))?;
 		let mut res_iter = stmt.query([key])?;
 		match res_iter.next()? {
@@ -99,7 +133,7 @@ impl IDb for SqliteDb {
 		let name = format!("tree_{}", name.replace(':', "_COLON_"));
 		let mut trees = self.trees.write().unwrap();
 
-		if let Some(i) = trees.iter().position(|x| x.as_ref() == &name) {
+		if let Some(i) = trees.iter().position(|x| x.name.as_ref() == &name) {
 			Ok(i)
 		} else {
 			let db = self.db.get()?;
@@ -117,11 +151,47 @@ impl IDb for SqliteDb {
 			trace!("table created: {}, unlocking", name);
 
 			let i = trees.len();
-			trees.push(name.to_string().into_boxed_str().into());
+			trees.push(SqliteTree {
+				name: name.to_string().into_boxed_str().into(),
+				counted: false,
+			});
 			Ok(i)
 		}
 	}
 
+	fn open_tree_counted(&self, name: &str) -> Result<usize> {
+		let i = self.open_tree(name)?;
+		let mut trees = self.trees.write().unwrap();
+
+		if !trees[i].counted {
+			// Always recount items when the tree is opened, in case the table
+			// was modified by a process that did not maintain the counter
+			let db = self.db.get()?;
+			let lock = self.write_lock.lock();
+			db.execute(
+				&format!(
+					"CREATE TABLE IF NOT EXISTS {} (
+						tree TEXT PRIMARY KEY,
+						n INTEGER NOT NULL
+					)",
+					COUNTS_TABLE
+				),
+				[],
+			)?;
+			db.execute(
+				&format!(
+					"INSERT OR REPLACE INTO {} (tree, n) SELECT ?1, COUNT(*) FROM {}",
+					COUNTS_TABLE, trees[i].name
+				),
+				params![trees[i].name.as_ref()],
+			)?;
+			drop(lock);
+
+			trees[i].counted = true;
+		}
+		Ok(i)
+	}
+
 	fn list_trees(&self) -> Result<Vec<String>> {
 		let mut trees = vec![];
 
@@ -154,75 +224,89 @@ impl IDb for SqliteDb {
 
 	fn get(&self, tree: usize, key: &[u8]) -> Result<Option<Value>> {
 		let tree = self.get_tree(tree)?;
-		self.internal_get(&self.db.get()?, &tree, key)
+		let db = self.db.get()?;
+		self.internal_get(&db, &tree.name, key)
 	}
 
 	fn len(&self, tree: usize) -> Result<usize> {
 		let tree = self.get_tree(tree)?;
 		let db = self.db.get()?;
-
-		let mut stmt = db.prepare(&format!("SELECT COUNT(*) FROM {}", tree))?;
-		let mut res_iter = stmt.query([])?;
-		match res_iter.next()? {
-			None => Ok(0),
-			Some(v) => Ok(v.get::<_, usize>(0)?),
-		}
+		Ok(internal_len(&db, &tree)?)
 	}
 
 	fn insert(&self, tree: usize, key: &[u8], value: &[u8]) -> Result<Option<Value>> {
-		let tree = self.get_tree(tree)?;
-		let db = self.db.get()?;
+		let mut db = self.db.get()?;
+		// Keep the tree list locked, so that the tree cannot become
+		// counted while we are modifying it
+		let trees = self.trees.read().unwrap();
 		let lock = self.write_lock.lock();
+		let tree = tree_at(&trees, tree)?;
+		let tx = db.transaction()?;
 
-		let old_val = self.internal_get(&db, &tree, key)?;
+		let old_val = self.internal_get(&tx, &tree.name, key)?;
 
 		let sql = match &old_val {
-			Some(_) => format!("UPDATE {} SET v = ?2 WHERE k = ?1", tree),
-			None => format!("INSERT INTO {} (k, v) VALUES (?1, ?2)", tree),
+			Some(_) => format!("UPDATE {} SET v = ?2 WHERE k = ?1", tree.name),
+			None => format!("INSERT INTO {} (k, v) VALUES (?1, ?2)", tree.name),
 		};
-		let n = db.execute(&sql, params![key, value])?;
+		let n = tx.execute(&sql, params![key, value])?;
 		assert_eq!(n, 1);
+		if old_val.is_none() {
+			update_count(&tx, &tree, 1)?;
+		}
 
+		tx.commit()?;
 		drop(lock);
 		Ok(old_val)
 	}
 
 	fn remove(&self, tree: usize, key: &[u8]) -> Result<Option<Value>> {
-		let tree = self.get_tree(tree)?;
-		let db = self.db.get()?;
+		let mut db = self.db.get()?;
+		let trees = self.trees.read().unwrap();
 		let lock = self.write_lock.lock();
+		let tree = tree_at(&trees, tree)?;
+		let tx = db.transaction()?;
 
-		let old_val = self.internal_get(&db, &tree, key)?;
+		let old_val = self.internal_get(&tx, &tree.name, key)?;
 
 		if old_val.is_some() {
-			let n = db.execute(&format!("DELETE FROM {} WHERE k = ?1", tree), params![key])?;
+			let n = tx.execute(
+				&format!("DELETE FROM {} WHERE k = ?1", tree.name),
+				params![key],
+			)?;
 			assert_eq!(n, 1);
+			update_count(&tx, &tree, -1)?;
 		}
 
+		tx.commit()?;
 		drop(lock);
 		Ok(old_val)
 	}
 
 	fn clear(&self, tree: usize) -> Result<()> {
-		let tree = self.get_tree(tree)?;
-		let db = self.db.get()?;
+		let mut db = self.db.get()?;
+		let trees = self.trees.read().unwrap();
 		let lock = self.write_lock.lock();
+		let tree = tree_at(&trees, tree)?;
+		let tx = db.transaction()?;
 
-		db.execute(&format!("DELETE FROM {}", tree), [])?;
+		tx.execute(&format!("DELETE FROM {}", tree.name), [])?;
+		reset_count(&tx, &tree)?;
 
+		tx.commit()?;
 		drop(lock);
 		Ok(())
 	}
 
 	fn iter(&self, tree: usize) -> Result<ValueIter<'_>> {
 		let tree = self.get_tree(tree)?;
-		let sql = format!("SELECT k, v FROM {} ORDER BY k ASC", tree);
+		let sql = format!("SELECT k, v FROM {} ORDER BY k ASC", tree.name);
 		DbValueIterator::make(self.db.get()?, &sql, [])
 	}
 
 	fn iter_rev(&self, tree: usize) -> Result<ValueIter<'_>> {
 		let tree = self.get_tree(tree)?;
-		let sql = format!("SELECT k, v FROM {} ORDER BY k DESC", tree);
+		let sql = format!("SELECT k, v FROM {} ORDER BY k DESC", tree.name);
 		DbValueIterator::make(self.db.get()?, &sql, [])
 	}
 
@@ -235,7 +319,10 @@ impl IDb for SqliteDb {
 		let tree = self.get_tree(tree)?;
 
 		let (bounds_sql, params) = bounds_sql(low, high);
-		let sql = format!("SELECT k, v FROM {} {} ORDER BY k ASC", tree, bounds_sql);
+		let sql = format!(
+			"SELECT k, v FROM {} {} ORDER BY k ASC",
+			tree.name, bounds_sql
+		);
 
 		let params = params
 			.iter()
@@ -253,7 +340,10 @@ impl IDb for SqliteDb {
 		let tree = self.get_tree(tree)?;
 
 		let (bounds_sql, params) = bounds_sql(low, high);
-		let sql = format!("SELECT k, v FROM {} {} ORDER BY k DESC", tree, bounds_sql);
+		let sql = format!(
+			"SELECT k, v FROM {} {} ORDER BY k DESC",
+			tree.name, bounds_sql
+		);
 
 		let params = params
 			.iter()
@@ -298,16 +388,20 @@ impl IDb for SqliteDb {
 	}
 }
 
+fn tree_at(trees: &[SqliteTree], i: usize) -> Result<&SqliteTree> {
+	trees.get(i).ok_or_else(|| Error("invalid tree id".into()))
+}
+
 // ----
 
 struct SqliteTx<'a> {
 	tx: Transaction<'a>,
-	trees: &'a [Arc<str>],
+	trees: &'a [SqliteTree],
 }
 
 impl<'a> SqliteTx<'a> {
-	fn get_tree(&self, i: usize) -> TxOpResult<&'_ str> {
-		self.trees.get(i).map(Arc::as_ref).ok_or_else(|| {
+	fn get_tree(&self, i: usize) -> TxOpResult<&'a SqliteTree> {
+		self.trees.get(i).ok_or_else(|| {
 			TxOpError(Error(
 				"invalid tree id (it might have been openned after the transaction started)".into(),
 			))
@@ -329,58 +423,59 @@ impl<'a> SqliteTx<'a> {
 impl<'a> ITx for SqliteTx<'a> {
 	fn get(&self, tree: usize, key: &[u8]) -> TxOpResult<Option<Value>> {
 		let tree = self.get_tree(tree)?;
-		self.internal_get(tree, key)
+		self.internal_get(&tree.name, key)
 	}
 	fn len(&self, tree: usize) -> TxOpResult<usize> {
 		let tree = self.get_tree(tree)?;
-		let mut stmt = self.tx.prepare(&format!("SELECT COUNT(*) FROM {}", tree))?;
-		let mut res_iter = stmt.query([])?;
-		match res_iter.next()? {
-			None => Ok(0),
-			Some(v) => Ok(v.get::<_, usize>(0)?),
-		}
+		Ok(internal_len(&self.tx, tree)?)
 	}
 
 	fn insert(&mut self, tree: usize, key: &[u8], value: &[u8]) -> TxOpResult<Option<Value>> {
 		let tree = self.get_tree(tree)?;
-		let old_val = self.internal_get(tree, key)?;
+		let old_val = self.internal_get(&tree.name, key)?;
 
 		let sql = match &old_val {
-			Some(_) => format!("UPDATE {} SET v = ?2 WHERE k = ?1", tree),
-			None => format!("INSERT INTO {} (k, v) VALUES (?1, ?2)", tree),
+			Some(_) => format!("UPDATE {} SET v = ?2 WHERE k = ?1", tree.name),
+			None => format!("INSERT INTO {} (k, v) VALUES (?1, ?2)", tree.name),
 		};
 		let n = self.tx.execute(&sql, params![key, value])?;
 		assert_eq!(n, 1);
+		if old_val.is_none() {
+			update_count(&self.tx, tree, 1)?;
+		}
 
 		Ok(old_val)
 	}
 	fn remove(&mut self, tree: usize, key: &[u8]) -> TxOpResult<Option<Value>> {
 		let tree = self.get_tree(tree)?;
-		let old_val = self.internal_get(tree, key)?;
+		let old_val = self.internal_get(&tree.name, key)?;
 
 		if old_val.is_some() {
-			let n = self
-				.tx
-				.execute(&format!("DELETE FROM {} WHERE k = ?1", tree), params![key])?;
+			let n = self.tx.execute(
+				&format!("DELETE FROM {} WHERE k = ?1", tree.name),
+				params![key],
+			)?;
 			assert_eq!(n, 1);
+			update_count(&self.tx, tree, -1)?;
 		}
 
 		Ok(old_val)
 	}
 	fn clear(&mut self, tree: usize) -> TxOpResult<()> {
 		let tree = self.get_tree(tree)?;
-		self.tx.execute(&format!("DELETE FROM {}", tree), [])?;
+		self.tx.execute(&format!("DELETE FROM {}", tree.name), [])?;
+		reset_count(&self.tx, tree)?;
 		Ok(())
 	}
 
 	fn iter(&self, tree: usize) -> TxOpResult<TxValueIter<'_>> {
 		let tree = self.get_tree(tree)?;
-		let sql = format!("SELECT k, v FROM {} ORDER BY k ASC", tree);
+		let sql = format!("SELECT k, v FROM {} ORDER BY k ASC", tree.name);
 		TxValueIterator::make(self, &sql, [])
 	}
 	fn iter_rev(&self, tree: usize) -> TxOpResult<TxValueIter<'_>> {
 		let tree = self.get_tree(tree)?;
-		let sql = format!("SELECT k, v FROM {} ORDER BY k DESC", tree);
+		let sql = format!("SELECT k, v FROM {} ORDER BY k DESC", tree.name);
 		TxValueIterator::make(self, &sql, [])
 	}
 
@@ -393,7 +488,10 @@ impl<'a> ITx for SqliteTx<'a> {
 		let tree = self.get_tree(tree)?;
 
 		let (bounds_sql, params) = bounds_sql(low, high);
-		let sql = format!("SELECT k, v FROM {} {} ORDER BY k ASC", tree, bounds_sql);
+		let sql = format!(
+			"SELECT k, v FROM {} {} ORDER BY k ASC",
+			tree.name, bounds_sql
+		);
 
 		let params = params
 			.iter()
@@ -411,7 +509,10 @@ impl<'a> ITx for SqliteTx<'a> {
 		let tree = self.get_tree(tree)?;
 
 		let (bounds_sql, params) = bounds_sql(low, high);
-		let sql = format!("SELECT k, v FROM {} {} ORDER BY k DESC", tree, bounds_sql);
+		let sql = format!(
+			"SELECT k, v FROM {} {} ORDER BY k DESC",
+			tree.name, bounds_sql
+		);
 
 		let params = params
 			.iter()
@@ -550,6 +651,41 @@ impl<'a> Iterator for TxValueIteratorPin<'a> {
 
 // ---- utility ----
 
+fn internal_len(db: &rusqlite::Connection, tree: &SqliteTree) -> rusqlite::Result<usize> {
+	let mut stmt;
+	let mut res_iter = if tree.counted {
+		stmt = db.prepare(&format!("SELECT n FROM {} WHERE tree = ?1", COUNTS_TABLE))?;
+		stmt.query(params![tree.name.as_ref()])?
+	} else {
+		stmt = db.prepare(&format!("SELECT COUNT(*) FROM {}", tree.name))?;
+		stmt.query([])?
+	};
+	match res_iter.next()? {
+		None => Ok(0),
+		Some(v) => Ok(v.get::<_, usize>(0)?),
+	}
+}
+
+fn update_count(db: &rusqlite::Connection, tree: &SqliteTree, delta: i64) -> rusqlite::Result<()> {
+	if tree.counted {
+		db.execute(
+			&format!("UPDATE {} SET n = n + ?2 WHERE tree = ?1", COUNTS_TABLE),
+			params![tree.name.as_ref(), delta],
+		)?;
+	}
+	Ok(())
+}
+
+fn reset_count(db: &rusqlite::Connection, tree: &SqliteTree) -> rusqlite::Result<()> {
+	if tree.counted {
+		db.execute(
+			&format!("UPDATE {} SET n = 0 WHERE tree = ?1", COUNTS_TABLE),
+			params![tree.name.as_ref()],
+		)?;
+	}
+	Ok(())
+}
+
 fn bounds_sql<'r>(low: Bound<&'r [u8]>, high: Bound<&'r [u8]>) -> (String, Vec<Vec<u8>>) {
 	let mut sql = String::new();
 	let mut params: Vec<Vec<u8>> = vec![];
@@ -124,6 +124,102 @@ fn test_suite(db: Db) {
 	.unwrap();
 }
 
+fn test_counted_tree(db: Db) {
+	let tree = db.open_tree_counted("counted").unwrap();
+	let other = db.open_tree("not_counted").unwrap();
+
+	let full_scan = |t: &Tree| t.iter().unwrap().count();
+
+	// Simple pseudo-random generator, so that the sequence is reproducible
+	let mut state = 0x2545f4914f6cdd1du64;
+	let mut rand = move || {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		state
+	};
+
+	for i in 0..1000 {
+		let key = (rand() % 64).to_be_bytes();
+		let key2 = (rand() % 64).to_be_bytes();
+		match rand() % 4 {
+			0 => {
+				tree.insert(key, b"v").unwrap();
+			}
+			1 => {
+				tree.remove(key).unwrap();
+			}
+			2 => {
+				db.transaction::<_, (), _>(|tx| {
+					tx.insert(&tree, key, b"w")?;
+					tx.remove(&tree, key2)?;
+					tx.insert(&other, key, b"w")?;
+					assert_eq!(tx.len(&tree)?, tx.iter(&tree)?.count());
+					Ok(())
+				})
+				.unwrap();
+			}
+			_ => {
+				// Aborted transactions must not change the count
+				let res = db.transaction::<(), _, _>(|tx| {
+					tx.insert(&tree, key, b"x")?;
+					tx.remove(&tree, key2)?;
+					Err(TxError::Abort(()))
+				});
+				assert!(matches!(res, Err(TxError::Abort(()))));
+			}
+		}
+		if i % 250 == 249 {
+			tree.clear().unwrap();
+		}
+		assert_eq!(tree.len().unwrap(), full_scan(&tree));
+	}
+	assert_eq!(other.len().unwrap(), full_scan(&other));
+
+	// Re-opening the tree keeps the count consistent
+	let tree2 = db.open_tree_counted("counted").unwrap();
+	assert_eq!(tree2.len().unwrap(), full_scan(&tree));
+	let tree3 = db.open_tree("counted").unwrap();
+	tree3.insert(b"reopened", b"v").unwrap();
+	assert_eq!(tree.len().unwrap(), full_scan(&tree));
+}
+
+fn fill_for_snapshot(db: &Db) {
+	let tree = db.open_tree("tree").unwrap();
+	let counted = db.open_tree_counted("counted").unwrap();
+	for i in 0u64..100 {
+		tree.insert(i.to_be_bytes(), format!("value {}", i))
+			.unwrap();
+		if i % 3 == 0 {
+			counted.insert(i.to_be_bytes(), b"c").unwrap();
+		}
+	}
+	db.open_tree("empty").unwrap();
+}
+
+fn check_snapshot(db: &Db, snapshot: &Db) {
+	let mut trees = db.list_trees().unwrap();
+	trees.sort();
+	let mut snapshot_trees = snapshot.list_trees().unwrap();
+	snapshot_trees.sort();
+	assert_eq!(trees, snapshot_trees);
+
+	for name in trees.iter() {
+		let items = |d: &Db| {
+			d.open_tree(name)
+				.unwrap()
+				.iter()
+				.unwrap()
+				.map(|x| x.unwrap())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(items(db), items(snapshot), "tree {}", name);
+	}
+
+	let counted = snapshot.open_tree_counted("counted").unwrap();
+	assert_eq!(counted.len().unwrap(), 34);
+}
+
 #[test]
 #[cfg(feature = "lmdb")]
 fn test_lmdb_db() {
@@ -135,7 +231,8 @@ fn test_lmdb_db() {
 		.open(&path)
 		.unwrap();
 	let db = LmdbDb::init(db);
-	test_suite(db);
+	test_suite(db.clone());
+	test_counted_tree(db);
 	drop(path);
 }
 
@@ -148,3 +245,118 @@ fn test_sqlite_db() {
 	let db = SqliteDb::new(manager, false).unwrap();
 	test_suite(db);
 }
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_counted_tree() {
+	use crate::sqlite_adapter::SqliteDb;
+
+	let path = mktemp::Temp::new_dir().unwrap();
+	let manager = r2d2_sqlite::SqliteConnectionManager::file(path.join("db.sqlite"));
+	let db = SqliteDb::new(manager, false).unwrap();
+	test_counted_tree(db);
+	drop(path);
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_concurrent_open_and_write() {
+	use crate::sqlite_adapter::SqliteDb;
+
+	let path = mktemp::Temp::new_dir().unwrap();
+	let manager = r2d2_sqlite::SqliteConnectionManager::file(path.join("db.sqlite"));
+	let db = SqliteDb::new(manager, false).unwrap();
+	let tree = db.open_tree_counted("counted").unwrap();
+
+	// Opening trees while other threads write must not deadlock
+	std::thread::scope(|s| {
+		for t in 0u64..4 {
+			let (db, tree) = (&db, &tree);
+			s.spawn(move || {
+				for i in 0u64..100 {
+					let key = (t * 1000 + i).to_be_bytes();
+					match i % 3 {
+						0 => {
+							tree.insert(key, b"v").unwrap();
+						}
+						1 => {
+							db.transaction::<_, (), _>(|tx| {
+								tx.insert(tree, key, b"w")?;
+								Ok(())
+							})
+							.unwrap();
+						}
+						_ => {
+							db.open_tree_counted(&format!("counted_{}_{}", t, i))
+								.unwrap();
+						}
+					}
+				}
+			});
+		}
+	});
+	assert_eq!(tree.len().unwrap(), 4 * 67);
+	drop(path);
+}
+
+#[test]
+#[cfg(feature = "lmdb")]
+fn test_lmdb_snapshot() {
+	use crate::lmdb_adapter::LmdbDb;
+
+	let open = |path: &std::path::Path| {
+		let env = heed::EnvOpenOptions::new().max_dbs(100).open(path).unwrap();
+		LmdbDb::init(env)
+	};
+
+	let path = mktemp::Temp::new_dir().unwrap();
+	let db = open(&path);
+	fill_for_snapshot(&db);
+
+	let snapshot_dir = mktemp::Temp::new_dir().unwrap();
+	let snapshot_path = snapshot_dir.join("snapshot.lmdb");
+	db.snapshot(&snapshot_path).unwrap();
+
+	// Writes after the snapshot are not part of it
+	db.open_tree("tree").unwrap().insert(b"late", b"x").unwrap();
+	let snapshot = open(&snapshot_path);
+	assert!(snapshot
+		.open_tree("tree")
+		.unwrap()
+		.get(b"late")
+		.unwrap()
+		.is_none());
+	db.open_tree("tree").unwrap().remove(b"late").unwrap();
+
+	check_snapshot(&db, &snapshot);
+	drop(path);
+	drop(snapshot_dir);
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_snapshot() {
+	use crate::sqlite_adapter::SqliteDb;
+
+	let path = mktemp::Temp::new_dir().unwrap();
+	let manager = r2d2_sqlite::SqliteConnectionManager::file(path.join("db.sqlite"));
+	let db = SqliteDb::new(manager, false).unwrap();
+	fill_for_snapshot(&db);
+
+	let snapshot_path = path.join("snapshot.sqlite");
+	db.snapshot(&snapshot_path).unwrap();
+
+	db.open_tree("tree").unwrap().insert(b"late", b"x").unwrap();
+	let manager = r2d2_sqlite::SqliteConnectionManager::file(&snapshot_path);
+	let snapshot = SqliteDb::new(manager, false).unwrap();
+	assert!(snapshot
+		.open_tree("tree")
+		.unwrap()
+		.get(b"late")
+		.unwrap()
+		.is_none());
+	db.open_tree("tree").unwrap().remove(b"late").unwrap();
+
+	check_snapshot(&db, &snapshot);
+	drop(path);
+}
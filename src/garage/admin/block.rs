@@ -10,6 +10,9 @@ use crate::cli::*;
 
 use super::*;
 
+/// Number of block references fetched at once by `garage block purge-ref`
+const PURGE_REF_BATCH_SIZE: usize = 1000;
+
 impl AdminRpcHandler {
 	pub(super) async fn handle_block_cmd(&self, cmd: &BlockOperation) -> Result<AdminRpc, Error> {
 		match cmd {
@@ -21,6 +24,7 @@ impl AdminRpcHandler {
 				self.handle_block_retry_now(*all, blocks).await
 			}
 			BlockOperation::Purge { yes, blocks } => self.handle_block_purge(*yes, blocks).await,
+			BlockOperation::PurgeRef { yes, hash } => self.handle_block_purge_ref(*yes, hash).await,
 		}
 	}
 
@@ -189,6 +193,85 @@ impl AdminRpcHandler {
 		Ok(())
 	}
 
+	async fn handle_block_purge_ref(&self, yes: bool, hash: &str) -> Result<AdminRpc, Error> {
+		if !yes {
+			return Err(Error::BadRequest(
+				"Pass the --yes flag to confirm block reference purge operation.".into(),
+			));
+		}
+
+		let hash = hex::decode(hash).ok_or_bad_request("invalid hash")?;
+		let hash = Hash::try_from(&hash).ok_or_bad_request("invalid hash")?;
+
+		let mut purged = 0;
+		let mut kept = 0;
+		let mut not_found = 0;
+		let mut start: Option<Uuid> = None;
+		loop {
+			let block_refs = self
+				.garage
+				.block_ref_table
+				.get_range(
+					&hash,
+					start,
+					Some(DeletedFilter::NotDeleted),
+					PURGE_REF_BATCH_SIZE,
+					Default::default(),
+				)
+				.await?;
+			let batch_len = block_refs.len();
+			let prev_start = std::mem::replace(&mut start, block_refs.last().map(|br| br.version));
+
+			for mut br in block_refs {
+				// The range starts at the last reference of the previous batch
+				if Some(br.version) == prev_start {
+					continue;
+				}
+
+				// Only references to versions that exist and are deleted are removed.
+				// A version that is not found might simply not have been written
+				// or propagated yet (e.g. during an upload), so its references
+				// are kept.
+				let version = self
+					.garage
+					.version_table
+					.get(&br.version, &EmptyKey)
+					.await?;
+				match version {
+					Some(v) if v.deleted.get() => (),
+					Some(_) => {
+						kept += 1;
+						continue;
+					}
+					None => {
+						not_found += 1;
+						continue;
+					}
+				}
+
+				info!(
+					"Purging dangling reference to block {:?} from deleted version {:?}",
+					hash, br.version
+				);
+				br.deleted.set();
+				self.garage.block_ref_table.insert(&br).await?;
+				purged += 1;
+			}
+
+			if batch_len < PURGE_REF_BATCH_SIZE {
+				break;
+			}
+		}
+
+		Ok(AdminRpc::Ok(format!(
+			"Purged {} references to block {} from deleted versions, kept {} valid references and {} references to versions that were not found",
+			purged,
+			hex::encode(hash),
+			kept,
+			not_found,
+		)))
+	}
+
 	// ---- helper function ----
 	fn find_block_hash_by_prefix(&self, prefix: &str) -> Result<Hash, Error> {
 		if prefix.len() < 4 {
@@ -10,6 +10,7 @@ use garage_model::bucket_alias_table::*;
 use garage_model::bucket_table::*;
 use garage_model::helper::error::{Error, OkOrBadRequest};
 use garage_model::permission::*;
+use garage_model::s3::object_table;
 
 use crate::cli::*;
 
@@ -28,6 +29,9 @@ impl AdminRpcHandler {
 			BucketOperation::Deny(query) => self.handle_bucket_deny(query).await,
 			BucketOperation::Website(query) => self.handle_bucket_website(query).await,
 			BucketOperation::SetQuotas(query) => self.handle_bucket_set_quotas(query).await,
+			BucketOperation::ListIncompleteUploads(query) => {
+				self.handle_bucket_list_incomplete_uploads(query).await
+			}
 			BucketOperation::CleanupIncompleteUploads(query) => {
 				self.handle_bucket_cleanup_incomplete_uploads(query).await
 			}
@@ -126,7 +130,7 @@ impl AdminRpcHandler {
 
 	#[allow(clippy::ptr_arg)]
 	async fn handle_create_bucket(&self, name: &String) -> Result<AdminRpc, Error> {
-		if !is_valid_bucket_name(name) {
+		if !is_valid_bucket_name(name, self.garage.config.s3_api.relaxed_bucket_names) {
 			return Err(Error::BadRequest(format!(
 				"{}: {}",
 				name, INVALID_BUCKET_NAME_MESSAGE
@@ -174,6 +178,7 @@ impl AdminRpcHandler {
 		// Check bucket doesn't have other aliases
 		let mut bucket = helper.bucket().get_existing_bucket(bucket_id).await?;
 		let bucket_state = bucket.state.as_option().unwrap();
+
 		if bucket_state
 			.aliases
 			.items()
@@ -192,12 +197,51 @@ impl AdminRpcHandler {
 			return Err(Error::BadRequest(format!("Bucket {} still has other local aliases. Use `bucket unalias` to delete them one by one.", query.name)));
 		}
 
-		// Check bucket is empty
+		// Check bucket is empty, unless its contents are to be deleted as well
 		if !helper.bucket().is_bucket_empty(bucket_id).await? {
-			return Err(Error::BadRequest(format!(
-				"Bucket {} is not empty",
-				query.name
-			)));
+			if !query.force {
+				let counters = self.get_bucket_object_counters(bucket_id).await?;
+				return Err(Error::BucketNotEmpty(format!(
+					"bucket {} contains {} objects ({}). Use --force to delete them along with the bucket.",
+					query.name,
+					counters.get(object_table::OBJECTS).unwrap_or(&0),
+					bytesize::ByteSize::b(*counters.get(object_table::BYTES).unwrap_or(&0) as u64),
+				)));
+			}
+			if bucket_state.object_lock_config.get().is_some() {
+				return Err(Error::BadRequest(format!(
+					"Bucket {} has object lock enabled, its objects cannot be deleted with --force",
+					query.name
+				)));
+			}
+		}
+
+		if query.dry_run {
+			let counters = self.get_bucket_object_counters(bucket_id).await?;
+
+			let global_aliases = bucket_state
+				.aliases
+				.items()
+				.iter()
+				.filter(|(_, _, active)| *active)
+				.map(|(name, _, _)| name.to_string());
+			let local_aliases = bucket_state
+				.local_aliases
+				.items()
+				.iter()
+				.filter(|(_, _, active)| *active)
+				.map(|((key_id, name), _, _)| format!("{} (local to key {})", name, key_id));
+
+			return Ok(AdminRpc::DeleteDryRun {
+				target: format!("bucket {} ({:?})", query.name, bucket_id),
+				counters: Some(counters),
+				aliases: global_aliases.chain(local_aliases).collect(),
+				permissions: bucket
+					.authorized_keys()
+					.iter()
+					.map(|(key_id, _)| key_id.to_string())
+					.collect(),
+			});
 		}
 
 		if !query.yes {
@@ -207,6 +251,18 @@ impl AdminRpcHandler {
 		}
 
 		// --- done checking, now commit ---
+		// 0. delete bucket contents if required
+		let mut deleted_objects = 0;
+		if query.force {
+			deleted_objects = helper.bucket().delete_all_objects(&bucket_id).await?;
+			if !helper.bucket().is_bucket_empty(bucket_id).await? {
+				return Err(Error::BucketNotEmpty(format!(
+					"bucket {} still contains K2V items or objects written during deletion",
+					query.name
+				)));
+			}
+		}
+
 		// 1. delete authorization from keys that had access
 		for (key_id, _) in bucket.authorized_keys() {
 			helper
@@ -225,7 +281,28 @@ impl AdminRpcHandler {
 		bucket.state = Deletable::delete();
 		self.garage.bucket_table.insert(&bucket).await?;
 
-		Ok(AdminRpc::Ok(format!("Bucket {} was deleted.", query.name)))
+		if query.force {
+			Ok(AdminRpc::Ok(format!(
+				"Bucket {} was deleted, along with {} objects.",
+				query.name, deleted_objects
+			)))
+		} else {
+			Ok(AdminRpc::Ok(format!("Bucket {} was deleted.", query.name)))
+		}
+	}
+
+	async fn get_bucket_object_counters(
+		&self,
+		bucket_id: Uuid,
+	) -> Result<HashMap<String, i64>, Error> {
+		Ok(self
+			.garage
+			.object_counter_table
+			.table
+			.get(&bucket_id, &EmptyKey)
+			.await?
+			.map(|x| x.filtered_values(&self.garage.system.cluster_layout()))
+			.unwrap_or_default())
 	}
 
 	async fn handle_alias_bucket(&self, query: &AliasBucketOpt) -> Result<AdminRpc, Error> {
@@ -424,16 +501,20 @@ impl AdminRpcHandler {
 			.await?;
 		let bucket_state = bucket.state.as_option_mut().unwrap();
 
-		if query.max_size.is_none() && query.max_objects.is_none() {
+		if query.max_size.is_none()
+			&& query.max_objects.is_none()
+			&& !query.strict
+			&& !query.no_strict
+		{
 			return Err(Error::BadRequest(
-				"You must specify either --max-size or --max-objects (or both) for this command to do something.".to_string(),
+				"You must specify at least one of --max-size, --max-objects, --strict or --no-strict for this command to do something.".to_string(),
 			));
 		}
 
 		let mut quotas = bucket_state.quotas.get().clone();
 
-		match query.max_size.as_ref().map(String::as_ref) {
-			Some("none") => quotas.max_size = None,
+		match query.max_size.as_deref() {
+			Some(v) if v.eq_ignore_ascii_case("none") => quotas.max_size = None,
 			Some(v) => {
 				let bs = v
 					.parse::<bytesize::ByteSize>()
@@ -443,8 +524,8 @@ impl AdminRpcHandler {
 			_ => (),
 		}
 
-		match query.max_objects.as_ref().map(String::as_ref) {
-			Some("none") => quotas.max_objects = None,
+		match query.max_objects.as_deref() {
+			Some(v) if v.eq_ignore_ascii_case("none") => quotas.max_objects = None,
 			Some(v) => {
 				let mo = v
 					.parse::<u64>()
@@ -454,6 +535,12 @@ impl AdminRpcHandler {
 			_ => (),
 		}
 
+		if query.strict {
+			quotas.strict = true;
+		} else if query.no_strict {
+			quotas.strict = false;
+		}
+
 		bucket_state.quotas.update(quotas);
 		self.garage.bucket_table.insert(&bucket).await?;
 
@@ -463,6 +550,56 @@ impl AdminRpcHandler {
 		)))
 	}
 
+	async fn handle_bucket_list_incomplete_uploads(
+		&self,
+		query: &ListIncompleteUploadsOpt,
+	) -> Result<AdminRpc, Error> {
+		let bucket_id = self
+			.garage
+			.bucket_helper()
+			.admin_get_existing_matching_bucket(&query.bucket)
+			.await?;
+
+		let older_than = match &query.older_than {
+			Some(d) => Some(
+				parse_duration::parse::parse(d)
+					.ok_or_bad_request("Invalid duration passed for --older-than parameter")?,
+			),
+			None => None,
+		};
+
+		let uploads = self
+			.garage
+			.bucket_helper()
+			.list_incomplete_uploads(&bucket_id, older_than)
+			.await?;
+
+		let mut ret = vec![];
+		for (key, version) in uploads {
+			let parts = match self.garage.mpu_table.get(&version.uuid, &EmptyKey).await? {
+				Some(mpu) => {
+					let mut part_numbers = mpu
+						.parts
+						.items()
+						.iter()
+						.map(|(k, _)| k.part_number)
+						.collect::<Vec<_>>();
+					part_numbers.dedup();
+					part_numbers.len()
+				}
+				None => 0,
+			};
+			ret.push(IncompleteUploadInfo {
+				key,
+				upload_id: version.uuid,
+				timestamp: version.timestamp,
+				parts,
+			});
+		}
+
+		Ok(AdminRpc::IncompleteUploadList(ret))
+	}
+
 	async fn handle_bucket_cleanup_incomplete_uploads(
 		&self,
 		query: &CleanupIncompleteUploadsOpt,
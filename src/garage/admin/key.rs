@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use garage_table::*;
+use garage_util::forwarded_headers::parse_cidr;
 
 use garage_model::helper::error::*;
 use garage_model::key_table::*;
@@ -16,6 +17,9 @@ impl AdminRpcHandler {
 			KeyOperation::Info(query) => self.handle_key_info(query).await,
 			KeyOperation::Create(query) => self.handle_create_key(query).await,
 			KeyOperation::Rename(query) => self.handle_rename_key(query).await,
+			KeyOperation::Update(query) => self.handle_update_key(query).await,
+			KeyOperation::SetRateLimit(query) => self.handle_key_set_rate_limit(query).await,
+			KeyOperation::SetAllowedCidrs(query) => self.handle_key_set_allowed_cidrs(query).await,
 			KeyOperation::Delete(query) => self.handle_delete_key(query).await,
 			KeyOperation::Allow(query) => self.handle_allow_key(query).await,
 			KeyOperation::Deny(query) => self.handle_deny_key(query).await,
@@ -56,7 +60,16 @@ impl AdminRpcHandler {
 	}
 
 	async fn handle_create_key(&self, query: &KeyNewOpt) -> Result<AdminRpc, Error> {
-		let key = Key::new(&query.name);
+		let mut key = Key::new(&query.name);
+		if let Some(description) = &query.description {
+			key.params_mut()
+				.unwrap()
+				.description
+				.update(Some(description.clone()));
+		}
+		if query.allow_create_bucket {
+			key.params_mut().unwrap().allow_create_bucket.update(true);
+		}
 		self.garage.key_table.insert(&key).await?;
 		self.key_info_result(key).await
 	}
@@ -75,6 +88,94 @@ impl AdminRpcHandler {
 		self.key_info_result(key).await
 	}
 
+	async fn handle_update_key(&self, query: &KeyUpdateOpt) -> Result<AdminRpc, Error> {
+		let mut key = self
+			.garage
+			.key_helper()
+			.get_existing_matching_key(&query.key_pattern)
+			.await?;
+		if let Some(description) = &query.description {
+			let description = Some(description.clone()).filter(|d| !d.is_empty());
+			key.params_mut().unwrap().description.update(description);
+		}
+		self.garage.key_table.insert(&key).await?;
+		self.key_info_result(key).await
+	}
+
+	async fn handle_key_set_rate_limit(&self, query: &KeyRateLimitOpt) -> Result<AdminRpc, Error> {
+		let mut key = self
+			.garage
+			.key_helper()
+			.get_existing_matching_key(&query.key_pattern)
+			.await?;
+
+		if query.requests_per_sec.is_none() && query.bytes_per_sec.is_none() {
+			return Err(Error::BadRequest(
+				"You must specify either --requests-per-sec or --bytes-per-sec (or both) for this command to do something.".to_string(),
+			));
+		}
+
+		let key_state = key.params_mut().unwrap();
+		let mut rate_limit = key_state.rate_limit.get().clone();
+
+		match query.requests_per_sec.as_ref().map(String::as_ref) {
+			Some("none") => rate_limit.requests_per_sec = None,
+			Some(v) => {
+				let rps = v
+					.parse::<u64>()
+					.ok_or_bad_request(format!("Invalid number specified: {}", v))?;
+				rate_limit.requests_per_sec = Some(rps);
+			}
+			_ => (),
+		}
+
+		match query.bytes_per_sec.as_ref().map(String::as_ref) {
+			Some("none") => rate_limit.bytes_per_sec = None,
+			Some(v) => {
+				let bs = v
+					.parse::<bytesize::ByteSize>()
+					.ok_or_bad_request(format!("Invalid size specified: {}", v))?;
+				rate_limit.bytes_per_sec = Some(bs.as_u64());
+			}
+			_ => (),
+		}
+
+		key_state.rate_limit.update(rate_limit);
+		self.garage.key_table.insert(&key).await?;
+
+		self.key_info_result(key).await
+	}
+
+	async fn handle_key_set_allowed_cidrs(
+		&self,
+		query: &KeyAllowedCidrsOpt,
+	) -> Result<AdminRpc, Error> {
+		let mut key = self
+			.garage
+			.key_helper()
+			.get_existing_matching_key(&query.key_pattern)
+			.await?;
+
+		let allowed_cidrs = match &query.cidrs[..] {
+			[none] if none.eq_ignore_ascii_case("none") => vec![],
+			cidrs => cidrs
+				.iter()
+				.map(|cidr| {
+					parse_cidr(cidr)
+						.ok_or_bad_request(format!("Invalid IP range specified: {}", cidr))
+				})
+				.collect::<Result<Vec<_>, _>>()?,
+		};
+
+		key.params_mut()
+			.unwrap()
+			.allowed_cidrs
+			.update(allowed_cidrs);
+		self.garage.key_table.insert(&key).await?;
+
+		self.key_info_result(key).await
+	}
+
 	async fn handle_delete_key(&self, query: &KeyDeleteOpt) -> Result<AdminRpc, Error> {
 		let helper = self.garage.locked_helper().await;
 
@@ -83,6 +184,27 @@ impl AdminRpcHandler {
 			.get_existing_matching_key(&query.key_pattern)
 			.await?;
 
+		if query.dry_run {
+			let state = key.state.as_option().unwrap();
+			return Ok(AdminRpc::DeleteDryRun {
+				target: format!("key {} ({})", key.key_id, state.name.get()),
+				counters: None,
+				aliases: state
+					.local_aliases
+					.items()
+					.iter()
+					.filter(|(_, _, to)| to.is_some())
+					.map(|(alias, _, _)| alias.to_string())
+					.collect(),
+				permissions: state
+					.authorized_buckets
+					.items()
+					.iter()
+					.map(|(bucket_id, _)| hex::encode(bucket_id))
+					.collect(),
+			});
+		}
+
 		if !query.yes {
 			return Err(Error::BadRequest(
 				"Add --yes flag to really perform this operation".to_string(),
@@ -1,6 +1,7 @@
 mod block;
 mod bucket;
 mod key;
+mod object;
 
 use std::collections::HashMap;
 use std::fmt::Write;
@@ -11,7 +12,7 @@ use serde::{Deserialize, Serialize};
 
 use format_table::format_table_to_string;
 
-use garage_util::background::BackgroundRunner;
+use garage_util::background::{BackgroundRunner, WorkerKind, WorkerState};
 use garage_util::data::*;
 use garage_util::error::Error as GarageError;
 
@@ -28,6 +29,7 @@ use garage_model::garage::Garage;
 use garage_model::helper::error::{Error, OkOrBadRequest};
 use garage_model::key_table::*;
 use garage_model::s3::mpu_table::MultipartUpload;
+use garage_model::s3::object_table::{Object, BYTES};
 use garage_model::s3::version_table::Version;
 
 use crate::cli::*;
@@ -44,6 +46,7 @@ pub enum AdminRpc {
 	Stats(StatsOpt),
 	Worker(WorkerOperation),
 	BlockOperation(BlockOperation),
+	ObjectOperation(ObjectOperation),
 	MetaOperation(MetaOperation),
 
 	// Replies
@@ -64,12 +67,45 @@ pub enum AdminRpc {
 	WorkerVars(Vec<(Uuid, String, String)>),
 	WorkerInfo(usize, garage_util::background::WorkerInfo),
 	BlockErrorList(Vec<BlockResyncErrorInfo>),
+	IncompleteUploadList(Vec<IncompleteUploadInfo>),
 	BlockInfo {
 		hash: Hash,
 		refcount: u64,
 		versions: Vec<Result<Version, Uuid>>,
 		uploads: Vec<MultipartUpload>,
 	},
+	ObjectInfo {
+		bucket_id: Uuid,
+		object: Object,
+		versions: Vec<Version>,
+		uploads: Vec<MultipartUpload>,
+		first_block_nodes: HashMap<Uuid, Vec<Uuid>>,
+	},
+	DeleteDryRun {
+		target: String,
+		counters: Option<HashMap<String, i64>>,
+		aliases: Vec<String>,
+		permissions: Vec<String>,
+	},
+	NodeStats(StatsJson),
+	RepairStatus(Vec<RepairStatusInfo>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IncompleteUploadInfo {
+	pub key: String,
+	pub upload_id: Uuid,
+	pub timestamp: u64,
+	pub parts: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepairStatusInfo {
+	pub node: Uuid,
+	/// Table sync workers, with the number of partitions they still have to sync
+	pub table_syncs: Vec<(String, u64)>,
+	/// Repair workers, with their state, progress and current phase
+	pub repairs: Vec<(String, WorkerState, Option<String>, Option<String>)>,
 }
 
 impl Rpc for AdminRpc {
@@ -97,6 +133,9 @@ impl AdminRpcHandler {
 	// ================ REPAIR COMMANDS ====================
 
 	async fn handle_launch_repair(self: &Arc<Self>, opt: RepairOpt) -> Result<AdminRpc, Error> {
+		if matches!(opt.what, RepairWhat::Status { .. }) {
+			return self.handle_repair_status(opt).await;
+		}
 		if !opt.yes {
 			return Err(Error::BadRequest(
 				"Please provide the --yes flag to initiate repair operations.".to_string(),
@@ -139,9 +178,66 @@ impl AdminRpcHandler {
 		}
 	}
 
+	async fn handle_repair_status(&self, opt: RepairOpt) -> Result<AdminRpc, Error> {
+		if !opt.all_nodes {
+			return Ok(AdminRpc::RepairStatus(vec![self.gather_repair_status()]));
+		}
+
+		let mut ret = vec![];
+		let all_nodes = self.garage.system.cluster_layout().all_nodes().to_vec();
+		for node in all_nodes.iter() {
+			let mut opt = opt.clone();
+			opt.all_nodes = false;
+
+			let node_id = (*node).into();
+			match self
+				.endpoint
+				.call(&node_id, AdminRpc::LaunchRepair(opt), PRIO_NORMAL)
+				.await??
+			{
+				AdminRpc::RepairStatus(s) => ret.extend(s),
+				m => return Err(GarageError::unexpected_rpc_message(m).into()),
+			}
+		}
+		Ok(AdminRpc::RepairStatus(ret))
+	}
+
+	fn gather_repair_status(&self) -> RepairStatusInfo {
+		let mut workers = self
+			.background
+			.get_worker_info()
+			.into_iter()
+			.collect::<Vec<_>>();
+		workers.sort_by_key(|(tid, _)| *tid);
+
+		let mut table_syncs = vec![];
+		let mut repairs = vec![];
+		for (_, wi) in workers {
+			match wi.kind {
+				WorkerKind::TableSync => {
+					table_syncs.push((wi.name, wi.status.queue_length.unwrap_or(0)));
+				}
+				WorkerKind::Repair => {
+					let phase = wi.status.freeform.into_iter().next();
+					repairs.push((wi.name, wi.state, wi.status.progress, phase));
+				}
+				WorkerKind::Other => (),
+			}
+		}
+
+		RepairStatusInfo {
+			node: self.garage.system.id,
+			table_syncs,
+			repairs,
+		}
+	}
+
 	// ================ STATS COMMANDS ====================
 
 	async fn handle_stats(&self, opt: StatsOpt) -> Result<AdminRpc, Error> {
+		if opt.json {
+			return self.handle_stats_json(opt).await;
+		}
 		if opt.all_nodes {
 			let mut ret = String::new();
 			let all_nodes = self.garage.system.cluster_layout().all_nodes().to_vec();
@@ -177,11 +273,103 @@ impl AdminRpcHandler {
 
 			Ok(AdminRpc::Ok(ret))
 		} else {
-			Ok(AdminRpc::Ok(self.gather_stats_local(opt)?))
+			Ok(AdminRpc::Ok(self.gather_stats_local(opt).await?))
 		}
 	}
 
-	fn gather_stats_local(&self, opt: StatsOpt) -> Result<String, Error> {
+	async fn handle_stats_json(&self, opt: StatsOpt) -> Result<AdminRpc, Error> {
+		if !opt.all_nodes {
+			return Ok(AdminRpc::NodeStats(StatsJson {
+				nodes: vec![self.gather_stats_local_json(&opt).await?],
+				errors: vec![],
+			}));
+		}
+
+		// Nodes that cannot be reached are reported along with the
+		// statistics of the others, as in the text output
+		let mut ret = StatsJson {
+			nodes: vec![],
+			errors: vec![],
+		};
+		let all_nodes = self.garage.system.cluster_layout().all_nodes().to_vec();
+		for node in all_nodes.iter() {
+			let mut opt = opt.clone();
+			opt.all_nodes = false;
+
+			let node_id = (*node).into();
+			let error = match self
+				.endpoint
+				.call(&node_id, AdminRpc::Stats(opt), PRIO_NORMAL)
+				.await
+			{
+				Ok(Ok(AdminRpc::NodeStats(s))) => {
+					ret.nodes.extend(s.nodes);
+					ret.errors.extend(s.errors);
+					continue;
+				}
+				Ok(Ok(x)) => format!("Bad answer: {:?}", x),
+				Ok(Err(e)) => format!("Remote error: {}", e),
+				Err(e) => format!("Network error: {}", e),
+			};
+			ret.errors.push(NodeErrorJson {
+				id: hex::encode(node),
+				error,
+			});
+		}
+		Ok(AdminRpc::NodeStats(ret))
+	}
+
+	async fn gather_stats_local_json(&self, opt: &StatsOpt) -> Result<NodeStatsJson, Error> {
+		let tables = vec![
+			self.gather_table_stats_json(&self.garage.bucket_table)?,
+			self.gather_table_stats_json(&self.garage.key_table)?,
+			self.gather_table_stats_json(&self.garage.object_table)?,
+			self.gather_table_stats_json(&self.garage.version_table)?,
+			self.gather_table_stats_json(&self.garage.block_ref_table)?,
+		];
+
+		let block_manager = &self.garage.block_manager;
+		let (resync_needed, resync_to_delete) = block_manager.resync.queue_composition()?;
+		let compression = opt.detailed.then(|| {
+			let stats = block_manager.compression_stats();
+			CompressionStatsJson {
+				bytes_in: stats.bytes_in(),
+				bytes_out: stats.bytes_out(),
+				ratio: stats.ratio(),
+			}
+		});
+		let deduplication = if opt.detailed {
+			let (logical_bytes, physical_bytes) = self.gather_dedup_stats().await?;
+			Some(DeduplicationStatsJson {
+				logical_bytes,
+				physical_bytes,
+				ratio: dedup_ratio(logical_bytes, physical_bytes),
+			})
+		} else {
+			None
+		};
+
+		Ok(NodeStatsJson {
+			id: hex::encode(self.garage.system.id),
+			garage_version: garage_util::version::garage_version().to_string(),
+			garage_features: garage_util::version::garage_features()
+				.map(|list| list.iter().map(|x| x.to_string()).collect()),
+			rust_version: garage_util::version::rust_version().to_string(),
+			db_engine: self.garage.db.engine(),
+			tables,
+			block_manager: BlockManagerStatsJson {
+				rc_entries: block_manager.rc_len()?,
+				resync_queue_length: block_manager.resync.queue_len()?,
+				resync_needed,
+				resync_to_delete,
+				resync_errors: block_manager.resync.errors_len()?,
+				compression,
+				deduplication,
+			},
+		})
+	}
+
+	async fn gather_stats_local(&self, opt: StatsOpt) -> Result<String, Error> {
 		let mut ret = String::new();
 		writeln!(
 			&mut ret,
@@ -197,7 +385,7 @@ impl AdminRpcHandler {
 		writeln!(&mut ret, "\nDatabase engine: {}", self.garage.db.engine()).unwrap();
 
 		// Gather table statistics
-		let mut table = vec!["  Table\tItems\tMklItems\tMklTodo\tGcTodo".into()];
+		let mut table = vec!["  Table\tItems\tMklItems\tMklTodo\tGcTodo\tSyncTodo".into()];
 		table.push(self.gather_table_stats(&self.garage.bucket_table)?);
 		table.push(self.gather_table_stats(&self.garage.key_table)?);
 		table.push(self.gather_table_stats(&self.garage.object_table)?);
@@ -209,6 +397,11 @@ impl AdminRpcHandler {
 			format_table_to_string(table)
 		)
 		.unwrap();
+		writeln!(
+			&mut ret,
+			"  (counts are approximate; SyncTodo = partitions remaining in current full sync)"
+		)
+		.unwrap();
 
 		// Gather block manager statistics
 		writeln!(&mut ret, "\nBlock manager stats:").unwrap();
@@ -220,10 +413,14 @@ impl AdminRpcHandler {
 			rc_len
 		)
 		.unwrap();
+		let (resync_needed, resync_unneeded) =
+			self.garage.block_manager.resync.queue_composition()?;
 		writeln!(
 			&mut ret,
-			"  resync queue length: {}",
-			self.garage.block_manager.resync.queue_len()?
+			"  resync queue length: {} ({} needed, {} to delete)",
+			self.garage.block_manager.resync.queue_len()?,
+			resync_needed,
+			resync_unneeded
 		)
 		.unwrap();
 		writeln!(
@@ -233,6 +430,33 @@ impl AdminRpcHandler {
 		)
 		.unwrap();
 
+		if opt.detailed {
+			let compression = self.garage.block_manager.compression_stats();
+			writeln!(
+				&mut ret,
+				"  compression: {} received, {} stored, ratio {}",
+				bytesize::ByteSize::b(compression.bytes_in()),
+				bytesize::ByteSize::b(compression.bytes_out()),
+				compression
+					.ratio()
+					.map(|r| format!("{:.2}", r))
+					.unwrap_or_else(|| "n/a".into())
+			)
+			.unwrap();
+
+			let (logical, physical) = self.gather_dedup_stats().await?;
+			writeln!(
+				&mut ret,
+				"  deduplication (approximate): {} of object data, {} of blocks stored, ratio {}",
+				bytesize::ByteSize::b(logical),
+				bytesize::ByteSize::b(physical),
+				dedup_ratio(logical, physical)
+					.map(|r| format!("{:.2}", r))
+					.unwrap_or_else(|| "n/a".into())
+			)
+			.unwrap();
+		}
+
 		if !opt.skip_global {
 			write!(&mut ret, "\n{}", self.gather_cluster_stats()).unwrap();
 		}
@@ -240,6 +464,17 @@ impl AdminRpcHandler {
 		Ok(ret)
 	}
 
+	/// Returns the total size of objects stored in the partitions of this node,
+	/// and the total size of the data blocks stored on this node.
+	/// The two do not cover exactly the same data (objects and blocks are not
+	/// placed according to the same hash, and small objects are stored inline
+	/// without any data block), so their ratio is only an estimate.
+	async fn gather_dedup_stats(&self) -> Result<(u64, u64), Error> {
+		let logical = self.garage.object_counter_table.local_total(BYTES)?;
+		let physical = self.garage.block_manager.stored_bytes().await?;
+		Ok((std::cmp::max(logical, 0) as u64, physical))
+	}
+
 	fn gather_cluster_stats(&self) -> String {
 		let mut ret = String::new();
 
@@ -343,15 +578,31 @@ impl AdminRpcHandler {
 		let mkl_len = t.merkle_updater.merkle_tree_len()?.to_string();
 
 		Ok(format!(
-			"  {}\t{}\t{}\t{}\t{}",
+			"  {}\t{}\t{}\t{}\t{}\t{}",
 			F::TABLE_NAME,
 			data_len,
 			mkl_len,
 			t.merkle_updater.todo_len()?,
-			t.data.gc_todo_len()?
+			t.data.gc_todo_len()?,
+			t.syncer.pending_partitions(),
 		))
 	}
 
+	fn gather_table_stats_json<F, R>(&self, t: &Arc<Table<F, R>>) -> Result<TableStatsJson, Error>
+	where
+		F: TableSchema + 'static,
+		R: TableReplication + 'static,
+	{
+		Ok(TableStatsJson {
+			name: F::TABLE_NAME.to_string(),
+			items: t.data.store.len().map_err(GarageError::from)?,
+			merkle_items: t.merkle_updater.merkle_tree_len()?,
+			merkle_todo: t.merkle_updater.todo_len()?,
+			gc_todo: t.data.gc_todo_len()?,
+			sync_todo: t.syncer.pending_partitions(),
+		})
+	}
+
 	// ================ WORKER COMMANDS ====================
 
 	async fn handle_worker_cmd(&self, cmd: &WorkerOperation) -> Result<AdminRpc, Error> {
@@ -471,7 +722,7 @@ impl AdminRpcHandler {
 
 	async fn handle_meta_cmd(self: &Arc<Self>, mo: &MetaOperation) -> Result<AdminRpc, Error> {
 		match mo {
-			MetaOperation::Snapshot { all: true } => {
+			MetaOperation::Snapshot { all: true, output } => {
 				let to = self.garage.system.cluster_layout().all_nodes().to_vec();
 
 				let resps = futures::future::join_all(to.iter().map(|to| async move {
@@ -479,7 +730,10 @@ impl AdminRpcHandler {
 					self.endpoint
 						.call(
 							&to,
-							AdminRpc::MetaOperation(MetaOperation::Snapshot { all: false }),
+							AdminRpc::MetaOperation(MetaOperation::Snapshot {
+								all: false,
+								output: output.clone(),
+							}),
 							PRIO_NORMAL,
 						)
 						.await
@@ -489,7 +743,9 @@ impl AdminRpcHandler {
 				let mut ret = vec![];
 				for (to, resp) in to.iter().zip(resps.iter()) {
 					let res_str = match resp {
-						Ok(_) => "ok".to_string(),
+						Ok(Ok(AdminRpc::Ok(msg))) => msg.clone(),
+						Ok(Ok(_)) => "ok".to_string(),
+						Ok(Err(e)) => format!("error: {}", e),
 						Err(e) => format!("error: {}", e),
 					};
 					ret.push(format!("{:?}\t{}", to, res_str));
@@ -497,14 +753,28 @@ impl AdminRpcHandler {
 
 				Ok(AdminRpc::Ok(format_table_to_string(ret)))
 			}
-			MetaOperation::Snapshot { all: false } => {
-				garage_model::snapshot::async_snapshot_metadata(&self.garage).await?;
-				Ok(AdminRpc::Ok("Snapshot has been saved.".into()))
+			MetaOperation::Snapshot { all: false, output } => {
+				let path =
+					garage_model::snapshot::async_snapshot_metadata(&self.garage, output.clone())
+						.await?;
+				Ok(AdminRpc::Ok(format!(
+					"Snapshot has been saved to {}",
+					path.display()
+				)))
 			}
 		}
 	}
 }
 
+/// Size of object data divided by size of stored blocks,
+/// or None if no block is stored on this node
+fn dedup_ratio(logical: u64, physical: u64) -> Option<f64> {
+	match physical {
+		0 => None,
+		_ => Some(logical as f64 / physical as f64),
+	}
+}
+
 #[async_trait]
 impl EndpointHandler<AdminRpc> for AdminRpcHandler {
 	async fn handle(
@@ -519,6 +789,7 @@ impl EndpointHandler<AdminRpc> for AdminRpcHandler {
 			AdminRpc::Stats(opt) => self.handle_stats(opt.clone()).await,
 			AdminRpc::Worker(wo) => self.handle_worker_cmd(wo).await,
 			AdminRpc::BlockOperation(bo) => self.handle_block_cmd(bo).await,
+			AdminRpc::ObjectOperation(oo) => self.handle_object_cmd(oo).await,
 			AdminRpc::MetaOperation(mo) => self.handle_meta_cmd(mo).await,
 			m => Err(GarageError::unexpected_rpc_message(m).into()),
 		}
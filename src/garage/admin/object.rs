@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use garage_table::*;
+
+use garage_model::helper::error::{Error, OkOrBadRequest};
+use garage_model::s3::object_table::*;
+
+use crate::cli::*;
+
+use super::*;
+
+impl AdminRpcHandler {
+	pub(super) async fn handle_object_cmd(&self, cmd: &ObjectOperation) -> Result<AdminRpc, Error> {
+		match cmd {
+			ObjectOperation::Info { bucket, key, nodes } => {
+				self.handle_object_info(bucket, key, *nodes).await
+			}
+		}
+	}
+
+	async fn handle_object_info(
+		&self,
+		bucket: &String,
+		key: &String,
+		nodes: bool,
+	) -> Result<AdminRpc, Error> {
+		let bucket_id = self
+			.garage
+			.bucket_helper()
+			.admin_get_existing_matching_bucket(bucket)
+			.await?;
+
+		let object = self
+			.garage
+			.object_table
+			.get(&bucket_id, key)
+			.await?
+			.ok_or_bad_request("Object not found")?;
+
+		let mut versions = vec![];
+		let mut uploads = vec![];
+		let mut first_block_nodes = HashMap::new();
+		for ov in object.versions().iter() {
+			match &ov.state {
+				ObjectVersionState::Uploading {
+					multipart: true, ..
+				} => {
+					if let Some(u) = self.garage.mpu_table.get(&ov.uuid, &EmptyKey).await? {
+						uploads.push(u);
+					}
+				}
+				ObjectVersionState::Uploading { .. }
+				| ObjectVersionState::Complete(ObjectVersionData::FirstBlock(..)) => {
+					if let Some(v) = self.garage.version_table.get(&ov.uuid, &EmptyKey).await? {
+						versions.push(v);
+					}
+				}
+				_ => (),
+			}
+
+			if let ObjectVersionState::Complete(ObjectVersionData::FirstBlock(_, hash)) = &ov.state
+			{
+				if nodes {
+					first_block_nodes.insert(
+						ov.uuid,
+						self.garage.block_manager.replication.storage_nodes(hash),
+					);
+				}
+			}
+		}
+
+		Ok(AdminRpc::ObjectInfo {
+			bucket_id,
+			object,
+			versions,
+			uploads,
+			first_block_nodes,
+		})
+	}
+}
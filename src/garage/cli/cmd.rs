@@ -2,6 +2,7 @@ use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 
 use format_table::format_table;
+use garage_util::background::WorkerState;
 use garage_util::error::*;
 
 use garage_rpc::layout::*;
@@ -18,9 +19,10 @@ pub async fn cli_command_dispatch(
 	system_rpc_endpoint: &Endpoint<SystemRpc, ()>,
 	admin_rpc_endpoint: &Endpoint<AdminRpc, ()>,
 	rpc_host: NodeID,
+	output: OutputFormat,
 ) -> Result<(), HelperError> {
 	match cmd {
-		Command::Status => Ok(cmd_status(system_rpc_endpoint, rpc_host).await?),
+		Command::Status => Ok(cmd_status(system_rpc_endpoint, rpc_host, output).await?),
 		Command::Node(NodeOperation::Connect(connect_opt)) => {
 			Ok(cmd_connect(system_rpc_endpoint, rpc_host, connect_opt).await?)
 		}
@@ -28,30 +30,91 @@ pub async fn cli_command_dispatch(
 			Ok(cli_layout_command_dispatch(layout_opt, system_rpc_endpoint, rpc_host).await?)
 		}
 		Command::Bucket(bo) => {
-			cmd_admin(admin_rpc_endpoint, rpc_host, AdminRpc::BucketOperation(bo)).await
+			cmd_admin(
+				admin_rpc_endpoint,
+				rpc_host,
+				AdminRpc::BucketOperation(bo),
+				output,
+			)
+			.await
 		}
 		Command::Key(ko) => {
-			cmd_admin(admin_rpc_endpoint, rpc_host, AdminRpc::KeyOperation(ko)).await
+			cmd_admin(
+				admin_rpc_endpoint,
+				rpc_host,
+				AdminRpc::KeyOperation(ko),
+				output,
+			)
+			.await
 		}
 		Command::Repair(ro) => {
-			cmd_admin(admin_rpc_endpoint, rpc_host, AdminRpc::LaunchRepair(ro)).await
+			if let RepairWhat::Status { watch: Some(secs) } = ro.what {
+				return cmd_repair_status_watch(
+					admin_rpc_endpoint,
+					rpc_host,
+					ro,
+					Duration::from_secs(std::cmp::max(secs, 1)),
+				)
+				.await;
+			}
+			cmd_admin(
+				admin_rpc_endpoint,
+				rpc_host,
+				AdminRpc::LaunchRepair(ro),
+				output,
+			)
+			.await
+		}
+		Command::Stats(mut so) => {
+			so.json = output == OutputFormat::Json;
+			cmd_admin(admin_rpc_endpoint, rpc_host, AdminRpc::Stats(so), output).await
+		}
+		Command::Worker(wo) => {
+			cmd_admin(admin_rpc_endpoint, rpc_host, AdminRpc::Worker(wo), output).await
 		}
-		Command::Stats(so) => cmd_admin(admin_rpc_endpoint, rpc_host, AdminRpc::Stats(so)).await,
-		Command::Worker(wo) => cmd_admin(admin_rpc_endpoint, rpc_host, AdminRpc::Worker(wo)).await,
 		Command::Block(bo) => {
-			cmd_admin(admin_rpc_endpoint, rpc_host, AdminRpc::BlockOperation(bo)).await
+			cmd_admin(
+				admin_rpc_endpoint,
+				rpc_host,
+				AdminRpc::BlockOperation(bo),
+				output,
+			)
+			.await
+		}
+		Command::Object(oo) => {
+			cmd_admin(
+				admin_rpc_endpoint,
+				rpc_host,
+				AdminRpc::ObjectOperation(oo),
+				output,
+			)
+			.await
 		}
 		Command::Meta(mo) => {
-			cmd_admin(admin_rpc_endpoint, rpc_host, AdminRpc::MetaOperation(mo)).await
+			cmd_admin(
+				admin_rpc_endpoint,
+				rpc_host,
+				AdminRpc::MetaOperation(mo),
+				output,
+			)
+			.await
 		}
 		_ => unreachable!(),
 	}
 }
 
-pub async fn cmd_status(rpc_cli: &Endpoint<SystemRpc, ()>, rpc_host: NodeID) -> Result<(), Error> {
+pub async fn cmd_status(
+	rpc_cli: &Endpoint<SystemRpc, ()>,
+	rpc_host: NodeID,
+	output: OutputFormat,
+) -> Result<(), Error> {
 	let status = fetch_status(rpc_cli, rpc_host).await?;
 	let layout = fetch_layout(rpc_cli, rpc_host).await?;
 
+	if output == OutputFormat::Json {
+		return print_json(&status_json(&status, &layout));
+	}
+
 	println!("==== HEALTHY NODES ====");
 	let mut healthy_nodes =
 		vec!["ID\tHostname\tAddress\tTags\tZone\tCapacity\tDataAvail".to_string()];
@@ -215,6 +278,7 @@ pub async fn cmd_admin(
 	rpc_cli: &Endpoint<AdminRpc, ()>,
 	rpc_host: NodeID,
 	args: AdminRpc,
+	output: OutputFormat,
 ) -> Result<(), HelperError> {
 	match rpc_cli.call(&rpc_host, args, PRIO_NORMAL).await?? {
 		AdminRpc::Ok(msg) => {
@@ -228,15 +292,26 @@ pub async fn cmd_admin(
 			relevant_keys,
 			counters,
 			mpu_counters,
-		} => {
-			print_bucket_info(&bucket, &relevant_keys, &counters, &mpu_counters);
-		}
+		} => match output {
+			OutputFormat::Table => {
+				print_bucket_info(&bucket, &relevant_keys, &counters, &mpu_counters);
+			}
+			OutputFormat::Json => {
+				print_json(&bucket_info_json(
+					&bucket,
+					&relevant_keys,
+					&counters,
+					&mpu_counters,
+				)?)?;
+			}
+		},
 		AdminRpc::KeyList(kl) => {
 			print_key_list(kl);
 		}
-		AdminRpc::KeyInfo(key, rb) => {
-			print_key_info(&key, &rb);
-		}
+		AdminRpc::KeyInfo(key, rb) => match output {
+			OutputFormat::Table => print_key_info(&key, &rb),
+			OutputFormat::Json => print_json(&key_info_json(&key, &rb)?)?,
+		},
 		AdminRpc::WorkerList(wi, wlo) => {
 			print_worker_list(wi, wlo);
 		}
@@ -249,6 +324,9 @@ pub async fn cmd_admin(
 		AdminRpc::BlockErrorList(el) => {
 			print_block_error_list(el);
 		}
+		AdminRpc::IncompleteUploadList(ul) => {
+			print_incomplete_upload_list(ul);
+		}
 		AdminRpc::BlockInfo {
 			hash,
 			refcount,
@@ -257,6 +335,29 @@ pub async fn cmd_admin(
 		} => {
 			print_block_info(hash, refcount, versions, uploads);
 		}
+		AdminRpc::ObjectInfo {
+			bucket_id,
+			object,
+			versions,
+			uploads,
+			first_block_nodes,
+		} => {
+			print_object_info(bucket_id, object, versions, uploads, first_block_nodes);
+		}
+		AdminRpc::DeleteDryRun {
+			target,
+			counters,
+			aliases,
+			permissions,
+		} => {
+			print_delete_dry_run(&target, counters.as_ref(), &aliases, &permissions);
+		}
+		AdminRpc::NodeStats(stats) => {
+			print_json(&stats)?;
+		}
+		AdminRpc::RepairStatus(nodes) => {
+			print_repair_status(nodes);
+		}
 		r => {
 			error!("Unexpected response: {:?}", r);
 		}
@@ -264,6 +365,39 @@ pub async fn cmd_admin(
 	Ok(())
 }
 
+pub async fn cmd_repair_status_watch(
+	rpc_cli: &Endpoint<AdminRpc, ()>,
+	rpc_host: NodeID,
+	opt: RepairOpt,
+	interval: Duration,
+) -> Result<(), HelperError> {
+	loop {
+		match rpc_cli
+			.call(&rpc_host, AdminRpc::LaunchRepair(opt.clone()), PRIO_NORMAL)
+			.await??
+		{
+			AdminRpc::RepairStatus(nodes) => {
+				let finished = nodes.iter().all(|node| {
+					node.table_syncs
+						.iter()
+						.all(|(_, remaining)| *remaining == 0)
+						&& node.repairs.iter().all(|(_, state, _, _)| {
+							!matches!(state, WorkerState::Busy | WorkerState::Throttled(_))
+						})
+				});
+				print_repair_status(nodes);
+				if finished {
+					println!("All table syncs and repair operations are finished.");
+					return Ok(());
+				}
+			}
+			m => return Err(Error::unexpected_rpc_message(m).into()),
+		}
+		println!();
+		tokio::time::sleep(interval).await;
+	}
+}
+
 // ---- utility ----
 
 pub async fn fetch_status(
@@ -0,0 +1,537 @@
+//! Structured output of the CLI when invoked with `--output json`.
+//!
+//! The structs in this module define the JSON schema printed by
+//! `garage status`, `garage stats`, `garage bucket info` and `garage key info`.
+//! Fields may be added in future versions, but existing fields are not
+//! renamed or removed, so scripts can rely on them.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use garage_util::data::*;
+use garage_util::error::Error;
+
+use garage_rpc::layout::*;
+use garage_rpc::system::*;
+
+use garage_model::bucket_table::*;
+use garage_model::key_table::*;
+use garage_model::permission::BucketKeyPerm;
+use garage_model::s3::mpu_table;
+use garage_model::s3::object_table;
+
+/// Output format of the CLI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+	/// Human-readable tables (default)
+	#[default]
+	Table,
+	/// JSON, following the schemas defined in this module
+	Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"table" => Ok(Self::Table),
+			"json" => Ok(Self::Json),
+			_ => Err(format!(
+				"invalid output format `{}`, expected `table` or `json`",
+				s
+			)),
+		}
+	}
+}
+
+pub fn print_json<T: Serialize>(value: &T) -> Result<(), Error> {
+	let json = serde_json::to_string_pretty(value)
+		.map_err(|e| Error::Message(format!("Could not serialize output: {}", e)))?;
+	println!("{}", json);
+	Ok(())
+}
+
+// ---- garage status ----
+
+/// Output of `garage status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusJson {
+	/// Version of the current cluster layout
+	pub layout_version: u64,
+	/// All nodes known to the node we are connected to, as well as all nodes
+	/// that have a role in one of the active layout versions
+	pub nodes: Vec<NodeStatusJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStatusJson {
+	/// Full node ID, hex-encoded
+	pub id: String,
+	/// Hostname reported by the node, if known
+	pub hostname: Option<String>,
+	/// RPC address of the node, if known
+	pub addr: Option<String>,
+	/// Whether the node is currently connected
+	pub is_up: bool,
+	/// Seconds since the node was last seen, if it was ever seen
+	pub last_seen_secs_ago: Option<u64>,
+	/// Role of the node in the current layout version, if any
+	pub role: Option<NodeRoleJson>,
+	/// True if the node has no role in the current layout version but still
+	/// has one in an older version, i.e. metadata is being drained from it
+	pub draining: bool,
+	/// Disk space of the data directory, if reported by the node
+	pub data_partition: Option<DiskSpaceJson>,
+	/// Disk space of the metadata directory, if reported by the node
+	pub metadata_partition: Option<DiskSpaceJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeRoleJson {
+	pub zone: String,
+	/// Capacity in bytes, or `null` for gateway nodes
+	pub capacity: Option<u64>,
+	pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskSpaceJson {
+	/// Available space in bytes
+	pub available: u64,
+	/// Total size of the partition in bytes
+	pub total: u64,
+}
+
+impl From<(u64, u64)> for DiskSpaceJson {
+	fn from((available, total): (u64, u64)) -> Self {
+		Self { available, total }
+	}
+}
+
+impl From<&NodeRole> for NodeRoleJson {
+	fn from(role: &NodeRole) -> Self {
+		Self {
+			zone: role.zone.clone(),
+			capacity: role.capacity,
+			tags: role.tags.clone(),
+		}
+	}
+}
+
+pub fn status_json(status: &[KnownNodeInfo], layout: &LayoutHistory) -> StatusJson {
+	let current = layout.current();
+	let mut listed = HashSet::new();
+	let mut nodes = vec![];
+
+	let mut push_node = |id: Uuid, adv: Option<&KnownNodeInfo>| {
+		if !listed.insert(id) {
+			return;
+		}
+		let role = match current.roles.get(&id) {
+			Some(NodeRoleV(Some(role))) => Some(NodeRoleJson::from(role)),
+			_ => None,
+		};
+		let draining = role.is_none()
+			&& layout
+				.versions
+				.iter()
+				.any(|v| matches!(v.roles.get(&id), Some(NodeRoleV(Some(_)))));
+		nodes.push(NodeStatusJson {
+			id: hex::encode(id),
+			hostname: adv.and_then(|x| x.status.hostname.clone()),
+			addr: adv.and_then(|x| x.addr).map(|a| a.to_string()),
+			is_up: adv.map(|x| x.is_up).unwrap_or(false),
+			last_seen_secs_ago: adv.and_then(|x| x.last_seen_secs_ago),
+			role,
+			draining,
+			data_partition: adv.and_then(|x| x.status.data_disk_avail).map(Into::into),
+			metadata_partition: adv.and_then(|x| x.status.meta_disk_avail).map(Into::into),
+		});
+	};
+
+	for adv in status.iter() {
+		push_node(adv.id, Some(adv));
+	}
+	for ver in layout.versions.iter().rev() {
+		for (id, _, role) in ver.roles.items().iter() {
+			if let NodeRoleV(Some(_)) = role {
+				push_node(*id, None);
+			}
+		}
+	}
+
+	StatusJson {
+		layout_version: current.version,
+		nodes,
+	}
+}
+
+// ---- garage stats ----
+
+/// Output of `garage stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsJson {
+	/// Statistics of each queried node (a single node unless `--all-nodes`
+	/// is given)
+	pub nodes: Vec<NodeStatsJson>,
+	/// Nodes whose statistics could not be retrieved
+	pub errors: Vec<NodeErrorJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeErrorJson {
+	/// Full node ID, hex-encoded
+	pub id: String,
+	pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStatsJson {
+	/// Full node ID, hex-encoded
+	pub id: String,
+	pub garage_version: String,
+	/// Cargo features Garage was compiled with, if known
+	pub garage_features: Option<Vec<String>>,
+	pub rust_version: String,
+	/// Name of the metadata database engine
+	pub db_engine: String,
+	pub tables: Vec<TableStatsJson>,
+	pub block_manager: BlockManagerStatsJson,
+}
+
+/// Statistics of a metadata table. Counts are approximate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableStatsJson {
+	pub name: String,
+	pub items: usize,
+	pub merkle_items: usize,
+	pub merkle_todo: usize,
+	pub gc_todo: usize,
+	/// Partitions remaining in the current full sync
+	pub sync_todo: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockManagerStatsJson {
+	/// Number of reference counter entries (approximately the number of blocks)
+	pub rc_entries: usize,
+	pub resync_queue_length: usize,
+	pub resync_needed: usize,
+	pub resync_to_delete: usize,
+	pub resync_errors: usize,
+	/// Only present when `--detailed` is given
+	pub compression: Option<CompressionStatsJson>,
+	/// Only present when `--detailed` is given
+	pub deduplication: Option<DeduplicationStatsJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressionStatsJson {
+	/// Bytes of block data received before compression
+	pub bytes_in: u64,
+	/// Bytes of block data stored after compression
+	pub bytes_out: u64,
+	/// `bytesIn / bytesOut`, or `null` if nothing was compressed yet
+	pub ratio: Option<f64>,
+}
+
+/// Approximate storage savings due to deduplication of data blocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeduplicationStatsJson {
+	/// Total size of the objects in the partitions stored by this node
+	pub logical_bytes: u64,
+	/// Total size of the data blocks stored on this node
+	pub physical_bytes: u64,
+	/// `logicalBytes / physicalBytes`, or `null` if no block is stored
+	pub ratio: Option<f64>,
+}
+
+// ---- garage bucket info ----
+
+/// Output of `garage bucket info`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketInfoJson {
+	/// Full bucket ID, hex-encoded
+	pub id: String,
+	pub global_aliases: Vec<String>,
+	pub local_aliases: Vec<BucketLocalAliasJson>,
+	pub website_access: bool,
+	pub website_config: Option<WebsiteConfigJson>,
+	pub quotas: QuotasJson,
+	/// Bucket tags, as set by PutBucketTagging
+	#[serde(default)]
+	pub tags: BTreeMap<String, String>,
+	pub keys: Vec<BucketKeyPermJson>,
+	/// Number of objects in the bucket
+	pub objects: i64,
+	/// Total size of objects in the bucket, in bytes
+	pub bytes: i64,
+	/// Number of unfinished uploads (multipart and non-multipart)
+	pub unfinished_uploads: i64,
+	pub unfinished_multipart_uploads: i64,
+	/// Total size of unfinished multipart uploads, in bytes
+	pub unfinished_multipart_upload_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketLocalAliasJson {
+	pub access_key_id: String,
+	pub alias: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebsiteConfigJson {
+	pub index_document: String,
+	pub error_document: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotasJson {
+	/// Maximum size in bytes, or `null` if unlimited
+	pub max_size: Option<u64>,
+	/// Maximum number of objects, or `null` if unlimited
+	pub max_objects: Option<u64>,
+	/// Whether writes in progress are counted against the quotas
+	pub strict: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketKeyPermJson {
+	pub access_key_id: String,
+	/// Name of the key, or `null` if the key was deleted
+	pub name: Option<String>,
+	pub permissions: PermissionsJson,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionsJson {
+	pub read: bool,
+	pub write: bool,
+	pub owner: bool,
+}
+
+impl From<&BucketKeyPerm> for PermissionsJson {
+	fn from(perm: &BucketKeyPerm) -> Self {
+		Self {
+			read: perm.allow_read,
+			write: perm.allow_write,
+			owner: perm.allow_owner,
+		}
+	}
+}
+
+pub fn bucket_info_json(
+	bucket: &Bucket,
+	relevant_keys: &HashMap<String, Key>,
+	counters: &HashMap<String, i64>,
+	mpu_counters: &HashMap<String, i64>,
+) -> Result<BucketInfoJson, Error> {
+	let p = bucket
+		.state
+		.as_option()
+		.ok_or_else(|| Error::Message("Bucket is deleted.".into()))?;
+	let key_name = |k: &String| {
+		relevant_keys
+			.get(k)
+			.and_then(|k| k.params())
+			.map(|p| p.name.get().clone())
+	};
+
+	let website_config = p.website_config.get().as_ref().map(|w| WebsiteConfigJson {
+		index_document: w.index_document.clone(),
+		error_document: w.error_document.clone(),
+	});
+	let quotas = p.quotas.get();
+
+	Ok(BucketInfoJson {
+		id: hex::encode(bucket.id),
+		global_aliases: p
+			.aliases
+			.items()
+			.iter()
+			.filter(|(_, _, active)| *active)
+			.map(|(alias, _, _)| alias.clone())
+			.collect(),
+		local_aliases: p
+			.local_aliases
+			.items()
+			.iter()
+			.filter(|(_, _, active)| *active)
+			.map(|((key_id, alias), _, _)| BucketLocalAliasJson {
+				access_key_id: key_id.clone(),
+				alias: alias.clone(),
+			})
+			.collect(),
+		website_access: website_config.is_some(),
+		website_config,
+		quotas: QuotasJson {
+			max_size: quotas.max_size,
+			max_objects: quotas.max_objects,
+			strict: quotas.strict,
+		},
+		tags: p.tags.get().clone(),
+		keys: p
+			.authorized_keys
+			.items()
+			.iter()
+			.filter(|(_, perm)| perm.is_any())
+			.map(|(k, perm)| BucketKeyPermJson {
+				access_key_id: k.clone(),
+				name: key_name(k),
+				permissions: perm.into(),
+			})
+			.collect(),
+		objects: *counters.get(object_table::OBJECTS).unwrap_or(&0),
+		bytes: *counters.get(object_table::BYTES).unwrap_or(&0),
+		unfinished_uploads: *counters.get(object_table::UNFINISHED_UPLOADS).unwrap_or(&0),
+		unfinished_multipart_uploads: *mpu_counters.get(mpu_table::UPLOADS).unwrap_or(&0),
+		unfinished_multipart_upload_bytes: *mpu_counters.get(mpu_table::BYTES).unwrap_or(&0),
+	})
+}
+
+// ---- garage key info ----
+
+/// Output of `garage key info`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyInfoJson {
+	pub access_key_id: String,
+	pub name: String,
+	pub description: Option<String>,
+	/// Creation date as an RFC 3339 timestamp, if known
+	pub created: Option<String>,
+	pub secret_access_key: String,
+	pub permissions: KeyPermissionsJson,
+	pub rate_limit: KeyRateLimitJson,
+	/// IP ranges from which the key can be used, empty if unrestricted
+	pub allowed_cidrs: Vec<String>,
+	pub buckets: Vec<KeyBucketJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyPermissionsJson {
+	pub create_bucket: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyRateLimitJson {
+	/// `null` if unlimited
+	pub requests_per_sec: Option<u64>,
+	/// `null` if unlimited
+	pub bytes_per_sec: Option<u64>,
+}
+
+/// A bucket the key has permissions on or a local alias for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyBucketJson {
+	/// Full bucket ID, hex-encoded
+	pub id: String,
+	pub global_aliases: Vec<String>,
+	/// Aliases of the bucket in this key's namespace
+	pub local_aliases: Vec<String>,
+	pub permissions: PermissionsJson,
+}
+
+pub fn key_info_json(
+	key: &Key,
+	relevant_buckets: &HashMap<Uuid, Bucket>,
+) -> Result<KeyInfoJson, Error> {
+	let p = key
+		.state
+		.as_option()
+		.ok_or_else(|| Error::Message("Key is deleted.".into()))?;
+
+	let mut bucket_ids = p
+		.authorized_buckets
+		.items()
+		.iter()
+		.filter(|(_, perm)| perm.is_any())
+		.map(|(id, _)| *id)
+		.collect::<Vec<_>>();
+	for (_, _, alias) in p.local_aliases.items().iter() {
+		if let Some(id) = alias {
+			if !bucket_ids.contains(id) {
+				bucket_ids.push(*id);
+			}
+		}
+	}
+
+	let buckets =
+		bucket_ids
+			.into_iter()
+			.map(|id| KeyBucketJson {
+				id: hex::encode(id),
+				global_aliases: relevant_buckets
+					.get(&id)
+					.and_then(|b| b.state.as_option())
+					.map(|b| {
+						b.aliases
+							.items()
+							.iter()
+							.filter(|(_, _, active)| *active)
+							.map(|(a, _, _)| a.clone())
+							.collect()
+					})
+					.unwrap_or_default(),
+				local_aliases: p
+					.local_aliases
+					.items()
+					.iter()
+					.filter(|(_, _, a)| *a == Some(id))
+					.map(|(a, _, _)| a.clone())
+					.collect(),
+				permissions: p.authorized_buckets.get(&id).map(Into::into).unwrap_or(
+					PermissionsJson {
+						read: false,
+						write: false,
+						owner: false,
+					},
+				),
+			})
+			.collect();
+
+	let rate_limit = p.rate_limit.get();
+	Ok(KeyInfoJson {
+		access_key_id: key.key_id.clone(),
+		name: p.name.get().clone(),
+		description: p.description.get().clone(),
+		created: (p.created > 0).then(|| garage_util::time::msec_to_rfc3339(p.created)),
+		secret_access_key: p.secret_key.clone(),
+		permissions: KeyPermissionsJson {
+			create_bucket: *p.allow_create_bucket.get(),
+		},
+		rate_limit: KeyRateLimitJson {
+			requests_per_sec: rate_limit.requests_per_sec,
+			bytes_per_sec: rate_limit.bytes_per_sec,
+		},
+		allowed_cidrs: p
+			.allowed_cidrs
+			.get()
+			.iter()
+			.map(|net| net.to_string())
+			.collect(),
+		buckets,
+	})
+}
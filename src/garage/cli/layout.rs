@@ -39,6 +39,25 @@ pub async fn cli_layout_command_dispatch(
 	}
 }
 
+/// Numbers without unit below this value are too small to be meaningful
+/// as a capacity in bytes: they are relative capacities as used before v0.9
+const MIN_UNITLESS_CAPACITY: u64 = 1_000_000;
+
+/// Parse the capacity of a node, given in bytes with an optional unit suffix.
+/// Small numbers without unit are rejected, as they are most likely relative
+/// capacities of older Garage versions that would be taken as a few bytes.
+pub(crate) fn parse_capacity(s: &str) -> Result<ByteSize, String> {
+	match s.trim().parse::<u64>() {
+		Ok(n) if n != 0 && n < MIN_UNITLESS_CAPACITY => Err(format!(
+			"Capacity {} has no unit and is too small to be a number of bytes. \
+			Capacities are no longer relative values, use an explicit unit (e.g. {}GB).",
+			n, n
+		)),
+		Ok(n) => Ok(ByteSize::b(n)),
+		Err(_) => s.parse::<ByteSize>(),
+	}
+}
+
 pub async fn cmd_assign_role(
 	rpc_cli: &Endpoint<SystemRpc, ()>,
 	rpc_host: NodeID,
@@ -570,3 +589,27 @@ pub fn print_staging_role_changes(layout: &LayoutHistory) -> bool {
 		false
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_capacity() {
+		assert_eq!(parse_capacity("2TB"), Ok(ByteSize::tb(2)));
+		assert_eq!(parse_capacity("1.5 TB"), Ok(ByteSize::gb(1500)));
+		assert_eq!(parse_capacity("500GB"), Ok(ByteSize::gb(500)));
+		assert_eq!(parse_capacity("5000000000B"), Ok(ByteSize::gb(5)));
+
+		// Numbers without unit are bytes
+		assert_eq!(parse_capacity("5000000000"), Ok(ByteSize::gb(5)));
+		assert_eq!(parse_capacity("1000000000000"), Ok(ByteSize::tb(1)));
+		assert_eq!(parse_capacity("0"), Ok(ByteSize::b(0)));
+
+		// Relative capacities of older versions are rejected
+		assert!(parse_capacity("1").is_err());
+		assert!(parse_capacity("100").is_err());
+
+		assert!(parse_capacity("lots").is_err());
+	}
+}
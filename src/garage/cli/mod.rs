@@ -1,5 +1,6 @@
 pub(crate) mod cmd;
 pub(crate) mod init;
+pub(crate) mod json;
 pub(crate) mod layout;
 pub(crate) mod structs;
 pub(crate) mod util;
@@ -8,6 +9,7 @@ pub(crate) mod convert_db;
 
 pub(crate) use cmd::*;
 pub(crate) use init::*;
+pub(crate) use json::*;
 pub(crate) use layout::*;
 pub(crate) use structs::*;
 pub(crate) use util::*;
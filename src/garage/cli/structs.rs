@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
@@ -52,6 +54,10 @@ pub enum Command {
 	#[structopt(name = "block", version = garage_version())]
 	Block(BlockOperation),
 
+	/// Low-level debug operations on objects
+	#[structopt(name = "object", version = garage_version())]
+	Object(ObjectOperation),
+
 	/// Operations on the metadata db
 	#[structopt(name = "meta", version = garage_version())]
 	Meta(MetaOperation),
@@ -133,8 +139,10 @@ pub struct AssignRoleOpt {
 	#[structopt(short = "z", long = "zone")]
 	pub(crate) zone: Option<String>,
 
-	/// Storage capacity, in bytes (supported suffixes: B, KB, MB, GB, TB, PB)
-	#[structopt(short = "c", long = "capacity")]
+	/// Storage capacity, in bytes (supported suffixes: B, KB, MB, GB, TB, PB).
+	/// Numbers without a suffix are bytes; small ones, such as the relative
+	/// capacities of older Garage versions, are rejected.
+	#[structopt(short = "c", long = "capacity", parse(try_from_str = crate::cli::layout::parse_capacity))]
 	pub(crate) capacity: Option<bytesize::ByteSize>,
 
 	/// Gateway-only node
@@ -229,9 +237,13 @@ pub enum BucketOperation {
 	Website(WebsiteOpt),
 
 	/// Set the quotas for this bucket
-	#[structopt(name = "set-quotas", version = garage_version())]
+	#[structopt(name = "set-quotas", alias = "set-quota", version = garage_version())]
 	SetQuotas(SetQuotasOpt),
 
+	/// List incomplete multipart uploads
+	#[structopt(name = "list-incomplete-uploads", version = garage_version())]
+	ListIncompleteUploads(ListIncompleteUploadsOpt),
+
 	/// Clean up (abort) old incomplete multipart uploads
 	#[structopt(name = "cleanup-incomplete-uploads", version = garage_version())]
 	CleanupIncompleteUploads(CleanupIncompleteUploadsOpt),
@@ -273,6 +285,14 @@ pub struct DeleteBucketOpt {
 	/// If this flag is not given, the bucket won't be deleted
 	#[structopt(long = "yes")]
 	pub yes: bool,
+
+	/// Only report what would be deleted, without deleting anything
+	#[structopt(long = "dry-run")]
+	pub dry_run: bool,
+
+	/// Also delete all objects and uploads in progress if the bucket is not empty
+	#[structopt(long = "force")]
+	pub force: bool,
 }
 
 #[derive(Serialize, Deserialize, StructOpt, Debug)]
@@ -334,6 +354,26 @@ pub struct SetQuotasOpt {
 	/// Set a maximum number of objects for the bucket (or `none` for no restriction)
 	#[structopt(long = "max-objects")]
 	pub max_objects: Option<String>,
+
+	/// Enforce quotas strictly: count writes in progress on each node against the quotas,
+	/// to avoid exceeding them under concurrent uploads (best-effort, concurrent writes
+	/// going through different nodes can still slightly exceed the quotas)
+	#[structopt(long = "strict")]
+	pub strict: bool,
+
+	/// Go back to enforcing quotas using only the object counters
+	#[structopt(long = "no-strict", conflicts_with = "strict")]
+	pub no_strict: bool,
+}
+
+#[derive(Serialize, Deserialize, StructOpt, Debug)]
+pub struct ListIncompleteUploadsOpt {
+	/// Only list multipart uploads older than this value
+	#[structopt(long = "older-than")]
+	pub older_than: Option<String>,
+
+	/// Bucket name
+	pub bucket: String,
 }
 
 #[derive(Serialize, Deserialize, StructOpt, Debug)]
@@ -365,6 +405,18 @@ pub enum KeyOperation {
 	#[structopt(name = "rename", version = garage_version())]
 	Rename(KeyRenameOpt),
 
+	/// Update key metadata
+	#[structopt(name = "update", version = garage_version())]
+	Update(KeyUpdateOpt),
+
+	/// Set request and bandwidth rate limits for key
+	#[structopt(name = "set-rate-limit", version = garage_version())]
+	SetRateLimit(KeyRateLimitOpt),
+
+	/// Restrict the IP addresses from which key can be used
+	#[structopt(name = "set-allowed-cidrs", version = garage_version())]
+	SetAllowedCidrs(KeyAllowedCidrsOpt),
+
 	/// Delete key
 	#[structopt(name = "delete", version = garage_version())]
 	Delete(KeyDeleteOpt),
@@ -396,6 +448,14 @@ pub struct KeyNewOpt {
 	/// Name of the key
 	#[structopt(default_value = "Unnamed key")]
 	pub name: String,
+
+	/// Free-form description of the key
+	#[structopt(long = "description")]
+	pub description: Option<String>,
+
+	/// Allow the key to create buckets using S3's CreateBucket call
+	#[structopt(long = "allow-create-bucket")]
+	pub allow_create_bucket: bool,
 }
 
 #[derive(Serialize, Deserialize, StructOpt, Debug)]
@@ -407,6 +467,16 @@ pub struct KeyRenameOpt {
 	pub new_name: String,
 }
 
+#[derive(Serialize, Deserialize, StructOpt, Debug)]
+pub struct KeyUpdateOpt {
+	/// ID or name of the key
+	pub key_pattern: String,
+
+	/// New description of the key (an empty string removes the description)
+	#[structopt(long = "description")]
+	pub description: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, StructOpt, Debug)]
 pub struct KeyDeleteOpt {
 	/// ID or name of the key
@@ -415,6 +485,10 @@ pub struct KeyDeleteOpt {
 	/// Confirm deletion
 	#[structopt(long = "yes")]
 	pub yes: bool,
+
+	/// Only report what would be deleted, without deleting anything
+	#[structopt(long = "dry-run")]
+	pub dry_run: bool,
 }
 
 #[derive(Serialize, Deserialize, StructOpt, Debug)]
@@ -427,6 +501,32 @@ pub struct KeyPermOpt {
 	pub create_bucket: bool,
 }
 
+#[derive(Serialize, Deserialize, StructOpt, Debug)]
+pub struct KeyRateLimitOpt {
+	/// ID or name of the key
+	pub key_pattern: String,
+
+	/// Maximum number of requests per second (or `none` for no restriction)
+	#[structopt(long = "requests-per-sec")]
+	pub requests_per_sec: Option<String>,
+
+	/// Maximum number of uploaded and downloaded bytes per second (specify
+	/// a size e.g. in MiB, or `none` for no restriction)
+	#[structopt(long = "bytes-per-sec")]
+	pub bytes_per_sec: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, StructOpt, Debug)]
+pub struct KeyAllowedCidrsOpt {
+	/// ID or name of the key
+	pub key_pattern: String,
+
+	/// IP ranges in CIDR notation (e.g. 192.0.2.0/24) or single addresses from
+	/// which requests can be made with this key (or `none` for no restriction)
+	#[structopt(required = true)]
+	pub cidrs: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, StructOpt, Debug)]
 pub struct KeyImportOpt {
 	/// Access key ID
@@ -487,6 +587,31 @@ pub enum RepairWhat {
 	/// Rebalance data blocks among HDDs on individual nodes
 	#[structopt(name = "rebalance", version = garage_version())]
 	Rebalance,
+	/// Send data blocks to the nodes that store them in the current cluster layout,
+	/// and queue blocks that are no longer needed locally for deletion
+	#[structopt(name = "cluster-rebalance", version = garage_version())]
+	ClusterRebalance {
+		/// Tranquility level (higher is slower and has less impact on the node)
+		#[structopt(short = "t", long = "tranquility", default_value = "2")]
+		tranquility: u32,
+	},
+	/// Rewrite stored data blocks using the currently configured compression level
+	#[structopt(name = "recompress", version = garage_version())]
+	Recompress {
+		#[structopt(subcommand)]
+		cmd: RecompressCmd,
+	},
+	/// Do a full sync of all metadata tables and repair the set of stored blocks
+	#[structopt(name = "full", version = garage_version())]
+	Full,
+	/// Show the progress of table syncs and repair operations (does not require --yes)
+	#[structopt(name = "status", version = garage_version())]
+	Status {
+		/// Refresh the status every given number of seconds, until all table syncs
+		/// and repair operations are finished
+		#[structopt(short = "w", long = "watch")]
+		watch: Option<u64>,
+	},
 }
 
 #[derive(Serialize, Deserialize, StructOpt, Debug, Eq, PartialEq, Clone)]
@@ -511,6 +636,20 @@ pub enum ScrubCmd {
 	},
 }
 
+#[derive(Serialize, Deserialize, StructOpt, Debug, Eq, PartialEq, Clone)]
+pub enum RecompressCmd {
+	/// Start recompressing blocks
+	#[structopt(name = "start", version = garage_version())]
+	Start {
+		/// Tranquility level (higher is slower and has less impact on the node)
+		#[structopt(short = "t", long = "tranquility", default_value = "2")]
+		tranquility: u32,
+	},
+	/// Cancel block recompression in progress
+	#[structopt(name = "cancel", version = garage_version())]
+	Cancel,
+}
+
 #[derive(Serialize, Deserialize, StructOpt, Debug, Clone)]
 pub struct OfflineRepairOpt {
 	/// Confirm the launch of the repair operation
@@ -538,10 +677,19 @@ pub struct StatsOpt {
 	#[structopt(short = "a", long = "all-nodes")]
 	pub all_nodes: bool,
 
+	/// Show detailed statistics
+	#[structopt(short = "d", long = "detailed")]
+	pub detailed: bool,
+
 	/// Don't show global cluster stats (internal use in RPC)
 	#[structopt(skip)]
 	#[serde(default)]
 	pub skip_global: bool,
+
+	/// Return structured statistics instead of text (set by `--output json`)
+	#[structopt(skip)]
+	#[serde(default)]
+	pub json: bool,
 }
 
 #[derive(Serialize, Deserialize, StructOpt, Debug, Eq, PartialEq, Clone)]
@@ -617,9 +765,35 @@ pub enum BlockOperation {
 		#[structopt(required = true)]
 		blocks: Vec<String>,
 	},
+	/// Remove the references to a block that belong to deleted versions
+	#[structopt(name = "purge-ref", version = garage_version())]
+	PurgeRef {
+		/// Mandatory to confirm this operation
+		#[structopt(long = "yes")]
+		yes: bool,
+		/// Hash of the block whose dangling references should be removed
+		hash: String,
+	},
 }
 
-#[derive(Serialize, Deserialize, StructOpt, Debug, Eq, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, StructOpt, Debug, Eq, PartialEq, Clone)]
+pub enum ObjectOperation {
+	/// Get detailed information about the internal state of an object
+	#[structopt(name = "info", version = garage_version())]
+	Info {
+		/// Bucket in which the object is stored
+		#[structopt(long = "bucket")]
+		bucket: String,
+		/// Key of the object
+		#[structopt(long = "key")]
+		key: String,
+		/// Also print the storage nodes of the first block of each version
+		#[structopt(long = "nodes")]
+		nodes: bool,
+	},
+}
+
+#[derive(Serialize, Deserialize, StructOpt, Debug, Eq, PartialEq, Clone)]
 pub enum MetaOperation {
 	/// Save a snapshot of the metadata db file
 	#[structopt(name = "snapshot", version = garage_version())]
@@ -627,5 +801,10 @@ pub enum MetaOperation {
 		/// Run on all nodes instead of only local node
 		#[structopt(long = "all")]
 		all: bool,
+		/// Directory in which to save the snapshot, on the filesystem of the node(s)
+		/// taking it (default: `snapshots` in the metadata directory, where only the
+		/// two most recent snapshots are kept)
+		#[structopt(long = "output")]
+		output: Option<PathBuf>,
 	},
 }
@@ -16,6 +16,7 @@ use garage_model::s3::mpu_table::{self, MultipartUpload};
 use garage_model::s3::object_table;
 use garage_model::s3::version_table::*;
 
+use crate::admin::{IncompleteUploadInfo, RepairStatusInfo};
 use crate::cli::structs::WorkerListOpt;
 
 pub fn print_bucket_list(bl: Vec<Bucket>) {
@@ -81,8 +82,37 @@ pub fn print_key_info(key: &Key, relevant_buckets: &HashMap<Uuid, Bucket>) {
 		Deletable::Present(p) => {
 			println!("Key name: {}", p.name.get());
 			println!("Key ID: {}", key.key_id);
+			if let Some(description) = p.description.get() {
+				println!("Description: {}", description);
+			}
+			if p.created > 0 {
+				println!("Created: {}", msec_to_rfc3339(p.created));
+			}
 			println!("Secret key: {}", p.secret_key);
 			println!("Can create buckets: {}", p.allow_create_bucket.get());
+			let rate_limit = p.rate_limit.get();
+			if !rate_limit.is_unlimited() {
+				println!("\nRate limits:");
+				if let Some(rps) = rate_limit.requests_per_sec {
+					println!(" requests per second: {}", rps);
+				}
+				if let Some(bps) = rate_limit.bytes_per_sec {
+					let bps = bytesize::ByteSize::b(bps);
+					println!(
+						" bytes per second: {} ({})",
+						bps.to_string_as(true),
+						bps.to_string_as(false)
+					);
+				}
+			}
+			let allowed_cidrs = p.allowed_cidrs.get();
+			if !allowed_cidrs.is_empty() {
+				let allowed_cidrs = allowed_cidrs
+					.iter()
+					.map(|net| net.to_string())
+					.collect::<Vec<_>>();
+				println!("\nAllowed source addresses: {}", allowed_cidrs.join(", "));
+			}
 			println!("\nKey-specific bucket aliases:");
 			let mut table = vec![];
 			for (alias_name, _, alias) in p.local_aliases.items().iter() {
@@ -178,6 +208,11 @@ pub fn print_bucket_info(
 
 			println!("\nWebsite access: {}", p.website_config.get().is_some());
 
+			let canned_acl = p.canned_acl.get();
+			if *canned_acl != CannedAcl::Private {
+				println!("Canned ACL: {}", canned_acl.as_str());
+			}
+
 			let quotas = p.quotas.get();
 			if quotas.max_size.is_some() || quotas.max_objects.is_some() {
 				println!("\nQuotas:");
@@ -192,6 +227,17 @@ pub fn print_bucket_info(
 				if let Some(mo) = quotas.max_objects {
 					println!(" maximum number of objects: {}", mo);
 				}
+				if quotas.strict {
+					println!(" strict enforcement: enabled");
+				}
+			}
+
+			let tags = p.tags.get();
+			if !tags.is_empty() {
+				println!("\nTags:");
+				for (k, v) in tags.iter() {
+					println!("  {} = {}", k, v);
+				}
 			}
 
 			println!("\nGlobal aliases:");
@@ -254,6 +300,39 @@ pub fn find_matching_node(
 	}
 }
 
+pub fn print_repair_status(nodes: Vec<RepairStatusInfo>) {
+	let mut total_remaining = 0;
+	for node in nodes {
+		println!("==== NODE {:?} ====", node.node);
+
+		let mut table = vec!["Table sync\tPartitions remaining".to_string()];
+		for (name, remaining) in node.table_syncs {
+			table.push(format!("{}\t{}", name, remaining));
+			total_remaining += remaining;
+		}
+		format_table(table);
+		println!();
+
+		if node.repairs.is_empty() {
+			println!("No repair operation was launched since the node started.");
+		} else {
+			let mut table = vec!["Repair\tState\tDone\tPhase".to_string()];
+			for (name, state, progress, phase) in node.repairs {
+				table.push(format!(
+					"{}\t{}\t{}\t{}",
+					name,
+					state,
+					progress.as_deref().unwrap_or("-"),
+					phase.as_deref().unwrap_or("-"),
+				));
+			}
+			format_table(table);
+		}
+		println!();
+	}
+	println!("Total partitions remaining to sync: {}", total_remaining);
+}
+
 pub fn print_worker_list(wi: HashMap<usize, WorkerInfo>, wlo: WorkerListOpt) {
 	let mut wi = wi.into_iter().collect::<Vec<_>>();
 	wi.sort_by_key(|(tid, info)| {
@@ -400,6 +479,28 @@ pub fn print_block_error_list(el: Vec<BlockResyncErrorInfo>) {
 	format_table(table);
 }
 
+pub fn print_incomplete_upload_list(ul: Vec<IncompleteUploadInfo>) {
+	if ul.is_empty() {
+		println!("No incomplete multipart uploads.");
+		return;
+	}
+
+	let now = now_msec();
+	let tf = timeago::Formatter::new();
+
+	let mut table = vec!["Key\tUpload ID\tStarted\tParts".into()];
+	for u in ul {
+		table.push(format!(
+			"{}\t{}\t{}\t{}",
+			u.key,
+			hex::encode(u.upload_id),
+			tf.convert(Duration::from_millis(now.saturating_sub(u.timestamp))),
+			u.parts
+		));
+	}
+	format_table(table);
+}
+
 pub fn print_block_info(
 	hash: Hash,
 	refcount: u64,
@@ -455,3 +556,118 @@ pub fn print_block_info(
 		);
 	}
 }
+
+pub fn print_object_info(
+	bucket_id: Uuid,
+	object: object_table::Object,
+	versions: Vec<Version>,
+	uploads: Vec<MultipartUpload>,
+	first_block_nodes: HashMap<Uuid, Vec<Uuid>>,
+) {
+	use object_table::{ObjectVersionData, ObjectVersionState};
+
+	println!("Bucket: {}", hex::encode(bucket_id));
+	println!("Key: {}", object.key);
+	println!();
+
+	let mut table = vec!["Version\tTimestamp\tState\tData\tSize\tETag\tBlocks\tParts".into()];
+	for ov in object.versions().iter() {
+		let (state, data, meta) = match &ov.state {
+			ObjectVersionState::Uploading {
+				multipart: true, ..
+			} => ("uploading", "multipart", None),
+			ObjectVersionState::Uploading { .. } => ("uploading", "blocks", None),
+			ObjectVersionState::Aborted => ("aborted", "-", None),
+			ObjectVersionState::Complete(ObjectVersionData::DeleteMarker) => {
+				("delete marker", "-", None)
+			}
+			ObjectVersionState::Complete(ObjectVersionData::Inline(meta, _)) => {
+				("complete", "inline", Some(meta))
+			}
+			ObjectVersionState::Complete(ObjectVersionData::FirstBlock(meta, _)) => {
+				("complete", "first block", Some(meta))
+			}
+		};
+
+		let (blocks, parts) = if let Some(v) = versions.iter().find(|v| v.uuid == ov.uuid) {
+			(
+				v.blocks.len().to_string(),
+				v.n_parts()
+					.map(|n| n.to_string())
+					.unwrap_or_else(|_| "?".into()),
+			)
+		} else if let Some(u) = uploads.iter().find(|u| u.upload_id == ov.uuid) {
+			let mut part_numbers = u
+				.parts
+				.items()
+				.iter()
+				.map(|(k, _)| k.part_number)
+				.collect::<Vec<_>>();
+			part_numbers.dedup();
+			("-".into(), part_numbers.len().to_string())
+		} else {
+			("-".into(), "-".into())
+		};
+
+		table.push(format!(
+			"{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+			hex::encode(ov.uuid),
+			msec_to_rfc3339(ov.timestamp),
+			state,
+			data,
+			meta.map(|m| m.size.to_string())
+				.unwrap_or_else(|| "-".into()),
+			meta.map(|m| m.etag.as_str()).unwrap_or("-"),
+			blocks,
+			parts,
+		));
+	}
+	format_table(table);
+
+	for ov in object.versions().iter() {
+		if let Some(nodes) = first_block_nodes.get(&ov.uuid) {
+			println!();
+			println!(
+				"Storage nodes for first block of version {}:",
+				hex::encode(ov.uuid)
+			);
+			for node in nodes.iter() {
+				println!("  {}", hex::encode(node));
+			}
+		}
+	}
+}
+
+pub fn print_delete_dry_run(
+	target: &str,
+	counters: Option<&HashMap<String, i64>>,
+	aliases: &[String],
+	permissions: &[String],
+) {
+	println!("DRY RUN — nothing was deleted.");
+	println!();
+	println!("Deleting {} would remove:", target);
+
+	if let Some(counters) = counters {
+		let size = bytesize::ByteSize::b(*counters.get(object_table::BYTES).unwrap_or(&0) as u64);
+		println!(
+			"  Objects: {}",
+			*counters.get(object_table::OBJECTS).unwrap_or(&0)
+		);
+		println!(
+			"  Size: {} ({})",
+			size.to_string_as(true),
+			size.to_string_as(false)
+		);
+	}
+
+	println!("  Aliases:");
+	for alias in aliases.iter() {
+		println!("    {}", alias);
+	}
+
+	println!("  Permissions granted to:");
+	for perm in permissions.iter() {
+		println!("    {}", perm);
+	}
+}
@@ -62,6 +62,10 @@ struct Opt {
 	)]
 	pub config_file: PathBuf,
 
+	/// Output format of `status`, `stats`, `bucket info` and `key info`: `table` or `json`
+	#[structopt(long = "output", global = true, default_value = "table")]
+	pub output: OutputFormat,
+
 	#[structopt(subcommand)]
 	cmd: Command,
 }
@@ -284,7 +288,15 @@ async fn cli_command(opt: Opt) -> Result<(), Error> {
 	let system_rpc_endpoint = netapp.endpoint::<SystemRpc, ()>(SYSTEM_RPC_PATH.into());
 	let admin_rpc_endpoint = netapp.endpoint::<AdminRpc, ()>(ADMIN_RPC_PATH.into());
 
-	match cli_command_dispatch(opt.cmd, &system_rpc_endpoint, &admin_rpc_endpoint, id).await {
+	match cli_command_dispatch(
+		opt.cmd,
+		&system_rpc_endpoint,
+		&admin_rpc_endpoint,
+		id,
+		opt.output,
+	)
+	.await
+	{
 		Err(HelperError::Internal(i)) => Err(Error::Message(format!("Internal error: {}", i))),
 		Err(HelperError::BadRequest(b)) => Err(Error::Message(b)),
 		Err(e) => Err(Error::Message(format!("{}", e))),
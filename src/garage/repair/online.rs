@@ -39,6 +39,31 @@ pub async fn launch_online_repair(
 			garage.block_ref_table.syncer.add_full_sync()?;
 			garage.key_table.syncer.add_full_sync()?;
 		}
+		RepairWhat::Full => {
+			info!("Launching a full sync of all tables and a repair of the stored blocks");
+			garage.bucket_table.syncer.add_full_sync()?;
+			garage.bucket_alias_table.syncer.add_full_sync()?;
+			garage.key_table.syncer.add_full_sync()?;
+			garage.object_table.syncer.add_full_sync()?;
+			garage.object_counter_table.table.syncer.add_full_sync()?;
+			garage.mpu_table.syncer.add_full_sync()?;
+			garage.mpu_counter_table.table.syncer.add_full_sync()?;
+			garage.version_table.syncer.add_full_sync()?;
+			garage.block_ref_table.syncer.add_full_sync()?;
+			#[cfg(feature = "k2v")]
+			{
+				garage.k2v.item_table.syncer.add_full_sync()?;
+				garage.k2v.counter_table.table.syncer.add_full_sync()?;
+			}
+			bg.spawn_worker(garage_block::repair::RepairWorker::new(
+				garage.block_manager.clone(),
+			));
+		}
+		RepairWhat::Status { .. } => {
+			return Err(Error::Message(
+				"`repair status` does not launch a repair operation".into(),
+			));
+		}
 		RepairWhat::Versions => {
 			info!("Repairing the versions table");
 			bg.spawn_worker(TableRepairWorker::new(garage.clone(), RepairVersions));
@@ -87,6 +112,26 @@ pub async fn launch_online_repair(
 				garage.block_manager.clone(),
 			));
 		}
+		RepairWhat::ClusterRebalance { tranquility } => {
+			info!("Rebalancing the stored blocks among cluster nodes");
+			bg.spawn_worker(garage_block::repair::ClusterRebalanceWorker::new(
+				garage.block_manager.clone(),
+				tranquility,
+			));
+		}
+		RepairWhat::Recompress { cmd } => match cmd {
+			RecompressCmd::Start { tranquility } => {
+				info!("Recompressing the stored blocks");
+				bg.spawn_worker(garage_block::repair::RecompressWorker::new(
+					garage.block_manager.clone(),
+					tranquility,
+				));
+			}
+			RecompressCmd::Cancel => {
+				info!("Cancelling block recompression");
+				garage.block_manager.cancel_recompress();
+			}
+		},
 	}
 	Ok(())
 }
@@ -132,6 +177,10 @@ impl<R: TableRepair> Worker for TableRepairWorker<R> {
 		format!("{} repair worker", R::T::TABLE_NAME)
 	}
 
+	fn kind(&self) -> WorkerKind {
+		WorkerKind::Repair
+	}
+
 	fn status(&self) -> WorkerStatus {
 		WorkerStatus {
 			progress: Some(format!("{} ({})", self.counter, self.repairs)),
@@ -325,6 +374,10 @@ impl Worker for BlockRcRepair {
 		format!("Block refcount repair worker")
 	}
 
+	fn kind(&self) -> WorkerKind {
+		WorkerKind::Repair
+	}
+
 	fn status(&self) -> WorkerStatus {
 		WorkerStatus {
 			progress: Some(format!("{} ({})", self.counter, self.repairs)),
@@ -19,14 +19,6 @@ use crate::secrets::{fill_secrets, Secrets};
 #[cfg(feature = "telemetry-otlp")]
 use crate::tracing_setup::*;
 
-async fn wait_from(mut chan: watch::Receiver<bool>) {
-	while !*chan.borrow() {
-		if chan.changed().await.is_err() {
-			return;
-		}
-	}
-}
-
 pub async fn run_server(config_file: PathBuf, secrets: Secrets) -> Result<(), Error> {
 	info!("Loading configuration...");
 	let config = fill_secrets(read_config(config_file)?, secrets)?;
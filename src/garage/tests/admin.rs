@@ -1,5 +1,13 @@
 use crate::common;
 use crate::common::ext::*;
+use crate::json_body;
+
+use http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use http_body_util::Full as FullBody;
+use hyper::body::Bytes;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
 
 const BCKT_NAME: &str = "seau";
 
@@ -51,3 +59,993 @@ async fn test_admin_bucket_perms() {
 
 	assert!(hb().await.is_err());
 }
+
+#[tokio::test]
+async fn test_admin_health() {
+	let ctx = common::context();
+
+	let client = Client::builder(TokioExecutor::new()).build_http();
+	let health = |accept: Option<&str>| {
+		let mut req = Request::builder()
+			.method("GET")
+			.uri(format!("http://127.0.0.1:{}/health", ctx.garage.admin_port));
+		if let Some(accept) = accept {
+			req = req.header("accept", accept);
+		}
+		client.request(req.body(FullBody::new(Bytes::new())).unwrap())
+	};
+
+	// No authentication is required, and the body is plain text by default
+	let resp = health(None).await.unwrap();
+	assert_eq!(resp.status(), StatusCode::OK);
+	assert_eq!(resp.headers()["content-type"], "text/plain");
+	let body = resp.into_body().collect().await.unwrap().to_bytes();
+	let body = String::from_utf8(body.to_vec()).unwrap();
+	assert!(body.starts_with("Garage is fully operational"), "{}", body);
+
+	let resp = health(Some("application/json")).await.unwrap();
+	assert_eq!(resp.status(), StatusCode::OK);
+
+	// The test cluster has a single node, which has no peers
+	let body = json_body(resp).await;
+	assert_eq!(body["status"], "healthy");
+	assert!(body["connectedPeers"].as_array().unwrap().is_empty());
+	assert!(body["downNodes"].as_array().unwrap().is_empty());
+	assert!(body["layoutVersion"].as_u64().unwrap() >= 1);
+	assert!(body["tablesSynced"].is_boolean());
+}
+
+#[tokio::test]
+async fn test_admin_metrics_inline_objects() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("metrics-inline");
+
+	let bucket_info = ctx
+		.garage
+		.command()
+		.args(["bucket", "info", &bucket])
+		.expect_success_output("Could not get bucket info");
+	let bucket_info = String::from_utf8(bucket_info.stdout).unwrap();
+	let bucket_id = bucket_info
+		.lines()
+		.find_map(|l| l.strip_prefix("Bucket: "))
+		.unwrap()
+		.trim()
+		.to_string();
+
+	// The test instance has the default inline threshold of 3072 bytes
+	for (key, size) in [("small", 100), ("large", 10_000)] {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(key)
+			.body(vec![0x42; size].into())
+			.send()
+			.await
+			.unwrap();
+	}
+
+	let client = Client::builder(TokioExecutor::new()).build_http();
+	let req = Request::builder()
+		.method("GET")
+		.uri(format!(
+			"http://127.0.0.1:{}/metrics",
+			ctx.garage.admin_port
+		))
+		.body(FullBody::new(Bytes::new()))
+		.unwrap();
+	let resp = client.request(req).await.unwrap();
+	assert_eq!(resp.status(), StatusCode::OK);
+	let metrics = resp.into_body().collect().await.unwrap().to_bytes();
+	let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+
+	let metric_value = |name: &str, storage: &str| -> u64 {
+		metrics
+			.lines()
+			.find(|l| {
+				l.starts_with(name)
+					&& l.contains(&format!("bucket_id=\"{}\"", bucket_id))
+					&& l.contains(&format!("storage=\"{}\"", storage))
+			})
+			.and_then(|l| l.rsplit(' ').next())
+			.unwrap_or_else(|| panic!("no {} metric for {} storage", name, storage))
+			.parse()
+			.unwrap()
+	};
+
+	assert_eq!(metric_value("s3_objects_stored", "inline"), 1);
+	assert_eq!(metric_value("s3_objects_stored", "blocks"), 1);
+	assert_eq!(metric_value("s3_object_bytes_stored", "inline"), 100);
+	assert_eq!(metric_value("s3_object_bytes_stored", "blocks"), 10_000);
+}
+
+#[tokio::test]
+async fn test_admin_metrics_bandwidth() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("metrics-bandwidth");
+
+	let body = vec![0x42; 10_000];
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("obj")
+		.body(body.clone().into())
+		.send()
+		.await
+		.unwrap();
+	for _ in 0..2 {
+		let o = ctx
+			.client
+			.get_object()
+			.bucket(&bucket)
+			.key("obj")
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(o.body.collect().await.unwrap().into_bytes().len(), 10_000);
+	}
+
+	let client = Client::builder(TokioExecutor::new()).build_http();
+	let req = Request::builder()
+		.method("GET")
+		.uri(format!(
+			"http://127.0.0.1:{}/metrics",
+			ctx.garage.admin_port
+		))
+		.body(FullBody::new(Bytes::new()))
+		.unwrap();
+	let resp = client.request(req).await.unwrap();
+	assert_eq!(resp.status(), StatusCode::OK);
+	let metrics = resp.into_body().collect().await.unwrap().to_bytes();
+	let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+
+	let metric_value = |name: &str, endpoint: &str| -> u64 {
+		metrics
+			.lines()
+			.find(|l| {
+				l.starts_with(name)
+					&& l.contains(&format!("api_endpoint=\"{}\"", endpoint))
+					&& l.contains(&format!("bucket=\"{}\"", bucket))
+			})
+			.and_then(|l| l.rsplit(' ').next())
+			.unwrap_or_else(|| panic!("no {} metric for {}", name, endpoint))
+			.parse()
+			.unwrap()
+	};
+
+	assert_eq!(metric_value("api_s3_bytes_received", "PutObject"), 10_000);
+	assert_eq!(metric_value("api_s3_bytes_sent", "GetObject"), 20_000);
+}
+
+#[tokio::test]
+async fn test_admin_metrics_merkle_lag() {
+	const BURST: u64 = 50;
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("metrics-merkle");
+
+	let client = Client::builder(TokioExecutor::new()).build_http();
+	let object_table_metric = |name: &'static str| {
+		let client = &client;
+		let admin_port = ctx.garage.admin_port;
+		async move {
+			let req = Request::builder()
+				.method("GET")
+				.uri(format!("http://127.0.0.1:{}/metrics", admin_port))
+				.body(FullBody::new(Bytes::new()))
+				.unwrap();
+			let resp = client.request(req).await.unwrap();
+			assert_eq!(resp.status(), StatusCode::OK);
+			let metrics = resp.into_body().collect().await.unwrap().to_bytes();
+			let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+			metrics
+				.lines()
+				.find(|l| l.starts_with(name) && l.contains("table_name=\"object\""))
+				.and_then(|l| l.rsplit(' ').next())
+				.map(|v| v.parse::<u64>().unwrap())
+				.unwrap_or(0)
+		}
+	};
+
+	let processed_before = object_table_metric("table_merkle_updater_items_processed").await;
+
+	let puts = (0..BURST).map(|i| {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(format!("obj-{}", i))
+			.body(b"hello".to_vec().into())
+			.send()
+	});
+	for res in futures::future::join_all(puts).await {
+		res.unwrap();
+	}
+
+	// Every write goes through the Merkle updater queue: the reported
+	// lag has to come back to zero once all items have been processed.
+	let mut max_lag = 0;
+	let mut drained = false;
+	for _ in 0..50 {
+		let lag = object_table_metric("table_merkle_updater_todo_queue_length").await;
+		max_lag = std::cmp::max(max_lag, lag);
+		let processed = object_table_metric("table_merkle_updater_items_processed").await;
+		if lag == 0 && processed >= processed_before + BURST {
+			drained = true;
+			break;
+		}
+		tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+	}
+	assert!(
+		drained,
+		"Merkle updater queue did not drain (max observed lag: {})",
+		max_lag
+	);
+}
+
+#[tokio::test]
+async fn test_block_read_prefer_local() {
+	use aws_sdk_s3::primitives::ByteStream;
+
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		root: r#"block_read_strategy = "prefer_local""#,
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("block-read-prefer-local");
+
+	let client = Client::builder(TokioExecutor::new()).build_http();
+	let block_metric = |name: &'static str| {
+		let client = &client;
+		let admin_port = ctx.garage.admin_port;
+		async move {
+			let req = Request::builder()
+				.method("GET")
+				.uri(format!("http://127.0.0.1:{}/metrics", admin_port))
+				.body(FullBody::new(Bytes::new()))
+				.unwrap();
+			let resp = client.request(req).await.unwrap();
+			assert_eq!(resp.status(), StatusCode::OK);
+			let metrics = resp.into_body().collect().await.unwrap().to_bytes();
+			let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+			metrics
+				.lines()
+				.find(|l| l.starts_with(name))
+				.and_then(|l| l.rsplit(' ').next())
+				.map(|v| v.parse::<u64>().unwrap())
+				.unwrap_or(0)
+		}
+	};
+
+	// Larger than the inline threshold, so that it is stored as a data block
+	let body = (0..16384u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("block")
+		.body(ByteStream::from(body.clone()))
+		.send()
+		.await
+		.unwrap();
+
+	let bytes_read_before = block_metric("block_bytes_read").await;
+
+	let res = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("block")
+		.send()
+		.await
+		.unwrap();
+	assert_bytes_eq!(res.body, &body[..]);
+
+	// With `block_read_strategy = "prefer_local"`, the block is read from disk, and no request for it is sent through RPC,
+	// not even to the local node
+	assert!(block_metric("block_bytes_read").await > bytes_read_before);
+	assert_eq!(block_metric("block_rpc_get_counter").await, 0);
+}
+
+#[tokio::test]
+async fn test_cli_json_output() {
+	use serde::Deserialize;
+
+	// Mirrors of the schemas defined in src/garage/cli/json.rs
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct Status {
+		layout_version: u64,
+		nodes: Vec<NodeStatus>,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct NodeStatus {
+		id: String,
+		is_up: bool,
+		role: Option<NodeRole>,
+		draining: bool,
+	}
+	#[derive(Deserialize)]
+	struct NodeRole {
+		zone: String,
+		capacity: Option<u64>,
+	}
+	#[derive(Deserialize)]
+	struct Stats {
+		nodes: Vec<NodeStats>,
+		errors: Vec<NodeError>,
+	}
+	#[derive(Deserialize, Debug)]
+	#[allow(dead_code)]
+	struct NodeError {
+		id: String,
+		error: String,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct NodeStats {
+		id: String,
+		db_engine: String,
+		tables: Vec<TableStats>,
+		block_manager: BlockManagerStats,
+	}
+	#[derive(Deserialize)]
+	struct TableStats {
+		name: String,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct BlockManagerStats {
+		resync_errors: usize,
+		compression: Option<CompressionStats>,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct CompressionStats {
+		bytes_in: u64,
+		bytes_out: u64,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct BucketInfo {
+		id: String,
+		global_aliases: Vec<String>,
+		keys: Vec<BucketKeyPerm>,
+		objects: i64,
+		bytes: i64,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct BucketKeyPerm {
+		access_key_id: String,
+		permissions: Permissions,
+	}
+	#[derive(Deserialize)]
+	struct Permissions {
+		read: bool,
+		write: bool,
+		owner: bool,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct KeyInfo {
+		access_key_id: String,
+		secret_access_key: String,
+		buckets: Vec<KeyBucket>,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct KeyBucket {
+		id: String,
+		global_aliases: Vec<String>,
+		permissions: Permissions,
+	}
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("cli-json-output");
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("hello")
+		.body(vec![0x42; 100].into())
+		.send()
+		.await
+		.unwrap();
+
+	let run = |args: &[&str]| {
+		let output = ctx
+			.garage
+			.command()
+			.args(["--output", "json"])
+			.args(args)
+			.expect_success_output("Could not run CLI command with --output json");
+		output.stdout
+	};
+
+	let status: Status = serde_json::from_slice(&run(&["status"])).unwrap();
+	assert!(status.layout_version >= 1);
+	assert_eq!(status.nodes.len(), 1);
+	let node = &status.nodes[0];
+	assert_eq!(node.id.len(), 64);
+	assert!(node.is_up);
+	assert!(!node.draining);
+	let role = node.role.as_ref().unwrap();
+	assert!(!role.zone.is_empty());
+	assert!(role.capacity.is_some());
+
+	let stats: Stats = serde_json::from_slice(&run(&["stats"])).unwrap();
+	assert_eq!(stats.nodes.len(), 1);
+	assert_eq!(stats.nodes[0].id, node.id);
+	assert!(!stats.nodes[0].db_engine.is_empty());
+	assert!(stats.nodes[0].tables.iter().any(|t| t.name == "object"));
+	assert_eq!(stats.nodes[0].block_manager.resync_errors, 0);
+	assert!(stats.nodes[0].block_manager.compression.is_none());
+	assert!(stats.errors.is_empty());
+
+	let stats: Stats = serde_json::from_slice(&run(&["stats", "--all-nodes"])).unwrap();
+	assert_eq!(stats.nodes.len(), 1);
+	assert!(stats.errors.is_empty(), "{:?}", stats.errors);
+
+	let stats: Stats = serde_json::from_slice(&run(&["stats", "--detailed"])).unwrap();
+	let compression = stats.nodes[0].block_manager.compression.as_ref().unwrap();
+	assert!(compression.bytes_in == 0 || compression.bytes_out > 0);
+
+	let bucket_info: BucketInfo =
+		serde_json::from_slice(&run(&["bucket", "info", &bucket])).unwrap();
+	assert_eq!(bucket_info.id.len(), 64);
+	assert_eq!(bucket_info.global_aliases, vec![bucket.clone()]);
+	assert_eq!(bucket_info.objects, 1);
+	assert_eq!(bucket_info.bytes, 100);
+	let perm = bucket_info
+		.keys
+		.iter()
+		.find(|k| k.access_key_id == ctx.key.id)
+		.unwrap();
+	assert!(perm.permissions.read && perm.permissions.write && perm.permissions.owner);
+
+	let key_info: KeyInfo = serde_json::from_slice(&run(&["key", "info", &ctx.key.id])).unwrap();
+	assert_eq!(key_info.access_key_id, ctx.key.id);
+	assert_eq!(key_info.secret_access_key, ctx.key.secret);
+	let kb = key_info
+		.buckets
+		.iter()
+		.find(|b| b.id == bucket_info.id)
+		.unwrap();
+	assert_eq!(kb.global_aliases, vec![bucket.clone()]);
+	assert!(kb.permissions.read && kb.permissions.write && kb.permissions.owner);
+
+	// The default output format is unchanged
+	let bucket_info = ctx
+		.garage
+		.command()
+		.args(["bucket", "info", &bucket])
+		.expect_success_output("Could not get bucket info");
+	let bucket_info = String::from_utf8(bucket_info.stdout).unwrap();
+	assert!(bucket_info.starts_with("Bucket: "));
+}
+
+#[tokio::test]
+async fn test_cli_block_purge_ref() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("block-purge-ref");
+
+	// Larger than the default inline threshold of 3072 bytes,
+	// so this object is stored as a single data block
+	let body = vec![0x17; 10_000];
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("obj")
+		.body(body.clone().into())
+		.send()
+		.await
+		.unwrap();
+	let hash = hex::encode(garage_util::data::blake2sum(&body));
+
+	// Confirmation is mandatory
+	let output = ctx
+		.garage
+		.command()
+		.args(["block", "purge-ref", &hash])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+
+	// References that belong to an existing version are never removed
+	let output = ctx
+		.garage
+		.command()
+		.args(["block", "purge-ref", "--yes", &hash])
+		.expect_success_output("Could not purge block references");
+	let output = String::from_utf8(output.stdout).unwrap();
+	assert!(output.contains("Purged 0 references"));
+	assert!(output.contains("kept 1 valid references"));
+
+	let info = ctx
+		.garage
+		.command()
+		.args(["block", "info", &hash])
+		.expect_success_output("Could not get block info");
+	let info = String::from_utf8(info.stdout).unwrap();
+	assert!(info.contains("Refcount: 1"));
+
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("obj")
+		.send()
+		.await
+		.unwrap();
+	assert_bytes_eq!(o.body, &body[..]);
+}
+
+#[tokio::test]
+async fn test_cli_repair_full() {
+	let ctx = common::context();
+
+	let remaining = || {
+		let output = ctx
+			.garage
+			.command()
+			.args(["repair", "--all-nodes", "status"])
+			.expect_success_output("Could not get repair status");
+		let output = String::from_utf8(output.stdout).unwrap();
+		let remaining = output
+			.lines()
+			.find_map(|l| l.strip_prefix("Total partitions remaining to sync: "))
+			.unwrap()
+			.trim()
+			.parse::<u64>()
+			.unwrap();
+		(remaining, output)
+	};
+
+	// Confirmation is mandatory to launch the repair, not to get its status
+	let output = ctx
+		.garage
+		.command()
+		.args(["repair", "--all-nodes", "full"])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+
+	ctx.garage
+		.command()
+		.args(["repair", "--all-nodes", "--yes", "full"])
+		.quiet()
+		.expect_success_status("Could not launch full repair");
+
+	let (mut last, output) = remaining();
+	assert!(output.contains("object sync"), "{}", output);
+	assert!(output.contains("Block repair worker"), "{}", output);
+
+	// Remaining work decreases until everything has been synced and repaired
+	for _ in 0..120 {
+		tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+		let (cur, output) = remaining();
+		assert!(cur <= last, "remaining work increased: {} -> {}", last, cur);
+		last = cur;
+		if cur == 0
+			&& output
+				.lines()
+				.any(|l| l.starts_with("Block repair worker") && l.contains("Done"))
+		{
+			return;
+		}
+	}
+	panic!(
+		"full repair did not complete, {} partitions remaining",
+		last
+	);
+}
+
+#[tokio::test]
+async fn test_cli_repair_status_watch() {
+	let ctx = common::context();
+
+	ctx.garage
+		.command()
+		.args(["repair", "--all-nodes", "--yes", "blocks"])
+		.quiet()
+		.expect_success_status("Could not launch block repair");
+
+	// The watch exits by itself once all syncs and repairs are finished
+	let output = ctx
+		.garage
+		.command()
+		.args(["repair", "--all-nodes", "status", "--watch", "1"])
+		.expect_success_output("Could not watch repair status");
+	let output = String::from_utf8(output.stdout).unwrap();
+	assert!(output.contains("Block repair worker"), "{}", output);
+	assert!(
+		output.ends_with("All table syncs and repair operations are finished.\n"),
+		"{}",
+		output
+	);
+
+	let last = output
+		.rsplit("Total partitions remaining to sync: ")
+		.next()
+		.unwrap();
+	assert!(last.starts_with("0\n"), "{}", output);
+	assert!(
+		last.lines()
+			.any(|l| l.starts_with("Block repair worker") && l.contains("Done")),
+		"{}",
+		output
+	);
+}
+
+#[tokio::test]
+async fn test_cli_bucket_set_quotas() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("set-quotas");
+
+	let info = || {
+		let output = ctx
+			.garage
+			.command()
+			.args(["bucket", "info", &bucket])
+			.expect_success_output("Could not get bucket info");
+		String::from_utf8(output.stdout).unwrap()
+	};
+	assert!(!info().contains("Quotas:"));
+
+	ctx.garage
+		.command()
+		.args([
+			"bucket",
+			"set-quota",
+			&bucket,
+			"--max-size",
+			"10GB",
+			"--max-objects",
+			"1000000",
+		])
+		.quiet()
+		.expect_success_status("Could not set quotas");
+	let output = info();
+	assert!(output.contains("Quotas:"), "{}", output);
+	assert!(output.contains(" maximum size: "), "{}", output);
+	assert!(output.contains("10.0 GB"), "{}", output);
+	assert!(
+		output.contains(" maximum number of objects: 1000000"),
+		"{}",
+		output
+	);
+
+	// Invalid values are rejected and leave the quotas unchanged
+	for args in [
+		["--max-size=-1GB"],
+		["--max-size=lots"],
+		["--max-objects=-1"],
+	] {
+		let output = ctx
+			.garage
+			.command()
+			.args(["bucket", "set-quotas", &bucket])
+			.args(args)
+			.output()
+			.unwrap();
+		assert!(!output.status.success());
+	}
+	assert!(info().contains(" maximum number of objects: 1000000"));
+
+	// Quotas are cleared one at a time with `none`
+	ctx.garage
+		.command()
+		.args(["bucket", "set-quotas", &bucket, "--max-size", "none"])
+		.quiet()
+		.expect_success_status("Could not clear size quota");
+	let output = info();
+	assert!(!output.contains("maximum size"), "{}", output);
+	assert!(
+		output.contains(" maximum number of objects: 1000000"),
+		"{}",
+		output
+	);
+
+	ctx.garage
+		.command()
+		.args(["bucket", "set-quotas", &bucket, "--max-objects", "none"])
+		.quiet()
+		.expect_success_status("Could not clear object quota");
+	assert!(!info().contains("Quotas:"));
+}
+
+#[tokio::test]
+async fn test_cli_bucket_delete_force() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("delete-force");
+
+	for i in 0..3 {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(format!("obj{}", i))
+			.body(vec![0x2a; 10_000].into())
+			.send()
+			.await
+			.unwrap();
+	}
+	ctx.client
+		.create_multipart_upload()
+		.bucket(&bucket)
+		.key("mpu")
+		.send()
+		.await
+		.unwrap();
+
+	// A non-empty bucket is not deleted without --force
+	let output = ctx
+		.garage
+		.command()
+		.args(["bucket", "delete", "--yes", &bucket])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(stderr.contains("Bucket not empty"));
+	assert!(stderr.contains("--force"));
+	assert!(ctx
+		.client
+		.head_bucket()
+		.bucket(&bucket)
+		.send()
+		.await
+		.is_ok());
+
+	// A dry run goes through the same checks as the actual deletion
+	let output = ctx
+		.garage
+		.command()
+		.args(["bucket", "delete", "--dry-run", &bucket])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(stderr.contains("Bucket not empty"));
+
+	let output = ctx
+		.garage
+		.command()
+		.args(["bucket", "delete", "--dry-run", "--force", &bucket])
+		.expect_success_output("Could not simulate bucket deletion");
+	let output = String::from_utf8(output.stdout).unwrap();
+	assert!(output.contains("DRY RUN"), "{}", output);
+	assert!(output.contains("Objects:"), "{}", output);
+	assert!(ctx
+		.client
+		.head_bucket()
+		.bucket(&bucket)
+		.send()
+		.await
+		.is_ok());
+
+	// --force still requires confirmation
+	let output = ctx
+		.garage
+		.command()
+		.args(["bucket", "delete", "--force", &bucket])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+	assert!(ctx
+		.client
+		.head_bucket()
+		.bucket(&bucket)
+		.send()
+		.await
+		.is_ok());
+
+	let output = ctx
+		.garage
+		.command()
+		.args(["bucket", "delete", "--yes", "--force", &bucket])
+		.expect_success_output("Could not delete bucket");
+	let output = String::from_utf8(output.stdout).unwrap();
+	assert!(output.contains("along with 3 objects"));
+	assert!(ctx
+		.client
+		.head_bucket()
+		.bucket(&bucket)
+		.send()
+		.await
+		.is_err());
+
+	// An empty bucket is deleted with --yes only
+	let bucket = ctx.create_bucket("delete-empty");
+	ctx.garage
+		.command()
+		.args(["bucket", "delete", "--yes", &bucket])
+		.quiet()
+		.expect_success_status("Could not delete bucket");
+	assert!(ctx
+		.client
+		.head_bucket()
+		.bucket(&bucket)
+		.send()
+		.await
+		.is_err());
+}
+
+#[tokio::test]
+async fn test_cli_stats_deduplication() {
+	use serde::Deserialize;
+
+	#[derive(Deserialize)]
+	struct Stats {
+		nodes: Vec<NodeStats>,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct NodeStats {
+		block_manager: BlockManagerStats,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct BlockManagerStats {
+		deduplication: Option<DeduplicationStats>,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct DeduplicationStats {
+		logical_bytes: u64,
+		physical_bytes: u64,
+	}
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("stats-dedup");
+
+	let stats = || {
+		let output = ctx
+			.garage
+			.command()
+			.args(["--output", "json", "stats", "--detailed"])
+			.expect_success_output("Could not get stats");
+		let stats: Stats = serde_json::from_slice(&output.stdout).unwrap();
+		let dedup = stats.nodes[0].block_manager.deduplication.as_ref().unwrap();
+		(dedup.logical_bytes, dedup.physical_bytes)
+	};
+
+	// Incompressible content spanning many blocks, so that objects
+	// written by other tests running concurrently do not weigh much
+	// in the measured difference
+	const SIZE: usize = 32 * 1024 * 1024;
+	let mut state = 0x9e3779b97f4a7c15u64;
+	let body = (0..SIZE)
+		.map(|_| {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			(state >> 56) as u8
+		})
+		.collect::<Vec<u8>>();
+
+	let (logical_before, physical_before) = stats();
+
+	for key in ["first", "second"] {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(key)
+			.body(body.clone().into())
+			.send()
+			.await
+			.unwrap();
+	}
+
+	let (logical_after, physical_after) = stats();
+	let logical = logical_after.saturating_sub(logical_before);
+	let physical = physical_after.saturating_sub(physical_before);
+	assert!(physical > 0);
+
+	// Both objects share the same blocks: twice as much object data as block data
+	let ratio = logical as f64 / physical as f64;
+	assert!(ratio > 1.5 && ratio < 2.5, "{} {}", logical, physical);
+
+	// The text output is labelled as an estimate
+	let output = ctx
+		.garage
+		.command()
+		.args(["stats", "--detailed"])
+		.expect_success_output("Could not get stats");
+	let output = String::from_utf8(output.stdout).unwrap();
+	assert!(
+		output.contains("deduplication (approximate): "),
+		"{}",
+		output
+	);
+}
+
+#[tokio::test]
+async fn test_cli_stats_compression() {
+	use serde::Deserialize;
+
+	#[derive(Deserialize)]
+	struct Stats {
+		nodes: Vec<NodeStats>,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct NodeStats {
+		block_manager: BlockManagerStats,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct BlockManagerStats {
+		compression: Option<CompressionStats>,
+	}
+	#[derive(Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct CompressionStats {
+		bytes_in: u64,
+		bytes_out: u64,
+		ratio: Option<f64>,
+	}
+
+	// Dedicated instance, so that only the blocks written by this test are counted
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		root: "compression_level = 1",
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("stats-compression");
+
+	let stats = || {
+		let output = ctx
+			.garage
+			.command()
+			.args(["--output", "json", "stats", "--detailed"])
+			.expect_success_output("Could not get stats");
+		let stats: Stats = serde_json::from_slice(&output.stdout).unwrap();
+		stats
+			.nodes
+			.into_iter()
+			.next()
+			.unwrap()
+			.block_manager
+			.compression
+			.unwrap()
+	};
+
+	let compression = stats();
+	assert_eq!(compression.bytes_in, 0);
+	assert!(compression.ratio.is_none());
+
+	// Highly compressible content made of distinct blocks
+	let body = (0..200_000)
+		.map(|i| format!("line number {:08}\n", i))
+		.collect::<String>()
+		.into_bytes();
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("compressible")
+		.body(body.clone().into())
+		.send()
+		.await
+		.unwrap();
+
+	let compression = stats();
+	assert_eq!(compression.bytes_in, body.len() as u64);
+	assert!(compression.bytes_out > 0);
+	assert!(compression.bytes_out < compression.bytes_in / 2);
+	let ratio = compression.ratio.unwrap();
+	assert!(ratio > 2., "{}", ratio);
+	assert_eq!(
+		ratio,
+		compression.bytes_in as f64 / compression.bytes_out as f64
+	);
+
+	// The text output shows the same ratio
+	let output = ctx
+		.garage
+		.command()
+		.args(["stats", "--detailed"])
+		.expect_success_output("Could not get stats");
+	let output = String::from_utf8(output.stdout).unwrap();
+	assert!(
+		output.contains(&format!("ratio {:.2}", ratio)),
+		"{}",
+		output
+	);
+}
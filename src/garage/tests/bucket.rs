@@ -1,6 +1,9 @@
 use crate::common;
+use crate::common::custom_requester::{self, CustomRequester};
 use crate::common::ext::CommandExt;
+use aws_sdk_s3::error::ProvideErrorMetadata;
 use aws_sdk_s3::operation::delete_bucket::DeleteBucketOutput;
+use hyper::{Method, StatusCode};
 
 #[tokio::test]
 async fn test_bucket_all() {
@@ -76,9 +79,37 @@ async fn test_bucket_all() {
 
 		assert!(r.status.is_none());
 	}
+	{
+		// Check a non-empty bucket cannot be deleted
+		ctx.client
+			.put_object()
+			.bucket(bucket_name)
+			.key("obj")
+			.body(b"hello".to_vec().into())
+			.send()
+			.await
+			.unwrap();
+
+		let err = ctx
+			.client
+			.delete_bucket()
+			.bucket(bucket_name)
+			.send()
+			.await
+			.unwrap_err();
+		assert_eq!(err.raw_response().unwrap().status().as_u16(), 409);
+		assert_eq!(err.code(), Some("BucketNotEmpty"));
+
+		ctx.client
+			.delete_object()
+			.bucket(bucket_name)
+			.key("obj")
+			.send()
+			.await
+			.unwrap();
+	}
 	{
 		// Delete bucket
-		// @TODO add a check with a non-empty bucket and check failure
 		let r = ctx
 			.client
 			.delete_bucket()
@@ -101,3 +132,339 @@ async fn test_bucket_all() {
 			.any(|x| x.name.as_ref().unwrap() == "hello"));
 	}
 }
+
+#[tokio::test]
+async fn test_create_bucket_invalid_name() {
+	let ctx = common::context();
+
+	for name in [
+		"ab",
+		"-bucket",
+		"bucket.",
+		"my..bucket",
+		"192.168.5.4",
+		"xn--bucket",
+	] {
+		let err = ctx
+			.client
+			.create_bucket()
+			.bucket(name)
+			.send()
+			.await
+			.unwrap_err();
+		assert_eq!(
+			err.raw_response().unwrap().status().as_u16(),
+			400,
+			"{}",
+			name
+		);
+		assert_eq!(err.code(), Some("InvalidBucketName"), "{}", name);
+	}
+
+	// Valid names are still accepted
+	ctx.client
+		.create_bucket()
+		.bucket("valid.bucket-name")
+		.send()
+		.await
+		.unwrap();
+}
+
+#[tokio::test]
+async fn test_list_buckets_scoped_to_key() {
+	let ctx = common::context();
+	let key2 = ctx.garage.key(Some("list-buckets-other"));
+	let client2 = common::client::build_client(&key2);
+
+	for key in [&ctx.key, &key2] {
+		ctx.garage
+			.command()
+			.args(["key", "allow"])
+			.args(["--create-bucket", &key.id])
+			.quiet()
+			.expect_success_output("Could not allow key to create buckets");
+	}
+
+	ctx.client
+		.create_bucket()
+		.bucket("list-scoped-a")
+		.send()
+		.await
+		.unwrap();
+	client2
+		.create_bucket()
+		.bucket("list-scoped-b")
+		.send()
+		.await
+		.unwrap();
+
+	// A local alias is only visible to the key that owns it
+	ctx.garage
+		.command()
+		.args(["bucket", "alias", "--local", &key2.id])
+		.args(["list-scoped-b", "list-scoped-b-local"])
+		.quiet()
+		.expect_success_output("Could not add local alias");
+
+	let names = |r: aws_sdk_s3::operation::list_buckets::ListBucketsOutput| {
+		r.buckets
+			.unwrap_or_default()
+			.into_iter()
+			.filter_map(|b| b.name)
+			.collect::<Vec<_>>()
+	};
+
+	let r = ctx.client.list_buckets().send().await.unwrap();
+	assert_eq!(names(r), vec!["list-scoped-a".to_string()]);
+
+	let r = client2.list_buckets().send().await.unwrap();
+	assert_eq!(
+		names(r),
+		vec![
+			"list-scoped-b".to_string(),
+			"list-scoped-b-local".to_string()
+		]
+	);
+}
+
+#[tokio::test]
+async fn test_key_create_allow_create_bucket() {
+	let ctx = common::context();
+
+	let create_key = |args: &[&str]| {
+		let output = ctx
+			.garage
+			.command()
+			.args(["key", "create"])
+			.args(args)
+			.expect_success_output("Could not create key");
+		let stdout = String::from_utf8(output.stdout).unwrap();
+		let field = |prefix: &str| {
+			stdout
+				.lines()
+				.find_map(|l| l.strip_prefix(prefix))
+				.unwrap()
+				.trim()
+				.to_string()
+		};
+		assert_eq!(
+			field("Can create buckets: "),
+			args.contains(&"--allow-create-bucket").to_string()
+		);
+		common::garage::Key {
+			name: None,
+			id: field("Key ID: "),
+			secret: field("Secret key: "),
+		}
+	};
+
+	// A key created without the flag cannot create buckets
+	let key = create_key(&["key-no-create-bucket"]);
+	let client = common::client::build_client(&key);
+	let err = client
+		.create_bucket()
+		.bucket("denied-self-service")
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
+
+	// A key created with the flag can, and owns the bucket it created
+	let key = create_key(&["key-create-bucket", "--allow-create-bucket"]);
+	let client = common::client::build_client(&key);
+	client
+		.create_bucket()
+		.bucket("allowed-self-service")
+		.send()
+		.await
+		.unwrap();
+	client
+		.put_object()
+		.bucket("allowed-self-service")
+		.key("obj")
+		.body(b"hello".to_vec().into())
+		.send()
+		.await
+		.unwrap();
+
+	// Once denied again, it cannot create more buckets
+	ctx.garage
+		.command()
+		.args(["key", "deny", "--create-bucket", &key.id])
+		.quiet()
+		.expect_success_output("Could not deny key to create buckets");
+	let err = client
+		.create_bucket()
+		.bucket("denied-self-service-2")
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn test_key_allowed_cidrs() {
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		s3_api: r#"trusted_proxies = ["127.0.0.1"]"#,
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("key-allowed-cidrs");
+	let key2 = ctx.garage.key(Some("allowed-cidrs"));
+	ctx.garage
+		.command()
+		.args(["bucket", "allow", "--read", &bucket, "--key", &key2.id])
+		.quiet()
+		.expect_success_output("Could not allow key on bucket");
+	let requester = CustomRequester::new_s3(ctx.garage, &key2);
+
+	let set_allowed = |cidrs: &[&str]| {
+		let output = ctx
+			.garage
+			.command()
+			.args(["key", "set-allowed-cidrs", &key2.id])
+			.args(cidrs)
+			.expect_success_output("Could not set allowed CIDRs");
+		String::from_utf8(output.stdout).unwrap()
+	};
+	let list_status = |forwarded_for: Option<&str>| {
+		let mut req = requester.builder(bucket.clone());
+		req.method(Method::GET);
+		if let Some(ip) = forwarded_for {
+			req.unsigned_header("x-forwarded-for", ip);
+		}
+		async move { req.send().await.unwrap().status() }
+	};
+
+	// Without restriction, requests are allowed from any address
+	assert_eq!(list_status(None).await, StatusCode::OK);
+
+	// Tests connect from 127.0.0.1
+	let output = set_allowed(&["127.0.0.0/8", "2001:db8::1"]);
+	assert!(
+		output.contains("Allowed source addresses: 127.0.0.0/8, 2001:db8::1/128"),
+		"{}",
+		output
+	);
+	assert_eq!(list_status(None).await, StatusCode::OK);
+
+	set_allowed(&["192.0.2.0/24"]);
+	assert_eq!(list_status(None).await, StatusCode::FORBIDDEN);
+
+	// 127.0.0.1 is a trusted proxy for this instance, so the
+	// address it forwards is the one that is checked
+	assert_eq!(list_status(Some("192.0.2.7")).await, StatusCode::OK);
+	assert_eq!(list_status(Some("::ffff:192.0.2.7")).await, StatusCode::OK);
+	assert_eq!(
+		list_status(Some("198.51.100.7")).await,
+		StatusCode::FORBIDDEN
+	);
+	// Only the last address of the list can be trusted
+	assert_eq!(
+		list_status(Some("192.0.2.7, 198.51.100.7")).await,
+		StatusCode::FORBIDDEN
+	);
+
+	// Other keys are not affected
+	ctx.client
+		.list_objects_v2()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+
+	// Invalid ranges are refused, and `none` removes the restriction
+	let output = ctx
+		.garage
+		.command()
+		.args(["key", "set-allowed-cidrs", &key2.id, "192.0.2.0/33"])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+
+	let output = set_allowed(&["none"]);
+	assert!(!output.contains("Allowed source addresses"), "{}", output);
+	assert_eq!(list_status(None).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_key_rate_limit() {
+	use aws_sdk_s3::primitives::ByteStream;
+	use http_body_util::BodyExt;
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("key-rate-limit");
+	let large = vec![0x42; 300_000];
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("large")
+		.body(ByteStream::from(large.clone()))
+		.send()
+		.await
+		.unwrap();
+
+	let key2 = ctx.garage.key(Some("rate-limited"));
+	ctx.garage
+		.command()
+		.args(["bucket", "allow", "--read", &bucket, "--key", &key2.id])
+		.quiet()
+		.expect_success_output("Could not allow key on bucket");
+	let requester = CustomRequester::new_s3(ctx.garage, &key2);
+
+	let set_rate_limit = |args: &[&str]| {
+		ctx.garage
+			.command()
+			.args(["key", "set-rate-limit", &key2.id])
+			.args(args)
+			.quiet()
+			.expect_success_output("Could not set rate limit");
+	};
+	let get = |key: Option<&str>| {
+		let mut req = requester.builder(bucket.clone());
+		req.method(Method::GET);
+		if let Some(key) = key {
+			req.path(key);
+		}
+		async move { req.send().await.unwrap() }
+	};
+	let assert_slow_down = |resp: hyper::Response<custom_requester::Body>| async move {
+		assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+		assert!(resp.headers().contains_key("retry-after"));
+		let body = resp.into_body().collect().await.unwrap().to_bytes();
+		let body = String::from_utf8(body.to_vec()).unwrap();
+		assert!(body.contains("<Code>SlowDown</Code>"), "{}", body);
+	};
+
+	// A burst of requests is throttled
+	set_rate_limit(&["--requests-per-sec", "2"]);
+	let mut throttled = None;
+	for _ in 0..10 {
+		let resp = get(None).await;
+		if resp.status() != StatusCode::OK {
+			throttled = Some(resp);
+			break;
+		}
+	}
+	assert_slow_down(throttled.expect("no request was throttled")).await;
+
+	// The bytes sent in a response body are charged to the bandwidth limit:
+	// downloading 300kB with a limit of 100kB/s delays the next request
+	set_rate_limit(&["--requests-per-sec", "none", "--bytes-per-sec", "100KB"]);
+	let resp = get(Some("large")).await;
+	assert_eq!(resp.status(), StatusCode::OK);
+	let body = resp.into_body().collect().await.unwrap().to_bytes();
+	assert_eq!(body.len(), large.len());
+	assert_slow_down(get(Some("large")).await).await;
+
+	tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+	assert_eq!(get(None).await.status(), StatusCode::OK);
+
+	// Other keys are not affected
+	ctx.client
+		.get_object()
+		.bucket(&bucket)
+		.key("large")
+		.send()
+		.await
+		.unwrap();
+}
@@ -201,6 +201,7 @@ impl<'a> RequestBuilder<'a> {
 		let body_sha = match self.body_signature {
 			BodySignature::Unsigned => "UNSIGNED-PAYLOAD".to_owned(),
 			BodySignature::Classic => hex::encode(garage_util::data::sha256sum(&self.body)),
+			BodySignature::Fixed(ref sha256) => sha256.clone(),
 			BodySignature::Streaming(size) => {
 				all_headers.insert(
 					CONTENT_ENCODING,
@@ -308,6 +309,8 @@ impl<'a> RequestBuilder<'a> {
 pub enum BodySignature {
 	Unsigned,
 	Classic,
+	/// Send the given value as x-amz-content-sha256, whatever the body is
+	Fixed(String),
 	Streaming(usize),
 }
 
@@ -1,7 +1,8 @@
 use std::mem::MaybeUninit;
 use std::path::{Path, PathBuf};
 use std::process;
-use std::sync::Once;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Mutex, Once};
 
 use super::ext::*;
 
@@ -18,8 +19,19 @@ pub struct Key {
 	pub secret: String,
 }
 
+/// Settings added to the configuration of an instance dedicated to some
+/// tests, for settings that would change the behavior of other tests.
+/// `{path}` is replaced by the runtime directory of the instance.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InstanceConfig<'a> {
+	/// Added at the top level of the configuration file
+	pub root: &'a str,
+	/// Added to the `[s3_api]` section
+	pub s3_api: &'a str,
+}
+
 pub struct Instance {
-	process: process::Child,
+	process: Mutex<process::Child>,
 	pub path: PathBuf,
 	pub default_key: Key,
 	pub s3_port: u16,
@@ -29,16 +41,22 @@ pub struct Instance {
 }
 
 impl Instance {
-	fn new() -> Instance {
+	/// Start instance number `index`: the shared instance is number 0, the
+	/// instances dedicated to some tests use the following ports
+	fn new(index: u16, extra_config: InstanceConfig) -> Instance {
 		use std::{env, fs};
 
 		let port = env::var("GARAGE_TEST_INTEGRATION_PORT")
 			.map(|value| value.parse().expect("Invalid port provided"))
 			.ok()
-			.unwrap_or(DEFAULT_PORT);
+			.unwrap_or(DEFAULT_PORT)
+			+ 5 * index;
 
 		let path = env::var("GARAGE_TEST_INTEGRATION_PATH")
-			.map(PathBuf::from)
+			.map(|path| match index {
+				0 => PathBuf::from(path),
+				_ => PathBuf::from(format!("{}-{}", path, index)),
+			})
 			.ok()
 			.unwrap_or_else(|| env::temp_dir().join(format!("garage-integ-test-{}", port)));
 
@@ -52,6 +70,7 @@ impl Instance {
 		}
 		fs::create_dir(&path).expect("Could not create test runtime directory");
 
+		let path_str = path.display().to_string();
 		let config = format!(
 			r#"
 metadata_dir = "{path}/meta"
@@ -59,6 +78,7 @@ data_dir = "{path}/data"
 db_engine = "{db_engine}"
 
 replication_factor = 1
+{root_config}
 
 rpc_bind_addr = "127.0.0.1:{rpc_port}"
 rpc_public_addr = "127.0.0.1:{rpc_port}"
@@ -68,6 +88,7 @@ rpc_secret = "{secret}"
 s3_region = "{region}"
 api_bind_addr = "127.0.0.1:{s3_port}"
 root_domain = ".s3.garage"
+{s3_api_config}
 
 [k2v_api]
 api_bind_addr = "127.0.0.1:{k2v_port}"
@@ -80,7 +101,7 @@ index = "index.html"
 [admin]
 api_bind_addr = "127.0.0.1:{admin_port}"
 "#,
-			path = path.display(),
+			path = path_str,
 			secret = GARAGE_TEST_SECRET,
 			region = super::REGION,
 			s3_port = port,
@@ -88,6 +109,8 @@ api_bind_addr = "127.0.0.1:{admin_port}"
 			rpc_port = port + 2,
 			web_port = port + 3,
 			admin_port = port + 4,
+			root_config = extra_config.root.replace("{path}", &path_str),
+			s3_api_config = extra_config.s3_api.replace("{path}", &path_str),
 		);
 		fs::write(path.join("config.toml"), config).expect("Could not write garage config file");
 
@@ -105,7 +128,7 @@ api_bind_addr = "127.0.0.1:{admin_port}"
 			.expect("Could not start garage");
 
 		Instance {
-			process: child,
+			process: Mutex::new(child),
 			path,
 			default_key: Key::default(),
 			s3_port: port,
@@ -156,9 +179,11 @@ api_bind_addr = "127.0.0.1:{admin_port}"
 			.expect_success_status("Could not apply garage node layout");
 	}
 
-	fn terminate(&mut self) {
+	fn terminate(&self) {
 		// TODO: Terminate "gracefully" the process with SIGTERM instead of directly SIGKILL it.
 		self.process
+			.lock()
+			.unwrap()
 			.kill()
 			.expect("Could not terminate garage process");
 	}
@@ -223,20 +248,28 @@ api_bind_addr = "127.0.0.1:{admin_port}"
 static mut INSTANCE: MaybeUninit<Instance> = MaybeUninit::uninit();
 static INSTANCE_INIT: Once = Once::new();
 
+static DEDICATED_INSTANCES: Mutex<Vec<&'static Instance>> = Mutex::new(Vec::new());
+static DEDICATED_INSTANCES_COUNT: AtomicU16 = AtomicU16::new(0);
+
 #[static_init::destructor]
 extern "C" fn terminate_instance() {
 	if INSTANCE_INIT.is_completed() {
 		// This block is sound as it depends on `INSTANCE_INIT` being completed, meaning `INSTANCE`
 		// is actually initialized.
 		unsafe {
-			INSTANCE.assume_init_mut().terminate();
+			INSTANCE.assume_init_ref().terminate();
+		}
+	}
+	if let Ok(instances) = DEDICATED_INSTANCES.lock() {
+		for instance in instances.iter() {
+			instance.terminate();
 		}
 	}
 }
 
 pub fn instance() -> &'static Instance {
 	INSTANCE_INIT.call_once(|| unsafe {
-		let mut instance = Instance::new();
+		let mut instance = Instance::new(0, InstanceConfig::default());
 		instance.setup();
 
 		INSTANCE.write(instance);
@@ -247,6 +280,18 @@ pub fn instance() -> &'static Instance {
 	unsafe { INSTANCE.assume_init_ref() }
 }
 
+/// Start a new instance, with some additional configuration, that is only
+/// used by the caller. It is terminated at the end of the tests.
+pub fn dedicated_instance(extra_config: InstanceConfig) -> &'static Instance {
+	let index = DEDICATED_INSTANCES_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+	let mut instance = Instance::new(index, extra_config);
+	instance.setup();
+
+	let instance: &'static Instance = Box::leak(Box::new(instance));
+	DEDICATED_INSTANCES.lock().unwrap().push(instance);
+	instance
+}
+
 pub fn command(config_path: &Path) -> process::Command {
 	use std::env;
 
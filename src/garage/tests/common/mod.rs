@@ -32,8 +32,7 @@ pub struct K2VContext {
 }
 
 impl Context {
-	fn new() -> Self {
-		let garage = garage::instance();
+	fn new(garage: &'static garage::Instance) -> Self {
 		let key = garage.key(None);
 		let client = client::build_client(&key);
 		let custom_request = CustomRequester::new_s3(garage, &key);
@@ -91,5 +90,11 @@ impl Context {
 }
 
 pub fn context() -> Context {
-	Context::new()
+	Context::new(garage::instance())
+}
+
+/// Context of a new Garage instance, for tests that need settings that
+/// would change the behavior of the other tests
+pub fn context_with_config(config: garage::InstanceConfig) -> Context {
+	Context::new(garage::dedicated_instance(config))
 }
@@ -344,6 +344,72 @@ async fn test_items_and_indices() {
 	}
 }
 
+#[tokio::test]
+async fn test_index_mixed_concurrent_values() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("test-k2v-index-mixed");
+
+	let (mut entries, mut conflicts, mut values, mut bytes) = (0, 0, 0, 0);
+	for i in 0..30 {
+		let sk = format!("sk{:02}", i);
+		// Every third item gets one concurrent value, every fifth item
+		// gets two (items that are multiples of 15 get three in total)
+		let n_concurrent = 1 + (i % 3 == 0) as usize + (i % 5 == 0) as usize;
+		for j in 0..n_concurrent {
+			let content = format!("{}: value {} {}", sk, j, "x".repeat(i)).into_bytes();
+			bytes += content.len();
+			let res = ctx
+				.k2v
+				.request
+				.builder(bucket.clone())
+				.path("mixed")
+				.query_param("sort_key", Some(&sk))
+				.body(content)
+				.method(Method::PUT)
+				.send()
+				.await
+				.unwrap();
+			assert_eq!(res.status(), StatusCode::NO_CONTENT);
+		}
+		entries += 1;
+		values += n_concurrent;
+		if n_concurrent > 1 {
+			conflicts += 1;
+		}
+	}
+
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	let res = ctx
+		.k2v
+		.request
+		.builder(bucket.clone())
+		.send()
+		.await
+		.unwrap();
+	let res_body = json_body(res).await;
+	assert_json_eq!(
+		res_body,
+		json!({
+			"prefix": null,
+			"start": null,
+			"end": null,
+			"limit": null,
+			"reverse": false,
+			"partitionKeys": [
+			{
+				"pk": "mixed",
+				"entries": entries,
+				"conflicts": conflicts,
+				"values": values,
+				"bytes": bytes,
+			}
+			],
+			"more": false,
+			"nextStart": null
+		})
+	);
+}
+
 #[tokio::test]
 async fn test_item_return_format() {
 	let ctx = common::context();
@@ -58,3 +58,178 @@ async fn test_special_chars() {
 	assert_eq!(res.items.len(), 1);
 	assert_eq!(res.items.keys().next().unwrap(), sk);
 }
+
+#[tokio::test]
+async fn test_read_modify_write() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("test-k2v-client-read-modify-write");
+	let k2v_client = ctx.k2v_client(&bucket);
+
+	// Two concurrent writes without causality information create a conflict
+	k2v_client
+		.insert_item("root", "counter", b"1".to_vec(), None)
+		.await
+		.unwrap();
+	k2v_client
+		.insert_item("root", "counter", b"2".to_vec(), None)
+		.await
+		.unwrap();
+
+	let res = k2v_client.read_item("root", "counter").await.unwrap();
+	assert_eq!(res.value.len(), 2);
+
+	// Resolve the conflict using the causality token returned by the read
+	let sum = res
+		.value
+		.iter()
+		.map(|v| match v {
+			K2vValue::Value(v) => std::str::from_utf8(v).unwrap().parse::<u64>().unwrap(),
+			K2vValue::Tombstone => 0,
+		})
+		.sum::<u64>();
+	k2v_client
+		.insert_item(
+			"root",
+			"counter",
+			sum.to_string().into_bytes(),
+			Some(res.causality),
+		)
+		.await
+		.unwrap();
+
+	let res = k2v_client.read_item("root", "counter").await.unwrap();
+	assert_eq!(res.value, vec![K2vValue::Value(b"3".to_vec())]);
+
+	// Delete conditionally on what we just read
+	k2v_client
+		.delete_item("root", "counter", res.causality)
+		.await
+		.unwrap();
+
+	match k2v_client.read_item("root", "counter").await {
+		Err(Error::NotFound) => (),
+		Ok(res) => assert_eq!(res.value, vec![K2vValue::Tombstone]),
+		Err(e) => panic!("unexpected error: {}", e),
+	}
+}
+
+#[tokio::test]
+async fn test_insert_with_ttl() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("test-k2v-client-ttl");
+	let k2v_client = ctx.k2v_client(&bucket);
+
+	k2v_client
+		.insert_item_with_ttl(
+			"root",
+			"expiring",
+			b"short-lived".to_vec(),
+			None,
+			Some(Duration::from_secs(2)),
+		)
+		.await
+		.unwrap();
+	k2v_client
+		.insert_item("root", "permanent", b"here to stay".to_vec(), None)
+		.await
+		.unwrap();
+
+	// Before expiry, the value is returned normally
+	let res = k2v_client.read_item("root", "expiring").await.unwrap();
+	assert_eq!(res.value, vec![K2vValue::Value(b"short-lived".to_vec())]);
+
+	tokio::time::sleep(Duration::from_secs(3)).await;
+
+	// After expiry, the item is not found even if it has not been deleted yet,
+	// and it is not listed anymore
+	match k2v_client.read_item("root", "expiring").await {
+		Err(Error::NotFound) => (),
+		r => panic!("unexpected result: {:?}", r),
+	}
+	let res = k2v_client.read_item("root", "permanent").await.unwrap();
+	assert_eq!(res.value, vec![K2vValue::Value(b"here to stay".to_vec())]);
+
+	let res = k2v_client
+		.read_batch(&[BatchReadOp {
+			partition_key: "root",
+			filter: Default::default(),
+			single_item: false,
+			conflicts_only: false,
+			tombstones: false,
+		}])
+		.await
+		.unwrap();
+	assert_eq!(
+		res[0].items.keys().collect::<Vec<_>>(),
+		vec![&"permanent".to_string()]
+	);
+
+	// Writing the item again makes it visible again
+	k2v_client
+		.insert_item("root", "expiring", b"back".to_vec(), None)
+		.await
+		.unwrap();
+	let res = k2v_client.read_item("root", "expiring").await.unwrap();
+	assert_eq!(res.value, vec![K2vValue::Value(b"back".to_vec())]);
+}
+
+#[tokio::test]
+async fn test_read_batch_pagination() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("test-k2v-client-read-batch-pagination");
+	let k2v_client = ctx.k2v_client(&bucket);
+
+	let keys = (0..25).map(|i| format!("sk{:03}", i)).collect::<Vec<_>>();
+	let ops = keys
+		.iter()
+		.map(|sk| BatchInsertOp {
+			partition_key: "root",
+			sort_key: sk,
+			causality: None,
+			value: K2vValue::Value(sk.as_bytes().to_vec()),
+		})
+		.collect::<Vec<_>>();
+	k2v_client.insert_batch(&ops).await.unwrap();
+
+	for reverse in [false, true] {
+		let mut seen = vec![];
+		let mut next_start: Option<String> = None;
+		loop {
+			let res = k2v_client
+				.read_batch(&[BatchReadOp {
+					partition_key: "root",
+					filter: Filter {
+						start: next_start.as_deref(),
+						limit: Some(7),
+						reverse,
+						..Default::default()
+					},
+					single_item: false,
+					conflicts_only: false,
+					tombstones: false,
+				}])
+				.await
+				.unwrap();
+			assert_eq!(res.len(), 1);
+			let page = res.into_iter().next().unwrap();
+			assert!(page.items.len() <= 7);
+
+			let mut page_keys = page.items.keys().cloned().collect::<Vec<_>>();
+			if reverse {
+				page_keys.reverse();
+			}
+			seen.extend(page_keys);
+
+			match page.next_start {
+				Some(ns) => next_start = Some(ns),
+				None => break,
+			}
+		}
+
+		let mut expected = keys.clone();
+		if reverse {
+			expected.reverse();
+		}
+		assert_eq!(seen, expected);
+	}
+}
@@ -0,0 +1,219 @@
+use crate::common;
+use crate::common::ext::CommandExt;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{BucketCannedAcl, ObjectCannedAcl, Permission, Type};
+use http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use http_body_util::Empty;
+use hyper::body::Bytes;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+
+const BODY: &[u8; 16] = b"<h1>bonjour</h1>";
+
+/// Make a GET request without any signature on the S3 API
+async fn anonymous_get(ctx: &common::Context, bucket: &str, key: &str) -> (StatusCode, Bytes) {
+	let client = Client::builder(TokioExecutor::new()).build_http();
+	let req = Request::builder()
+		.method("GET")
+		.uri(format!(
+			"http://127.0.0.1:{}/{}/{}",
+			ctx.garage.s3_port, bucket, key
+		))
+		.body(Empty::<Bytes>::new())
+		.unwrap();
+	let resp = client.request(req).await.unwrap();
+	let status = resp.status();
+	let body = resp.into_body().collect().await.unwrap().to_bytes();
+	(status, body)
+}
+
+#[tokio::test]
+async fn test_bucket_acl() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("bucketacl");
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("index.html")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	// Buckets are private by default
+	let acl = ctx
+		.client
+		.get_bucket_acl()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	let grants = acl.grants.unwrap();
+	assert_eq!(grants.len(), 1);
+	assert_eq!(grants[0].permission, Some(Permission::FullControl));
+	assert_eq!(
+		grants[0].grantee.as_ref().unwrap().r#type,
+		Type::CanonicalUser
+	);
+
+	let (status, _) = anonymous_get(&ctx, &bucket, "index.html").await;
+	assert_eq!(status, StatusCode::FORBIDDEN);
+
+	// Make the bucket public
+	ctx.client
+		.put_bucket_acl()
+		.bucket(&bucket)
+		.acl(BucketCannedAcl::PublicRead)
+		.send()
+		.await
+		.unwrap();
+
+	let acl = ctx
+		.client
+		.get_bucket_acl()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	let grants = acl.grants.unwrap();
+	assert_eq!(grants.len(), 2);
+	assert!(grants.iter().any(|g| {
+		let grantee = g.grantee.as_ref().unwrap();
+		grantee.r#type == Type::Group
+			&& grantee.uri.as_deref() == Some("http://acs.amazonaws.com/groups/global/AllUsers")
+			&& g.permission == Some(Permission::Read)
+	}));
+
+	let (status, body) = anonymous_get(&ctx, &bucket, "index.html").await;
+	assert_eq!(status, StatusCode::OK);
+	assert_eq!(&body[..], BODY);
+
+	// Objects that don't exist are not found
+	let (status, _) = anonymous_get(&ctx, &bucket, "missing.html").await;
+	assert_eq!(status, StatusCode::NOT_FOUND);
+
+	// Response headers can't be overridden by anonymous requests
+	let (status, _) =
+		anonymous_get(&ctx, &bucket, "index.html?response-content-type=text/plain").await;
+	assert_eq!(status, StatusCode::BAD_REQUEST);
+
+	// Anonymous users still can't write
+	let client = Client::builder(TokioExecutor::new()).build_http();
+	let req = Request::builder()
+		.method("PUT")
+		.uri(format!(
+			"http://127.0.0.1:{}/{}/anonymous.html",
+			ctx.garage.s3_port, bucket
+		))
+		.body(http_body_util::Full::new(Bytes::from_static(BODY)))
+		.unwrap();
+	let resp = client.request(req).await.unwrap();
+	assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+	// Back to private
+	ctx.client
+		.put_bucket_acl()
+		.bucket(&bucket)
+		.acl(BucketCannedAcl::Private)
+		.send()
+		.await
+		.unwrap();
+
+	let (status, _) = anonymous_get(&ctx, &bucket, "index.html").await;
+	assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_object_acl() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("objectacl");
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("public.html")
+		.acl(ObjectCannedAcl::PublicRead)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("private.html")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	let (status, body) = anonymous_get(&ctx, &bucket, "public.html").await;
+	assert_eq!(status, StatusCode::OK);
+	assert_eq!(&body[..], BODY);
+
+	let (status, _) = anonymous_get(&ctx, &bucket, "private.html").await;
+	assert_eq!(status, StatusCode::FORBIDDEN);
+
+	// The ACL is not returned as object metadata
+	let o = ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key("public.html")
+		.send()
+		.await
+		.unwrap();
+	assert!(o.metadata.unwrap_or_default().is_empty());
+}
+
+#[tokio::test]
+async fn test_object_acl_permissions() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("objectacl-perms");
+
+	let key2 = ctx.garage.key(Some("objectacl-writer"));
+	ctx.garage
+		.command()
+		.args([
+			"bucket", "allow", "--read", "--write", &bucket, "--key", &key2.id,
+		])
+		.quiet()
+		.expect_success_output("Could not allow key on bucket");
+	let client2 = common::client::build_client(&key2);
+
+	// Only the owner of the bucket can make objects public
+	let err = client2
+		.put_object()
+		.bucket(&bucket)
+		.key("public.html")
+		.acl(ObjectCannedAcl::PublicRead)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
+
+	let (status, _) = anonymous_get(&ctx, &bucket, "public.html").await;
+	assert_eq!(status, StatusCode::FORBIDDEN);
+
+	// Canned ACLs that don't give access to anyone else are accepted
+	for acl in [
+		ObjectCannedAcl::Private,
+		ObjectCannedAcl::BucketOwnerFullControl,
+		ObjectCannedAcl::BucketOwnerRead,
+		ObjectCannedAcl::AwsExecRead,
+	] {
+		client2
+			.put_object()
+			.bucket(&bucket)
+			.key("private.html")
+			.acl(acl)
+			.body(ByteStream::from_static(BODY))
+			.send()
+			.await
+			.unwrap();
+		let (status, _) = anonymous_get(&ctx, &bucket, "private.html").await;
+		assert_eq!(status, StatusCode::FORBIDDEN);
+	}
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use crate::common;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{
+	BucketLifecycleConfiguration, ExpirationStatus, LifecycleExpiration, LifecycleRule,
+	LifecycleRuleFilter,
+};
+
+const BODY: &[u8; 5] = b"hello";
+
+#[tokio::test]
+async fn test_lifecycle_expiration() {
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		root: r#"lifecycle_scan_interval = "1s""#,
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("lifecycle-expiration");
+
+	for key in ["expire/a", "expire/b", "keep/c"] {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(key)
+			.body(ByteStream::from_static(BODY))
+			.send()
+			.await
+			.unwrap();
+	}
+
+	let lifecycle = BucketLifecycleConfiguration::builder()
+		.rules(
+			LifecycleRule::builder()
+				.id("expire-now")
+				.filter(LifecycleRuleFilter::Prefix("expire/".into()))
+				.status(ExpirationStatus::Enabled)
+				.expiration(LifecycleExpiration::builder().days(0).build())
+				.build()
+				.unwrap(),
+		)
+		.build()
+		.unwrap();
+
+	ctx.client
+		.put_bucket_lifecycle_configuration()
+		.bucket(&bucket)
+		.lifecycle_configuration(lifecycle)
+		.send()
+		.await
+		.unwrap();
+
+	// This instance runs the lifecycle worker every second,
+	// objects under the prefix should be expired on its next run
+	let mut remaining = vec![];
+	for _ in 0..30 {
+		tokio::time::sleep(Duration::from_secs(1)).await;
+		let list = ctx
+			.client
+			.list_objects_v2()
+			.bucket(&bucket)
+			.send()
+			.await
+			.unwrap();
+		remaining = list
+			.contents()
+			.iter()
+			.map(|o| o.key().unwrap().to_string())
+			.collect::<Vec<_>>();
+		if remaining.len() == 1 {
+			break;
+		}
+	}
+	assert_eq!(remaining, vec!["keep/c".to_string()]);
+
+	assert!(ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("expire/a")
+		.send()
+		.await
+		.is_err());
+
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("keep/c")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(o.body.collect().await.unwrap().into_bytes(), BODY.as_ref());
+}
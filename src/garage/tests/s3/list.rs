@@ -220,6 +220,88 @@ async fn test_listobjectsv2() {
 	}
 }
 
+#[tokio::test]
+async fn test_listobjectsv2_start_after_pagination() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("listobjectsv2-start-after");
+
+	for k in KEYS {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(k)
+			.send()
+			.await
+			.unwrap();
+	}
+
+	let keys = |r: &aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Output| {
+		r.contents()
+			.iter()
+			.map(|o| o.key.clone().unwrap())
+			.collect::<Vec<_>>()
+	};
+
+	// First page starts after start_after, following pages are requested with
+	// the continuation token and a start_after that must be ignored
+	let mut listed = vec![];
+	let r = ctx
+		.client
+		.list_objects_v2()
+		.bucket(&bucket)
+		.start_after("a/a")
+		.max_keys(2)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(keys(&r), ["a/b", "a/c"]);
+	assert_eq!(r.start_after.as_deref(), Some("a/a"));
+	listed.extend(keys(&r));
+
+	let mut next = r.next_continuation_token;
+	while let Some(token) = next {
+		let r = ctx
+			.client
+			.list_objects_v2()
+			.bucket(&bucket)
+			.start_after("a/a")
+			.continuation_token(&token)
+			.max_keys(2)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(r.continuation_token.as_deref(), Some(token.as_str()));
+
+		// The same token always gives the same page
+		let again = ctx
+			.client
+			.list_objects_v2()
+			.bucket(&bucket)
+			.continuation_token(&token)
+			.max_keys(2)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(keys(&again), keys(&r));
+
+		listed.extend(keys(&r));
+		next = r.next_continuation_token;
+	}
+	assert_eq!(listed, &KEYS[2..]);
+
+	// A start_after that sorts before the prefix does not skip any key
+	let r = ctx
+		.client
+		.list_objects_v2()
+		.bucket(&bucket)
+		.prefix("b")
+		.start_after("a/b")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(keys(&r), ["b"]);
+}
+
 #[tokio::test]
 async fn test_listobjectsv1() {
 	let ctx = common::context();
@@ -673,3 +755,238 @@ async fn test_multichar_delimiter() {
 		assert_eq!(common_prefixes[1].prefix.as_deref().unwrap(), "a/c/b/");
 	}
 }
+
+#[tokio::test]
+async fn test_listobjects_owner() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("listobjectsowner");
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("a")
+		.send()
+		.await
+		.unwrap();
+
+	{
+		// Owner is omitted by default in ListObjectsV2
+		let r = ctx
+			.client
+			.list_objects_v2()
+			.bucket(&bucket)
+			.send()
+			.await
+			.unwrap();
+
+		let contents = r.contents.unwrap();
+		assert_eq!(contents.len(), 1);
+		assert!(contents[0].owner.is_none());
+	}
+
+	{
+		// ... and returned when fetch-owner=true
+		let r = ctx
+			.client
+			.list_objects_v2()
+			.bucket(&bucket)
+			.fetch_owner(true)
+			.send()
+			.await
+			.unwrap();
+
+		let contents = r.contents.unwrap();
+		assert_eq!(contents.len(), 1);
+		let owner = contents[0].owner.as_ref().unwrap();
+		assert_eq!(owner.id.as_deref(), Some(ctx.key.id.as_str()));
+	}
+
+	{
+		// ListObjects (v1) always returns the owner
+		let r = ctx
+			.client
+			.list_objects()
+			.bucket(&bucket)
+			.send()
+			.await
+			.unwrap();
+
+		let contents = r.contents.unwrap();
+		assert_eq!(contents.len(), 1);
+		let owner = contents[0].owner.as_ref().unwrap();
+		assert_eq!(owner.id.as_deref(), Some(ctx.key.id.as_str()));
+	}
+}
+
+#[tokio::test]
+async fn test_listobjectversions() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("listobjectversions");
+
+	let mut last_version = None;
+	for _ in 0..3 {
+		let r = ctx
+			.client
+			.put_object()
+			.bucket(&bucket)
+			.key("a")
+			.send()
+			.await
+			.unwrap();
+		last_version = r.version_id;
+	}
+	for k in ["b", "c"] {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(k)
+			.send()
+			.await
+			.unwrap();
+	}
+	ctx.client
+		.delete_object()
+		.bucket(&bucket)
+		.key("c")
+		.send()
+		.await
+		.unwrap();
+
+	{
+		let r = ctx
+			.client
+			.list_object_versions()
+			.bucket(&bucket)
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(r.is_truncated, Some(false));
+
+		// Garage only keeps the most recent version of each object,
+		// which is the one that must be listed first
+		let versions = r.versions.unwrap();
+		assert_eq!(versions.len(), 2);
+		assert_eq!(versions[0].key.as_deref(), Some("a"));
+		assert_eq!(versions[0].version_id, last_version);
+		assert_eq!(versions[0].is_latest, Some(true));
+		assert_eq!(versions[1].key.as_deref(), Some("b"));
+
+		let delete_markers = r.delete_markers.unwrap();
+		assert_eq!(delete_markers.len(), 1);
+		assert_eq!(delete_markers[0].key.as_deref(), Some("c"));
+		assert_eq!(delete_markers[0].is_latest, Some(true));
+	}
+
+	{
+		// Paginate one entry at a time
+		let mut keys = vec![];
+		let mut key_marker = None;
+		let mut version_id_marker = None;
+		loop {
+			let r = ctx
+				.client
+				.list_object_versions()
+				.bucket(&bucket)
+				.max_keys(1)
+				.set_key_marker(key_marker)
+				.set_version_id_marker(version_id_marker)
+				.send()
+				.await
+				.unwrap();
+
+			for v in r.versions.unwrap_or_default() {
+				keys.push(v.key.unwrap());
+			}
+			for dm in r.delete_markers.unwrap_or_default() {
+				keys.push(dm.key.unwrap());
+			}
+
+			if r.is_truncated != Some(true) {
+				break;
+			}
+			key_marker = r.next_key_marker;
+			version_id_marker = r.next_version_id_marker;
+		}
+		assert_eq!(keys, vec!["a", "b", "c"]);
+	}
+
+	{
+		// With a prefix
+		let r = ctx
+			.client
+			.list_object_versions()
+			.bucket(&bucket)
+			.prefix("b")
+			.send()
+			.await
+			.unwrap();
+
+		let versions = r.versions.unwrap();
+		assert_eq!(versions.len(), 1);
+		assert_eq!(versions[0].key.as_deref(), Some("b"));
+		assert!(r.delete_markers.is_none());
+	}
+}
+
+#[tokio::test]
+async fn test_listobjectversions_suspended() {
+	use aws_sdk_s3::types::{BucketVersioningStatus, VersioningConfiguration};
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("listobjectversionssuspended");
+
+	ctx.client
+		.put_bucket_versioning()
+		.bucket(&bucket)
+		.versioning_configuration(
+			VersioningConfiguration::builder()
+				.status(BucketVersioningStatus::Suspended)
+				.build(),
+		)
+		.send()
+		.await
+		.unwrap();
+
+	for k in ["a", "b"] {
+		let r = ctx
+			.client
+			.put_object()
+			.bucket(&bucket)
+			.key(k)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(r.version_id.as_deref(), Some("null"));
+	}
+
+	// Version ids are listed as they were reported when the objects were
+	// written, and can be used as markers
+	let r = ctx
+		.client
+		.list_object_versions()
+		.bucket(&bucket)
+		.max_keys(1)
+		.send()
+		.await
+		.unwrap();
+	let versions = r.versions.unwrap();
+	assert_eq!(versions.len(), 1);
+	assert_eq!(versions[0].version_id.as_deref(), Some("null"));
+	assert_eq!(r.is_truncated, Some(true));
+	assert_eq!(r.next_version_id_marker.as_deref(), Some("null"));
+
+	let r = ctx
+		.client
+		.list_object_versions()
+		.bucket(&bucket)
+		.set_key_marker(r.next_key_marker)
+		.set_version_id_marker(r.next_version_id_marker)
+		.send()
+		.await
+		.unwrap();
+	let versions = r.versions.unwrap();
+	assert_eq!(versions.len(), 1);
+	assert_eq!(versions[0].key.as_deref(), Some("b"));
+	assert_eq!(r.is_truncated, Some(false));
+}
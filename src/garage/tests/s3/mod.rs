@@ -1,8 +1,12 @@
+mod acl;
+mod lifecycle;
 mod list;
 mod multipart;
+mod object_lock;
 mod objects;
 mod presigned;
 mod simple;
 mod ssec;
 mod streaming_signature;
+mod tagging;
 mod website;
@@ -0,0 +1,211 @@
+use crate::common;
+use crate::common::ext::CommandExt;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{
+	DefaultRetention, ObjectLockConfiguration, ObjectLockEnabled, ObjectLockRetentionMode,
+	ObjectLockRule,
+};
+
+const BODY: &[u8; 16] = b"some locked data";
+
+fn lock_config(mode: ObjectLockRetentionMode, days: i32) -> ObjectLockConfiguration {
+	ObjectLockConfiguration::builder()
+		.object_lock_enabled(ObjectLockEnabled::Enabled)
+		.rule(
+			ObjectLockRule::builder()
+				.default_retention(DefaultRetention::builder().mode(mode).days(days).build())
+				.build(),
+		)
+		.build()
+}
+
+#[tokio::test]
+async fn test_object_lock_governance() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("objlock-governance");
+
+	// No configuration yet
+	assert!(ctx
+		.client
+		.get_object_lock_configuration()
+		.bucket(&bucket)
+		.send()
+		.await
+		.is_err());
+
+	ctx.client
+		.put_object_lock_configuration()
+		.bucket(&bucket)
+		.object_lock_configuration(lock_config(ObjectLockRetentionMode::Governance, 1))
+		.send()
+		.await
+		.unwrap();
+
+	let conf = ctx
+		.client
+		.get_object_lock_configuration()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap()
+		.object_lock_configuration
+		.unwrap();
+	let retention = conf.rule.unwrap().default_retention.unwrap();
+	assert_eq!(retention.mode, Some(ObjectLockRetentionMode::Governance));
+	assert_eq!(retention.days, Some(1));
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("locked")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	// Object cannot be overwritten nor deleted during retention period
+	assert!(ctx
+		.client
+		.put_object()
+		.bucket(&bucket)
+		.key("locked")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.is_err());
+	assert!(ctx
+		.client
+		.delete_object()
+		.bucket(&bucket)
+		.key("locked")
+		.send()
+		.await
+		.is_err());
+
+	// ... unless governance retention is bypassed explicitly
+	ctx.client
+		.delete_object()
+		.bucket(&bucket)
+		.key("locked")
+		.bypass_governance_retention(true)
+		.send()
+		.await
+		.unwrap();
+	assert!(ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key("locked")
+		.send()
+		.await
+		.is_err());
+}
+
+#[tokio::test]
+async fn test_object_lock_compliance() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("objlock-compliance");
+
+	ctx.client
+		.put_object_lock_configuration()
+		.bucket(&bucket)
+		.object_lock_configuration(lock_config(ObjectLockRetentionMode::Compliance, 1))
+		.send()
+		.await
+		.unwrap();
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("locked")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	// Bypass header has no effect in compliance mode
+	assert!(ctx
+		.client
+		.delete_object()
+		.bucket(&bucket)
+		.key("locked")
+		.bypass_governance_retention(true)
+		.send()
+		.await
+		.is_err());
+
+	// Compliance retention cannot be relaxed
+	assert!(ctx
+		.client
+		.put_object_lock_configuration()
+		.bucket(&bucket)
+		.object_lock_configuration(lock_config(ObjectLockRetentionMode::Governance, 1))
+		.send()
+		.await
+		.is_err());
+
+	ctx.client
+		.head_object()
+		.bucket(&bucket)
+		.key("locked")
+		.send()
+		.await
+		.unwrap();
+}
+
+#[tokio::test]
+async fn test_object_lock_retention_recorded_on_write() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("objlock-recorded");
+
+	// Objects written before the configuration are not retained
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("before")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	ctx.client
+		.put_object_lock_configuration()
+		.bucket(&bucket)
+		.object_lock_configuration(lock_config(ObjectLockRetentionMode::Compliance, 1))
+		.send()
+		.await
+		.unwrap();
+
+	ctx.client
+		.delete_object()
+		.bucket(&bucket)
+		.key("before")
+		.send()
+		.await
+		.unwrap();
+}
+
+#[tokio::test]
+async fn test_object_lock_config_requires_owner() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("objlock-owner");
+
+	let key2 = ctx.garage.key(Some("objlock-writer"));
+	ctx.garage
+		.command()
+		.args([
+			"bucket", "allow", "--read", "--write", &bucket, "--key", &key2.id,
+		])
+		.quiet()
+		.expect_success_output("Could not allow key on bucket");
+	let client2 = common::client::build_client(&key2);
+
+	let err = client2
+		.put_object_lock_configuration()
+		.bucket(&bucket)
+		.object_lock_configuration(lock_config(ObjectLockRetentionMode::Compliance, 1))
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
+}
@@ -1,6 +1,13 @@
 use crate::common;
+use crate::common::custom_requester::BodySignature;
 use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::types::{Delete, ObjectIdentifier};
+use aws_sdk_s3::types::{
+	BucketVersioningStatus, Delete, ObjectIdentifier, ObjectStorageClass, StorageClass,
+	VersioningConfiguration,
+};
+
+use http_body_util::BodyExt;
+use hyper::{Method, StatusCode};
 
 const STD_KEY: &str = "hello world";
 const CTRL_KEY: &str = "\x00\x01\x02\x00";
@@ -187,6 +194,161 @@ async fn test_getobject() {
 	}
 }
 
+#[tokio::test]
+async fn test_getobject_suffix_range() {
+	// The first object is smaller than the inline threshold,
+	// the second object spans two data blocks
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("getobjectsuffixrange");
+
+	let inline = (0..2000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+	let blocks = (0..1_500_000u32)
+		.map(|i| (i % 251) as u8)
+		.collect::<Vec<_>>();
+
+	for (key, body) in [("inline", &inline), ("blocks", &blocks)] {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(key)
+			.body(ByteStream::from(body.clone()))
+			.send()
+			.await
+			.unwrap();
+
+		let size = body.len();
+		for (range, begin) in [
+			("bytes=-100".to_string(), size - 100),
+			("bytes=100-".to_string(), 100),
+			(format!("bytes=-{}", size * 2), 0),
+		] {
+			let o = ctx
+				.client
+				.get_object()
+				.bucket(&bucket)
+				.key(key)
+				.range(&range)
+				.send()
+				.await
+				.unwrap();
+			assert_eq!(
+				o.content_range.unwrap(),
+				format!("bytes {}-{}/{}", begin, size - 1, size)
+			);
+			assert_eq!(o.content_length.unwrap(), (size - begin) as i64);
+			assert_bytes_eq!(o.body, &body[begin..]);
+		}
+	}
+
+	// A suffix range on an empty object cannot be satisfied
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("empty")
+		.send()
+		.await
+		.unwrap();
+	let err = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("empty")
+		.range("bytes=-100")
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 416);
+}
+
+#[tokio::test]
+async fn test_getobject_range_errors() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("getobjectrangeerrors");
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	for method in [Method::GET, Method::HEAD] {
+		// A valid range outside of the object cannot be satisfied
+		let res = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.method(method.clone())
+			.path(STD_KEY.to_owned())
+			.unsigned_header("range", "bytes=100-200")
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+		assert_eq!(res.headers().get("content-range").unwrap(), "bytes */62");
+
+		// A syntactically invalid range is ignored
+		for range in ["bytes=20-10", "bytes=abc", "lines=1-2"] {
+			let res = ctx
+				.custom_request
+				.builder(bucket.clone())
+				.method(method.clone())
+				.path(STD_KEY.to_owned())
+				.unsigned_header("range", range)
+				.send()
+				.await
+				.unwrap();
+			assert_eq!(res.status(), StatusCode::OK, "{}", range);
+			assert!(res.headers().get("content-range").is_none());
+			assert_eq!(res.headers().get("content-length").unwrap(), "62");
+			if method == Method::GET {
+				let body = res.into_body().collect().await.unwrap().to_bytes();
+				assert_eq!(&body[..], &BODY[..]);
+			}
+		}
+
+		// A valid range is answered with partial content
+		let res = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.method(method.clone())
+			.path(STD_KEY.to_owned())
+			.unsigned_header("range", "bytes=10-19")
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+		assert_eq!(
+			res.headers().get("content-range").unwrap(),
+			"bytes 10-19/62"
+		);
+		assert_eq!(res.headers().get("content-length").unwrap(), "10");
+	}
+}
+
+#[tokio::test]
+async fn test_method_not_allowed() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("method-not-allowed");
+
+	for path in ["", STD_KEY] {
+		let res = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.method(Method::PATCH)
+			.path(path.to_owned())
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+		assert_eq!(
+			res.headers().get("allow").unwrap(),
+			"GET, HEAD, PUT, POST, DELETE, OPTIONS"
+		);
+	}
+}
+
 #[tokio::test]
 async fn test_metadata() {
 	let ctx = common::context();
@@ -315,6 +477,24 @@ async fn test_metadata() {
 		assert_eq!(o.content_encoding.unwrap().as_str(), "cedummy");
 		assert_eq!(o.content_language.unwrap().as_str(), "cldummy");
 		assert_eq!(o.expires.unwrap(), exp);
+
+		// Overrides also apply to HEAD requests
+		let o = ctx
+			.client
+			.head_object()
+			.bucket(&bucket)
+			.key(STD_KEY)
+			.response_content_type("application/x-dummy-test")
+			.response_cache_control("ccdummy")
+			.response_expires(exp)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(o.e_tag.unwrap().as_str(), etag);
+		assert_eq!(o.content_type.unwrap().as_str(), "application/x-dummy-test");
+		assert_eq!(o.cache_control.unwrap().as_str(), "ccdummy");
+		assert_eq!(o.content_disposition.unwrap().as_str(), "cdtest");
+		assert_eq!(o.expires.unwrap(), exp);
 	}
 }
 
@@ -404,3 +584,1647 @@ async fn test_deleteobject() {
 		.await
 		.unwrap();
 }
+
+#[tokio::test]
+async fn test_versioning_state() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("versioningstate");
+
+	let r = ctx
+		.client
+		.get_bucket_versioning()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	assert!(r.status.is_none());
+
+	// Unset: deletions report neither a delete marker nor a version
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+	let r = ctx
+		.client
+		.delete_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await
+		.unwrap();
+	assert!(r.delete_marker.is_none());
+	assert!(r.version_id.is_none());
+
+	// Enabled is refused, as Garage does not keep previous versions of objects
+	let err = ctx
+		.client
+		.put_bucket_versioning()
+		.bucket(&bucket)
+		.versioning_configuration(
+			VersioningConfiguration::builder()
+				.status(BucketVersioningStatus::Enabled)
+				.build(),
+		)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 501);
+
+	let r = ctx
+		.client
+		.get_bucket_versioning()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	assert!(r.status.is_none());
+
+	// Suspended: objects get the null version id and are deleted permanently,
+	// deletions report the null version id but no delete marker
+	ctx.client
+		.put_bucket_versioning()
+		.bucket(&bucket)
+		.versioning_configuration(
+			VersioningConfiguration::builder()
+				.status(BucketVersioningStatus::Suspended)
+				.build(),
+		)
+		.send()
+		.await
+		.unwrap();
+
+	let r = ctx
+		.client
+		.get_bucket_versioning()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(r.status, Some(BucketVersioningStatus::Suspended));
+
+	let r = ctx
+		.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(r.version_id.as_deref(), Some("null"));
+
+	let r = ctx
+		.client
+		.delete_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await
+		.unwrap();
+	assert!(r.delete_marker.is_none());
+	assert_eq!(r.version_id.as_deref(), Some("null"));
+
+	assert!(ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await
+		.is_err());
+}
+
+#[tokio::test]
+async fn test_inline_threshold() {
+	// Larger than the default threshold of 3072 bytes
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		root: "inline_threshold = 4096",
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("inlinethreshold");
+
+	let is_stored_as_block = |body: &[u8]| {
+		let hash = hex::encode(garage_util::data::blake2sum(body));
+		let dir = ctx
+			.garage
+			.path
+			.join("data")
+			.join(&hash[0..2])
+			.join(&hash[2..4]);
+		dir.join(&hash).exists() || dir.join(format!("{}.zst", hash)).exists()
+	};
+
+	for (key, size, expect_block) in [("small", 4000, false), ("large", 4200, true)] {
+		let body = vec![0x5a; size];
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(key)
+			.body(ByteStream::from(body.clone()))
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(is_stored_as_block(&body), expect_block);
+
+		let o = ctx
+			.client
+			.get_object()
+			.bucket(&bucket)
+			.key(key)
+			.send()
+			.await
+			.unwrap();
+		assert_bytes_eq!(o.body, &body[..]);
+	}
+}
+
+#[tokio::test]
+async fn test_data_fsync() {
+	// The other tests run with the default data_fsync = false
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		root: "data_fsync = true",
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("datafsync");
+
+	// Larger than the inline threshold, so that it is written as a data block
+	let body = (0..100_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from(body.clone()))
+		.send()
+		.await
+		.unwrap();
+
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await
+		.unwrap();
+	assert_bytes_eq!(o.body, &body[..]);
+}
+
+#[tokio::test]
+async fn test_putobject_bad_digest() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("putobject-bad-digest");
+
+	{
+		// Content-MD5 that does not match the body
+		let res = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.method(Method::PUT)
+			.path(STD_KEY.to_owned())
+			.signed_header("content-md5", "1B2M2Y8AsgTpgAmY7PhCfg==")
+			.body(BODY.to_vec())
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+		let body = res.into_body().collect().await.unwrap().to_bytes();
+		let body = std::str::from_utf8(&body).unwrap();
+		assert!(body.contains("<Code>BadDigest</Code>"), "{}", body);
+	}
+
+	{
+		// x-amz-content-sha256 that does not match the body
+		let res = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.method(Method::PUT)
+			.path(STD_KEY.to_owned())
+			.body(BODY.to_vec())
+			.body_signature(BodySignature::Fixed(hex::encode(
+				garage_util::data::sha256sum(b"some other body"),
+			)))
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+		let body = res.into_body().collect().await.unwrap().to_bytes();
+		let body = std::str::from_utf8(&body).unwrap();
+		assert!(
+			body.contains("<Code>XAmzContentSHA256Mismatch</Code>"),
+			"{}",
+			body
+		);
+	}
+
+	// The object was not created
+	let r = ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await;
+	assert!(r.is_err());
+}
+
+#[tokio::test]
+async fn test_putobject_metadata_size_limit() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("putobject-metadata-size");
+
+	// User metadata is limited to 2KiB by default, counting both
+	// the keys (without the x-amz-meta- prefix) and the values
+	let put = |key: &str, value: String| {
+		let mut req = ctx.custom_request.builder(bucket.clone());
+		req.method(Method::PUT)
+			.path(key.to_owned())
+			.signed_header("x-amz-meta-a", "b")
+			.signed_header("x-amz-meta-large", value)
+			.body(BODY.to_vec());
+		req
+	};
+
+	// 1 + 1 + 5 + 2041 = 2048 bytes
+	let res = put("just-under", "x".repeat(2041)).send().await.unwrap();
+	assert_eq!(res.status(), StatusCode::OK);
+
+	let o = ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key("just-under")
+		.send()
+		.await
+		.unwrap();
+	let metadata = o.metadata.unwrap();
+	assert_eq!(metadata["a"], "b");
+	assert_eq!(metadata["large"].len(), 2041);
+
+	// 1 + 1 + 5 + 2042 = 2049 bytes
+	let res = put("just-over", "x".repeat(2042)).send().await.unwrap();
+	assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+	let body = res.into_body().collect().await.unwrap().to_bytes();
+	let body = std::str::from_utf8(&body).unwrap();
+	assert!(body.contains("<Code>MetadataTooLarge</Code>"), "{}", body);
+
+	let r = ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key("just-over")
+		.send()
+		.await;
+	assert!(r.is_err());
+}
+
+#[tokio::test]
+async fn test_storage_class() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("storage-class");
+
+	// Objects uploaded without storage class are STANDARD objects,
+	// for which the storage class is not returned by HeadObject
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("standard")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+	let o = ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key("standard")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(o.storage_class, None);
+
+	// Non-default storage classes are recorded and returned
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("ia")
+		.storage_class(StorageClass::StandardIa)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+	let o = ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key("ia")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(o.storage_class, Some(StorageClass::StandardIa));
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("ia")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(o.storage_class, Some(StorageClass::StandardIa));
+
+	let l = ctx
+		.client
+		.list_objects_v2()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	let classes = l
+		.contents
+		.unwrap()
+		.into_iter()
+		.map(|o| (o.key.unwrap(), o.storage_class.unwrap()))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		classes,
+		vec![
+			("ia".to_string(), ObjectStorageClass::StandardIa),
+			("standard".to_string(), ObjectStorageClass::Standard),
+		]
+	);
+
+	// CopyObject does not keep the storage class of the source object,
+	// and changing the storage class allows copying an object onto itself
+	ctx.client
+		.copy_object()
+		.bucket(&bucket)
+		.key("standard")
+		.copy_source(format!("{}/standard", bucket))
+		.storage_class(StorageClass::Glacier)
+		.send()
+		.await
+		.unwrap();
+	ctx.client
+		.copy_object()
+		.bucket(&bucket)
+		.key("ia-copy")
+		.copy_source(format!("{}/ia", bucket))
+		.send()
+		.await
+		.unwrap();
+	for (key, class) in [("standard", Some(StorageClass::Glacier)), ("ia-copy", None)] {
+		let o = ctx
+			.client
+			.head_object()
+			.bucket(&bucket)
+			.key(key)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(o.storage_class, class, "{}", key);
+	}
+
+	// Unknown storage classes are rejected
+	let res = ctx
+		.custom_request
+		.builder(bucket.clone())
+		.method(Method::PUT)
+		.path("invalid".to_owned())
+		.signed_header("x-amz-storage-class", "PLATINUM")
+		.body(BODY.to_vec())
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+	let body = res.into_body().collect().await.unwrap().to_bytes();
+	let body = std::str::from_utf8(&body).unwrap();
+	assert!(
+		body.contains("<Code>InvalidStorageClass</Code>"),
+		"{}",
+		body
+	);
+}
+
+#[tokio::test]
+async fn test_if_match_put_delete() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("if-match-put-delete");
+
+	let put = |if_match: &str, body: &[u8]| {
+		let mut req = ctx.custom_request.builder(bucket.clone());
+		req.method(Method::PUT)
+			.path(STD_KEY.to_owned())
+			.signed_header("if-match", if_match)
+			.body(body.to_vec());
+		req
+	};
+	let delete = |if_match: &str| {
+		let mut req = ctx.custom_request.builder(bucket.clone());
+		req.method(Method::DELETE)
+			.path(STD_KEY.to_owned())
+			.signed_header("if-match", if_match);
+		req
+	};
+
+	// If-Match on an object that does not exist fails, even with a wildcard
+	let res = put("*", BODY).send().await.unwrap();
+	assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+	let etag = ctx
+		.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap()
+		.e_tag
+		.unwrap();
+
+	// Mismatching ETag: object is not overwritten nor deleted
+	let res = put("\"0123456789abcdef0123456789abcdef\"", b"other")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+	let res = delete("\"0123456789abcdef0123456789abcdef\"")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(o.e_tag.as_ref(), Some(&etag));
+
+	// Matching ETag: object is overwritten
+	let res = put(&etag, b"other").send().await.unwrap();
+	assert_eq!(res.status(), StatusCode::OK);
+
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(
+		o.body.collect().await.unwrap().into_bytes(),
+		b"other".as_ref()
+	);
+
+	// Wildcard matches any existing object
+	let res = put("*", BODY).send().await.unwrap();
+	assert_eq!(res.status(), StatusCode::OK);
+	let res = delete("*").send().await.unwrap();
+	assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+	let r = ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await;
+	assert!(r.is_err());
+}
+
+#[tokio::test]
+async fn test_if_none_match_get() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("if-none-match-get");
+
+	let etag = ctx
+		.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap()
+		.e_tag
+		.unwrap();
+	let weak_etag = format!("W/{}", etag);
+
+	let get = |header: &str, value: &str| {
+		let mut req = ctx.custom_request.builder(bucket.clone());
+		req.method(Method::GET)
+			.path(STD_KEY.to_owned())
+			.signed_header(header, value);
+		req
+	};
+
+	for if_none_match in [
+		etag.clone(),
+		weak_etag.clone(),
+		"*".to_string(),
+		format!("\"0123456789abcdef0123456789abcdef\", {}", etag),
+		format!("W/\"0123456789abcdef0123456789abcdef\",{}", weak_etag),
+	] {
+		let res = get("if-none-match", &if_none_match).send().await.unwrap();
+		assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "{}", if_none_match);
+	}
+
+	for if_none_match in [
+		"\"0123456789abcdef0123456789abcdef\"",
+		"W/\"0123456789abcdef0123456789abcdef\", \"abc\"",
+	] {
+		let res = get("if-none-match", if_none_match).send().await.unwrap();
+		assert_eq!(res.status(), StatusCode::OK, "{}", if_none_match);
+	}
+
+	// If-Match uses the strong comparison: weak ETags never match
+	let res = ctx
+		.custom_request
+		.builder(bucket.clone())
+		.method(Method::PUT)
+		.path(STD_KEY.to_owned())
+		.signed_header("if-match", &weak_etag)
+		.body(b"other".to_vec())
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+}
+
+#[tokio::test]
+async fn test_if_modified_since_get() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("if-modified-since-get");
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	let get = |value: &str| {
+		let mut req = ctx.custom_request.builder(bucket.clone());
+		req.method(Method::GET)
+			.path(STD_KEY.to_owned())
+			.signed_header("if-modified-since", value);
+		req
+	};
+
+	let res = ctx
+		.custom_request
+		.builder(bucket.clone())
+		.method(Method::GET)
+		.path(STD_KEY.to_owned())
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(res.status(), StatusCode::OK);
+	let last_modified = res.headers()["last-modified"].to_str().unwrap().to_string();
+	let last_modified_date =
+		chrono::DateTime::parse_from_rfc2822(&last_modified.replace("GMT", "+0000")).unwrap();
+
+	// The Last-Modified date returned by the server is not modified since
+	let res = get(&last_modified).send().await.unwrap();
+	assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+	let fmt_date = |date: chrono::DateTime<chrono::FixedOffset>| {
+		date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+	};
+
+	let later = fmt_date(last_modified_date + chrono::Duration::seconds(1));
+	let res = get(&later).send().await.unwrap();
+	assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+	let earlier = fmt_date(last_modified_date - chrono::Duration::seconds(1));
+	let res = get(&earlier).send().await.unwrap();
+	assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_access_log() {
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		s3_api: r#"
+access_log = true
+access_log_file = "{path}/access.log"
+"#,
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("access-log");
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+	ctx.client
+		.get_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await
+		.unwrap();
+	assert!(ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("does-not-exist")
+		.send()
+		.await
+		.is_err());
+
+	// Entries are written to the file in the background
+	tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+	let log = std::fs::read_to_string(ctx.garage.path.join("access.log")).unwrap();
+	let entries = log
+		.lines()
+		.map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+		.filter(|e| e["bucket"] == bucket.as_str() && e["operation"] == "GetObject")
+		.collect::<Vec<_>>();
+
+	let ok = entries.iter().find(|e| e["key"] == STD_KEY).unwrap();
+	assert_eq!(ok["key_id"], ctx.key.id.as_str());
+	assert_eq!(ok["status"], 200);
+	assert_eq!(ok["bytes_received"], 0);
+	assert_eq!(ok["bytes_sent"], BODY.len());
+	assert!(ok["duration_ms"].is_u64());
+	assert!(ok["time"].is_string());
+
+	let err = entries
+		.iter()
+		.find(|e| e["key"] == "does-not-exist")
+		.unwrap();
+	assert_eq!(err["key_id"], ctx.key.id.as_str());
+	assert_eq!(err["status"], 404);
+}
+
+#[tokio::test]
+async fn test_object_info_cli() {
+	use crate::common::ext::CommandExt;
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("objectinfo");
+
+	let object_info = |key: &str, extra: &[&str]| {
+		let out = ctx
+			.garage
+			.command()
+			.args(["object", "info", "--bucket", &bucket, "--key", key])
+			.args(extra)
+			.expect_success_output("Could not get object info");
+		String::from_utf8(out.stdout).unwrap()
+	};
+
+	let small = ctx
+		.client
+		.put_object()
+		.bucket(&bucket)
+		.key("small")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	let out = object_info("small", &[]);
+	assert!(out.contains("Key: small"));
+	let line = out.lines().find(|l| l.contains("complete")).unwrap();
+	assert!(line.contains("inline"));
+	assert!(line.contains(&BODY.len().to_string()));
+	assert!(line.contains(small.e_tag.unwrap().trim_matches('"')));
+
+	let big_body = vec![b'x'; 10_000];
+	let big = ctx
+		.client
+		.put_object()
+		.bucket(&bucket)
+		.key("big")
+		.body(ByteStream::from(big_body.clone()))
+		.send()
+		.await
+		.unwrap();
+
+	let out = object_info("big", &["--nodes"]);
+	assert!(out.contains("Key: big"));
+	let line = out.lines().find(|l| l.contains("complete")).unwrap();
+	assert!(line.contains("first block"));
+	assert!(line.contains("10000"));
+	assert!(line.contains(big.e_tag.unwrap().trim_matches('"')));
+	let fields = line.split_whitespace().collect::<Vec<_>>();
+	assert_eq!(fields[fields.len() - 2..], ["1", "1"]);
+	assert!(out.contains("Storage nodes for first block of version"));
+}
+
+#[tokio::test]
+async fn test_copy_metadata_directive() {
+	use aws_sdk_s3::types::MetadataDirective;
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("copymetadatadirective");
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("src")
+		.content_type("text/plain")
+		.metadata("a", "1")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	let head = |key: &'static str| ctx.client.head_object().bucket(&bucket).key(key).send();
+
+	// Default directive is COPY: metadata comes from the source object
+	ctx.client
+		.copy_object()
+		.bucket(&bucket)
+		.key("dst-copy")
+		.copy_source(format!("{}/src", bucket))
+		.content_type("application/json")
+		.send()
+		.await
+		.unwrap();
+	let r = head("dst-copy").await.unwrap();
+	assert_eq!(r.content_type.unwrap(), "text/plain");
+	assert_eq!(r.metadata.unwrap().get("a").unwrap(), "1");
+
+	// REPLACE directive: metadata comes from the request
+	ctx.client
+		.copy_object()
+		.bucket(&bucket)
+		.key("dst-replace")
+		.copy_source(format!("{}/src", bucket))
+		.metadata_directive(MetadataDirective::Replace)
+		.content_type("application/json")
+		.metadata("b", "2")
+		.send()
+		.await
+		.unwrap();
+	let r = head("dst-replace").await.unwrap();
+	assert_eq!(r.content_type.unwrap(), "application/json");
+	let metadata = r.metadata.unwrap();
+	assert!(metadata.get("a").is_none());
+	assert_eq!(metadata.get("b").unwrap(), "2");
+
+	// Copying an object onto itself without changing anything is rejected
+	let err = ctx
+		.client
+		.copy_object()
+		.bucket(&bucket)
+		.key("src")
+		.copy_source(format!("{}/src", bucket))
+		.metadata_directive(MetadataDirective::Copy)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 400);
+
+	// Invalid directives are rejected
+	let err = ctx
+		.client
+		.copy_object()
+		.bucket(&bucket)
+		.key("dst-invalid")
+		.copy_source(format!("{}/src", bucket))
+		.metadata_directive(MetadataDirective::from("MERGE"))
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 400);
+
+	// Copying an object onto itself to change its metadata is allowed
+	ctx.client
+		.copy_object()
+		.bucket(&bucket)
+		.key("src")
+		.copy_source(format!("{}/src", bucket))
+		.metadata_directive(MetadataDirective::Replace)
+		.content_type("text/html")
+		.send()
+		.await
+		.unwrap();
+	let r = head("src").await.unwrap();
+	assert_eq!(r.content_type.unwrap(), "text/html");
+	assert!(r.metadata.unwrap_or_default().get("a").is_none());
+
+	let res = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("src")
+		.send()
+		.await
+		.unwrap();
+	assert_bytes_eq!(res.body, BODY);
+}
+
+#[tokio::test]
+async fn test_copy_self_replace_shares_blocks() {
+	use crate::common::ext::CommandExt;
+	use aws_sdk_s3::types::MetadataDirective;
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("copyselfreplace");
+
+	// Larger than the default inline threshold of 3072 bytes,
+	// so this object is stored as a single data block
+	let body = vec![0x42; 10_000];
+	let hash = hex::encode(garage_util::data::blake2sum(&body));
+	let put = ctx
+		.client
+		.put_object()
+		.bucket(&bucket)
+		.key("obj")
+		.content_type("text/plain")
+		.body(body.clone().into())
+		.send()
+		.await
+		.unwrap();
+
+	let copy = ctx
+		.client
+		.copy_object()
+		.bucket(&bucket)
+		.key("obj")
+		.copy_source(format!("{}/obj", bucket))
+		.metadata_directive(MetadataDirective::Replace)
+		.content_type("application/octet-stream")
+		.metadata("updated", "yes")
+		.send()
+		.await
+		.unwrap();
+	assert_ne!(copy.version_id, put.version_id);
+	assert_eq!(copy.copy_object_result.unwrap().e_tag, put.e_tag);
+
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("obj")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(o.version_id, copy.version_id);
+	assert_eq!(o.content_type.as_deref(), Some("application/octet-stream"));
+	assert_eq!(o.metadata.as_ref().unwrap().get("updated").unwrap(), "yes");
+	assert_bytes_eq!(o.body, &body[..]);
+
+	// The previous version is untouched
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key("obj")
+		.version_id(put.version_id.unwrap())
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(o.content_type.as_deref(), Some("text/plain"));
+
+	// Both versions reference the same data block, which was not rewritten
+	let info = ctx
+		.garage
+		.command()
+		.args(["block", "info", &hash])
+		.expect_success_output("Could not get block info");
+	let info = String::from_utf8(info.stdout).unwrap();
+	assert!(info.contains("Refcount: 2"), "{}", info);
+}
+
+#[tokio::test]
+async fn test_empty_object() {
+	use crate::common::ext::CommandExt;
+
+	const EMPTY_ETAG: &str = "\"d41d8cd98f00b204e9800998ecf8427e\"";
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("emptyobject");
+
+	// Zero-byte PUT, with a classic and with a streaming signature
+	for (key, signature) in [
+		("empty", BodySignature::Classic),
+		("empty-streaming", BodySignature::Streaming(16)),
+	] {
+		let res = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.method(Method::PUT)
+			.path(key.to_owned())
+			.body(vec![])
+			.body_signature(signature)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::OK);
+		assert_eq!(res.headers().get("etag").unwrap(), EMPTY_ETAG);
+
+		let head = ctx
+			.client
+			.head_object()
+			.bucket(&bucket)
+			.key(key)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(head.content_length.unwrap(), 0);
+		assert_eq!(head.e_tag.unwrap(), EMPTY_ETAG);
+
+		let res = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.method(Method::GET)
+			.path(key.to_owned())
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::OK);
+		assert_eq!(res.headers().get("content-length").unwrap(), "0");
+		assert_eq!(res.headers().get("etag").unwrap(), EMPTY_ETAG);
+		let body = res.into_body().collect().await.unwrap().to_bytes();
+		assert!(body.is_empty());
+	}
+
+	let list = ctx
+		.client
+		.list_objects_v2()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	let contents = list.contents.unwrap();
+	assert_eq!(contents.len(), 2);
+	assert!(contents.iter().all(|o| o.size == Some(0)));
+
+	// Zero-byte objects count towards the object quota
+	ctx.garage
+		.command()
+		.args(["bucket", "set-quotas", &bucket, "--max-objects", "2"])
+		.quiet()
+		.expect_success_status("Could not set bucket quotas");
+	tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+	let res = ctx
+		.client
+		.put_object()
+		.bucket(&bucket)
+		.key("one-too-many")
+		.send()
+		.await;
+	assert_eq!(
+		res.unwrap_err().raw_response().unwrap().status().as_u16(),
+		403
+	);
+}
+
+#[tokio::test]
+async fn test_strict_quota_concurrent_puts() {
+	const MAX_OBJECTS: usize = 5;
+	const CONCURRENT_PUTS: usize = 20;
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("strictquota");
+
+	ctx.garage
+		.command()
+		.args([
+			"bucket",
+			"set-quotas",
+			&bucket,
+			"--max-objects",
+			&MAX_OBJECTS.to_string(),
+			"--strict",
+		])
+		.quiet()
+		.expect_success_status("Could not set bucket quotas");
+	tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+	let puts = (0..CONCURRENT_PUTS).map(|i| {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(format!("obj{}", i))
+			.body(ByteStream::from(vec![0x42; 100]))
+			.send()
+	});
+	let results = futures::future::join_all(puts).await;
+
+	let accepted = results.iter().filter(|r| r.is_ok()).count();
+	for err in results.iter().filter_map(|r| r.as_ref().err()) {
+		assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
+	}
+
+	// This test runs with a single node, so the quota can be exceeded
+	// by at most one object
+	let list = ctx
+		.client
+		.list_objects_v2()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	let stored = list.contents.unwrap_or_default().len();
+	assert_eq!(stored, accepted);
+	assert!(stored >= 1);
+	assert!(stored <= MAX_OBJECTS + 1, "{} objects stored", stored);
+}
+
+#[tokio::test]
+async fn test_object_too_large() {
+	const MAX_OBJECT_SIZE: usize = 8 * 1024 * 1024;
+
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		s3_api: r#"max_object_size = "8MiB""#,
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("objecttoolarge");
+
+	let res = ctx
+		.custom_request
+		.builder(bucket.clone())
+		.method(Method::PUT)
+		.path("too-large".to_owned())
+		.body(vec![0x42; MAX_OBJECT_SIZE + 1])
+		.body_signature(BodySignature::Unsigned)
+		.send()
+		.await;
+	// The server might close the connection before the whole body is sent,
+	// but if we get a response it must be an EntityTooLarge error
+	if let Ok(res) = res {
+		assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+		let body = res.into_body().collect().await.unwrap().to_bytes();
+		let body = std::str::from_utf8(&body).unwrap();
+		assert!(body.contains("<Code>EntityTooLarge</Code>"), "{}", body);
+	}
+
+	// The interrupted upload must not leave a visible or in-progress version
+	tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+	assert!(ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key("too-large")
+		.send()
+		.await
+		.is_err());
+
+	let out = ctx
+		.garage
+		.command()
+		.args(["object", "info", "--bucket", &bucket, "--key", "too-large"])
+		.output()
+		.unwrap();
+	if out.status.success() {
+		let out = String::from_utf8(out.stdout).unwrap();
+		assert!(!out.contains("uploading"), "{}", out);
+		assert!(!out.contains("complete"), "{}", out);
+	}
+
+	// An object of exactly the maximum size is accepted
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("max-size")
+		.body(ByteStream::from(vec![0x42; MAX_OBJECT_SIZE]))
+		.send()
+		.await
+		.unwrap();
+}
+
+#[tokio::test]
+async fn test_read_your_writes() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("readyourwrites");
+
+	// The test cluster has a single node, so all reads see all writes anyway:
+	// this only checks that the stronger read path returns the latest version
+	// and that invalid values of the header are rejected.
+	for body in [&b"first version"[..], &b"second version"[..]] {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(STD_KEY)
+			.body(ByteStream::from(body.to_vec()))
+			.send()
+			.await
+			.unwrap();
+
+		let res = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.method(Method::GET)
+			.path(STD_KEY.to_owned())
+			.signed_header("x-garage-consistency", "read-your-writes")
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::OK);
+		let res_body = res.into_body().collect().await.unwrap().to_bytes();
+		assert_eq!(&res_body[..], body);
+	}
+
+	let res = ctx
+		.custom_request
+		.builder(bucket.clone())
+		.method(Method::HEAD)
+		.path(STD_KEY.to_owned())
+		.signed_header("x-garage-consistency", "read-your-writes")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(res.status(), StatusCode::OK);
+
+	let res = ctx
+		.custom_request
+		.builder(bucket.clone())
+		.method(Method::GET)
+		.path(STD_KEY.to_owned())
+		.signed_header("x-garage-consistency", "eventually")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_request_timeout() {
+	use aws_sdk_s3::presigning::PresigningConfig;
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		s3_api: r#"request_timeout = "5s""#,
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("requesttimeout");
+
+	let psc = PresigningConfig::expires_in(std::time::Duration::from_secs(3600)).unwrap();
+	let req = ctx
+		.client
+		.put_object()
+		.bucket(&bucket)
+		.key("stalled")
+		.presigned(psc)
+		.await
+		.unwrap();
+	let uri: http::Uri = req.uri().parse().unwrap();
+
+	// Announce a 3 MiB body but only send the first 1.5 MiB, so that the first
+	// data block is written before the upload stalls
+	let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", ctx.garage.s3_port))
+		.await
+		.unwrap();
+	let head = format!(
+		"PUT {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n\r\n",
+		uri.path_and_query().unwrap(),
+		uri.authority().unwrap(),
+		3 * 1024 * 1024
+	);
+	stream.write_all(head.as_bytes()).await.unwrap();
+	stream.write_all(&vec![0x42; 1536 * 1024]).await.unwrap();
+
+	let mut response = vec![];
+	let read = tokio::time::timeout(std::time::Duration::from_secs(30), async {
+		let mut buf = [0u8; 4096];
+		while !String::from_utf8_lossy(&response).contains("</Error>") {
+			match stream.read(&mut buf).await.unwrap() {
+				0 => break,
+				n => response.extend_from_slice(&buf[..n]),
+			}
+		}
+	})
+	.await;
+	assert!(read.is_ok(), "request was not interrupted");
+	let response = String::from_utf8_lossy(&response);
+	assert!(
+		response.starts_with("HTTP/1.1 408"),
+		"unexpected response: {}",
+		response
+	);
+	assert!(
+		response.contains("<Code>RequestTimeout</Code>"),
+		"{}",
+		response
+	);
+
+	// The interrupted upload must not leave a visible or in-progress version
+	tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+	assert!(ctx
+		.client
+		.head_object()
+		.bucket(&bucket)
+		.key("stalled")
+		.send()
+		.await
+		.is_err());
+
+	let out = ctx
+		.garage
+		.command()
+		.args(["object", "info", "--bucket", &bucket, "--key", "stalled"])
+		.output()
+		.unwrap();
+	if out.status.success() {
+		let out = String::from_utf8(out.stdout).unwrap();
+		assert!(!out.contains("uploading"), "{}", out);
+		assert!(!out.contains("complete"), "{}", out);
+	}
+}
+
+#[tokio::test]
+async fn test_expect_continue() {
+	use crate::common::ext::CommandExt;
+	use aws_sdk_s3::presigning::PresigningConfig;
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	async fn read_until(stream: &mut tokio::net::TcpStream, pattern: &str) -> Option<String> {
+		let fut = async {
+			let mut response = vec![];
+			let mut buf = [0u8; 4096];
+			while !String::from_utf8_lossy(&response).contains(pattern) {
+				match stream.read(&mut buf).await.unwrap() {
+					0 => break,
+					n => response.extend_from_slice(&buf[..n]),
+				}
+			}
+			String::from_utf8_lossy(&response).into_owned()
+		};
+		tokio::time::timeout(std::time::Duration::from_secs(10), fut)
+			.await
+			.ok()
+	}
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("expectcontinue");
+
+	ctx.garage
+		.command()
+		.args(["bucket", "set-quotas", &bucket, "--max-size", "1MiB"])
+		.quiet()
+		.expect_success_status("Could not set bucket quotas");
+	tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+	// Send the headers of a PutObject request with Expect: 100-continue,
+	// and return a connection to send the body on
+	let (ctx, bucket) = (&ctx, &bucket);
+	let put_headers = |key: &'static str, size: usize| async move {
+		let psc = PresigningConfig::expires_in(std::time::Duration::from_secs(3600)).unwrap();
+		let req = ctx
+			.client
+			.put_object()
+			.bucket(bucket)
+			.key(key)
+			.presigned(psc)
+			.await
+			.unwrap();
+		let uri: http::Uri = req.uri().parse().unwrap();
+
+		let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", ctx.garage.s3_port))
+			.await
+			.unwrap();
+		let head = format!(
+			"PUT {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nExpect: 100-continue\r\n\r\n",
+			uri.path_and_query().unwrap(),
+			uri.authority().unwrap(),
+			size
+		);
+		stream.write_all(head.as_bytes()).await.unwrap();
+		stream
+	};
+
+	// An upload that exceeds the bucket quota is refused without the body being sent
+	let mut stream = put_headers("too-large", 10 * 1024 * 1024).await;
+	let response = read_until(&mut stream, "</Error>")
+		.await
+		.expect("no early response to a denied upload");
+	assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+
+	// An upload that passes the checks receives a 100 Continue response
+	let mut stream = put_headers("small", BODY.len()).await;
+	let response = read_until(&mut stream, "\r\n\r\n")
+		.await
+		.expect("no 100 Continue response");
+	assert!(response.starts_with("HTTP/1.1 100"), "{}", response);
+	stream.write_all(BODY).await.unwrap();
+	let response = read_until(&mut stream, "\r\n\r\n")
+		.await
+		.expect("no response after sending the body");
+	assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(bucket)
+		.key("small")
+		.send()
+		.await
+		.unwrap();
+	assert_bytes_eq!(o.body, &BODY[..]);
+}
+
+#[tokio::test]
+async fn test_getobject_missing_block() {
+	// The default block size is 1MiB, this object is stored in three blocks
+	// whose content is distinct so that they are not deduplicated.
+	const MB: usize = 1024 * 1024;
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("getobjectmissingblock");
+
+	let body = (0..3 * MB)
+		.map(|i| (i / MB) as u8 + b'a')
+		.collect::<Vec<_>>();
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from(body.clone()))
+		.send()
+		.await
+		.unwrap();
+
+	// Remove the data file of the second block
+	let hash = garage_util::data::blake2sum(&body[MB..2 * MB]);
+	let block_dir = ctx
+		.garage
+		.path
+		.join("data")
+		.join(hex::encode(&hash.as_slice()[0..1]))
+		.join(hex::encode(&hash.as_slice()[1..2]));
+	let mut removed = false;
+	for ext in ["", ".zst"] {
+		let path = block_dir.join(format!("{}{}", hex::encode(hash.as_slice()), ext));
+		removed |= std::fs::remove_file(&path).is_ok();
+	}
+	assert!(removed);
+
+	// Headers announce the full object, but the body cannot be completed:
+	// the client must see an error instead of a silently truncated body.
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(o.content_length, Some(3 * MB as i64));
+	assert!(o.body.collect().await.is_err());
+
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.range(format!("bytes={}-{}", MB / 2, 2 * MB + 10))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(o.content_length, Some((3 * MB / 2 + 11) as i64));
+	assert!(o.body.collect().await.is_err());
+
+	// Reading blocks that are still present works fine
+	let o = ctx
+		.client
+		.get_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.range(format!("bytes={}-{}", 2 * MB, 2 * MB + 99))
+		.send()
+		.await
+		.unwrap();
+	assert_bytes_eq!(o.body, &body[2 * MB..2 * MB + 100]);
+}
+
+#[tokio::test]
+async fn test_getobject_zstd() {
+	// Blocks of the test instance are stored compressed with the default
+	// compression level. The second block of this object is incompressible
+	// and is therefore stored uncompressed.
+	const MB: usize = 1024 * 1024;
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		s3_api: "zstd_content_encoding = true",
+		..Default::default()
+	});
+	let bucket = ctx.create_bucket("getobjectzstd");
+
+	let mut body = BODY.iter().cycle().take(MB).copied().collect::<Vec<_>>();
+	let mut h = garage_util::data::sha256sum(b"seed");
+	while body.len() < 2 * MB {
+		h = garage_util::data::sha256sum(h.as_slice());
+		body.extend_from_slice(h.as_slice());
+	}
+	body.extend(BODY.iter().cycle().skip(7).take(MB + 1000));
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key(STD_KEY)
+		.body(ByteStream::from(body.clone()))
+		.send()
+		.await
+		.unwrap();
+
+	let get = |path: &str, accept_encoding: Option<&str>, range: Option<&str>| {
+		let mut req = ctx.custom_request.builder(bucket.clone());
+		req.method(Method::GET).path(path.to_owned());
+		if let Some(ae) = accept_encoding {
+			req.unsigned_header("accept-encoding", ae);
+		}
+		if let Some(r) = range {
+			req.unsigned_header("range", r);
+		}
+		async move { req.send().await.unwrap() }
+	};
+
+	{
+		// Client supports zstd: compressed blocks are sent as they are
+		let res = get(STD_KEY, Some("gzip, zstd"), None).await;
+		assert_eq!(res.status(), StatusCode::OK);
+		assert_eq!(res.headers().get("content-encoding").unwrap(), "zstd");
+		assert_eq!(res.headers().get("vary").unwrap(), "accept-encoding");
+		assert!(res.headers().get("content-length").is_none());
+		let etag = res.headers().get("etag").unwrap().to_str().unwrap();
+		assert!(etag.starts_with("W/\""), "{}", etag);
+		let data = res.into_body().collect().await.unwrap().to_bytes();
+		assert!(data.len() < body.len());
+		assert!(zstd::stream::decode_all(&data[..]).unwrap() == body);
+	}
+
+	{
+		// Client does not support zstd, or refuses it: data is decompressed
+		for ae in [None, Some("gzip"), Some("zstd;q=0")] {
+			let res = get(STD_KEY, ae, None).await;
+			assert_eq!(res.status(), StatusCode::OK);
+			assert!(res.headers().get("content-encoding").is_none());
+			assert_eq!(res.headers().get("vary").unwrap(), "accept-encoding");
+			let etag = res.headers().get("etag").unwrap().to_str().unwrap();
+			assert!(!etag.starts_with("W/"), "{}", etag);
+			assert_eq!(
+				res.headers().get("content-length").unwrap(),
+				&body.len().to_string()
+			);
+			let data = res.into_body().collect().await.unwrap().to_bytes();
+			assert!(data[..] == body[..]);
+		}
+	}
+
+	{
+		// Range requests are always answered with uncompressed data
+		let res = get(STD_KEY, Some("zstd"), Some("bytes=100-199")).await;
+		assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+		assert!(res.headers().get("content-encoding").is_none());
+		let data = res.into_body().collect().await.unwrap().to_bytes();
+		assert_eq!(&data[..], &body[100..200]);
+	}
+
+	{
+		// Objects stored with their own Content-Encoding are not compressed again
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key("encoded")
+			.content_encoding("gzip")
+			.body(ByteStream::from(body.clone()))
+			.send()
+			.await
+			.unwrap();
+
+		let res = get("encoded", Some("zstd"), None).await;
+		assert_eq!(res.status(), StatusCode::OK);
+		assert_eq!(res.headers().get("content-encoding").unwrap(), "gzip");
+		let data = res.into_body().collect().await.unwrap().to_bytes();
+		assert!(data[..] == body[..]);
+	}
+	{
+		// Compressed data is only sent if enabled in the configuration
+		let ctx = common::context();
+		let bucket = ctx.create_bucket("getobjectzstd-disabled");
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(STD_KEY)
+			.body(ByteStream::from(body.clone()))
+			.send()
+			.await
+			.unwrap();
+
+		let res = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.method(Method::GET)
+			.path(STD_KEY.to_owned())
+			.unsigned_header("accept-encoding", "zstd")
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::OK);
+		assert!(res.headers().get("content-encoding").is_none());
+		assert!(res.headers().get("vary").is_none());
+		let data = res.into_body().collect().await.unwrap().to_bytes();
+		assert!(data[..] == body[..]);
+	}
+}
+
+#[tokio::test]
+async fn test_content_encoding_round_trip() {
+	// The Content-Encoding of an object is chosen by the client and is
+	// independent of the compression of blocks in storage: it must be
+	// returned as is, whether blocks are compressed or not.
+	const MB: usize = 1024 * 1024;
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("content-encoding");
+
+	// Compressible data spanning several blocks, and a small inline object
+	let body = BODY
+		.iter()
+		.cycle()
+		.take(2 * MB + 1000)
+		.copied()
+		.collect::<Vec<_>>();
+	for (key, data) in [("gzip-large", &body[..]), ("gzip-small", &BODY[..])] {
+		ctx.client
+			.put_object()
+			.bucket(&bucket)
+			.key(key)
+			.content_encoding("gzip")
+			.body(ByteStream::from(data.to_vec()))
+			.send()
+			.await
+			.unwrap();
+
+		let h = ctx
+			.client
+			.head_object()
+			.bucket(&bucket)
+			.key(key)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(h.content_encoding.as_deref(), Some("gzip"));
+
+		for (accept_encoding, range) in [
+			(None, None),
+			(Some("zstd"), None),
+			(Some("gzip, zstd"), Some("bytes=10-49")),
+		] {
+			let mut req = ctx.custom_request.builder(bucket.clone());
+			req.method(Method::GET).path(key.to_owned());
+			if let Some(ae) = accept_encoding {
+				req.unsigned_header("accept-encoding", ae);
+			}
+			if let Some(r) = range {
+				req.unsigned_header("range", r);
+			}
+			let res = req.send().await.unwrap();
+			assert_eq!(res.headers().get("content-encoding").unwrap(), "gzip");
+			let res_body = res.into_body().collect().await.unwrap().to_bytes();
+			match range {
+				None => assert!(res_body[..] == data[..]),
+				Some(_) => assert_eq!(&res_body[..], &data[10..50]),
+			}
+		}
+	}
+
+	// aws-chunked only describes how the request body is framed
+	// and is not stored with the object
+	{
+		let mut req = ctx.custom_request.builder(bucket.clone());
+		req.method(Method::PUT)
+			.path("chunked".to_owned())
+			.body_signature(BodySignature::Streaming(16))
+			.body(BODY.to_vec());
+		assert_eq!(req.send().await.unwrap().status(), StatusCode::OK);
+
+		let mut req = ctx.custom_request.builder(bucket.clone());
+		req.method(Method::PUT)
+			.path("chunked-gzip".to_owned())
+			.signed_header("content-encoding", "aws-chunked, gzip")
+			.body(BODY.to_vec());
+		assert_eq!(req.send().await.unwrap().status(), StatusCode::OK);
+
+		for (key, expected) in [("chunked", None), ("chunked-gzip", Some("gzip"))] {
+			let o = ctx
+				.client
+				.get_object()
+				.bucket(&bucket)
+				.key(key)
+				.send()
+				.await
+				.unwrap();
+			assert_eq!(o.content_encoding.as_deref(), expected);
+			assert_bytes_eq!(o.body, BODY);
+		}
+	}
+}
@@ -52,7 +52,7 @@ async fn test_presigned_url() {
 			.get_object()
 			.bucket(&bucket)
 			.key(STD_KEY)
-			.presigned(psc)
+			.presigned(psc.clone())
 			.await
 			.unwrap();
 
@@ -69,4 +69,50 @@ async fn test_presigned_url() {
 		let body2 = BodyExt::collect(res.into_body()).await.unwrap().to_bytes();
 		assert_eq!(body, body2);
 	}
+
+	{
+		// GetObject with response header overrides, as used by
+		// presigned download links that force a filename
+		let req = ctx
+			.client
+			.get_object()
+			.bucket(&bucket)
+			.key(STD_KEY)
+			.response_cache_control("no-cache")
+			.response_content_disposition("attachment; filename=\"hello.txt\"")
+			.response_content_encoding("identity")
+			.response_content_language("en")
+			.response_content_type("text/plain")
+			.response_expires(aws_sdk_s3::primitives::DateTime::from_secs(1_000_000_000))
+			.presigned(psc)
+			.await
+			.unwrap();
+
+		let client = ctx.custom_request.client();
+		let req = Request::builder()
+			.method("GET")
+			.uri(req.uri())
+			.body(Full::new(Bytes::new()))
+			.unwrap();
+		let res = client.request(req).await.unwrap();
+		assert_eq!(res.status(), 200);
+
+		let headers = res.headers();
+		assert_eq!(headers.get("etag").unwrap(), etag);
+		assert_eq!(headers.get("cache-control").unwrap(), "no-cache");
+		assert_eq!(
+			headers.get("content-disposition").unwrap(),
+			"attachment; filename=\"hello.txt\""
+		);
+		assert_eq!(headers.get("content-encoding").unwrap(), "identity");
+		assert_eq!(headers.get("content-language").unwrap(), "en");
+		assert_eq!(headers.get("content-type").unwrap(), "text/plain");
+		assert_eq!(
+			headers.get("expires").unwrap(),
+			"Sun, 09 Sep 2001 01:46:40 GMT"
+		);
+
+		let body2 = BodyExt::collect(res.into_body()).await.unwrap().to_bytes();
+		assert_eq!(body, body2);
+	}
 }
@@ -29,3 +29,46 @@ async fn test_simple() {
 
 	assert_bytes_eq!(res.body, b"Hello world!");
 }
+
+#[tokio::test]
+async fn test_wrong_region() {
+	use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+	use aws_sdk_s3::error::ProvideErrorMetadata;
+
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("test-wrong-region");
+
+	let config = aws_sdk_s3::Config::builder()
+		.endpoint_url(format!("http://127.0.0.1:{}", ctx.garage.s3_port))
+		.region(Region::from_static("us-east-1"))
+		.credentials_provider(Credentials::new(
+			&ctx.key.id,
+			&ctx.key.secret,
+			None,
+			None,
+			"garage-integ-test",
+		))
+		.behavior_version(BehaviorVersion::v2023_11_09())
+		.build();
+	let client = aws_sdk_s3::Client::from_conf(config);
+
+	let err = client
+		.head_bucket()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 400);
+
+	// HeadBucket responses have no body, use a request that returns one
+	let err = client
+		.list_objects_v2()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.code(), Some("AuthorizationHeaderMalformed"));
+	let msg = err.message().unwrap();
+	assert!(msg.contains("us-east-1"), "{}", msg);
+	assert!(msg.contains("garage-integ-test"), "{}", msg);
+}
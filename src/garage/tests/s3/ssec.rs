@@ -7,7 +7,7 @@ const SSEC_KEY_MD5: &str = "jMGbs3GyZkYjJUP6q5jA7g==";
 const SSEC_KEY2: &str = "XkYVk4Z3vVDO2yJaUqCAEZX6lL10voMxtV06d8my/eU=";
 const SSEC_KEY2_MD5: &str = "kedo2ab8J1MCjHwJuLTJHw==";
 
-const SZ_2MB: usize = 2 * 1024 * 1024;
+const SZ_5MB: usize = 5 * 1024 * 1024;
 
 #[tokio::test]
 async fn test_ssec_object() {
@@ -174,9 +174,9 @@ async fn test_multipart_upload() {
 	let ctx = common::context();
 	let bucket = ctx.create_bucket("test-ssec-mpu");
 
-	let u1 = vec![0x11; SZ_2MB];
-	let u2 = vec![0x22; SZ_2MB];
-	let u3 = vec![0x33; SZ_2MB];
+	let u1 = vec![0x11; SZ_5MB];
+	let u2 = vec![0x22; SZ_5MB];
+	let u3 = vec![0x33; SZ_5MB];
 	let all = [&u1[..], &u2[..], &u3[..]].concat();
 
 	// Test simple encrypted mpu
@@ -301,7 +301,7 @@ async fn test_multipart_upload() {
 			.upload_id(uid)
 			.part_number(2)
 			.copy_source(format!("{}/a", bucket))
-			.copy_source_range("bytes=500-550000")
+			.copy_source_range("bytes=500-5500000")
 			.copy_source_sse_customer_algorithm("AES256")
 			.copy_source_sse_customer_key(SSEC_KEY)
 			.copy_source_sse_customer_key_md5(SSEC_KEY_MD5)
@@ -384,7 +384,7 @@ async fn test_multipart_upload() {
 			.unwrap();
 
 		// (check) Get object
-		let expected = [&u3[..], &all[500..550001], &u2[..], &u1[1500..20501]].concat();
+		let expected = [&u3[..], &all[500..5500001], &u2[..], &u1[1500..20501]].concat();
 		test_read_encrypted(
 			&ctx,
 			&bucket,
@@ -432,10 +432,8 @@ async fn test_read_encrypted(
 		.sse_customer_key_md5(wrong_enc_key_md5)
 		.send()
 		.await;
-	assert!(
-		o.is_err(),
-		"encrypted file could be read with incorrect encryption key"
-	);
+	let err = o.expect_err("encrypted file could be read with incorrect encryption key");
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
 
 	// Test read encrypted with correct key
 	let o = ctx
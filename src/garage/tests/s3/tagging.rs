@@ -0,0 +1,204 @@
+use crate::common;
+use crate::common::ext::CommandExt;
+use aws_sdk_s3::types::{Tag, Tagging};
+
+fn tagging(tags: &[(&str, &str)]) -> Tagging {
+	Tagging::builder()
+		.set_tag_set(Some(
+			tags.iter()
+				.map(|(k, v)| Tag::builder().key(*k).value(*v).build().unwrap())
+				.collect(),
+		))
+		.build()
+		.unwrap()
+}
+
+#[tokio::test]
+async fn test_bucket_tagging() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("bucket-tagging");
+
+	// No tags initially
+	let err = ctx
+		.client
+		.get_bucket_tagging()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 404);
+
+	// Set tags and read them back
+	ctx.client
+		.put_bucket_tagging()
+		.bucket(&bucket)
+		.tagging(tagging(&[("project", "garage"), ("team", "infra")]))
+		.send()
+		.await
+		.unwrap();
+
+	let r = ctx
+		.client
+		.get_bucket_tagging()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	let mut tags = r
+		.tag_set()
+		.iter()
+		.map(|t| (t.key().to_string(), t.value().to_string()))
+		.collect::<Vec<_>>();
+	tags.sort();
+	assert_eq!(
+		tags,
+		vec![
+			("project".to_string(), "garage".to_string()),
+			("team".to_string(), "infra".to_string())
+		]
+	);
+
+	// Tags are shown by the CLI
+	let info = ctx
+		.garage
+		.command()
+		.args(["bucket", "info", &bucket])
+		.expect_success_output("Could not get bucket info");
+	let info = String::from_utf8(info.stdout).unwrap();
+	assert!(info.contains("project = garage"));
+
+	// PutBucketTagging replaces the whole tag set
+	ctx.client
+		.put_bucket_tagging()
+		.bucket(&bucket)
+		.tagging(tagging(&[("project", "other")]))
+		.send()
+		.await
+		.unwrap();
+
+	let r = ctx
+		.client
+		.get_bucket_tagging()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(r.tag_set().len(), 1);
+	assert_eq!(r.tag_set()[0].key(), "project");
+	assert_eq!(r.tag_set()[0].value(), "other");
+
+	// Delete tags
+	ctx.client
+		.delete_bucket_tagging()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+
+	let err = ctx
+		.client
+		.get_bucket_tagging()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_bucket_tagging_invalid() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("bucket-tagging-invalid");
+
+	let keys = (0..51).map(|i| format!("key{}", i)).collect::<Vec<_>>();
+	let too_many = keys.iter().map(|k| (k.as_str(), "v")).collect::<Vec<_>>();
+
+	// 50 tags are accepted
+	ctx.client
+		.put_bucket_tagging()
+		.bucket(&bucket)
+		.tagging(tagging(&too_many[..50]))
+		.send()
+		.await
+		.unwrap();
+
+	// 51 tags are rejected, and the previous tags are kept
+	let err = ctx
+		.client
+		.put_bucket_tagging()
+		.bucket(&bucket)
+		.tagging(tagging(&too_many))
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 400);
+
+	let r = ctx
+		.client
+		.get_bucket_tagging()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(r.tag_set().len(), 50);
+
+	// Duplicate keys, reserved prefixes and overlong keys are rejected
+	let long_key = "k".repeat(129);
+	for tags in [
+		vec![("dup", "a"), ("dup", "b")],
+		vec![("aws:reserved", "v")],
+		vec![(long_key.as_str(), "v")],
+	] {
+		let err = ctx
+			.client
+			.put_bucket_tagging()
+			.bucket(&bucket)
+			.tagging(tagging(&tags))
+			.send()
+			.await
+			.unwrap_err();
+		assert_eq!(err.raw_response().unwrap().status().as_u16(), 400);
+	}
+}
+
+#[tokio::test]
+async fn test_bucket_tagging_requires_owner() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("bucket-tagging-owner");
+
+	let key2 = ctx.garage.key(Some("bucket-tagging-writer"));
+	ctx.garage
+		.command()
+		.args([
+			"bucket", "allow", "--read", "--write", &bucket, "--key", &key2.id,
+		])
+		.quiet()
+		.expect_success_output("Could not allow key on bucket");
+	let client2 = common::client::build_client(&key2);
+
+	let err = client2
+		.put_bucket_tagging()
+		.bucket(&bucket)
+		.tagging(tagging(&[("project", "garage")]))
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
+
+	let err = client2
+		.delete_bucket_tagging()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 403);
+
+	// Reading the tags only requires read access
+	let err = client2
+		.get_bucket_tagging()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 404);
+}
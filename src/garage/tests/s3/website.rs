@@ -4,10 +4,11 @@ use crate::json_body;
 
 use assert_json_diff::assert_json_eq;
 use aws_sdk_s3::{
+	error::ProvideErrorMetadata,
 	primitives::ByteStream,
 	types::{CorsConfiguration, CorsRule, ErrorDocument, IndexDocument, WebsiteConfiguration},
 };
-use http::{Request, StatusCode};
+use http::{Method, Request, StatusCode};
 use http_body_util::BodyExt;
 use http_body_util::Full as FullBody;
 use hyper::body::Bytes;
@@ -339,8 +340,6 @@ async fn test_website_s3_api() {
 		);
 	}
 
-	//@TODO test CORS on the S3 endpoint. We need to handle auth manually to check it.
-
 	// Delete cors
 	ctx.client
 		.delete_bucket_cors()
@@ -350,15 +349,15 @@ async fn test_website_s3_api() {
 		.unwrap();
 
 	// Check CORS are deleted from the API
-	// @FIXME check what is the expected behavior when GetBucketCors is called on a bucket without
-	// any CORS.
-	assert!(ctx
+	let err = ctx
 		.client
 		.get_bucket_cors()
 		.bucket(&bucket)
 		.send()
 		.await
-		.is_err());
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 404);
+	assert_eq!(err.code(), Some("NoSuchCORSConfiguration"));
 
 	// Test CORS are not sent anymore on a previously allowed request
 	{
@@ -406,6 +405,232 @@ async fn test_website_s3_api() {
 	}
 }
 
+#[tokio::test]
+async fn test_cors_s3_api() {
+	const BCKT_NAME: &str = "my-cors-bucket";
+	let ctx = common::context();
+	let bucket = ctx.create_bucket(BCKT_NAME);
+
+	ctx.client
+		.put_object()
+		.bucket(&bucket)
+		.key("index.html")
+		.body(ByteStream::from_static(BODY))
+		.send()
+		.await
+		.unwrap();
+
+	let cors = CorsConfiguration::builder()
+		.cors_rules(
+			CorsRule::builder()
+				.allowed_headers("content-type")
+				.allowed_methods("GET")
+				.allowed_methods("PUT")
+				.allowed_origins("https://example.com")
+				.build()
+				.unwrap(),
+		)
+		.build()
+		.unwrap();
+
+	ctx.client
+		.put_bucket_cors()
+		.bucket(&bucket)
+		.cors_configuration(cors)
+		.send()
+		.await
+		.unwrap();
+
+	let client = Client::builder(TokioExecutor::new()).build_http();
+	let preflight = |origin: &str, method: &str, headers: &str| {
+		Request::builder()
+			.method("OPTIONS")
+			.uri(format!(
+				"http://127.0.0.1:{}/{}/index.html",
+				ctx.garage.s3_port, BCKT_NAME
+			))
+			.header("Origin", origin)
+			.header("Access-Control-Request-Method", method)
+			.header("Access-Control-Request-Headers", headers)
+			.body(Body::new(Bytes::new()))
+			.unwrap()
+	};
+
+	// Preflight request with a matching origin, method and headers
+	{
+		let resp = client
+			.request(preflight("https://example.com", "PUT", "content-type"))
+			.await
+			.unwrap();
+
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert_eq!(
+			resp.headers().get("access-control-allow-origin").unwrap(),
+			"https://example.com"
+		);
+		assert_eq!(
+			resp.headers().get("access-control-allow-methods").unwrap(),
+			"GET, PUT"
+		);
+		assert_eq!(
+			resp.headers().get("access-control-allow-headers").unwrap(),
+			"content-type"
+		);
+	}
+
+	// Preflight requests that don't match the rule are refused
+	for (origin, method, headers) in [
+		("https://evil.example.com", "PUT", "content-type"),
+		("https://example.com", "DELETE", "content-type"),
+		("https://example.com", "PUT", "x-amz-meta-foo"),
+	] {
+		let resp = client
+			.request(preflight(origin, method, headers))
+			.await
+			.unwrap();
+
+		assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+		assert!(resp.headers().get("access-control-allow-origin").is_none());
+	}
+
+	// Actual requests from a matching origin get the CORS headers
+	{
+		let resp = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.path("index.html")
+			.signed_header("origin", "https://example.com")
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert_eq!(
+			resp.headers().get("access-control-allow-origin").unwrap(),
+			"https://example.com"
+		);
+	}
+
+	// Actual requests from another origin don't
+	{
+		let resp = ctx
+			.custom_request
+			.builder(bucket.clone())
+			.path("index.html")
+			.signed_header("origin", "https://evil.example.com")
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert!(resp.headers().get("access-control-allow-origin").is_none());
+	}
+}
+
+#[tokio::test]
+async fn test_cors_config_lifecycle() {
+	let ctx = common::context();
+	let bucket = ctx.create_bucket("cors-lifecycle");
+
+	// No CORS configuration yet
+	let err = ctx
+		.client
+		.get_bucket_cors()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 404);
+	assert_eq!(err.code(), Some("NoSuchCORSConfiguration"));
+
+	// A configuration with an unsupported method is rejected, not stored
+	let invalid = CorsConfiguration::builder()
+		.cors_rules(
+			CorsRule::builder()
+				.allowed_methods("GET")
+				.allowed_methods("PATCH")
+				.allowed_origins("*")
+				.build()
+				.unwrap(),
+		)
+		.build()
+		.unwrap();
+	let err = ctx
+		.client
+		.put_bucket_cors()
+		.bucket(&bucket)
+		.cors_configuration(invalid)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.raw_response().unwrap().status().as_u16(), 400);
+	assert!(ctx
+		.client
+		.get_bucket_cors()
+		.bucket(&bucket)
+		.send()
+		.await
+		.is_err());
+
+	// A valid configuration round-trips
+	let valid = CorsConfiguration::builder()
+		.cors_rules(
+			CorsRule::builder()
+				.id("rule")
+				.allowed_methods("GET")
+				.allowed_methods("HEAD")
+				.allowed_origins("https://*.example.com")
+				.expose_headers("etag")
+				.max_age_seconds(600)
+				.build()
+				.unwrap(),
+		)
+		.build()
+		.unwrap();
+	ctx.client
+		.put_bucket_cors()
+		.bucket(&bucket)
+		.cors_configuration(valid)
+		.send()
+		.await
+		.unwrap();
+
+	let res = ctx
+		.client
+		.get_bucket_cors()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(res.cors_rules().len(), 1);
+	let rule = &res.cors_rules()[0];
+	assert_eq!(rule.id(), Some("rule"));
+	assert_eq!(rule.allowed_methods(), ["GET", "HEAD"]);
+	assert_eq!(rule.allowed_origins(), ["https://*.example.com"]);
+	assert_eq!(rule.expose_headers(), ["etag"]);
+	assert_eq!(rule.max_age_seconds(), Some(600));
+
+	// Deleting it answers 204, and it is gone afterwards
+	let resp = ctx
+		.custom_request
+		.builder(bucket.clone())
+		.method(Method::DELETE)
+		.query_param("cors", None::<String>)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+	let err = ctx
+		.client
+		.get_bucket_cors()
+		.bucket(&bucket)
+		.send()
+		.await
+		.unwrap_err();
+	assert_eq!(err.code(), Some("NoSuchCORSConfiguration"));
+}
+
 #[tokio::test]
 async fn test_website_check_domain() {
 	let ctx = common::context();
@@ -0,0 +1,96 @@
+use crate::common;
+use crate::common::ext::CommandExt;
+
+#[tokio::test]
+async fn test_resync_worker_count() {
+	let ctx = common::context_with_config(common::garage::InstanceConfig {
+		root: "block_resync_workers = 3",
+		..Default::default()
+	});
+
+	let output = ctx
+		.garage
+		.command()
+		.args(["worker", "list"])
+		.expect_success_output("Could not list workers");
+	let output = String::from_utf8(output.stdout).unwrap();
+	let n_resync_workers = output
+		.lines()
+		.filter(|l| l.contains("Block resync worker"))
+		.count();
+	assert_eq!(n_resync_workers, 3);
+
+	// The number of active workers cannot exceed the number of spawned workers
+	ctx.garage
+		.command()
+		.args(["worker", "set", "resync-worker-count", "3"])
+		.quiet()
+		.expect_success_status("Could not set resync-worker-count");
+	let status = ctx
+		.garage
+		.command()
+		.args(["worker", "set", "resync-worker-count", "4"])
+		.quiet()
+		.status()
+		.unwrap();
+	assert!(!status.success());
+}
+
+#[tokio::test]
+async fn test_worker_list() {
+	let ctx = common::context();
+
+	let output = ctx
+		.garage
+		.command()
+		.args(["worker", "list"])
+		.expect_success_output("Could not list workers");
+	let output = String::from_utf8(output.stdout).unwrap();
+
+	// Table rows are: TID, State, Name, ...
+	let rows = output
+		.lines()
+		.skip(1)
+		.map(|l| l.split_whitespace().collect::<Vec<_>>())
+		.filter(|cols| cols.len() >= 3)
+		.collect::<Vec<_>>();
+	let find = |name: &str| {
+		rows.iter()
+			.find(|cols| cols[2..].join(" ").starts_with(name))
+			.unwrap_or_else(|| panic!("worker {} not found in:\n{}", name, output))
+	};
+
+	for name in ["object sync", "bucket_v2 GC", "Block scrub worker"] {
+		let cols = find(name);
+		assert!(cols[0].parse::<usize>().is_ok());
+		assert!(
+			["Busy", "Busy*", "Idle", "Done"].contains(&cols[1]),
+			"{:?}",
+			cols
+		);
+	}
+
+	// Detailed information about a single worker
+	let scrub_tid = find("Block scrub worker")[0];
+	let output = ctx
+		.garage
+		.command()
+		.args(["worker", "info", scrub_tid])
+		.expect_success_output("Could not get worker info");
+	let output = String::from_utf8(output.stdout).unwrap();
+	assert!(output.contains("Block scrub worker"), "{}", output);
+	assert!(output.contains("Worker state:"), "{}", output);
+	assert!(output.contains("Total errors:"), "{}", output);
+
+	// Only busy workers are listed with --busy
+	let output = ctx
+		.garage
+		.command()
+		.args(["worker", "list", "--busy"])
+		.expect_success_output("Could not list workers");
+	let output = String::from_utf8(output.stdout).unwrap();
+	for l in output.lines().skip(1) {
+		let state = l.split_whitespace().nth(1).unwrap();
+		assert!(state == "Busy" || state == "Busy*", "{}", l);
+	}
+}
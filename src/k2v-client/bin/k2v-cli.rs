@@ -44,6 +44,9 @@ enum Command {
 		/// Causality of the insertion
 		#[clap(short, long)]
 		causality: Option<String>,
+		/// Number of seconds after which the value expires
+		#[clap(long)]
+		ttl: Option<u64>,
 		/// Value to insert
 		#[clap(flatten)]
 		value: Value,
@@ -410,21 +413,23 @@ async fn main() -> Result<(), Error> {
 		user_agent: None,
 	};
 
-	let client = K2vClient::new(config)?;
+	let client = K2vClient::new(config)?.with_retry_policy(RetryPolicy::default());
 
 	match args.command {
 		Command::Insert {
 			partition_key,
 			sort_key,
 			causality,
+			ttl,
 			value,
 		} => {
 			client
-				.insert_item(
+				.insert_item_with_ttl(
 					&partition_key,
 					&sort_key,
 					value.to_data().await?,
 					causality.map(Into::into),
+					ttl.map(Duration::from_secs),
 				)
 				.await?;
 		}
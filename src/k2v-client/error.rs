@@ -37,3 +37,15 @@ pub enum Error {
 	#[error("{0}")]
 	Message(Cow<'static, str>),
 }
+
+impl Error {
+	/// Whether the error might be caused by a transient failure (network error,
+	/// timeout or server error), in which case the request can be retried.
+	pub fn is_transient(&self) -> bool {
+		match self {
+			Error::Remote(status, ..) => status.is_server_error(),
+			Error::IoError(_) | Error::Hyper(_) | Error::HyperClient(_) | Error::Timeout => true,
+			_ => false,
+		}
+	}
+}
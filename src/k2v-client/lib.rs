@@ -1,10 +1,41 @@
+//! Client library for Garage's K2V API.
+//!
+//! Every read returns, along with the value(s) stored for an item, a causality
+//! token describing which versions of the item were seen. Passing this token
+//! back to a subsequent write indicates that the written value supersedes the
+//! versions that were read, which allows implementing read-modify-write cycles
+//! and resolving conflicts between concurrent writers:
+//!
+//! ```no_run
+//! # use k2v_client::*;
+//! # async fn example(client: &K2vClient) -> Result<(), Error> {
+//! let CausalValue { causality, value } = client.read_item("pk", "sk").await?;
+//!
+//! // If several concurrent values exist, merge them as appropriate
+//! let mut merged = vec![];
+//! for v in value {
+//! 	if let K2vValue::Value(v) = v {
+//! 		merged.extend(v);
+//! 	}
+//! }
+//!
+//! // Writing with the token replaces all the values that were read
+//! client.insert_item("pk", "sk", merged, Some(causality)).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `delete_item` takes a causality token in the same way, so that only the
+//! values that were read are deleted.
+
 use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::time::{Duration, SystemTime};
 
 use base64::prelude::*;
-use log::{debug, error};
+use log::{debug, error, warn};
 use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use rand::Rng;
 
 use http::header::{ACCEPT, CONTENT_TYPE};
 use http::status::StatusCode;
@@ -33,6 +64,7 @@ const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(300);
 const SERVICE: &str = "k2v";
 const AMZ_CONTENT_SHA256: HeaderName = HeaderName::from_static("x-amz-content-sha256");
 const GARAGE_CAUSALITY_TOKEN: HeaderName = HeaderName::from_static("x-garage-causality-token");
+const GARAGE_TTL: HeaderName = HeaderName::from_static("x-garage-ttl");
 
 const STRICT_ENCODE_SET: AsciiSet = NON_ALPHANUMERIC
 	.remove(b'_')
@@ -55,9 +87,52 @@ pub struct K2vClientConfig {
 	pub user_agent: Option<String>,
 }
 
+/// Policy for retrying requests that failed because of a transient error
+/// (network error, timeout or 5xx response). Only requests that can safely
+/// be sent several times are retried: reads, and writes that carry
+/// a causality token. See `K2vClient::with_retry_policy`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+	/// Maximum number of attempts for a request, including the first one
+	pub max_attempts: u32,
+	/// Delay before the first retry, doubled at each subsequent retry
+	pub base_delay: Duration,
+	/// Maximum delay between two attempts
+	pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+	/// A policy that never retries failed requests
+	pub fn none() -> Self {
+		Self {
+			max_attempts: 1,
+			..Default::default()
+		}
+	}
+
+	/// Delay to wait before the given retry (starting at 1),
+	/// with a random jitter of up to half the delay
+	fn delay(&self, retry: u32) -> Duration {
+		let factor = 1u32.checked_shl(retry - 1).unwrap_or(u32::MAX);
+		let delay = std::cmp::min(self.base_delay.saturating_mul(factor), self.max_delay);
+		delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+	}
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_attempts: 3,
+			base_delay: Duration::from_millis(100),
+			max_delay: Duration::from_secs(2),
+		}
+	}
+}
+
 /// Client used to query a K2V server.
 pub struct K2vClient {
 	config: K2vClientConfig,
+	retry: RetryPolicy,
 	user_agent: HeaderValue,
 	client: HttpClient<HttpsConnector<HttpConnector>, Body>,
 }
@@ -80,11 +155,19 @@ impl K2vClient {
 			.map_err(|_| Error::Message("invalid user agent".into()))?;
 		Ok(K2vClient {
 			config,
+			retry: RetryPolicy::none(),
 			client,
 			user_agent,
 		})
 	}
 
+	/// Retry requests that fail with a transient error according to the
+	/// given policy. By default, failed requests are not retried.
+	pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+		self.retry = retry;
+		self
+	}
+
 	/// Perform a ReadItem request, reading the value(s) stored for a single pk+sk.
 	pub async fn read_item(
 		&self,
@@ -95,7 +178,7 @@ impl K2vClient {
 		let req = Request::get(url)
 			.header(ACCEPT, "application/octet-stream, application/json")
 			.body(Bytes::new())?;
-		let res = self.dispatch(req, None).await?;
+		let res = self.dispatch(req, None, true).await?;
 
 		let causality = res
 			.causality_token
@@ -147,7 +230,9 @@ impl K2vClient {
 			.header(ACCEPT, "application/octet-stream, application/json")
 			.body(Bytes::new())?;
 
-		let res = self.dispatch(req, Some(timeout + DEFAULT_TIMEOUT)).await?;
+		let res = self
+			.dispatch(req, Some(timeout + DEFAULT_TIMEOUT), true)
+			.await?;
 
 		if res.status == StatusCode::NOT_MODIFIED {
 			return Ok(None);
@@ -201,7 +286,9 @@ impl K2vClient {
 		let payload = serde_json::to_vec(&request)?;
 		let req = Request::post(url).body(Bytes::from(payload))?;
 
-		let res = self.dispatch(req, Some(timeout + DEFAULT_TIMEOUT)).await?;
+		let res = self
+			.dispatch(req, Some(timeout + DEFAULT_TIMEOUT), true)
+			.await?;
 
 		if res.status == StatusCode::NOT_MODIFIED {
 			return Ok(None);
@@ -236,15 +323,36 @@ impl K2vClient {
 		sort_key: &str,
 		value: Vec<u8>,
 		causality: Option<CausalityToken>,
+	) -> Result<(), Error> {
+		self.insert_item_with_ttl(partition_key, sort_key, value, causality, None)
+			.await
+	}
+
+	/// Perform an InsertItem request, inserting a value for a single pk+sk.
+	/// If a TTL is given, the value is no longer returned once it has elapsed
+	/// (with a precision of one second), and is eventually deleted.
+	pub async fn insert_item_with_ttl(
+		&self,
+		partition_key: &str,
+		sort_key: &str,
+		value: Vec<u8>,
+		causality: Option<CausalityToken>,
+		ttl: Option<Duration>,
 	) -> Result<(), Error> {
 		let url = self.build_url(Some(partition_key), &[("sort_key", sort_key)]);
+		// An insert with a causality token can safely be retried,
+		// as it always replaces the same set of values.
+		let idempotent = causality.is_some();
 		let mut req = Request::put(url);
 		if let Some(causality) = causality {
 			req = req.header(GARAGE_CAUSALITY_TOKEN, &causality.0);
 		}
+		if let Some(ttl) = ttl {
+			req = req.header(GARAGE_TTL, ttl.as_secs().max(1));
+		}
 		let req = req.body(Bytes::from(value))?;
 
-		self.dispatch(req, None).await?;
+		self.dispatch(req, None, idempotent).await?;
 		Ok(())
 	}
 
@@ -260,7 +368,7 @@ impl K2vClient {
 			.header(GARAGE_CAUSALITY_TOKEN, &causality.0)
 			.body(Bytes::new())?;
 
-		self.dispatch(req, None).await?;
+		self.dispatch(req, None, true).await?;
 		Ok(())
 	}
 
@@ -274,7 +382,7 @@ impl K2vClient {
 		let url = self.build_url(None, &params);
 		let req = Request::get(url).body(Bytes::new())?;
 
-		let res = self.dispatch(req, None).await?;
+		let res = self.dispatch(req, None, true).await?;
 
 		let resp: ReadIndexResponse = serde_json::from_slice(&res.body)?;
 
@@ -298,7 +406,8 @@ impl K2vClient {
 		let payload = serde_json::to_vec(operations)?;
 		let req = Request::post(url).body(payload.into())?;
 
-		self.dispatch(req, None).await?;
+		let idempotent = operations.iter().all(|op| op.causality.is_some());
+		self.dispatch(req, None, idempotent).await?;
 		Ok(())
 	}
 
@@ -311,7 +420,7 @@ impl K2vClient {
 		let payload = serde_json::to_vec(operations)?;
 		let req = Request::post(url).body(payload.into())?;
 
-		let res = self.dispatch(req, None).await?;
+		let res = self.dispatch(req, None, true).await?;
 
 		let resp: Vec<BatchReadResponse> = serde_json::from_slice(&res.body)?;
 
@@ -343,14 +452,49 @@ impl K2vClient {
 		let payload = serde_json::to_vec(operations)?;
 		let req = Request::post(url).body(payload.into())?;
 
-		let res = self.dispatch(req, None).await?;
+		// Range deletions are not retried, as they could delete values
+		// that were inserted between two attempts.
+		let res = self.dispatch(req, None, false).await?;
 
 		let resp: Vec<BatchDeleteResponse> = serde_json::from_slice(&res.body)?;
 
 		Ok(resp.into_iter().map(|r| r.deleted_items).collect())
 	}
 
+	/// Send a request, retrying it according to the retry policy if it fails
+	/// with a transient error. Requests are only retried if `idempotent` is set,
+	/// i.e. if sending them several times has the same effect as sending them once.
 	async fn dispatch(
+		&self,
+		req: Request<Bytes>,
+		timeout: Option<Duration>,
+		idempotent: bool,
+	) -> Result<Response, Error> {
+		let retry = &self.retry;
+		let max_attempts = if idempotent {
+			std::cmp::max(retry.max_attempts, 1)
+		} else {
+			1
+		};
+
+		let mut attempt = 1;
+		loop {
+			match self.dispatch_once(clone_request(&req), timeout).await {
+				Err(e) if e.is_transient() && attempt < max_attempts => {
+					let delay = retry.delay(attempt);
+					warn!(
+						"Request failed ({}), retrying in {:?} (attempt {}/{})",
+						e, delay, attempt, max_attempts
+					);
+					tokio::time::sleep(delay).await;
+					attempt += 1;
+				}
+				res => return res,
+			}
+		}
+	}
+
+	async fn dispatch_once(
 		&self,
 		mut req: Request<Bytes>,
 		timeout: Option<Duration>,
@@ -501,6 +645,15 @@ impl K2vClient {
 	}
 }
 
+fn clone_request(req: &Request<Bytes>) -> Request<Bytes> {
+	let mut new_req = Request::new(req.body().clone());
+	*new_req.method_mut() = req.method().clone();
+	*new_req.uri_mut() = req.uri().clone();
+	*new_req.version_mut() = req.version();
+	*new_req.headers_mut() = req.headers().clone();
+	new_req
+}
+
 /// An opaque token used to convey causality between operations.
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(transparent)]
@@ -581,7 +734,9 @@ impl Serialize for K2vValue {
 /// A set of K2vValue and associated causality information.
 #[derive(Debug, Clone, Serialize)]
 pub struct CausalValue {
+	/// Causality token, to be passed to a subsequent write of the same item
 	pub causality: CausalityToken,
+	/// All concurrent values of the item that were seen by the read
 	pub value: Vec<K2vValue>,
 }
 
@@ -589,6 +744,8 @@ pub struct CausalValue {
 #[derive(Debug, Clone)]
 pub struct PaginatedRange<V> {
 	pub items: BTreeMap<String, V>,
+	/// If the result was truncated, key of the next item to read: pass it
+	/// as the `start` of the filter in the next request to continue listing
 	pub next_start: Option<String>,
 }
 
@@ -781,3 +938,153 @@ struct Response {
 	causality_token: Option<CausalityToken>,
 	content_type: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::sync::Arc;
+
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+	use tokio::net::TcpListener;
+
+	use super::*;
+
+	/// Start a mock K2V server that answers the first `n_failures` requests
+	/// with a 503 error, and the following ones with a value.
+	/// Returns the endpoint of the server and a counter of received requests.
+	async fn mock_server(n_failures: usize) -> (String, Arc<AtomicUsize>) {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let endpoint = format!("http://{}", listener.local_addr().unwrap());
+		let count = Arc::new(AtomicUsize::new(0));
+
+		let count2 = count.clone();
+		tokio::spawn(async move {
+			loop {
+				let (mut sock, _) = listener.accept().await.unwrap();
+				let n = count2.fetch_add(1, Ordering::SeqCst);
+
+				// Read the whole request before answering
+				let mut buf = vec![];
+				let mut chunk = [0u8; 1024];
+				let header_len = loop {
+					if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+						break i + 4;
+					}
+					let len = sock.read(&mut chunk).await.unwrap();
+					assert!(len > 0);
+					buf.extend_from_slice(&chunk[..len]);
+				};
+				let content_length = std::str::from_utf8(&buf[..header_len])
+					.unwrap()
+					.lines()
+					.filter_map(|l| l.split_once(':'))
+					.find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+					.map(|(_, v)| v.trim().parse::<usize>().unwrap())
+					.unwrap_or(0);
+				while buf.len() < header_len + content_length {
+					let len = sock.read(&mut chunk).await.unwrap();
+					assert!(len > 0);
+					buf.extend_from_slice(&chunk[..len]);
+				}
+
+				let resp: &[u8] = if n < n_failures {
+					b"HTTP/1.1 503 Service Unavailable\r\n\
+					Content-Length: 0\r\n\
+					Connection: close\r\n\r\n"
+				} else {
+					b"HTTP/1.1 200 OK\r\n\
+					Content-Type: application/octet-stream\r\n\
+					X-Garage-Causality-Token: token\r\n\
+					Content-Length: 5\r\n\
+					Connection: close\r\n\r\n\
+					hello"
+				};
+				sock.write_all(resp).await.unwrap();
+				sock.shutdown().await.unwrap();
+			}
+		});
+
+		(endpoint, count)
+	}
+
+	fn client(endpoint: String, max_attempts: u32) -> K2vClient {
+		K2vClient::new(K2vClientConfig {
+			endpoint,
+			region: "garage".into(),
+			aws_access_key_id: "GK000000000000000000000000".into(),
+			aws_secret_access_key: "secret".into(),
+			bucket: "bucket".into(),
+			user_agent: None,
+		})
+		.unwrap()
+		.with_retry_policy(RetryPolicy {
+			max_attempts,
+			base_delay: Duration::from_millis(1),
+			max_delay: Duration::from_millis(10),
+		})
+	}
+
+	#[tokio::test]
+	async fn test_retry_transient_errors() {
+		let (endpoint, count) = mock_server(2).await;
+		let client = client(endpoint, 3);
+
+		let res = client.read_item("pk", "sk").await.unwrap();
+		assert_eq!(res.value, vec![K2vValue::Value(b"hello".to_vec())]);
+		assert_eq!(count.load(Ordering::SeqCst), 3);
+	}
+
+	#[tokio::test]
+	async fn test_no_retry_by_default() {
+		let (endpoint, count) = mock_server(2).await;
+		let client = K2vClient::new(K2vClientConfig {
+			endpoint,
+			region: "garage".into(),
+			aws_access_key_id: "GK000000000000000000000000".into(),
+			aws_secret_access_key: "secret".into(),
+			bucket: "bucket".into(),
+			user_agent: None,
+		})
+		.unwrap();
+
+		assert!(client.read_item("pk", "sk").await.is_err());
+		assert_eq!(count.load(Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test]
+	async fn test_retry_exhausted() {
+		let (endpoint, count) = mock_server(5).await;
+		let client = client(endpoint, 3);
+
+		match client.read_item("pk", "sk").await {
+			Err(Error::Remote(status, ..)) => assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE),
+			r => panic!("unexpected result: {:?}", r),
+		}
+		assert_eq!(count.load(Ordering::SeqCst), 3);
+	}
+
+	#[tokio::test]
+	async fn test_no_retry_non_idempotent() {
+		let (endpoint, count) = mock_server(2).await;
+		let client = client(endpoint, 3);
+
+		// Insert without causality token: not retried
+		assert!(client
+			.insert_item("pk", "sk", b"value".to_vec(), None)
+			.await
+			.is_err());
+		assert_eq!(count.load(Ordering::SeqCst), 1);
+
+		// Insert with causality token: retried
+		client
+			.insert_item(
+				"pk",
+				"sk",
+				b"value".to_vec(),
+				Some("token".to_string().into()),
+			)
+			.await
+			.unwrap();
+		assert_eq!(count.load(Ordering::SeqCst), 3);
+	}
+}
@@ -23,7 +23,7 @@ pub use v08::*;
 
 impl BucketAlias {
 	pub fn new(name: String, ts: u64, bucket_id: Option<Uuid>) -> Option<Self> {
-		if !is_valid_bucket_name(&name) {
+		if !is_valid_bucket_name(&name, true) {
 			None
 		} else {
 			Some(BucketAlias {
@@ -71,31 +71,141 @@ impl TableSchema for BucketAliasTable {
 	}
 }
 
-/// Check if a bucket name is valid.
+/// Check if a bucket name is valid, see `validate_bucket_name`
+pub fn is_valid_bucket_name(n: &str, relaxed: bool) -> bool {
+	validate_bucket_name(n, relaxed).is_ok()
+}
+
+/// Check if a bucket name is valid, and if not return the rule it violates.
 ///
 /// The requirements are listed here:
 ///
 /// <https://docs.aws.amazon.com/AmazonS3/latest/userguide/bucketnamingrules.html>
 ///
+/// If `relaxed` is set, the looser legacy rules of the us-east-1 region
+/// are used instead: up to 255 characters among letters (including
+/// uppercase), numbers, dots, dashes and underscores. Such names are not
+/// DNS-compatible and can only be used with path-style requests.
+///
 /// In the case of Garage, bucket names must not be hex-encoded
 /// 32 byte string, which is excluded thanks to the
-/// maximum length of 63 bytes given in the spec.
-pub fn is_valid_bucket_name(n: &str) -> bool {
-	// Bucket names must be between 3 and 63 characters
-	n.len() >= 3 && n.len() <= 63
-	// Bucket names must be composed of lowercase letters, numbers,
-	// dashes and dots
-	&& n.chars().all(|c| matches!(c, '.' | '-' | 'a'..='z' | '0'..='9'))
-	//  Bucket names must start and end with a letter or a number
-	&& !n.starts_with(&['-', '.'][..])
-	&& !n.ends_with(&['-', '.'][..])
-	// Bucket names must not be formated as an IP address
-	&& n.parse::<std::net::IpAddr>().is_err()
-	// Bucket names must not start wih "xn--"
-	&& !n.starts_with("xn--")
-	// Bucket names must not end with "-s3alias"
-	&& !n.ends_with("-s3alias")
+/// maximum length of 63 bytes given in the spec, and explicitly
+/// in relaxed mode.
+pub fn validate_bucket_name(n: &str, relaxed: bool) -> Result<(), &'static str> {
+	if relaxed {
+		if n.len() < 3 || n.len() > 255 {
+			return Err("bucket names must be between 3 and 255 characters long");
+		}
+		if !n
+			.chars()
+			.all(|c| matches!(c, '.' | '-' | '_' | 'a'..='z' | 'A'..='Z' | '0'..='9'))
+		{
+			return Err(
+				"bucket names must only contain letters, numbers, dots, dashes and underscores",
+			);
+		}
+		if n.len() == 64 && n.chars().all(|c| c.is_ascii_hexdigit()) {
+			return Err("bucket names must not be formatted as a bucket identifier");
+		}
+		return Ok(());
+	}
+
+	if n.len() < 3 || n.len() > 63 {
+		return Err("bucket names must be between 3 and 63 characters long");
+	}
+	if !n
+		.chars()
+		.all(|c| matches!(c, '.' | '-' | 'a'..='z' | '0'..='9'))
+	{
+		return Err("bucket names must only contain lowercase letters, numbers, dots and dashes");
+	}
+	if n.starts_with(&['-', '.'][..]) || n.ends_with(&['-', '.'][..]) {
+		return Err("bucket names must start and end with a letter or a number");
+	}
+	if n.contains("..") {
+		return Err("bucket names must not contain two adjacent dots");
+	}
+	if n.parse::<std::net::IpAddr>().is_ok() {
+		return Err("bucket names must not be formatted as an IP address");
+	}
+	if n.starts_with("xn--") || n.starts_with("sthree-") {
+		return Err("bucket names must not start with a reserved prefix");
+	}
+	if n.ends_with("-s3alias") || n.ends_with("--ol-s3") {
+		return Err("bucket names must not end with a reserved suffix");
+	}
+	Ok(())
 }
 
 /// Error message to return for invalid bucket names
 pub const INVALID_BUCKET_NAME_MESSAGE: &str = "Invalid bucket name. See AWS documentation for constraints on S3 bucket names:\nhttps://docs.aws.amazon.com/AmazonS3/latest/userguide/bucketnamingrules.html";
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_valid_bucket_names() {
+		for name in [
+			"abc",
+			"my-bucket",
+			"my.bucket.example.com",
+			"bucket-2024",
+			"0bucket9",
+			&"a".repeat(63),
+		] {
+			assert_eq!(validate_bucket_name(name, false), Ok(()), "{}", name);
+			assert_eq!(validate_bucket_name(name, true), Ok(()), "{}", name);
+		}
+	}
+
+	#[test]
+	fn test_invalid_bucket_names() {
+		for name in [
+			"",
+			"ab",
+			&"a".repeat(64),
+			"MyBucket",
+			"my_bucket",
+			"my bucket",
+			"bucket/key",
+			"-bucket",
+			"bucket-",
+			".bucket",
+			"bucket.",
+			"my..bucket",
+			"192.168.1.1",
+			"xn--bucket",
+			"sthree-bucket",
+			"bucket-s3alias",
+			"bucket--ol-s3",
+		] {
+			assert!(validate_bucket_name(name, false).is_err(), "{}", name);
+			assert!(!is_valid_bucket_name(name, false), "{}", name);
+		}
+	}
+
+	#[test]
+	fn test_relaxed_bucket_names() {
+		for name in [
+			"MyBucket",
+			"my_bucket",
+			"-bucket",
+			"my..bucket",
+			&"a".repeat(255),
+		] {
+			assert!(validate_bucket_name(name, false).is_err(), "{}", name);
+			assert_eq!(validate_bucket_name(name, true), Ok(()), "{}", name);
+		}
+		for name in [
+			"ab",
+			&"a".repeat(256),
+			"my bucket",
+			"bucket/key",
+			"bucket?",
+			&"0123456789abcdef".repeat(4),
+		] {
+			assert!(validate_bucket_name(name, true).is_err(), "{}", name);
+		}
+	}
+}
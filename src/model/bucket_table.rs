@@ -4,8 +4,11 @@ use garage_util::data::*;
 use garage_util::time::*;
 
 use crate::permission::BucketKeyPerm;
+use crate::s3::object_table::ObjectVersionRetention;
 
 mod v08 {
+	use std::collections::BTreeMap;
+
 	use crate::permission::BucketKeyPerm;
 	use garage_util::crdt;
 	use garage_util::data::Uuid;
@@ -54,6 +57,18 @@ mod v08 {
 		/// Bucket quotas
 		#[serde(default)]
 		pub quotas: crdt::Lww<BucketQuotas>,
+		/// Object lock configuration
+		#[serde(default)]
+		pub object_lock_config: crdt::Lww<Option<ObjectLockConfig>>,
+		/// Versioning state, as set by PutBucketVersioning
+		#[serde(default)]
+		pub versioning: crdt::Lww<VersioningState>,
+		/// Bucket tags, as set by PutBucketTagging
+		#[serde(default)]
+		pub tags: crdt::Lww<BTreeMap<String, String>>,
+		/// Canned ACL, as set by PutBucketAcl
+		#[serde(default)]
+		pub canned_acl: crdt::Lww<CannedAcl>,
 	}
 
 	#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
@@ -114,6 +129,65 @@ mod v08 {
 		pub max_size: Option<u64>,
 		/// Maximum number of non-deleted objects in the bucket
 		pub max_objects: Option<u64>,
+		/// Whether writes in progress on a node are counted against the quotas,
+		/// in addition to the (eventually consistent) object counters
+		#[serde(default)]
+		pub strict: bool,
+	}
+
+	/// Object lock configuration: objects stored in the bucket
+	/// cannot be deleted or overwritten during their retention period
+	#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+	pub struct ObjectLockConfig {
+		/// Retention applied by default to all objects of the bucket
+		pub default_retention: Option<ObjectLockRetention>,
+	}
+
+	#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+	pub struct ObjectLockRetention {
+		pub mode: ObjectLockMode,
+		pub period: ObjectLockPeriod,
+	}
+
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Serialize, Deserialize)]
+	pub enum ObjectLockMode {
+		/// Retention can be bypassed by users with the appropriate permissions
+		Governance,
+		/// Retention cannot be bypassed by anyone
+		Compliance,
+	}
+
+	#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+	pub enum ObjectLockPeriod {
+		Days(u32),
+		Years(u32),
+	}
+
+	/// Versioning state of a bucket. Garage only keeps the latest version of
+	/// each object in all cases, this only changes the version ids reported
+	/// to clients.
+	#[derive(
+		Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Serialize, Deserialize,
+	)]
+	pub enum VersioningState {
+		/// Versioning was never configured on this bucket
+		#[default]
+		Unset,
+		Suspended,
+	}
+
+	/// Canned ACL of a bucket or object. Only the permissions given to users
+	/// that don't have access through an access key are represented, the
+	/// permissions of access keys are managed separately.
+	#[derive(
+		Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Serialize, Deserialize,
+	)]
+	pub enum CannedAcl {
+		#[default]
+		Private,
+		PublicRead,
+		PublicReadWrite,
+		AuthenticatedRead,
 	}
 
 	impl garage_util::migrate::InitialFormat for Bucket {}
@@ -125,6 +199,14 @@ impl AutoCrdt for BucketQuotas {
 	const WARN_IF_DIFFERENT: bool = true;
 }
 
+impl AutoCrdt for VersioningState {
+	const WARN_IF_DIFFERENT: bool = true;
+}
+
+impl AutoCrdt for CannedAcl {
+	const WARN_IF_DIFFERENT: bool = true;
+}
+
 impl BucketParams {
 	/// Create an empty BucketParams with no authorized keys and no website accesss
 	fn new() -> Self {
@@ -137,6 +219,10 @@ impl BucketParams {
 			cors_config: crdt::Lww::new(None),
 			lifecycle_config: crdt::Lww::new(None),
 			quotas: crdt::Lww::new(BucketQuotas::default()),
+			object_lock_config: crdt::Lww::new(None),
+			versioning: crdt::Lww::new(VersioningState::Unset),
+			tags: crdt::Lww::new(Default::default()),
+			canned_acl: crdt::Lww::new(CannedAcl::Private),
 		}
 	}
 }
@@ -153,6 +239,74 @@ impl Crdt for BucketParams {
 		self.cors_config.merge(&o.cors_config);
 		self.lifecycle_config.merge(&o.lifecycle_config);
 		self.quotas.merge(&o.quotas);
+		self.object_lock_config.merge(&o.object_lock_config);
+		self.versioning.merge(&o.versioning);
+		self.tags.merge(&o.tags);
+		self.canned_acl.merge(&o.canned_acl);
+	}
+}
+
+impl ObjectLockConfig {
+	/// Retention to be recorded on an object version created at `timestamp`,
+	/// as given by the default retention of the bucket, if any
+	pub fn object_retention(&self, timestamp: u64) -> Option<ObjectVersionRetention> {
+		self.default_retention
+			.as_ref()
+			.map(|r| ObjectVersionRetention {
+				mode: r.mode,
+				retain_until: timestamp.saturating_add(r.period.as_msec()),
+			})
+	}
+}
+
+impl CannedAcl {
+	/// Parse the value of an x-amz-acl header. Canned ACLs that only give
+	/// permissions to the bucket owner or to AWS services don't give access
+	/// to anyone without an access key, and are equivalent to `private`.
+	pub fn parse(s: &str) -> Option<Self> {
+		match s {
+			"private"
+			| "bucket-owner-read"
+			| "bucket-owner-full-control"
+			| "aws-exec-read"
+			| "log-delivery-write" => Some(CannedAcl::Private),
+			"public-read" => Some(CannedAcl::PublicRead),
+			"public-read-write" => Some(CannedAcl::PublicReadWrite),
+			"authenticated-read" => Some(CannedAcl::AuthenticatedRead),
+			_ => None,
+		}
+	}
+
+	/// Value of this ACL in an x-amz-acl header
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			CannedAcl::Private => "private",
+			CannedAcl::PublicRead => "public-read",
+			CannedAcl::PublicReadWrite => "public-read-write",
+			CannedAcl::AuthenticatedRead => "authenticated-read",
+		}
+	}
+
+	/// Whether this ACL allows reading objects without an access key
+	pub fn allows_anonymous_read(&self) -> bool {
+		matches!(self, CannedAcl::PublicRead | CannedAcl::PublicReadWrite)
+	}
+
+	/// Whether this ACL allows reading objects with any valid access key
+	pub fn allows_authenticated_read(&self) -> bool {
+		*self != CannedAcl::Private
+	}
+}
+
+impl ObjectLockPeriod {
+	/// Length of the retention period in milliseconds
+	/// (a year is counted as 365 days)
+	pub fn as_msec(&self) -> u64 {
+		let days = match self {
+			ObjectLockPeriod::Days(d) => *d as u64,
+			ObjectLockPeriod::Years(y) => *y as u64 * 365,
+		};
+		days * 24 * 3600 * 1000
 	}
 }
 
@@ -20,8 +20,10 @@ use garage_table::*;
 
 use crate::s3::block_ref_table::*;
 use crate::s3::lifecycle_worker;
+use crate::s3::metrics::S3Metrics;
 use crate::s3::mpu_table::*;
 use crate::s3::object_table::*;
+use crate::s3::stale_upload_worker;
 use crate::s3::version_table::*;
 
 use crate::bucket_alias_table::*;
@@ -29,6 +31,8 @@ use crate::bucket_table::*;
 use crate::helper;
 use crate::index_counter::*;
 use crate::key_table::*;
+use crate::quota::QuotaReservations;
+use crate::rate_limit::RateLimiter;
 
 #[cfg(feature = "k2v")]
 use crate::k2v::{item_table::*, rpc::*, sub::*};
@@ -60,6 +64,11 @@ pub struct Garage {
 	/// Lock to prevent concurrent modification of buckets and access keys
 	bucket_lock: tokio::sync::Mutex<()>,
 
+	/// Per-key request and bandwidth rate limiter
+	pub rate_limiter: RateLimiter,
+	/// Objects and bytes reserved by writes in progress, for strict bucket quotas
+	pub quota_reservations: Arc<QuotaReservations>,
+
 	/// Table containing S3 objects
 	pub object_table: Arc<Table<ObjectTable, TableShardedReplication>>,
 	/// Counting table containing object counters
@@ -73,6 +82,9 @@ pub struct Garage {
 	/// Table containing S3 block references (not blocks themselves)
 	pub block_ref_table: Arc<Table<BlockRefTable, TableShardedReplication>>,
 
+	/// Metrics about objects written through the S3 API
+	pub s3_metrics: S3Metrics,
+
 	/// Persister for lifecycle worker info
 	pub lifecycle_persister: PersisterShared<lifecycle_worker::LifecycleWorkerPersisted>,
 
@@ -116,21 +128,17 @@ impl Garage {
 		info!("Opening database...");
 		let db_engine = db::Engine::from_str(&config.db_engine)
 			.ok_or_message("Invalid `db_engine` value in configuration file")?;
-		let mut db_path = config.metadata_dir.clone();
-		match db_engine {
-			db::Engine::Sqlite => {
-				db_path.push("db.sqlite");
-			}
-			db::Engine::Lmdb => {
-				db_path.push("db.lmdb");
-			}
-		}
+		db::check_engine(&config.metadata_dir, db_engine)
+			.ok_or_message("Metadata directory does not match configured `db_engine`")?;
+		let db_path = config.metadata_dir.join(db_engine.db_file_name());
 		let db_opt = db::OpenOpt {
 			fsync: config.metadata_fsync,
 			lmdb_map_size: match config.lmdb_map_size {
 				v if v == usize::default() => None,
 				v => Some(v),
 			},
+			sqlite_page_size: config.sqlite_page_size,
+			sqlite_cache_size: config.sqlite_cache_size,
 		};
 		let db = db::open_db(&db_path, db_engine, &db_opt)
 			.ok_or_message("Unable to open metadata db")?;
@@ -173,9 +181,17 @@ impl Garage {
 		let block_manager = BlockManager::new(&db, &config, data_rep_param, system.clone())?;
 		block_manager.register_bg_vars(&mut bg_vars);
 
+		let merkle_update_batch_size = config.merkle_update_batch_size;
+
 		// ---- admin tables ----
 		info!("Initialize bucket_table...");
-		let bucket_table = Table::new(BucketTable, control_rep_param.clone(), system.clone(), &db);
+		let bucket_table = Table::new(
+			BucketTable,
+			control_rep_param.clone(),
+			system.clone(),
+			&db,
+			merkle_update_batch_size,
+		);
 
 		info!("Initialize bucket_alias_table...");
 		let bucket_alias_table = Table::new(
@@ -183,9 +199,16 @@ impl Garage {
 			control_rep_param.clone(),
 			system.clone(),
 			&db,
+			merkle_update_batch_size,
 		);
 		info!("Initialize key_table_table...");
-		let key_table = Table::new(KeyTable, control_rep_param, system.clone(), &db);
+		let key_table = Table::new(
+			KeyTable,
+			control_rep_param,
+			system.clone(),
+			&db,
+			merkle_update_batch_size,
+		);
 
 		// ---- S3 tables ----
 		info!("Initialize block_ref_table...");
@@ -196,6 +219,7 @@ impl Garage {
 			meta_rep_param.clone(),
 			system.clone(),
 			&db,
+			merkle_update_batch_size,
 		);
 
 		info!("Initialize version_table...");
@@ -206,10 +230,16 @@ impl Garage {
 			meta_rep_param.clone(),
 			system.clone(),
 			&db,
+			merkle_update_batch_size,
 		);
 
 		info!("Initialize multipart upload counter table...");
-		let mpu_counter_table = IndexCounter::new(system.clone(), meta_rep_param.clone(), &db);
+		let mpu_counter_table = IndexCounter::new(
+			system.clone(),
+			meta_rep_param.clone(),
+			&db,
+			merkle_update_batch_size,
+		);
 
 		info!("Initialize multipart upload table...");
 		let mpu_table = Table::new(
@@ -220,10 +250,16 @@ impl Garage {
 			meta_rep_param.clone(),
 			system.clone(),
 			&db,
+			merkle_update_batch_size,
 		);
 
 		info!("Initialize object counter table...");
-		let object_counter_table = IndexCounter::new(system.clone(), meta_rep_param.clone(), &db);
+		let object_counter_table = IndexCounter::new(
+			system.clone(),
+			meta_rep_param.clone(),
+			&db,
+			merkle_update_batch_size,
+		);
 
 		info!("Initialize object_table...");
 		#[allow(clippy::redundant_clone)]
@@ -236,6 +272,7 @@ impl Garage {
 			meta_rep_param.clone(),
 			system.clone(),
 			&db,
+			merkle_update_batch_size,
 		);
 
 		info!("Load lifecycle worker state...");
@@ -245,7 +282,12 @@ impl Garage {
 
 		// ---- K2V ----
 		#[cfg(feature = "k2v")]
-		let k2v = GarageK2V::new(system.clone(), &db, meta_rep_param);
+		let k2v = GarageK2V::new(
+			system.clone(),
+			&db,
+			meta_rep_param,
+			merkle_update_batch_size,
+		);
 
 		// ---- setup block refcount recalculation ----
 		// this function can be used to fix inconsistencies in the RC table
@@ -267,12 +309,15 @@ impl Garage {
 			bucket_alias_table,
 			key_table,
 			bucket_lock: tokio::sync::Mutex::new(()),
+			rate_limiter: RateLimiter::new(),
+			quota_reservations: QuotaReservations::new(),
 			object_table,
 			object_counter_table,
 			mpu_table,
 			mpu_counter_table,
 			version_table,
 			block_ref_table,
+			s3_metrics: S3Metrics::new(),
 			lifecycle_persister,
 			#[cfg(feature = "k2v")]
 			k2v,
@@ -293,10 +338,42 @@ impl Garage {
 		self.version_table.spawn_workers(bg);
 		self.block_ref_table.spawn_workers(bg);
 
-		bg.spawn_worker(lifecycle_worker::LifecycleWorker::new(
-			self.clone(),
-			self.lifecycle_persister.clone(),
-		));
+		if !self.config.disable_lifecycle {
+			let scan_interval = self
+				.config
+				.lifecycle_scan_interval
+				.as_deref()
+				.map(|itv| {
+					parse_duration::parse(itv).ok_or_message("Invalid `lifecycle_scan_interval`")
+				})
+				.transpose()?;
+			bg.spawn_worker(lifecycle_worker::LifecycleWorker::new(
+				self.clone(),
+				self.lifecycle_persister.clone(),
+				scan_interval,
+			));
+		}
+
+		if let Some(age) = self.config.stale_upload_max_age.as_deref() {
+			let max_age =
+				parse_duration::parse(age).ok_or_message("Invalid `stale_upload_max_age`")?;
+			let scan_interval = match self.config.stale_upload_scan_interval.as_deref() {
+				Some(itv) => parse_duration::parse(itv)
+					.ok_or_message("Invalid `stale_upload_scan_interval`")?,
+				None => std::time::Duration::from_secs(3600),
+			};
+			if max_age < std::time::Duration::from_secs(3600) {
+				return Err(Error::Message(
+					"stale_upload_max_age too small, must be at least 1h".into(),
+				));
+			}
+
+			bg.spawn_worker(stale_upload_worker::StaleUploadWorker::new(
+				self.clone(),
+				max_age,
+				scan_interval,
+			));
+		}
 
 		#[cfg(feature = "k2v")]
 		self.k2v.spawn_workers(bg);
@@ -335,9 +412,19 @@ impl Garage {
 
 #[cfg(feature = "k2v")]
 impl GarageK2V {
-	fn new(system: Arc<System>, db: &db::Db, meta_rep_param: TableShardedReplication) -> Self {
+	fn new(
+		system: Arc<System>,
+		db: &db::Db,
+		meta_rep_param: TableShardedReplication,
+		merkle_update_batch_size: usize,
+	) -> Self {
 		info!("Initialize K2V counter table...");
-		let counter_table = IndexCounter::new(system.clone(), meta_rep_param.clone(), db);
+		let counter_table = IndexCounter::new(
+			system.clone(),
+			meta_rep_param.clone(),
+			db,
+			merkle_update_batch_size,
+		);
 
 		info!("Initialize K2V subscription manager...");
 		let subscriptions = Arc::new(SubscriptionManager::new());
@@ -351,6 +438,7 @@ impl GarageK2V {
 			meta_rep_param,
 			system.clone(),
 			db,
+			merkle_update_batch_size,
 		);
 
 		info!("Initialize K2V RPC handler...");
@@ -366,5 +454,10 @@ impl GarageK2V {
 	pub fn spawn_workers(&self, bg: &BackgroundRunner) {
 		self.item_table.spawn_workers(bg);
 		self.counter_table.spawn_workers(bg);
+
+		bg.spawn_worker(crate::k2v::expiry_worker::K2VExpiryWorker::new(
+			self.item_table.clone(),
+			self.rpc.clone(),
+		));
 	}
 }
@@ -182,8 +182,117 @@ impl<'a> BucketHelper<'a> {
 		Ok(true)
 	}
 
+	/// Deletes all objects of a bucket by writing a delete marker over their
+	/// current version, and aborts all uploads in progress in the bucket.
+	/// Returns the number of objects deleted.
+	pub async fn delete_all_objects(&self, bucket_id: &Uuid) -> Result<usize, Error> {
+		let mut ret = 0usize;
+		let mut start = None;
+
+		loop {
+			let objects = self
+				.0
+				.object_table
+				.get_range(
+					bucket_id,
+					start,
+					Some(ObjectFilter::IsData),
+					1000,
+					EnumerationOrder::Forward,
+				)
+				.await?;
+
+			let deletions = objects
+				.iter()
+				.map(|object| {
+					let timestamp = object
+						.versions()
+						.iter()
+						.map(|v| v.timestamp)
+						.max()
+						.map(|t| std::cmp::max(t + 1, now_msec()))
+						.unwrap_or_else(now_msec);
+					Object::new(
+						object.bucket_id,
+						object.key.clone(),
+						vec![ObjectVersion {
+							uuid: gen_uuid(),
+							timestamp,
+							state: ObjectVersionState::Complete(ObjectVersionData::DeleteMarker),
+						}],
+					)
+				})
+				.collect::<Vec<_>>();
+
+			ret += deletions.len();
+			self.0.object_table.insert_many(deletions).await?;
+
+			if objects.len() < 1000 {
+				break;
+			} else {
+				start = Some(objects.last().unwrap().key.clone());
+			}
+		}
+
+		self.cleanup_incomplete_uploads(bucket_id, Duration::ZERO)
+			.await?;
+
+		Ok(ret)
+	}
+
 	// ----
 
+	/// List multipart uploads in progress in a bucket, optionally only
+	/// those that were started more than `older_than` ago
+	pub async fn list_incomplete_uploads(
+		&self,
+		bucket_id: &Uuid,
+		older_than: Option<Duration>,
+	) -> Result<Vec<(String, ObjectVersion)>, Error> {
+		let older_than = older_than
+			.map(|d| now_msec().saturating_sub(d.as_millis() as u64))
+			.unwrap_or(u64::MAX);
+
+		let mut ret = vec![];
+		let mut start: Option<String> = None;
+
+		loop {
+			let objects = self
+				.0
+				.object_table
+				.get_range(
+					bucket_id,
+					start.clone(),
+					Some(ObjectFilter::IsUploading {
+						check_multipart: Some(true),
+					}),
+					1000,
+					EnumerationOrder::Forward,
+				)
+				.await?;
+
+			// The range starts at the last object of the previous page,
+			// which has already been listed
+			for object in objects.iter().filter(|o| start.as_ref() != Some(&o.key)) {
+				ret.extend(
+					object
+						.versions()
+						.iter()
+						.filter(|v| v.is_uploading(Some(true)) && v.timestamp < older_than)
+						.map(|v| (object.key.clone(), v.clone())),
+				);
+			}
+
+			if objects.len() < 1000 {
+				break;
+			} else {
+				start = Some(objects.last().unwrap().key.clone());
+			}
+		}
+
+		Ok(ret)
+	}
+
 	/// Deletes all incomplete multipart uploads that are older than a certain time.
 	/// Returns the number of uploads aborted.
 	/// This will also include non-multipart uploads, which may be lingering
@@ -20,6 +20,10 @@ pub enum Error {
 
 	#[error(display = "Bucket not found: {}", _0)]
 	NoSuchBucket(String),
+
+	/// Tried to delete a bucket that still contains data
+	#[error(display = "Bucket not empty: {}", _0)]
+	BucketNotEmpty(String),
 }
 
 impl From<garage_net::error::Error> for Error {
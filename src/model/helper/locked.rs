@@ -50,7 +50,7 @@ impl<'a> LockedHelper<'a> {
 		bucket_id: Uuid,
 		alias_name: &String,
 	) -> Result<(), Error> {
-		if !is_valid_bucket_name(alias_name) {
+		if !is_valid_bucket_name(alias_name, self.0.config.s3_api.relaxed_bucket_names) {
 			return Err(Error::InvalidBucketName(alias_name.to_string()));
 		}
 
@@ -211,7 +211,7 @@ impl<'a> LockedHelper<'a> {
 	) -> Result<(), Error> {
 		let key_helper = KeyHelper(self.0);
 
-		if !is_valid_bucket_name(alias_name) {
+		if !is_valid_bucket_name(alias_name, self.0.config.s3_api.relaxed_bucket_names) {
 			return Err(Error::InvalidBucketName(alias_name.to_string()));
 		}
 
@@ -173,6 +173,7 @@ impl<T: CountedItem> IndexCounter<T> {
 		system: Arc<System>,
 		replication: TableShardedReplication,
 		db: &db::Db,
+		merkle_update_batch_size: usize,
 	) -> Arc<Self> {
 		Arc::new(Self {
 			this_node: system.id,
@@ -186,6 +187,7 @@ impl<T: CountedItem> IndexCounter<T> {
 				replication,
 				system,
 				db,
+				merkle_update_batch_size,
 			),
 		})
 	}
@@ -249,6 +251,21 @@ impl<T: CountedItem> IndexCounter<T> {
 		Ok(())
 	}
 
+	/// Sum of the values of counter `name` over all entries counted on this node
+	/// (i.e. over the partitions of the counted table stored by this node)
+	pub fn local_total(&self, name: &str) -> Result<i64, Error> {
+		let mut total = 0;
+		for item in self.local_counter.iter()? {
+			let (_, bytes) = item?;
+			let ent = LocalCounterEntry::<T>::decode(&bytes)
+				.ok_or_message("Cannot decode local counter entry")?;
+			if let Some((_, v)) = ent.values.get(name) {
+				total += v;
+			}
+		}
+		Ok(total)
+	}
+
 	pub fn offline_recount_all<TS, TR>(
 		&self,
 		counted_table: &Arc<Table<TS, TR>>,
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::sync::watch;
+
+use garage_util::background::*;
+use garage_util::error::Error;
+use garage_util::time::*;
+
+use garage_table::replication::{TableReplication, TableShardedReplication};
+use garage_table::{PartitionKey, Table};
+
+use crate::k2v::item_table::*;
+use crate::k2v::rpc::K2VRpcHandler;
+
+/// Interval between two scans of the K2V item table for expired items
+const EXPIRY_SCAN_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Background worker that deletes K2V items whose values have all expired.
+/// Expired values are no longer returned to clients as soon as they expire,
+/// this worker only makes sure that they are eventually removed from storage.
+/// Each item is deleted by the first of its storage nodes only, so that
+/// the deletion is not sent once by every replica.
+pub struct K2VExpiryWorker {
+	item_table: Arc<Table<K2VItemTable, TableShardedReplication>>,
+	rpc: Arc<K2VRpcHandler>,
+
+	state: State,
+	last_run_deleted: Option<usize>,
+}
+
+enum State {
+	Idle { next_run: Instant },
+	Running { pos: Vec<u8>, deleted: usize },
+}
+
+impl K2VExpiryWorker {
+	pub(crate) fn new(
+		item_table: Arc<Table<K2VItemTable, TableShardedReplication>>,
+		rpc: Arc<K2VRpcHandler>,
+	) -> Self {
+		Self {
+			item_table,
+			rpc,
+			state: State::Idle {
+				next_run: Instant::now() + EXPIRY_SCAN_INTERVAL / 2,
+			},
+			last_run_deleted: None,
+		}
+	}
+
+	fn is_first_replica(
+		item_table: &Table<K2VItemTable, TableShardedReplication>,
+		item: &K2VItem,
+	) -> bool {
+		let replication = &item_table.data.replication;
+		let nodes = replication.storage_nodes(&item.partition.hash());
+		nodes.first() == Some(&replication.system.id)
+	}
+}
+
+#[async_trait]
+impl Worker for K2VExpiryWorker {
+	fn name(&self) -> String {
+		"K2V expiry worker".into()
+	}
+
+	fn status(&self) -> WorkerStatus {
+		let mut freeform = vec![];
+		if let Some(n) = self.last_run_deleted {
+			freeform.push(format!("Expired items deleted in last run: {}", n));
+		}
+		match &self.state {
+			State::Idle { next_run } => freeform.push(format!(
+				"Next run: {}",
+				(chrono::Utc::now() + (*next_run - Instant::now())).to_rfc3339()
+			)),
+			State::Running { deleted, .. } => {
+				freeform.push(format!("Running, deleted so far: {}", deleted))
+			}
+		}
+		WorkerStatus {
+			freeform,
+			..Default::default()
+		}
+	}
+
+	async fn work(&mut self, _must_exit: &mut watch::Receiver<bool>) -> Result<WorkerState, Error> {
+		let (pos, deleted) = match &mut self.state {
+			State::Idle { .. } => return Ok(WorkerState::Idle),
+			State::Running { pos, deleted } => (pos, deleted),
+		};
+
+		let now = now_msec();
+
+		// Process a batch of 100 items before yielding to bg task scheduler
+		for _ in 0..100 {
+			let (item_bytes, next_pos) = match self.item_table.data.store.get_gt(&*pos)? {
+				None => {
+					info!("K2V expiry worker finished, items deleted: {}", *deleted);
+					self.last_run_deleted = Some(*deleted);
+					self.state = State::Idle {
+						next_run: Instant::now() + EXPIRY_SCAN_INTERVAL,
+					};
+					return Ok(WorkerState::Idle);
+				}
+				Some((k, v)) => (v, k),
+			};
+
+			let item = self.item_table.data.decode_entry(&item_bytes)?;
+			if item.is_expired(now) && Self::is_first_replica(&self.item_table, &item) {
+				// The deletion only covers the values that were seen here,
+				// a value written concurrently is not removed.
+				let cc = item.causal_context();
+				self.rpc
+					.insert(
+						item.partition.bucket_id,
+						item.partition.partition_key,
+						item.sort_key,
+						Some(cc),
+						DvvsValue::Deleted,
+						None,
+					)
+					.await?;
+				*deleted += 1;
+			}
+			*pos = next_pos;
+		}
+
+		Ok(WorkerState::Busy)
+	}
+
+	async fn wait_for_work(&mut self) -> WorkerState {
+		if let State::Idle { next_run } = &self.state {
+			tokio::time::sleep_until((*next_run).into()).await;
+			self.state = State::Running {
+				pos: vec![],
+				deleted: 0,
+			};
+		}
+		WorkerState::Busy
+	}
+}
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 
 use garage_db as db;
 use garage_util::data::*;
+use garage_util::time::now_msec;
 
 use garage_table::crdt::*;
 use garage_table::*;
@@ -42,6 +43,10 @@ mod v08 {
 	pub struct DvvsEntry {
 		pub(super) t_discard: u64,
 		pub(super) values: Vec<(u64, DvvsValue)>,
+		/// Expiration date (in msec since the epoch) of the values inserted
+		/// with a TTL, indexed by the timestamp of the value in `values`
+		#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+		pub(super) expires: BTreeMap<u64, u64>,
 	}
 
 	#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
@@ -67,12 +72,14 @@ impl K2VItem {
 			items: BTreeMap::new(),
 		}
 	}
-	/// Updates a K2VItem with a new value or a deletion event
+	/// Updates a K2VItem with a new value or a deletion event,
+	/// optionally expiring at the given date (in msec since the epoch)
 	pub fn update(
 		&mut self,
 		this_node: Uuid,
 		context: &Option<CausalContext>,
 		new_value: DvvsValue,
+		expires_at: Option<u64>,
 		node_ts: u64,
 	) -> u64 {
 		if let Some(context) = context {
@@ -85,6 +92,7 @@ impl K2VItem {
 						DvvsEntry {
 							t_discard: *t_discard,
 							values: vec![],
+							expires: BTreeMap::new(),
 						},
 					);
 				}
@@ -97,10 +105,14 @@ impl K2VItem {
 		let e = self.items.entry(node_id).or_insert(DvvsEntry {
 			t_discard: 0,
 			values: vec![],
+			expires: BTreeMap::new(),
 		});
 		let t_prev = e.max_time();
 		let t_new = std::cmp::max(t_prev + 1, node_ts + 1);
 		e.values.push((t_new, new_value));
+		if let Some(expires_at) = expires_at {
+			e.expires.insert(t_new, expires_at);
+		}
 		t_new
 	}
 
@@ -126,6 +138,35 @@ impl K2VItem {
 		ret
 	}
 
+	/// Extract the list of values that have not expired at date `now`.
+	/// Expired values are still stored until the expiry worker replaces
+	/// them by a deletion, but they are no longer returned to clients.
+	pub fn values_at(&'_ self, now: u64) -> Vec<&'_ DvvsValue> {
+		let mut ret = vec![];
+		for (_, ent) in self.items.iter() {
+			for (t, v) in ent.values.iter() {
+				if !ent.is_expired(*t, now) && !ret.contains(&v) {
+					ret.push(v);
+				}
+			}
+		}
+		ret
+	}
+
+	/// Whether some values of this item have expired at date `now`,
+	/// and no value that has not expired remains
+	pub fn is_expired(&self, now: u64) -> bool {
+		let has_expired = self
+			.items
+			.values()
+			.any(|ent| ent.values.iter().any(|(t, _)| ent.is_expired(*t, now)));
+		has_expired
+			&& self
+				.values_at(now)
+				.iter()
+				.all(|v| matches!(v, DvvsValue::Deleted))
+	}
+
 	fn discard(&mut self) {
 		for (_, ent) in self.items.iter_mut() {
 			ent.discard();
@@ -140,11 +181,17 @@ impl DvvsEntry {
 			.fold(self.t_discard, |acc, (vts, _)| std::cmp::max(acc, *vts))
 	}
 
+	fn is_expired(&self, t: u64, now: u64) -> bool {
+		matches!(self.expires.get(&t), Some(expires_at) if *expires_at <= now)
+	}
+
 	fn discard(&mut self) {
 		self.values = std::mem::take(&mut self.values)
 			.into_iter()
 			.filter(|(t, _)| *t > self.t_discard)
 			.collect::<Vec<_>>();
+		let t_discard = self.t_discard;
+		self.expires.retain(|t, _| *t > t_discard);
 	}
 }
 
@@ -171,6 +218,11 @@ impl Crdt for DvvsEntry {
 				self.values.push((*vt, vv.clone()));
 			}
 		}
+		for (vt, exp) in other.expires.iter() {
+			if self.values.iter().any(|(t, _)| t == vt) {
+				self.expires.insert(*vt, *exp);
+			}
+		}
 	}
 }
 
@@ -248,9 +300,12 @@ impl TableSchema for K2VItemTable {
 
 	#[allow(clippy::nonminimal_bool)]
 	fn matches_filter(entry: &Self::E, filter: &Self::Filter) -> bool {
-		let v = entry.values();
+		// Expired values are filtered out as they would be when reading
+		// the item, even if they have not been deleted yet
+		let v = entry.values_at(now_msec());
 		!(filter.conflicts_only && v.len() < 2)
-			&& !(filter.exclude_only_tombstones && entry.is_tombstone())
+			&& !(filter.exclude_only_tombstones
+				&& v.iter().all(|v| matches!(v, DvvsValue::Deleted)))
 	}
 }
 
@@ -269,6 +324,9 @@ impl CountedItem for K2VItem {
 		&self.partition.partition_key
 	}
 
+	// The counts are computed on the values as returned to clients by ReadItem,
+	// so that ReadIndex is consistent with what can be read back:
+	// all concurrent values are counted, tombstones have no values nor bytes.
 	fn counts(&self) -> Vec<(&'static str, i64)> {
 		let values = self.values();
 
@@ -307,14 +365,123 @@ mod tests {
 				(5, DvvsValue::Value(vec![15])),
 				(6, DvvsValue::Value(vec![16])),
 			],
+			expires: BTreeMap::new(),
 		};
 		let e2 = DvvsEntry {
 			t_discard: 5,
 			values: vec![(6, DvvsValue::Value(vec![16])), (7, DvvsValue::Deleted)],
+			expires: BTreeMap::new(),
 		};
 
 		let mut e3 = e1;
 		e3.merge(&e2);
 		assert_eq!(e2, e3);
 	}
+
+	#[test]
+	fn test_update_discards_superseded_values() {
+		// K2V does not keep a history of the values of an item: values that
+		// are covered by the causal context of a write are discarded immediately,
+		// so an item that is repeatedly overwritten does not grow.
+		let node = gen_uuid();
+		let mut item = K2VItem::new(gen_uuid(), "partition".into(), "sort".into());
+
+		let mut ts = 0;
+		for i in 0..1000u32 {
+			let ct = Some(item.causal_context());
+			ts = item.update(
+				node,
+				&ct,
+				DvvsValue::Value(i.to_be_bytes().to_vec()),
+				None,
+				ts,
+			);
+		}
+		assert_eq!(item.items.len(), 1);
+		assert_eq!(item.items.values().next().unwrap().values.len(), 1);
+		assert_eq!(
+			item.values(),
+			vec![&DvvsValue::Value(999u32.to_be_bytes().to_vec())]
+		);
+
+		// A client polling with an old causal context still sees that the item
+		// has changed, as this only relies on the vector clock
+		let old_ct = CausalContext::new();
+		assert!(item.causal_context().is_newer_than(&old_ct));
+
+		// Concurrent writes (without causal context) are kept as conflicts
+		// until a write that covers them all is made
+		item.update(node, &None, DvvsValue::Deleted, None, ts);
+		assert_eq!(item.values().len(), 2);
+		let ct = Some(item.causal_context());
+		item.update(node, &ct, DvvsValue::Value(vec![42]), None, ts);
+		assert_eq!(item.values(), vec![&DvvsValue::Value(vec![42])]);
+	}
+
+	#[test]
+	fn test_counts_concurrent_values() {
+		let node = gen_uuid();
+		let count =
+			|item: &K2VItem| -> Vec<i64> { item.counts().iter().map(|(_, v)| *v).collect() };
+
+		let mut item = K2VItem::new(gen_uuid(), "partition".into(), "sort".into());
+		let ts = item.update(node, &None, DvvsValue::Value(vec![1; 10]), None, 0);
+		// entries, conflicts, values, bytes
+		assert_eq!(count(&item), vec![1, 0, 1, 10]);
+
+		// Two concurrent values: both are counted
+		let ts = item.update(node, &None, DvvsValue::Value(vec![2; 20]), None, ts);
+		assert_eq!(count(&item), vec![1, 1, 2, 30]);
+
+		// A concurrent deletion is a conflict but adds no value
+		let ts = item.update(node, &None, DvvsValue::Deleted, None, ts);
+		assert_eq!(count(&item), vec![1, 1, 2, 30]);
+
+		// Identical concurrent values are returned and counted once
+		let mut item2 = K2VItem::new(gen_uuid(), "partition".into(), "sort".into());
+		let ts2 = item2.update(node, &None, DvvsValue::Value(vec![3; 5]), None, 0);
+		item2.update(node, &None, DvvsValue::Value(vec![3; 5]), None, ts2);
+		assert_eq!(count(&item2), vec![1, 0, 1, 5]);
+
+		// Deleting with the full causal context leaves a tombstone
+		let ct = Some(item.causal_context());
+		item.update(node, &ct, DvvsValue::Deleted, None, ts);
+		assert_eq!(count(&item), vec![0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn test_values_expiry() {
+		let node = gen_uuid();
+		let mut item = K2VItem::new(gen_uuid(), "partition".into(), "sort".into());
+
+		// A value inserted with an expiration date is visible until that date
+		let ts = item.update(node, &None, DvvsValue::Value(vec![1]), Some(1000), 0);
+		assert_eq!(item.values_at(999), vec![&DvvsValue::Value(vec![1])]);
+		assert!(!item.is_expired(999));
+		assert!(item.values_at(1000).is_empty());
+		assert!(item.is_expired(1000));
+
+		// Expired values are still stored and counted until they are deleted
+		assert_eq!(item.values(), vec![&DvvsValue::Value(vec![1])]);
+		assert!(!item.is_tombstone());
+
+		// A concurrent value without TTL is still returned after expiry
+		let mut item2 = item.clone();
+		item2.update(node, &None, DvvsValue::Value(vec![2]), None, ts);
+		assert_eq!(item2.values_at(1000), vec![&DvvsValue::Value(vec![2])]);
+		assert!(!item2.is_expired(1000));
+
+		// The expiration date is kept when merging with other replicas
+		let mut item3 = K2VItem::new(gen_uuid(), "partition".into(), "sort".into());
+		item3.merge(&item);
+		assert!(item3.is_expired(1000));
+
+		// Deleting the expired value with its causal context leaves a tombstone,
+		// and the expiration date is discarded with the value
+		let ct = Some(item.causal_context());
+		item.update(node, &ct, DvvsValue::Deleted, None, ts);
+		assert!(item.is_tombstone());
+		assert!(!item.is_expired(1000));
+		assert!(item.items.values().all(|e| e.expires.is_empty()));
+	}
 }
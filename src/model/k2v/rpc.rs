@@ -65,6 +65,9 @@ struct InsertedItem {
 	sort_key: String,
 	causal_context: Option<CausalContext>,
 	value: DvvsValue,
+	/// Expiration date of the value in msec since the epoch, if it was inserted with a TTL
+	#[serde(default)]
+	expires_at: Option<u64>,
 }
 
 impl Rpc for K2VRpc {
@@ -118,7 +121,9 @@ impl K2VRpcHandler {
 		sort_key: String,
 		causal_context: Option<CausalContext>,
 		value: DvvsValue,
+		ttl_secs: Option<u64>,
 	) -> Result<(), Error> {
+		let expires_at = ttl_secs.map(|ttl| now_msec().saturating_add(ttl.saturating_mul(1000)));
 		let partition = K2VItemPartition {
 			bucket_id,
 			partition_key,
@@ -140,6 +145,7 @@ impl K2VRpcHandler {
 					sort_key,
 					causal_context,
 					value,
+					expires_at,
 				}),
 				RequestStrategy::with_priority(PRIO_NORMAL).with_quorum(1),
 			)
@@ -174,6 +180,7 @@ impl K2VRpcHandler {
 				sort_key,
 				causal_context,
 				value,
+				expires_at: None,
 			});
 		}
 
@@ -434,6 +441,7 @@ impl K2VRpcHandler {
 					self.system.id,
 					&item.causal_context,
 					item.value.clone(),
+					item.expires_at,
 					std::cmp::max(old_local_timestamp, now),
 				);
 
@@ -1,7 +1,11 @@
+use std::net::IpAddr;
+
 use serde::{Deserialize, Serialize};
 
-use garage_util::crdt::{self, Crdt};
+use garage_util::crdt::{self, AutoCrdt, Crdt};
 use garage_util::data::*;
+use garage_util::forwarded_headers::canonical_ip;
+use garage_util::time::now_msec;
 
 use garage_table::{DeletedFilter, EmptyKey, Entry, TableSchema};
 
@@ -11,6 +15,7 @@ mod v08 {
 	use crate::permission::BucketKeyPerm;
 	use garage_util::crdt;
 	use garage_util::data::Uuid;
+	use garage_util::forwarded_headers::IpNet;
 	use serde::{Deserialize, Serialize};
 
 	/// An api key
@@ -43,6 +48,32 @@ mod v08 {
 		/// A key can have a local view of buckets names it is
 		/// the only one to see, this is the namespace for these aliases
 		pub local_aliases: crdt::LwwMap<String, Option<Uuid>>,
+
+		/// Free-form description of the key
+		#[serde(default)]
+		pub description: crdt::Lww<Option<String>>,
+
+		/// Creation date of the key (immutable), in msec since UNIX epoch.
+		/// Zero for keys created before this field was introduced.
+		#[serde(default)]
+		pub created: u64,
+
+		/// Request and bandwidth rate limits applied to this key
+		#[serde(default)]
+		pub rate_limit: crdt::Lww<KeyRateLimit>,
+
+		/// IP ranges, in CIDR notation, from which requests can be made
+		/// with this key. Requests from any address are allowed if empty.
+		#[serde(default)]
+		pub allowed_cidrs: crdt::Lww<Vec<IpNet>>,
+	}
+
+	#[derive(Default, PartialEq, Eq, PartialOrd, Ord, Clone, Debug, Serialize, Deserialize)]
+	pub struct KeyRateLimit {
+		/// Maximum number of API requests per second
+		pub requests_per_sec: Option<u64>,
+		/// Maximum number of request and response body bytes per second
+		pub bytes_per_sec: Option<u64>,
 	}
 
 	impl garage_util::migrate::InitialFormat for Key {}
@@ -50,6 +81,17 @@ mod v08 {
 
 pub use v08::*;
 
+impl AutoCrdt for KeyRateLimit {
+	const WARN_IF_DIFFERENT: bool = true;
+}
+
+impl KeyRateLimit {
+	/// Returns true if no limit is set
+	pub fn is_unlimited(&self) -> bool {
+		self.requests_per_sec.is_none() && self.bytes_per_sec.is_none()
+	}
+}
+
 impl KeyParams {
 	fn new(secret_key: &str, name: &str) -> Self {
 		KeyParams {
@@ -58,7 +100,26 @@ impl KeyParams {
 			allow_create_bucket: crdt::Lww::new(false),
 			authorized_buckets: crdt::Map::new(),
 			local_aliases: crdt::LwwMap::new(),
+			description: crdt::Lww::new(None),
+			created: now_msec(),
+			rate_limit: crdt::Lww::new(KeyRateLimit::default()),
+			allowed_cidrs: crdt::Lww::new(vec![]),
+		}
+	}
+
+	/// Check whether requests from the given client address can be made with
+	/// this key. If the key is restricted to some IP ranges, requests whose
+	/// client address is unknown are refused.
+	pub fn allows_source_ip(&self, ip: Option<IpAddr>) -> bool {
+		let allowed_cidrs = self.allowed_cidrs.get();
+		if allowed_cidrs.is_empty() {
+			return true;
 		}
+		let ip = match ip {
+			Some(ip) => canonical_ip(ip),
+			None => return false,
+		};
+		allowed_cidrs.iter().any(|net| net.contains(&ip))
 	}
 }
 
@@ -68,6 +129,10 @@ impl Crdt for KeyParams {
 		self.allow_create_bucket.merge(&o.allow_create_bucket);
 		self.authorized_buckets.merge(&o.authorized_buckets);
 		self.local_aliases.merge(&o.local_aliases);
+		self.description.merge(&o.description);
+		self.created = std::cmp::max(self.created, o.created);
+		self.rate_limit.merge(&o.rate_limit);
+		self.allowed_cidrs.merge(&o.allowed_cidrs);
 	}
 }
 
@@ -192,3 +257,82 @@ impl TableSchema for KeyTable {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use garage_util::forwarded_headers::IpNet;
+	use garage_util::migrate::Migrate;
+
+	/// Encoding of keys before the `description` and `created` fields were added
+	mod old {
+		use crate::permission::BucketKeyPerm;
+		use garage_util::crdt;
+		use garage_util::data::Uuid;
+		use serde::{Deserialize, Serialize};
+
+		#[derive(Serialize, Deserialize)]
+		pub struct Key {
+			pub key_id: String,
+			pub state: crdt::Deletable<KeyParams>,
+		}
+
+		#[derive(Serialize, Deserialize)]
+		pub struct KeyParams {
+			pub secret_key: String,
+			pub name: crdt::Lww<String>,
+			pub allow_create_bucket: crdt::Lww<bool>,
+			pub authorized_buckets: crdt::Map<Uuid, BucketKeyPerm>,
+			pub local_aliases: crdt::LwwMap<String, Option<Uuid>>,
+		}
+
+		impl garage_util::migrate::InitialFormat for Key {}
+	}
+
+	#[test]
+	fn decode_key_without_description() {
+		let old_key = old::Key {
+			key_id: "GK31c2f218a2e44f485b94239e".to_string(),
+			state: crdt::Deletable::Present(old::KeyParams {
+				secret_key: "b892c0665f0ada8a4755dae98baa3b133590e11dae3bcc1f9d769d67f16c3835"
+					.to_string(),
+				name: crdt::Lww::new("old key".to_string()),
+				allow_create_bucket: crdt::Lww::new(true),
+				authorized_buckets: crdt::Map::new(),
+				local_aliases: crdt::LwwMap::new(),
+			}),
+		};
+		let encoded = old_key.encode().unwrap();
+
+		let key = Key::decode(&encoded).expect("old key encoding should decode");
+		assert_eq!(key.key_id, "GK31c2f218a2e44f485b94239e");
+		let params = key.params().unwrap();
+		assert_eq!(params.name.get(), "old key");
+		assert!(*params.allow_create_bucket.get());
+		assert_eq!(params.description.get(), &None);
+		assert_eq!(params.created, 0);
+		assert!(params.rate_limit.get().is_unlimited());
+		assert!(params.allowed_cidrs.get().is_empty());
+	}
+
+	#[test]
+	fn test_allows_source_ip() {
+		let ip = |s: &str| Some(s.parse::<IpAddr>().unwrap());
+		let mut key = Key::new("test");
+		let params = key.params_mut().unwrap();
+
+		assert!(params.allows_source_ip(ip("192.0.2.1")));
+		assert!(params.allows_source_ip(None));
+
+		params.allowed_cidrs.update(vec![
+			"192.0.2.0/24".parse::<IpNet>().unwrap(),
+			"2001:db8::1/128".parse::<IpNet>().unwrap(),
+		]);
+		assert!(params.allows_source_ip(ip("192.0.2.1")));
+		assert!(params.allows_source_ip(ip("::ffff:192.0.2.1")));
+		assert!(params.allows_source_ip(ip("2001:db8::1")));
+		assert!(!params.allows_source_ip(ip("2001:db8::2")));
+		assert!(!params.allows_source_ip(ip("198.51.100.1")));
+		assert!(!params.allows_source_ip(None));
+	}
+}
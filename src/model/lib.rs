@@ -15,4 +15,6 @@ pub mod s3;
 
 pub mod garage;
 pub mod helper;
+pub mod quota;
+pub mod rate_limit;
 pub mod snapshot;
@@ -0,0 +1,215 @@
+//! Quota reservations held in memory on each node, used by buckets with
+//! strict quotas to account for writes not yet visible in the object counters
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use garage_util::data::Uuid;
+
+/// How long a reservation is still counted after the write it was made for
+/// has completed, to give time to the object counters to catch up
+pub const RESERVATION_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Objects and bytes reserved by writes currently in progress on this node
+#[derive(Default)]
+pub struct QuotaReservations {
+	buckets: Mutex<HashMap<Uuid, Vec<Reservation>>>,
+	next_id: AtomicU64,
+}
+
+struct Reservation {
+	id: u64,
+	objects: i64,
+	bytes: i64,
+	/// None while the write is in progress
+	expires: Option<Instant>,
+}
+
+/// A reservation of objects and bytes in the quotas of a bucket.
+/// If the write it was made for has completed, it must be committed so that
+/// it keeps being counted for a grace period; otherwise it is released as soon
+/// as it is dropped.
+pub struct QuotaReservation {
+	reservations: Arc<QuotaReservations>,
+	bucket_id: Uuid,
+	id: u64,
+	committed: bool,
+}
+
+impl QuotaReservations {
+	pub fn new() -> Arc<Self> {
+		Arc::new(Self::default())
+	}
+
+	/// Reserve `objects` objects and `bytes` bytes in the quotas of `bucket_id`.
+	/// `check` is called with the number of objects and bytes already reserved
+	/// in the bucket by other writes, and the reservation is made only if it
+	/// returns `Ok`; this happens atomically with respect to other reservations
+	/// made on this node.
+	pub fn reserve<E>(
+		self: &Arc<Self>,
+		bucket_id: Uuid,
+		objects: i64,
+		bytes: i64,
+		check: impl FnOnce(i64, i64) -> Result<(), E>,
+	) -> Result<QuotaReservation, E> {
+		self.reserve_at(bucket_id, objects, bytes, check, Instant::now())
+	}
+
+	fn reserve_at<E>(
+		self: &Arc<Self>,
+		bucket_id: Uuid,
+		objects: i64,
+		bytes: i64,
+		check: impl FnOnce(i64, i64) -> Result<(), E>,
+		now: Instant,
+	) -> Result<QuotaReservation, E> {
+		let mut buckets = self.buckets.lock().unwrap();
+
+		let bucket = buckets.entry(bucket_id).or_default();
+		bucket.retain(|r| r.expires.map(|t| t > now).unwrap_or(true));
+
+		let pending_objects = bucket.iter().map(|r| r.objects).sum();
+		let pending_bytes = bucket.iter().map(|r| r.bytes).sum();
+		if let Err(e) = check(pending_objects, pending_bytes) {
+			if bucket.is_empty() {
+				buckets.remove(&bucket_id);
+			}
+			return Err(e);
+		}
+
+		let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+		bucket.push(Reservation {
+			id,
+			objects: std::cmp::max(objects, 0),
+			bytes: std::cmp::max(bytes, 0),
+			expires: None,
+		});
+
+		Ok(QuotaReservation {
+			reservations: self.clone(),
+			bucket_id,
+			id,
+			committed: false,
+		})
+	}
+
+	fn release(&self, bucket_id: Uuid, id: u64, expires: Option<Instant>) {
+		let mut buckets = self.buckets.lock().unwrap();
+		if let Some(bucket) = buckets.get_mut(&bucket_id) {
+			match expires {
+				Some(t) => {
+					if let Some(r) = bucket.iter_mut().find(|r| r.id == id) {
+						r.expires = Some(t);
+					}
+				}
+				None => bucket.retain(|r| r.id != id),
+			}
+			if bucket.is_empty() {
+				buckets.remove(&bucket_id);
+			}
+		}
+	}
+}
+
+impl QuotaReservation {
+	/// Mark the write this reservation was made for as completed
+	pub fn commit(mut self) {
+		self.committed = true;
+	}
+}
+
+impl Drop for QuotaReservation {
+	fn drop(&mut self) {
+		let expires = match self.committed {
+			true => Some(Instant::now() + RESERVATION_GRACE_PERIOD),
+			false => None,
+		};
+		self.reservations.release(self.bucket_id, self.id, expires);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use garage_util::data::gen_uuid;
+
+	fn pending(res: &Arc<QuotaReservations>, bucket_id: Uuid, now: Instant) -> (i64, i64) {
+		let mut ret = (0, 0);
+		let reserved = res.reserve_at(
+			bucket_id,
+			0,
+			0,
+			|o, b| {
+				ret = (o, b);
+				Err(())
+			},
+			now,
+		);
+		assert!(reserved.is_err());
+		ret
+	}
+
+	#[test]
+	fn committed_reservations_are_counted_until_grace_period_ends() {
+		let res = QuotaReservations::new();
+		let bucket = gen_uuid();
+		let now = Instant::now();
+
+		let r1 = res
+			.reserve_at(bucket, 1, 100, |_, _| Ok::<_, ()>(()), now)
+			.unwrap();
+		let r2 = res
+			.reserve_at(bucket, 1, 50, |_, _| Ok::<_, ()>(()), now)
+			.unwrap();
+		assert_eq!(pending(&res, bucket, now), (2, 150));
+
+		// Other buckets are not affected
+		assert_eq!(pending(&res, gen_uuid(), now), (0, 0));
+
+		r1.commit();
+		assert_eq!(pending(&res, bucket, Instant::now()), (2, 150));
+		assert_eq!(
+			pending(&res, bucket, Instant::now() + RESERVATION_GRACE_PERIOD * 2),
+			(1, 50)
+		);
+
+		// Reservations of writes that did not complete are released immediately
+		drop(r2);
+		assert_eq!(pending(&res, bucket, now), (0, 0));
+	}
+
+	#[test]
+	fn refused_reservation_is_not_counted() {
+		let res = QuotaReservations::new();
+		let bucket = gen_uuid();
+		let now = Instant::now();
+
+		let _r = res
+			.reserve_at(bucket, 1, 10, |_, _| Ok::<_, ()>(()), now)
+			.unwrap();
+		assert!(res
+			.reserve_at(
+				bucket,
+				1,
+				10,
+				|o, _| if o >= 1 { Err(()) } else { Ok(()) },
+				now
+			)
+			.is_err());
+		assert_eq!(pending(&res, bucket, now), (1, 10));
+	}
+
+	#[test]
+	fn negative_differences_are_not_reserved() {
+		let res = QuotaReservations::new();
+		let bucket = gen_uuid();
+		let now = Instant::now();
+
+		let _r = res
+			.reserve_at(bucket, 0, -1000, |_, _| Ok::<_, ()>(()), now)
+			.unwrap();
+		assert_eq!(pending(&res, bucket, now), (0, 0));
+	}
+}
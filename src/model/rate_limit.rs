@@ -0,0 +1,237 @@
+//! Per-access-key rate limiting, using token buckets held in memory on each node
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::key_table::KeyRateLimit;
+
+/// Token buckets for all access keys that have a rate limit configured
+#[derive(Default)]
+pub struct RateLimiter {
+	buckets: Mutex<HashMap<String, KeyBuckets>>,
+}
+
+#[derive(Default)]
+struct KeyBuckets {
+	requests: Option<TokenBucket>,
+	bytes: Option<TokenBucket>,
+}
+
+struct TokenBucket {
+	/// Number of tokens added per second, also the maximum number of tokens
+	rate: f64,
+	tokens: f64,
+	last_refill: Instant,
+}
+
+impl RateLimiter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Account for a request made with access key `key_id`, that will transfer
+	/// approximately `bytes_hint` bytes. Returns `Err` with the delay after which
+	/// the client should retry if the request must be rejected.
+	pub fn check_rate_limit(
+		&self,
+		key_id: &str,
+		limit: &KeyRateLimit,
+		bytes_hint: u64,
+	) -> Result<(), Duration> {
+		self.check_rate_limit_at(key_id, limit, bytes_hint, Instant::now())
+	}
+
+	fn check_rate_limit_at(
+		&self,
+		key_id: &str,
+		limit: &KeyRateLimit,
+		bytes_hint: u64,
+		now: Instant,
+	) -> Result<(), Duration> {
+		let mut buckets = self.buckets.lock().unwrap();
+
+		if limit.is_unlimited() {
+			buckets.remove(key_id);
+			return Ok(());
+		}
+
+		let key_buckets = buckets.entry(key_id.to_string()).or_default();
+		let requests = TokenBucket::update(&mut key_buckets.requests, limit.requests_per_sec, now);
+		let bytes = TokenBucket::update(&mut key_buckets.bytes, limit.bytes_per_sec, now);
+
+		// Check both limits before consuming tokens from either of them
+		let wait = [
+			requests.as_deref().and_then(|b| b.wait_time(1.0)),
+			bytes
+				.as_deref()
+				.and_then(|b| b.wait_time(bytes_hint as f64)),
+		]
+		.iter()
+		.flatten()
+		.copied()
+		.max();
+		if let Some(wait) = wait {
+			return Err(wait);
+		}
+
+		if let Some(b) = requests {
+			b.tokens -= 1.0;
+		}
+		if let Some(b) = bytes {
+			b.tokens -= bytes_hint as f64;
+		}
+		Ok(())
+	}
+
+	/// Take `bytes` tokens from the bandwidth bucket of access key `key_id`
+	/// without checking the limit, for data whose size was not known when the
+	/// request was accepted, e.g. a response body. The bucket can go into debt,
+	/// which delays the next requests made with this key.
+	pub fn consume_bytes(&self, key_id: &str, limit: &KeyRateLimit, bytes: u64) {
+		self.consume_bytes_at(key_id, limit, bytes, Instant::now())
+	}
+
+	fn consume_bytes_at(&self, key_id: &str, limit: &KeyRateLimit, bytes: u64, now: Instant) {
+		if limit.bytes_per_sec.is_none() {
+			return;
+		}
+		let mut buckets = self.buckets.lock().unwrap();
+		let key_buckets = buckets.entry(key_id.to_string()).or_default();
+		if let Some(b) = TokenBucket::update(&mut key_buckets.bytes, limit.bytes_per_sec, now) {
+			b.tokens -= bytes as f64;
+		}
+	}
+}
+
+impl TokenBucket {
+	/// Make bucket match the configured rate and refill it with tokens
+	/// accumulated since last time
+	fn update(
+		bucket: &mut Option<TokenBucket>,
+		rate: Option<u64>,
+		now: Instant,
+	) -> Option<&mut TokenBucket> {
+		let rate = match rate {
+			Some(r) => r as f64,
+			None => {
+				*bucket = None;
+				return None;
+			}
+		};
+
+		let b = bucket.get_or_insert_with(|| TokenBucket {
+			rate,
+			tokens: rate,
+			last_refill: now,
+		});
+		b.rate = rate;
+		let elapsed = now.saturating_duration_since(b.last_refill).as_secs_f64();
+		b.tokens = f64::min(b.tokens + elapsed * rate, rate);
+		b.last_refill = now;
+		Some(b)
+	}
+
+	/// Return the time to wait before `cost` tokens can be taken from this bucket,
+	/// or None if they can be taken now. Costs larger than the bucket's capacity
+	/// are allowed when the bucket is full, leaving it in debt.
+	fn wait_time(&self, cost: f64) -> Option<Duration> {
+		let needed = f64::min(cost, self.rate);
+		if self.tokens >= needed {
+			None
+		} else if self.rate <= 0.0 {
+			Some(Duration::from_secs(1))
+		} else {
+			Some(Duration::from_secs_f64((needed - self.tokens) / self.rate))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn burst_of_requests_is_throttled() {
+		let limiter = RateLimiter::new();
+		let limit = KeyRateLimit {
+			requests_per_sec: Some(5),
+			bytes_per_sec: None,
+		};
+		let now = Instant::now();
+
+		for _ in 0..5 {
+			assert!(limiter.check_rate_limit_at("GK1", &limit, 0, now).is_ok());
+		}
+		let wait = limiter
+			.check_rate_limit_at("GK1", &limit, 0, now)
+			.unwrap_err();
+		assert!(wait > Duration::ZERO && wait <= Duration::from_secs(1));
+
+		// Other keys are not affected
+		assert!(limiter.check_rate_limit_at("GK2", &limit, 0, now).is_ok());
+
+		// Tokens come back with time
+		let later = now + Duration::from_millis(300);
+		assert!(limiter.check_rate_limit_at("GK1", &limit, 0, later).is_ok());
+		assert!(limiter
+			.check_rate_limit_at("GK1", &limit, 0, later)
+			.is_err());
+	}
+
+	#[test]
+	fn bandwidth_is_throttled() {
+		let limiter = RateLimiter::new();
+		let limit = KeyRateLimit {
+			requests_per_sec: None,
+			bytes_per_sec: Some(1000),
+		};
+		let now = Instant::now();
+
+		// A request larger than the capacity is allowed on a full bucket...
+		assert!(limiter
+			.check_rate_limit_at("GK1", &limit, 3000, now)
+			.is_ok());
+		// ...but then the debt has to be paid back before the next one
+		let wait = limiter
+			.check_rate_limit_at("GK1", &limit, 10, now)
+			.unwrap_err();
+		assert!(wait >= Duration::from_secs(2));
+		assert!(limiter
+			.check_rate_limit_at("GK1", &limit, 10, now + Duration::from_secs(3))
+			.is_ok());
+	}
+
+	#[test]
+	fn response_bytes_are_charged() {
+		let limiter = RateLimiter::new();
+		let limit = KeyRateLimit {
+			requests_per_sec: None,
+			bytes_per_sec: Some(1000),
+		};
+		let now = Instant::now();
+
+		// A download is accepted, its body is charged as it is sent...
+		assert!(limiter.check_rate_limit_at("GK1", &limit, 0, now).is_ok());
+		limiter.consume_bytes_at("GK1", &limit, 2500, now);
+		// ...and the next requests wait until it has been paid back
+		let wait = limiter
+			.check_rate_limit_at("GK1", &limit, 0, now)
+			.unwrap_err();
+		assert!(wait >= Duration::from_secs(1));
+		assert!(limiter
+			.check_rate_limit_at("GK1", &limit, 0, now + Duration::from_secs(2))
+			.is_ok());
+	}
+
+	#[test]
+	fn unlimited_key_is_not_throttled() {
+		let limiter = RateLimiter::new();
+		let limit = KeyRateLimit::default();
+		let now = Instant::now();
+		for _ in 0..1000 {
+			assert!(limiter
+				.check_rate_limit_at("GK1", &limit, 1 << 30, now)
+				.is_ok());
+		}
+	}
+}
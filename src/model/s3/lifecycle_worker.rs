@@ -37,6 +37,11 @@ pub struct LifecycleWorker {
 	garage: Arc<Garage>,
 
 	state: State,
+	/// If set, the worker runs again after this interval instead of
+	/// waiting for the next day
+	scan_interval: Option<Duration>,
+	/// Time at which the last run completed (or at which the worker started)
+	completed_at: u64,
 
 	persister: PersisterShared<LifecycleWorkerPersisted>,
 }
@@ -69,7 +74,11 @@ pub fn register_bg_vars(
 }
 
 impl LifecycleWorker {
-	pub fn new(garage: Arc<Garage>, persister: PersisterShared<LifecycleWorkerPersisted>) -> Self {
+	pub fn new(
+		garage: Arc<Garage>,
+		persister: PersisterShared<LifecycleWorkerPersisted>,
+		scan_interval: Option<Duration>,
+	) -> Self {
 		let today = today();
 		let last_completed = persister.get_with(|x| {
 			x.last_completed
@@ -83,6 +92,8 @@ impl LifecycleWorker {
 		Self {
 			garage,
 			state,
+			scan_interval,
+			completed_at: now_msec(),
 			persister,
 		}
 	}
@@ -167,6 +178,7 @@ impl Worker for LifecycleWorker {
 							self.persister
 								.set_with(|x| x.last_completed = Some(date.to_string()))?;
 							self.state = State::Completed(*date);
+							self.completed_at = now_msec();
 							return Ok(WorkerState::Idle);
 						}
 						Some((k, v)) => (v, k),
@@ -205,8 +217,14 @@ impl Worker for LifecycleWorker {
 	async fn wait_for_work(&mut self) -> WorkerState {
 		match &self.state {
 			State::Completed(d) => {
-				let next_day = d.succ_opt().expect("no next day");
-				let next_start = midnight_ts(next_day);
+				let next_day = match self.scan_interval {
+					Some(_) => *d,
+					None => d.succ_opt().expect("no next day"),
+				};
+				let next_start = match self.scan_interval {
+					Some(itv) => self.completed_at + itv.as_millis() as u64,
+					None => midnight_ts(next_day),
+				};
 				loop {
 					let now = now_msec();
 					if now < next_start {
@@ -296,6 +314,8 @@ async fn process_object(
 
 				let size_match = check_size_filter(current_version_data, &rule.filter);
 				let date_match = match expire {
+					// A rule with zero days expires all objects on the next run
+					LifecycleExpiration::AfterDays(0) => true,
 					LifecycleExpiration::AfterDays(n_days) => {
 						(now_date - version_date) >= chrono::Duration::days(*n_days as i64)
 					}
@@ -309,7 +329,12 @@ async fn process_object(
 					}
 				};
 
-				if size_match && date_match {
+				let retained = current_version
+					.retention()
+					.map(|r| now_msec() < r.retain_until)
+					.unwrap_or(false);
+
+				if size_match && date_match && !retained {
 					// Delete expired version
 					let deleted_object = Object::new(
 						object.bucket_id,
@@ -0,0 +1,46 @@
+use opentelemetry::{global, metrics::*, KeyValue};
+
+use garage_util::data::*;
+
+/// Metrics about objects written through the S3 API
+pub struct S3Metrics {
+	pub(crate) objects_stored: Counter<u64>,
+	pub(crate) bytes_stored: Counter<u64>,
+}
+
+impl S3Metrics {
+	pub fn new() -> Self {
+		let meter = global::meter("garage_model/s3");
+		Self {
+			objects_stored: meter
+				.u64_counter("s3.objects_stored")
+				.with_description(
+					"Number of objects stored, inline in the object table or as data blocks",
+				)
+				.init(),
+			bytes_stored: meter
+				.u64_counter("s3.object_bytes_stored")
+				.with_description(
+					"Number of bytes of object data stored, inline in the object table or as data blocks",
+				)
+				.init(),
+		}
+	}
+
+	/// Record that an object of `size` bytes has been stored in a bucket,
+	/// either inline in the object table or as data blocks
+	pub fn record_object_stored(&self, bucket_id: &Uuid, inline: bool, size: u64) {
+		let attrs = [
+			KeyValue::new("bucket_id", hex::encode(bucket_id)),
+			KeyValue::new("storage", if inline { "inline" } else { "blocks" }),
+		];
+		self.objects_stored.add(1, &attrs);
+		self.bytes_stored.add(size, &attrs);
+	}
+}
+
+impl Default for S3Metrics {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -3,4 +3,7 @@ pub mod mpu_table;
 pub mod object_table;
 pub mod version_table;
 
+pub mod metrics;
+
 pub mod lifecycle_worker;
+pub mod stale_upload_worker;
@@ -176,6 +176,7 @@ mod v010 {
 	use serde::{Deserialize, Serialize};
 
 	use super::v09;
+	use crate::bucket_table::ObjectLockMode;
 
 	/// An object
 	#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
@@ -212,6 +213,9 @@ mod v010 {
 			checksum_algorithm: Option<ChecksumAlgorithm>,
 			/// Encryption params + headers to be included in the final object
 			encryption: ObjectVersionEncryption,
+			/// Storage class to be recorded in the final object
+			#[serde(default, skip_serializing_if = "Option::is_none")]
+			storage_class: Option<String>,
 		},
 		/// The version is fully received
 		Complete(ObjectVersionData),
@@ -243,6 +247,24 @@ mod v010 {
 		pub etag: String,
 		/// Encryption params + headers (encrypted or plaintext)
 		pub encryption: ObjectVersionEncryption,
+		/// Storage class requested by the client when the object was uploaded,
+		/// if it is not the default one. Garage stores all objects the same way,
+		/// this is only recorded to be returned to clients.
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub storage_class: Option<String>,
+		/// Object lock retention that applied to the object version when it
+		/// was written, if the bucket had a default retention at that time
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub retention: Option<ObjectVersionRetention>,
+	}
+
+	/// Object lock retention of an object version
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Serialize, Deserialize)]
+	pub struct ObjectVersionRetention {
+		/// Retention mode, as configured on the bucket when the version was written
+		pub mode: ObjectLockMode,
+		/// Timestamp (in msec) until which the version cannot be deleted or overwritten
+		pub retain_until: u64,
 	}
 
 	/// Encryption information + metadata
@@ -314,6 +336,7 @@ mod v010 {
 						multipart,
 						checksum_algorithm: None,
 						encryption: migrate_headers(headers),
+						storage_class: None,
 					}
 				}
 				v09::ObjectVersionState::Complete(d) => {
@@ -341,6 +364,8 @@ mod v010 {
 			size: old.size,
 			etag: old.etag,
 			encryption: migrate_headers(old.headers),
+			storage_class: None,
+			retention: None,
 		}
 	}
 
@@ -466,6 +491,17 @@ impl ObjectVersion {
 			_ => false,
 		}
 	}
+
+	/// Object lock retention recorded on the version, if it holds data
+	pub fn retention(&self) -> Option<&ObjectVersionRetention> {
+		match &self.state {
+			ObjectVersionState::Complete(ObjectVersionData::Inline(meta, _))
+			| ObjectVersionState::Complete(ObjectVersionData::FirstBlock(meta, _)) => {
+				meta.retention.as_ref()
+			}
+			_ => None,
+		}
+	}
 }
 
 impl Entry<Uuid, String> for Object {
@@ -688,3 +724,34 @@ impl CountedItem for Object {
 		]
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_merge_keeps_only_last_complete_version() {
+		// Objects do not accumulate versions: only the last complete version
+		// is kept, older versions are dropped (and their data is freed through
+		// the deletion propagation in ObjectTable::updated)
+		let bucket_id = gen_uuid();
+		let mut object = Object::new(bucket_id, "key".into(), vec![]);
+		let mut last = None;
+		for ts in 1..=5 {
+			let uuid = gen_uuid();
+			let other = Object::new(
+				bucket_id,
+				"key".into(),
+				vec![ObjectVersion {
+					uuid,
+					timestamp: ts,
+					state: ObjectVersionState::Complete(ObjectVersionData::DeleteMarker),
+				}],
+			);
+			object.merge(&other);
+			last = Some(uuid);
+		}
+		assert_eq!(object.versions().len(), 1);
+		assert_eq!(Some(object.versions()[0].uuid), last);
+	}
+}
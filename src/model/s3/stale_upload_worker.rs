@@ -0,0 +1,189 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use opentelemetry::{global, metrics::BoundValueRecorder};
+use tokio::sync::watch;
+
+use garage_util::background::*;
+use garage_util::error::Error;
+use garage_util::time::*;
+
+use garage_table::EmptyKey;
+
+use crate::s3::object_table::*;
+
+use crate::garage::Garage;
+
+/// Background worker that aborts uploads that have been in progress for
+/// longer than a configured age, so that their blocks can be reclaimed.
+pub struct StaleUploadWorker {
+	garage: Arc<Garage>,
+	max_age: Duration,
+	scan_interval: Duration,
+
+	state: State,
+	last_run_aborted: Option<usize>,
+
+	aborted_per_run: BoundValueRecorder<u64>,
+}
+
+enum State {
+	Idle { next_run: Instant },
+	Running { pos: Vec<u8>, aborted: usize },
+}
+
+impl StaleUploadWorker {
+	pub(crate) fn new(garage: Arc<Garage>, max_age: Duration, scan_interval: Duration) -> Self {
+		let meter = global::meter("garage_model/s3");
+		let aborted_per_run = meter
+			.u64_value_recorder("s3.stale_uploads_aborted")
+			.with_description(
+				"Number of stale uploads aborted in each run of the stale upload worker",
+			)
+			.init()
+			.bind(&[]);
+
+		Self {
+			garage,
+			max_age,
+			scan_interval,
+			state: State::Idle {
+				next_run: Instant::now() + scan_interval / 2,
+			},
+			last_run_aborted: None,
+			aborted_per_run,
+		}
+	}
+}
+
+#[async_trait]
+impl Worker for StaleUploadWorker {
+	fn name(&self) -> String {
+		"Stale upload worker".into()
+	}
+
+	fn status(&self) -> WorkerStatus {
+		let mut freeform = vec![format!("Max upload age: {:?}", self.max_age)];
+		if let Some(n) = self.last_run_aborted {
+			freeform.push(format!("Aborted in last run: {}", n));
+		}
+		match &self.state {
+			State::Idle { next_run } => freeform.push(format!(
+				"Next run: {}",
+				(chrono::Utc::now() + (*next_run - Instant::now())).to_rfc3339()
+			)),
+			State::Running { aborted, .. } => {
+				freeform.push(format!("Running, aborted so far: {}", aborted))
+			}
+		}
+		WorkerStatus {
+			freeform,
+			..Default::default()
+		}
+	}
+
+	async fn work(&mut self, _must_exit: &mut watch::Receiver<bool>) -> Result<WorkerState, Error> {
+		let (pos, aborted) = match &mut self.state {
+			State::Idle { .. } => return Ok(WorkerState::Idle),
+			State::Running { pos, aborted } => (pos, aborted),
+		};
+
+		let older_than = now_msec().saturating_sub(self.max_age.as_millis() as u64);
+
+		// Process a batch of 100 items before yielding to bg task scheduler
+		for _ in 0..100 {
+			let (object_bytes, next_pos) = match self.garage.object_table.data.store.get_gt(&*pos)? {
+				None => {
+					info!(
+						"Stale upload worker finished, uploads aborted: {}",
+						*aborted
+					);
+					self.aborted_per_run.record(*aborted as u64);
+					self.last_run_aborted = Some(*aborted);
+					self.state = State::Idle {
+						next_run: Instant::now() + self.scan_interval,
+					};
+					return Ok(WorkerState::Idle);
+				}
+				Some((k, v)) => (v, k),
+			};
+
+			let object = self.garage.object_table.data.decode_entry(&object_bytes)?;
+			*aborted += abort_stale_uploads(&self.garage, &object, older_than).await?;
+			*pos = next_pos;
+		}
+
+		Ok(WorkerState::Busy)
+	}
+
+	async fn wait_for_work(&mut self) -> WorkerState {
+		if let State::Idle { next_run } = &self.state {
+			tokio::time::sleep_until((*next_run).into()).await;
+			self.state = State::Running {
+				pos: vec![],
+				aborted: 0,
+			};
+		}
+		WorkerState::Busy
+	}
+}
+
+async fn abort_stale_uploads(
+	garage: &Arc<Garage>,
+	object: &Object,
+	older_than: u64,
+) -> Result<usize, Error> {
+	let mut aborted_versions = vec![];
+
+	for v in object.versions().iter() {
+		let multipart = match &v.state {
+			ObjectVersionState::Uploading { multipart, .. } => *multipart,
+			_ => continue,
+		};
+		if v.timestamp >= older_than {
+			continue;
+		}
+
+		// A multipart upload may have been started long ago but still be
+		// receiving parts: only abort it if no part was uploaded recently.
+		if multipart {
+			if let Some(mpu) = garage.mpu_table.get(&v.uuid, &EmptyKey).await? {
+				let last_activity = mpu
+					.parts
+					.items()
+					.iter()
+					.map(|(k, _)| k.timestamp)
+					.max()
+					.unwrap_or(mpu.timestamp);
+				if last_activity >= older_than {
+					continue;
+				}
+			}
+		}
+
+		aborted_versions.push(ObjectVersion {
+			state: ObjectVersionState::Aborted,
+			..*v
+		});
+	}
+
+	if aborted_versions.is_empty() {
+		return Ok(0);
+	}
+
+	let n_aborted = aborted_versions.len();
+	info!(
+		"Aborting {} stale upload(s) in bucket {:?}",
+		n_aborted, object.bucket_id
+	);
+	let aborted_object = Object::new(object.bucket_id, object.key.clone(), aborted_versions);
+	garage
+		.object_table
+		.data
+		.store
+		.db()
+		.transaction(|tx| garage.object_table.queue_insert(tx, &aborted_object))?;
+
+	Ok(n_aborted)
+}
@@ -1,5 +1,6 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
@@ -21,17 +22,23 @@ static SNAPSHOT_MUTEX: Mutex<()> = Mutex::new(());
 // ================ snapshotting logic =====================
 
 /// Run snashot_metadata in a blocking thread and async await on it
-pub async fn async_snapshot_metadata(garage: &Arc<Garage>) -> Result<(), Error> {
+pub async fn async_snapshot_metadata(
+	garage: &Arc<Garage>,
+	output_dir: Option<PathBuf>,
+) -> Result<PathBuf, Error> {
 	let garage = garage.clone();
-	let worker = tokio::task::spawn_blocking(move || snapshot_metadata(&garage));
-	worker.await.unwrap()?;
-	Ok(())
+	let worker =
+		tokio::task::spawn_blocking(move || snapshot_metadata(&garage, output_dir.as_deref()));
+	worker.await.unwrap()
 }
 
 /// Take a snapshot of the metadata database, and erase older
-/// snapshots if necessary.
+/// snapshots if necessary. The snapshot is named after the current time
+/// and the database engine, and is written in `output_dir` if given
+/// (in which case no cleanup is done), or in the `snapshots` directory
+/// of the metadata directory. Returns the path of the snapshot.
 /// This is not an async function, it should be spawned on a thread pool
-pub fn snapshot_metadata(garage: &Garage) -> Result<(), Error> {
+pub fn snapshot_metadata(garage: &Garage, output_dir: Option<&Path>) -> Result<PathBuf, Error> {
 	let lock = match SNAPSHOT_MUTEX.try_lock() {
 		Ok(lock) => lock,
 		Err(_) => {
@@ -41,24 +48,32 @@ pub fn snapshot_metadata(garage: &Garage) -> Result<(), Error> {
 		}
 	};
 
-	let mut snapshots_dir = garage.config.metadata_dir.clone();
-	snapshots_dir.push("snapshots");
+	let snapshots_dir = match output_dir {
+		Some(dir) => dir.to_path_buf(),
+		None => garage.config.metadata_dir.join("snapshots"),
+	};
 	fs::create_dir_all(&snapshots_dir)?;
 
-	let mut new_path = snapshots_dir.clone();
-	new_path.push(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+	let engine = garage_db::Engine::from_str(&garage.config.db_engine)?;
+	let new_path = snapshots_dir.join(format!(
+		"{}.{}",
+		chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+		engine.as_str()
+	));
 
 	info!("Snapshotting metadata db to {}", new_path.display());
 	garage.db.snapshot(&new_path)?;
 	info!("Metadata db snapshot finished");
 
-	if let Err(e) = cleanup_snapshots(&snapshots_dir) {
-		error!("Failed to do cleanup in snapshots directory: {}", e);
+	if output_dir.is_none() {
+		if let Err(e) = cleanup_snapshots(&snapshots_dir) {
+			error!("Failed to do cleanup in snapshots directory: {}", e);
+		}
 	}
 
 	drop(lock);
 
-	Ok(())
+	Ok(new_path)
 }
 
 fn cleanup_snapshots(snapshots_dir: &PathBuf) -> Result<(), Error> {
@@ -122,7 +137,7 @@ impl Worker for AutoSnapshotWorker {
 			return Ok(WorkerState::Idle);
 		}
 
-		async_snapshot_metadata(&self.garage).await?;
+		async_snapshot_metadata(&self.garage, None).await?;
 
 		let rand_factor = 1f32 + thread_rng().gen::<f32>() / 5f32;
 		self.next_snapshot = Instant::now() + self.snapshot_interval.mul_f32(rand_factor);
@@ -79,7 +79,12 @@ impl LayoutManager {
 			node_id.into(),
 			peering,
 			layout.clone(),
-			config.rpc_timeout_msec.map(Duration::from_millis),
+			RpcTimeouts::new(
+				config.rpc_timeout_msec.map(Duration::from_millis),
+				config
+					.rpc_background_timeout_msec
+					.map(Duration::from_millis),
+			),
 		);
 
 		Ok(Arc::new(Self {
@@ -117,6 +122,11 @@ impl LayoutManager {
 			.insert(table_name.to_string(), first_version);
 	}
 
+	/// Layout version up to which each table has been fully synced on this node
+	pub fn table_sync_versions(&self) -> HashMap<String, u64> {
+		self.table_sync_version.lock().unwrap().clone()
+	}
+
 	pub fn sync_table_until(self: &Arc<Self>, table_name: &'static str, version: u64) {
 		let mut table_sync_version = self.table_sync_version.lock().unwrap();
 		*table_sync_version.get_mut(table_name).unwrap() = version;
@@ -156,3 +156,36 @@ fn test_assignment() {
 	assert_eq!(cl.check(), Ok(()));
 	assert!(check_against_naive(cl.current()).unwrap());
 }
+
+#[test]
+fn test_assignment_byte_capacities() {
+	// Nodes with capacities of 1 TB, 1.5 TB and 2.5 TB in a single zone
+	// should store partitions in proportion to their capacity
+	const TB: u64 = 1_000_000_000_000;
+	let node_capacity_vec = vec![TB, 3 * TB / 2, 5 * TB / 2];
+	let node_zone_vec = vec!["A", "A", "A"];
+
+	let mut cl = LayoutHistory::new(ReplicationFactor::new(1).unwrap());
+	update_layout(&mut cl, &node_capacity_vec, &node_zone_vec, 1);
+	let v = cl.current().version;
+	let (cl, msg) = cl.apply_staged_changes(Some(v + 1)).unwrap();
+	show_msg(&msg);
+	assert_eq!(cl.check(), Ok(()));
+	assert!(check_against_naive(cl.current()).unwrap());
+
+	let layout = cl.current();
+	let total_capacity: u64 = node_capacity_vec.iter().sum();
+	for (i, capacity) in node_capacity_vec.iter().enumerate() {
+		let usage = layout.get_node_usage(&[i as u8; 32].into()).unwrap() as u64;
+		assert!(usage * layout.partition_size <= *capacity);
+
+		let expected = NB_PARTITIONS as u64 * capacity / total_capacity;
+		assert!(
+			usage + 2 >= expected && usage <= expected + 2,
+			"node {} stores {} partitions, expected about {}",
+			i,
+			usage,
+			expected
+		);
+	}
+}
@@ -132,7 +132,38 @@ struct RpcHelperInner {
 	peering: Arc<PeeringManager>,
 	layout: Arc<RwLock<LayoutHelper>>,
 	metrics: RpcMetrics,
-	rpc_timeout: Duration,
+	rpc_timeouts: RpcTimeouts,
+}
+
+/// Default timeouts applied to RPCs, depending on their priority class
+#[derive(Clone, Copy, Debug)]
+pub struct RpcTimeouts {
+	/// Timeout for foreground requests (PRIO_HIGH and PRIO_NORMAL)
+	pub foreground: Duration,
+	/// Timeout for background requests (PRIO_BACKGROUND), such as table
+	/// sync, offload or block resync
+	pub background: Duration,
+}
+
+impl RpcTimeouts {
+	/// Build timeouts from configured values; the background timeout
+	/// defaults to the foreground one when not set
+	pub fn new(foreground: Option<Duration>, background: Option<Duration>) -> Self {
+		let foreground = foreground.unwrap_or(DEFAULT_TIMEOUT);
+		Self {
+			foreground,
+			background: background.unwrap_or(foreground),
+		}
+	}
+
+	/// Get the timeout that applies to requests of the given priority
+	pub fn for_priority(&self, prio: RequestPriority) -> Duration {
+		if prio & PRIO_BACKGROUND != 0 {
+			self.background
+		} else {
+			self.foreground
+		}
+	}
 }
 
 impl RpcHelper {
@@ -140,7 +171,7 @@ impl RpcHelper {
 		our_node_id: Uuid,
 		peering: Arc<PeeringManager>,
 		layout: Arc<RwLock<LayoutHelper>>,
-		rpc_timeout: Option<Duration>,
+		rpc_timeouts: RpcTimeouts,
 	) -> Self {
 		let metrics = RpcMetrics::new();
 
@@ -149,12 +180,18 @@ impl RpcHelper {
 			peering,
 			layout,
 			metrics,
-			rpc_timeout: rpc_timeout.unwrap_or(DEFAULT_TIMEOUT),
+			rpc_timeouts,
 		}))
 	}
 
+	/// Default timeout for foreground RPCs
 	pub fn rpc_timeout(&self) -> Duration {
-		self.0.rpc_timeout
+		self.0.rpc_timeouts.foreground
+	}
+
+	/// Default timeout for RPCs of a given priority
+	pub fn rpc_timeout_for(&self, prio: RequestPriority) -> Duration {
+		self.0.rpc_timeouts.for_priority(prio)
 	}
 
 	pub async fn call<M, N, H, S>(
@@ -192,7 +229,9 @@ impl RpcHelper {
 		let timeout = async {
 			match strat.rs_timeout {
 				Timeout::None => futures::future::pending().await,
-				Timeout::Default => tokio::time::sleep(self.0.rpc_timeout).await,
+				Timeout::Default => {
+					tokio::time::sleep(self.rpc_timeout_for(strat.rs_priority)).await
+				}
 				Timeout::Custom(t) => tokio::time::sleep(t).await,
 			}
 		};
@@ -709,3 +748,108 @@ where
 		)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use async_trait::async_trait;
+	use serde::{Deserialize, Serialize};
+	use sodiumoxide::crypto::auth;
+	use sodiumoxide::crypto::sign::ed25519;
+
+	use crate::layout::LayoutHistory;
+	use crate::replication_mode::{ConsistencyMode, ReplicationFactor};
+
+	use super::*;
+
+	#[test]
+	fn test_rpc_timeouts_default() {
+		let t = RpcTimeouts::new(None, None);
+		assert_eq!(t.for_priority(PRIO_NORMAL), DEFAULT_TIMEOUT);
+		assert_eq!(t.for_priority(PRIO_BACKGROUND), DEFAULT_TIMEOUT);
+
+		let t = RpcTimeouts::new(Some(Duration::from_secs(10)), None);
+		assert_eq!(t.for_priority(PRIO_HIGH), Duration::from_secs(10));
+		assert_eq!(t.for_priority(PRIO_BACKGROUND), Duration::from_secs(10));
+	}
+
+	struct SlowHandler;
+
+	#[derive(Serialize, Deserialize)]
+	struct SlowRpc(u64);
+
+	impl Rpc for SlowRpc {
+		type Response = Result<(), Error>;
+	}
+
+	#[async_trait]
+	impl EndpointHandler<SlowRpc> for SlowHandler {
+		async fn handle(self: &Arc<Self>, msg: &SlowRpc, _from: NodeID) -> Result<(), Error> {
+			tokio::time::sleep(Duration::from_millis(msg.0)).await;
+			Ok(())
+		}
+	}
+
+	#[tokio::test]
+	async fn test_rpc_timeouts_per_priority() {
+		let (_, privkey) = ed25519::gen_keypair();
+		let netapp = NetApp::new(0u64, auth::gen_key(), privkey, None);
+		let peering = PeeringManager::new(netapp.clone(), vec![], None);
+
+		let replication_factor = ReplicationFactor::new(1).unwrap();
+		let layout = LayoutHelper::new(
+			replication_factor,
+			ConsistencyMode::Consistent,
+			LayoutHistory::new(replication_factor),
+			Default::default(),
+		);
+
+		let our_id: Uuid = netapp.id.into();
+		let helper = RpcHelper::new(
+			our_id,
+			peering,
+			Arc::new(RwLock::new(layout)),
+			RpcTimeouts::new(
+				Some(Duration::from_millis(50)),
+				Some(Duration::from_millis(1000)),
+			),
+		);
+
+		let endpoint: Arc<Endpoint<SlowRpc, SlowHandler>> =
+			netapp.endpoint("garage_rpc/rpc_helper.rs/test/SlowRpc".into());
+		endpoint.set_handler(Arc::new(SlowHandler));
+
+		// A foreground call times out...
+		let res = helper
+			.call(
+				&endpoint,
+				our_id,
+				SlowRpc(200),
+				RequestStrategy::with_priority(PRIO_NORMAL),
+			)
+			.await;
+		assert!(matches!(res, Err(Error::Timeout)));
+
+		// ... while a background one of the same duration is given enough time
+		let res = helper
+			.call(
+				&endpoint,
+				our_id,
+				SlowRpc(200),
+				RequestStrategy::with_priority(PRIO_BACKGROUND | PRIO_SECONDARY),
+			)
+			.await;
+		assert!(res.is_ok());
+
+		// An explicit timeout still overrides the background default
+		let res = helper
+			.call(
+				&endpoint,
+				our_id,
+				SlowRpc(200),
+				RequestStrategy::with_priority(PRIO_BACKGROUND)
+					.with_custom_timeout(Duration::from_millis(50)),
+			)
+			.await;
+		assert!(matches!(res, Err(Error::Timeout)));
+	}
+}
@@ -49,16 +49,18 @@ impl<F: TableSchema, R: TableReplication> TableData<F, R> {
 		let merkle_tree = db
 			.open_tree(format!("{}:merkle_tree", F::TABLE_NAME))
 			.expect("Unable to open DB Merkle tree tree");
+		// Length of the queues below is reported in metrics and stats,
+		// counted trees make this cheap on all DB engines
 		let merkle_todo = db
-			.open_tree(format!("{}:merkle_todo", F::TABLE_NAME))
+			.open_tree_counted(format!("{}:merkle_todo", F::TABLE_NAME))
 			.expect("Unable to open DB Merkle TODO tree");
 
 		let insert_queue = db
-			.open_tree(format!("{}:insert_queue", F::TABLE_NAME))
+			.open_tree_counted(format!("{}:insert_queue", F::TABLE_NAME))
 			.expect("Unable to open insert queue DB tree");
 
 		let gc_todo = db
-			.open_tree(format!("{}:gc_todo_v2", F::TABLE_NAME))
+			.open_tree_counted(format!("{}:gc_todo_v2", F::TABLE_NAME))
 			.expect("Unable to open GC DB tree");
 
 		let metrics = TableMetrics::new(
@@ -40,6 +40,10 @@ pub struct MerkleUpdater<F: TableSchema, R: TableReplication> {
 	// Field in data:
 	//		pub(crate) merkle_tree: db::Tree,
 	empty_node_hash: Hash,
+
+	// Maximum number of todo items processed by the worker in a single
+	// call to work(), before yielding to other background tasks
+	batch_size: usize,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -67,12 +71,13 @@ pub enum MerkleNode {
 }
 
 impl<F: TableSchema, R: TableReplication> MerkleUpdater<F, R> {
-	pub(crate) fn new(data: Arc<TableData<F, R>>) -> Arc<Self> {
+	pub(crate) fn new(data: Arc<TableData<F, R>>, batch_size: usize) -> Arc<Self> {
 		let empty_node_hash = blake2sum(&nonversioned_encode(&MerkleNode::Empty).unwrap()[..]);
 
 		Arc::new(Self {
 			data,
 			empty_node_hash,
+			batch_size: std::cmp::max(batch_size, 1),
 		})
 	}
 
@@ -83,6 +88,7 @@ impl<F: TableSchema, R: TableReplication> MerkleUpdater<F, R> {
 	fn updater_loop_iter(&self) -> Result<WorkerState, Error> {
 		if let Some((key, valhash)) = self.data.merkle_todo.first()? {
 			self.update_item(&key, &valhash)?;
+			self.data.metrics.merkle_updater_items_processed.add(1);
 			Ok(WorkerState::Busy)
 		} else {
 			Ok(WorkerState::Idle)
@@ -314,7 +320,7 @@ impl<F: TableSchema, R: TableReplication> Worker for MerkleWorker<F, R> {
 	async fn work(&mut self, _must_exit: &mut watch::Receiver<bool>) -> Result<WorkerState, Error> {
 		let updater = self.0.clone();
 		tokio::task::spawn_blocking(move || {
-			for _i in 0..100 {
+			for _i in 0..updater.batch_size {
 				let s = updater.updater_loop_iter();
 				if !matches!(s, Ok(WorkerState::Busy)) {
 					return s;
@@ -17,6 +17,8 @@ pub struct TableMetrics {
 	pub(crate) internal_update_counter: BoundCounter<u64>,
 	pub(crate) internal_delete_counter: BoundCounter<u64>,
 
+	pub(crate) merkle_updater_items_processed: BoundCounter<u64>,
+
 	pub(crate) sync_items_sent: Counter<u64>,
 	pub(crate) sync_items_received: Counter<u64>,
 }
@@ -119,6 +121,12 @@ impl TableMetrics {
 				.init()
 				.bind(&[KeyValue::new("table_name", table_name)]),
 
+			merkle_updater_items_processed: meter
+				.u64_counter("table.merkle_updater_items_processed")
+				.with_description("Number of items of the Merkle tree updater TODO queue that have been processed")
+				.init()
+				.bind(&[KeyValue::new("table_name", table_name)]),
+
 			sync_items_sent: meter
 				.u64_counter("table.sync_items_sent")
 				.with_description("Number of data items sent to other nodes during resync procedures")
@@ -1,4 +1,5 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -30,6 +31,11 @@ use crate::*;
 // Do anti-entropy every 10 minutes
 const ANTI_ENTROPY_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
+// Delay before retrying to sync a partition after a first failure,
+// doubled after each consecutive failure up to the maximum value
+const SYNC_RETRY_DELAY: Duration = Duration::from_secs(1);
+const SYNC_RETRY_DELAY_MAX: Duration = Duration::from_secs(5 * 60);
+
 pub struct TableSyncer<F: TableSchema, R: TableReplication> {
 	system: Arc<System>,
 	data: Arc<TableData<F, R>>,
@@ -37,6 +43,9 @@ pub struct TableSyncer<F: TableSchema, R: TableReplication> {
 
 	add_full_sync_tx: ArcSwapOption<mpsc::UnboundedSender<()>>,
 	endpoint: Arc<Endpoint<SyncRpc, Self>>,
+
+	/// Number of partitions remaining in the sync worker's todo list
+	pending_partitions: AtomicUsize,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -69,6 +78,7 @@ impl<F: TableSchema, R: TableReplication> TableSyncer<F, R> {
 			merkle,
 			add_full_sync_tx: ArcSwapOption::new(None),
 			endpoint,
+			pending_partitions: AtomicUsize::new(0),
 		});
 		syncer.endpoint.set_handler(syncer.clone());
 
@@ -86,10 +96,17 @@ impl<F: TableSchema, R: TableReplication> TableSyncer<F, R> {
 			layout_digest: self.system.cluster_layout().sync_digest(),
 			add_full_sync_rx,
 			todo: None,
+			backoff: HashMap::new(),
 			next_full_sync: Instant::now() + Duration::from_secs(20),
 		});
 	}
 
+	/// Number of partitions that still have to be synced in the current
+	/// full sync of this table (0 if no full sync is in progress)
+	pub fn pending_partitions(&self) -> usize {
+		self.pending_partitions.load(Ordering::Relaxed)
+	}
+
 	pub fn add_full_sync(&self) -> Result<(), Error> {
 		let tx = self.add_full_sync_tx.load();
 		let tx = tx
@@ -489,6 +506,8 @@ struct SyncWorker<F: TableSchema, R: TableReplication> {
 	next_full_sync: Instant,
 
 	todo: Option<SyncPartitions>,
+	/// Partitions whose last sync attempts failed, and when to retry them
+	backoff: HashMap<Partition, SyncBackoff>,
 }
 
 impl<F: TableSchema, R: TableReplication> SyncWorker<F, R> {
@@ -516,6 +535,14 @@ impl<F: TableSchema, R: TableReplication> SyncWorker<F, R> {
 		partitions.partitions.shuffle(&mut thread_rng());
 		self.todo = Some(partitions);
 		self.next_full_sync = Instant::now() + ANTI_ENTROPY_INTERVAL;
+		self.update_pending_partitions();
+	}
+
+	fn update_pending_partitions(&self) {
+		let pending = self.todo.as_ref().map(|x| x.partitions.len()).unwrap_or(0);
+		self.syncer
+			.pending_partitions
+			.store(pending, Ordering::Relaxed);
 	}
 }
 
@@ -525,9 +552,14 @@ impl<F: TableSchema, R: TableReplication> Worker for SyncWorker<F, R> {
 		format!("{} sync", F::TABLE_NAME)
 	}
 
+	fn kind(&self) -> WorkerKind {
+		WorkerKind::TableSync
+	}
+
 	fn status(&self) -> WorkerStatus {
 		WorkerStatus {
 			queue_length: Some(self.todo.as_ref().map(|x| x.partitions.len()).unwrap_or(0) as u64),
+			persistent_errors: Some(self.backoff.len() as u64),
 			..Default::default()
 		}
 	}
@@ -536,25 +568,55 @@ impl<F: TableSchema, R: TableReplication> Worker for SyncWorker<F, R> {
 		self.check_add_full_sync();
 
 		if let Some(todo) = &mut self.todo {
-			let partition = todo.partitions.pop().unwrap();
+			// Take the next partition that is not waiting before being retried
+			let now = Instant::now();
+			let backoff = &self.backoff;
+			let is_ready = |p: &SyncPartition| {
+				backoff
+					.get(&p.partition)
+					.map(|b| b.next_try <= now)
+					.unwrap_or(true)
+			};
+			let partition = match todo.partitions.iter().rposition(is_ready) {
+				Some(i) => todo.partitions.remove(i),
+				None => {
+					// All remaining partitions have failed recently
+					let next_try = todo
+						.partitions
+						.iter()
+						.filter_map(|p| backoff.get(&p.partition))
+						.map(|b| b.next_try)
+						.min()
+						.unwrap_or(now);
+					return Ok(WorkerState::Throttled(
+						next_try.saturating_duration_since(now).as_secs_f32(),
+					));
+				}
+			};
 
 			// process partition
 			if let Err(e) = self.syncer.sync_partition(&partition, must_exit).await {
+				// Retry partitions that fail repeatedly (e.g. because a node is gone)
+				// with an exponential backoff, without delaying the other partitions
+				let backoff = SyncBackoff::after_failure(
+					self.backoff.get(&partition.partition),
+					Instant::now(),
+				);
 				error!(
-					"{}: Failed to sync partition {:?}: {}",
+					"{}: Failed to sync partition {:?} ({} consecutive failures, retrying in {:?}): {}",
 					F::TABLE_NAME,
 					partition,
+					backoff.errors,
+					backoff.next_try.saturating_duration_since(Instant::now()),
 					e
 				);
-				// if error, put partition back at the other side of the queue,
+				self.backoff.insert(partition.partition, backoff);
+				// put partition back at the other side of the queue,
 				// so that other partitions will be tried in the meantime
 				todo.partitions.insert(0, partition);
-				// TODO: returning an error here will cause the background job worker
-				// to delay this task for some time, but maybe we don't want to
-				// delay it if there are lots of failures from nodes that are gone
-				// (we also don't want zero delays as that will cause lots of useless retries)
-				return Err(e);
+				return Ok(WorkerState::Busy);
 			}
+			self.backoff.remove(&partition.partition);
 
 			if todo.partitions.is_empty() {
 				info!(
@@ -568,6 +630,7 @@ impl<F: TableSchema, R: TableReplication> Worker for SyncWorker<F, R> {
 					.sync_table_until(F::TABLE_NAME, todo.layout_version);
 				self.todo = None;
 			}
+			self.update_pending_partitions();
 
 			Ok(WorkerState::Busy)
 		} else {
@@ -596,6 +659,34 @@ impl<F: TableSchema, R: TableReplication> Worker for SyncWorker<F, R> {
 	}
 }
 
+/// Consecutive failures to sync a partition, used to implement
+/// an exponential backoff for partitions that can't be synced
+#[derive(Clone, Copy, Debug)]
+struct SyncBackoff {
+	errors: u32,
+	next_try: Instant,
+}
+
+impl SyncBackoff {
+	fn after_failure(prev: Option<&Self>, now: Instant) -> Self {
+		let errors = prev.map(|b| b.errors + 1).unwrap_or(1);
+		Self {
+			errors,
+			next_try: now + Self::delay(errors),
+		}
+	}
+
+	fn delay(errors: u32) -> Duration {
+		let delay = std::cmp::min(
+			SYNC_RETRY_DELAY * 2u32.pow(std::cmp::min(errors - 1, 16)),
+			SYNC_RETRY_DELAY_MAX,
+		);
+		// Add some jitter, so that partitions that failed together
+		// (e.g. because of the same node) are not all retried at once
+		delay.mul_f64(thread_rng().gen_range(0.5..1.0))
+	}
+}
+
 // ---- UTIL ----
 
 fn hash_of_merkle_node(x: &MerkleNode) -> Result<Hash, Error> {
@@ -626,3 +717,40 @@ fn join_ordered<'a, K: Ord + Eq, V1, V2>(
 	}
 	ret
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_sync_backoff_spaces_out_retries() {
+		let now = Instant::now();
+
+		let mut backoff = SyncBackoff::after_failure(None, now);
+		assert_eq!(backoff.errors, 1);
+		assert!(backoff.next_try <= now + SYNC_RETRY_DELAY);
+
+		let mut delays = vec![];
+		for _ in 0..20 {
+			let prev = backoff;
+			backoff = SyncBackoff::after_failure(Some(&prev), prev.next_try);
+			delays.push(backoff.next_try - prev.next_try);
+		}
+		assert_eq!(backoff.errors, 21);
+
+		for (i, delay) in delays.iter().enumerate() {
+			assert!(*delay <= SYNC_RETRY_DELAY_MAX, "{:?}", delay);
+			// jitter is at most half of the delay, so the delay
+			// at least doubles every two consecutive failures
+			if i >= 2 {
+				assert!(
+					*delay >= delays[i - 2] || delays[i - 2] * 2 > SYNC_RETRY_DELAY_MAX,
+					"{:?}",
+					delays
+				);
+			}
+		}
+		// Partitions that keep failing end up being retried every few minutes
+		assert!(delays[19] >= SYNC_RETRY_DELAY_MAX / 2);
+	}
+}
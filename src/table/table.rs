@@ -69,14 +69,20 @@ impl<F: TableSchema> Rpc for TableRpc<F> {
 impl<F: TableSchema, R: TableReplication> Table<F, R> {
 	// =============== PUBLIC INTERFACE FUNCTIONS (new, insert, get, etc) ===============
 
-	pub fn new(instance: F, replication: R, system: Arc<System>, db: &db::Db) -> Arc<Self> {
+	pub fn new(
+		instance: F,
+		replication: R,
+		system: Arc<System>,
+		db: &db::Db,
+		merkle_update_batch_size: usize,
+	) -> Arc<Self> {
 		let endpoint = system
 			.netapp
 			.endpoint(format!("garage_table/table.rs/Rpc:{}", F::TABLE_NAME));
 
 		let data = TableData::new(system.clone(), instance, replication, db);
 
-		let merkle_updater = MerkleUpdater::new(data.clone());
+		let merkle_updater = MerkleUpdater::new(data.clone(), merkle_update_batch_size);
 
 		let syncer = TableSyncer::new(system.clone(), data.clone(), merkle_updater.clone());
 		let gc = TableGc::new(system.clone(), data.clone());
@@ -290,7 +296,30 @@ impl<F: TableSchema, R: TableReplication> Table<F, R> {
 		let span = tracer.start(format!("{} get", F::TABLE_NAME));
 
 		let res = self
-			.get_internal(partition_key, sort_key)
+			.get_internal(partition_key, sort_key, false)
+			.bound_record_duration(&self.data.metrics.get_request_duration)
+			.with_context(Context::current_with_span(span))
+			.await?;
+
+		self.data.metrics.get_request_counter.add(1);
+
+		Ok(res)
+	}
+
+	/// Same as `get`, but waits for answers from enough nodes to be sure
+	/// to see all writes that were acknowledged by a write quorum, even if
+	/// the configured read quorum is smaller than that (e.g. when using
+	/// the `degraded` or `dangerous` consistency modes).
+	pub async fn get_read_your_writes(
+		self: &Arc<Self>,
+		partition_key: &F::P,
+		sort_key: &F::S,
+	) -> Result<Option<F::E>, Error> {
+		let tracer = opentelemetry::global::tracer("garage_table");
+		let span = tracer.start(format!("{} get_read_your_writes", F::TABLE_NAME));
+
+		let res = self
+			.get_internal(partition_key, sort_key, true)
 			.bound_record_duration(&self.data.metrics.get_request_duration)
 			.with_context(Context::current_with_span(span))
 			.await?;
@@ -304,10 +333,19 @@ impl<F: TableSchema, R: TableReplication> Table<F, R> {
 		self: &Arc<Self>,
 		partition_key: &F::P,
 		sort_key: &F::S,
+		read_your_writes: bool,
 	) -> Result<Option<F::E>, Error> {
 		let hash = partition_key.hash();
 		let who = self.data.replication.read_nodes(&hash);
 
+		let mut quorum = self.data.replication.read_quorum();
+		if read_your_writes {
+			// Read and write quorums must intersect for all acknowledged
+			// writes to be visible
+			let min_quorum = (who.len() + 1).saturating_sub(self.data.replication.write_quorum());
+			quorum = std::cmp::min(std::cmp::max(quorum, min_quorum), who.len());
+		}
+
 		let rpc = TableRpc::<F>::ReadEntry(partition_key.clone(), sort_key.clone());
 		let resps = self
 			.system
@@ -316,8 +354,7 @@ impl<F: TableSchema, R: TableReplication> Table<F, R> {
 				&self.endpoint,
 				&who,
 				rpc,
-				RequestStrategy::with_priority(PRIO_NORMAL)
-					.with_quorum(self.data.replication.read_quorum()),
+				RequestStrategy::with_priority(PRIO_NORMAL).with_quorum(quorum),
 			)
 			.await?;
 
@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::{mpsc, watch};
 
 use worker::WorkerProcessor;
-pub use worker::{Worker, WorkerState};
+pub use worker::{Worker, WorkerKind, WorkerState};
 
 /// Job runner for futures and async functions
 pub struct BackgroundRunner {
@@ -21,6 +21,7 @@ pub struct BackgroundRunner {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct WorkerInfo {
 	pub name: String,
+	pub kind: WorkerKind,
 	pub status: WorkerStatus,
 	pub state: WorkerState,
 	pub errors: usize,
@@ -73,3 +74,13 @@ impl BackgroundRunner {
 			.expect("Could not put worker in queue");
 	}
 }
+
+/// Wait until the stop signal carried by a watch channel is set to true,
+/// or until its sender is dropped
+pub async fn wait_from(mut chan: watch::Receiver<bool>) {
+	while !*chan.borrow() {
+		if chan.changed().await.is_err() {
+			return;
+		}
+	}
+}
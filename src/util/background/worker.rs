@@ -37,10 +37,23 @@ impl std::fmt::Display for WorkerState {
 	}
 }
 
+/// Category of a worker, used to find workers of a given type among
+/// all the ones that are running without relying on their names
+#[derive(PartialEq, Eq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum WorkerKind {
+	TableSync,
+	Repair,
+	Other,
+}
+
 #[async_trait]
 pub trait Worker: Send {
 	fn name(&self) -> String;
 
+	fn kind(&self) -> WorkerKind {
+		WorkerKind::Other
+	}
+
 	fn status(&self) -> WorkerStatus {
 		Default::default()
 	}
@@ -129,6 +142,7 @@ impl WorkerProcessor {
 							None => {
 								wi.insert(worker.task_id, WorkerInfo {
 									name: worker.worker.name(),
+									kind: worker.worker.kind(),
 									state: worker.state,
 									status: worker.worker.status(),
 									errors: worker.errors,
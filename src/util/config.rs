@@ -31,6 +31,22 @@ pub struct Config {
 	#[serde(default)]
 	pub metadata_auto_snapshot_interval: Option<String>,
 
+	/// Abort uploads that have been in progress for longer than this
+	/// duration (disabled if not set)
+	#[serde(default)]
+	pub stale_upload_max_age: Option<String>,
+	/// Interval between two scans for stale uploads (default: 1h)
+	#[serde(default)]
+	pub stale_upload_scan_interval: Option<String>,
+
+	/// Disable the worker that applies bucket lifecycle rules
+	#[serde(default)]
+	pub disable_lifecycle: bool,
+	/// Interval between two runs of the lifecycle worker
+	/// (default: once per day, at midnight UTC)
+	#[serde(default)]
+	pub lifecycle_scan_interval: Option<String>,
+
 	/// Size of data blocks to save to disk
 	#[serde(
 		deserialize_with = "deserialize_capacity",
@@ -38,6 +54,14 @@ pub struct Config {
 	)]
 	pub block_size: usize,
 
+	/// Size under which objects are stored inline in the metadata store
+	/// instead of as data blocks
+	#[serde(
+		deserialize_with = "deserialize_capacity",
+		default = "default_inline_threshold"
+	)]
+	pub inline_threshold: usize,
+
 	/// Number of replicas. Can be any positive integer, but uneven numbers are more favorable.
 	/// - 1 for single-node clusters, or to disable replication
 	/// - 3 is the recommended and supported setting.
@@ -68,6 +92,20 @@ pub struct Config {
 	)]
 	pub block_ram_buffer_max: usize,
 
+	/// Number of block resync workers to spawn. The number of workers that
+	/// are actually active can be tuned at runtime, up to this value.
+	#[serde(default = "default_block_resync_workers")]
+	pub block_resync_workers: usize,
+
+	/// How to choose the node from which data blocks are read
+	#[serde(default)]
+	pub block_read_strategy: BlockReadStrategy,
+
+	/// Maximum number of items the Merkle tree updater of each table
+	/// processes in one go before yielding to other background tasks
+	#[serde(default = "default_merkle_update_batch_size")]
+	pub merkle_update_batch_size: usize,
+
 	/// Skip the permission check of secret files. Useful when
 	/// POSIX ACLs (or more complex chmods) are used.
 	#[serde(default)]
@@ -93,6 +131,9 @@ pub struct Config {
 	pub rpc_ping_timeout_msec: Option<u64>,
 	/// Timeout for Netapp RPC calls
 	pub rpc_timeout_msec: Option<u64>,
+	/// Timeout for background RPC calls (table sync, offload, block resync),
+	/// defaults to rpc_timeout_msec
+	pub rpc_background_timeout_msec: Option<u64>,
 
 	// -- Bootstraping and discovery
 	/// Bootstrap peers RPC address
@@ -114,6 +155,12 @@ pub struct Config {
 	/// LMDB map size
 	#[serde(deserialize_with = "deserialize_capacity", default)]
 	pub lmdb_map_size: usize,
+	/// Page size of the Sqlite database, applied when it is created
+	#[serde(deserialize_with = "deserialize_opt_capacity", default)]
+	pub sqlite_page_size: Option<usize>,
+	/// Size of the Sqlite page cache of each database connection
+	#[serde(deserialize_with = "deserialize_opt_capacity", default)]
+	pub sqlite_cache_size: Option<usize>,
 
 	// -- APIs
 	/// Configuration for S3 api
@@ -160,6 +207,44 @@ pub struct S3ApiConfig {
 	/// Suffix to remove from domain name to find bucket. If None,
 	/// vhost-style S3 request are disabled
 	pub root_domain: Option<String>,
+	/// Log every request made to the S3 API
+	#[serde(default)]
+	pub access_log: bool,
+	/// File to which access log entries are appended as JSON lines,
+	/// in addition to being logged as tracing events
+	#[serde(default)]
+	pub access_log_file: Option<PathBuf>,
+	/// Maximum size of an object that can be uploaded (unbounded if unset)
+	#[serde(deserialize_with = "deserialize_opt_capacity", default)]
+	pub max_object_size: Option<usize>,
+	/// Maximum total size of the user metadata (x-amz-meta-* headers)
+	/// of an object (default: 2KiB)
+	#[serde(deserialize_with = "deserialize_opt_capacity", default)]
+	pub max_user_metadata_size: Option<usize>,
+	/// Storage classes accepted in the x-amz-storage-class header
+	/// (default: the storage classes defined by AWS)
+	#[serde(default)]
+	pub storage_classes: Option<Vec<String>>,
+	/// Maximum duration of the processing of a request until response headers
+	/// are sent, e.g. "15m" (default: no limit)
+	#[serde(default)]
+	pub request_timeout: Option<String>,
+	/// Accept bucket names following the legacy S3 rules (uppercase letters,
+	/// underscores, up to 255 characters), which are not DNS-compatible and
+	/// can only be used with path-style requests
+	#[serde(default)]
+	pub relaxed_bucket_names: bool,
+	/// Number of data blocks fetched in parallel, ahead of the one being sent,
+	/// when streaming an object to a client (default: 2)
+	#[serde(default)]
+	pub get_prefetch_depth: Option<usize>,
+	/// Answer GET requests of clients that accept zstd with the compressed
+	/// blocks as they are stored, instead of decompressing them
+	#[serde(default)]
+	pub zstd_content_encoding: bool,
+	/// TCP and HTTP connection settings
+	#[serde(flatten)]
+	pub http: HttpServerConfig,
 }
 
 /// Configuration for K2V api
@@ -167,6 +252,9 @@ pub struct S3ApiConfig {
 pub struct K2VApiConfig {
 	/// Address and port to bind for api serving
 	pub api_bind_addr: UnixOrTCPSocketAddress,
+	/// TCP and HTTP connection settings
+	#[serde(flatten)]
+	pub http: HttpServerConfig,
 }
 
 /// Configuration for serving files as normal web server
@@ -176,6 +264,9 @@ pub struct WebConfig {
 	pub bind_addr: UnixOrTCPSocketAddress,
 	/// Suffix to remove from domain name to find bucket
 	pub root_domain: String,
+	/// TCP and HTTP connection settings
+	#[serde(flatten)]
+	pub http: HttpServerConfig,
 }
 
 /// Configuration for the admin and monitoring HTTP API
@@ -196,6 +287,72 @@ pub struct AdminConfig {
 
 	/// OTLP server to where to export traces
 	pub trace_sink: Option<String>,
+
+	/// TCP and HTTP connection settings
+	#[serde(flatten)]
+	pub http: HttpServerConfig,
+}
+
+/// TCP and HTTP connection settings of one of the HTTP servers,
+/// given in the configuration section of that server
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct HttpServerConfig {
+	/// Maximum length of the queue of pending TCP connections (default: 1024)
+	#[serde(default)]
+	pub tcp_backlog: Option<u32>,
+	/// Duration after which idle keep-alive connections are closed, e.g. "90s"
+	/// (default: no timeout, set to "0s" to disable keep-alive)
+	#[serde(default)]
+	pub http_keepalive_timeout: Option<String>,
+	/// Duration for which requests still in flight when Garage is asked to
+	/// shut down are allowed to complete, e.g. "30s" (default: 10 seconds)
+	#[serde(default)]
+	pub shutdown_grace_period: Option<String>,
+	/// PEM file containing the certificate chain to serve over TLS
+	/// (default: no TLS, plain HTTP is served)
+	#[serde(default)]
+	pub tls_cert_file: Option<PathBuf>,
+	/// PEM file containing the private key of the TLS certificate
+	#[serde(default)]
+	pub tls_key_file: Option<PathBuf>,
+	/// Maximum number of connections open at the same time
+	/// (default: no limit)
+	#[serde(default)]
+	pub max_connections: Option<usize>,
+	/// What to do with new connections when `max_connections` is reached
+	#[serde(default)]
+	pub connection_limit_action: ConnectionLimitAction,
+	/// Addresses or IP ranges of the reverse proxies whose X-Forwarded-For
+	/// header is trusted to give the address of clients
+	#[serde(default)]
+	pub trusted_proxies: Vec<String>,
+}
+
+/// What an HTTP server does with new connections when it already has
+/// the maximum number of open connections
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionLimitAction {
+	/// Leave new connections in the TCP backlog until a connection is closed
+	#[default]
+	Queue,
+	/// Accept new connections and close them immediately
+	Reject,
+}
+
+/// Order in which the nodes storing a data block are asked for it
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockReadStrategy {
+	/// This node first, then nodes of the same zone, then other nodes,
+	/// by increasing latency
+	#[default]
+	Latency,
+	/// Like `Latency`, but a block stored on this node is read directly
+	/// from disk without going through the RPC layer
+	PreferLocal,
+	/// Spread reads among the nodes storing a block, in turn
+	RoundRobin,
 }
 
 #[derive(Deserialize, Debug, Clone, Default)]
@@ -259,9 +416,18 @@ fn default_db_engine() -> String {
 fn default_block_size() -> usize {
 	1048576
 }
+fn default_inline_threshold() -> usize {
+	3072
+}
 fn default_block_ram_buffer_max() -> usize {
 	256 * 1024 * 1024
 }
+fn default_block_resync_workers() -> usize {
+	8
+}
+fn default_merkle_update_batch_size() -> usize {
+	100
+}
 
 fn default_consistency_mode() -> String {
 	"consistent".into()
@@ -363,6 +529,13 @@ where
 	deserializer.deserialize_any(CapacityVisitor)
 }
 
+fn deserialize_opt_capacity<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+	D: de::Deserializer<'de>,
+{
+	deserialize_capacity(deserializer).map(Some)
+}
+
 #[cfg(test)]
 mod tests {
 	use crate::error::Error;
@@ -85,3 +85,15 @@ impl AutoCrdt for String {
 impl AutoCrdt for bool {
 	const WARN_IF_DIFFERENT: bool = true;
 }
+
+impl AutoCrdt for std::collections::BTreeMap<String, String> {
+	const WARN_IF_DIFFERENT: bool = true;
+}
+
+impl AutoCrdt for Vec<String> {
+	const WARN_IF_DIFFERENT: bool = true;
+}
+
+impl AutoCrdt for Vec<ipnet::IpNet> {
+	const WARN_IF_DIFFERENT: bool = true;
+}
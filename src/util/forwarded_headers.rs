@@ -2,6 +2,8 @@ use http::{HeaderMap, HeaderValue};
 use std::net::IpAddr;
 use std::str::FromStr;
 
+pub use ipnet::IpNet;
+
 use crate::error::{Error, OkOrMessage};
 
 pub fn handle_forwarded_for_headers(headers: &HeaderMap<HeaderValue>) -> Result<String, Error> {
@@ -19,6 +21,71 @@ pub fn handle_forwarded_for_headers(headers: &HeaderMap<HeaderValue>) -> Result<
 	Ok(client_ip.to_string())
 }
 
+/// Parse an IP range in CIDR notation, e.g. `10.0.0.0/8`. A single address
+/// is also accepted, as a range that contains only this address.
+pub fn parse_cidr(s: &str) -> Result<IpNet, Error> {
+	let s = s.trim();
+	match IpAddr::from_str(s) {
+		Ok(ip) => Ok(IpNet::from(ip)),
+		Err(_) => IpNet::from_str(s).ok_or_message(format!("Invalid IP range: {}", s)),
+	}
+}
+
+/// Convert IPv4-mapped IPv6 addresses (e.g. `::ffff:192.0.2.1`), as reported
+/// for IPv4 peers of a socket bound to an IPv6 address, to plain IPv4 addresses
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+	match ip {
+		IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+		IpAddr::V4(_) => ip,
+	}
+}
+
+/// Determine the address of the client that made a request, from the address
+/// of the peer of the connection (`None` for unix sockets).
+///
+/// X-Forwarded-For is only taken into account if the peer is one of the trusted
+/// proxies, as it can otherwise be set to anything by the client. Peers on unix
+/// sockets are trusted as soon as trusted proxies are configured. The addresses
+/// listed in X-Forwarded-For are read from right to left, skipping other trusted
+/// proxies: the first one that is not trusted is the client.
+pub fn client_ip(
+	peer: Option<IpAddr>,
+	headers: &HeaderMap<HeaderValue>,
+	trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+	let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+
+	let peer = peer.map(canonical_ip);
+	let peer_trusted = match &peer {
+		Some(ip) => is_trusted(ip),
+		None => !trusted_proxies.is_empty(),
+	};
+	if !peer_trusted {
+		return peer;
+	}
+
+	let mut client = peer;
+	let forwarded = headers
+		.get_all("x-forwarded-for")
+		.iter()
+		.filter_map(|h| h.to_str().ok())
+		.flat_map(|h| h.split(','))
+		.collect::<Vec<_>>();
+	for addr in forwarded.into_iter().rev() {
+		match IpAddr::from_str(addr.trim()).map(canonical_ip) {
+			Ok(ip) => {
+				client = Some(ip);
+				if !is_trusted(&ip) {
+					break;
+				}
+			}
+			// An invalid address cannot be traced back any further
+			Err(_) => break,
+		}
+	}
+	client
+}
+
 #[cfg(test)]
 mod test {
 	use super::*;
@@ -60,4 +127,71 @@ mod test {
 		let result = handle_forwarded_for_headers(&test_headers);
 		assert!(result.is_err());
 	}
+
+	#[test]
+	fn test_parse_cidr() {
+		assert_eq!(
+			parse_cidr("10.0.0.0/8").unwrap(),
+			"10.0.0.0/8".parse::<IpNet>().unwrap()
+		);
+		assert_eq!(
+			parse_cidr(" 192.0.2.1 ").unwrap(),
+			"192.0.2.1/32".parse::<IpNet>().unwrap()
+		);
+		assert_eq!(
+			parse_cidr("2001:db8::1").unwrap(),
+			"2001:db8::1/128".parse::<IpNet>().unwrap()
+		);
+		assert!(parse_cidr("10.0.0.0/33").is_err());
+		assert!(parse_cidr("example.com").is_err());
+	}
+
+	#[test]
+	fn test_client_ip() {
+		let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+		let proxies = [parse_cidr("10.0.0.0/8").unwrap()];
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			"X-Forwarded-For",
+			"203.0.113.7, 198.51.100.1, 10.1.1.1".parse().unwrap(),
+		);
+
+		// X-Forwarded-For is ignored when the peer is not a trusted proxy
+		assert_eq!(
+			client_ip(Some(ip("192.0.2.1")), &headers, &proxies),
+			Some(ip("192.0.2.1"))
+		);
+		assert_eq!(
+			client_ip(Some(ip("10.0.0.2")), &headers, &[]),
+			Some(ip("10.0.0.2"))
+		);
+		assert_eq!(client_ip(None, &headers, &[]), None);
+
+		// Behind trusted proxies, the client is the last untrusted address:
+		// addresses further left could have been set by the client itself
+		assert_eq!(
+			client_ip(Some(ip("10.0.0.2")), &headers, &proxies),
+			Some(ip("198.51.100.1"))
+		);
+		assert_eq!(
+			client_ip(None, &headers, &proxies),
+			Some(ip("198.51.100.1"))
+		);
+
+		// No header: the peer itself is the client
+		assert_eq!(
+			client_ip(Some(ip("10.0.0.2")), &HeaderMap::new(), &proxies),
+			Some(ip("10.0.0.2"))
+		);
+
+		// IPv4-mapped addresses are handled as IPv4 addresses
+		assert_eq!(
+			client_ip(Some(ip("::ffff:10.0.0.2")), &headers, &proxies),
+			Some(ip("198.51.100.1"))
+		);
+		assert_eq!(
+			client_ip(Some(ip("::ffff:192.0.2.1")), &HeaderMap::new(), &proxies),
+			Some(ip("192.0.2.1"))
+		);
+	}
 }
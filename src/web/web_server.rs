@@ -2,7 +2,7 @@ use std::fs::{self, Permissions};
 use std::os::unix::prelude::PermissionsExt;
 use std::{convert::Infallible, sync::Arc};
 
-use tokio::net::{TcpListener, UnixListener};
+use tokio::net::UnixListener;
 use tokio::sync::watch;
 
 use hyper::{
@@ -20,7 +20,7 @@ use opentelemetry::{
 
 use crate::error::*;
 
-use garage_api::generic_server::{server_loop, UnixListenerOn};
+use garage_api::generic_server::{server_loop, ServerOptions, UnixListenerOn};
 use garage_api::helpers::*;
 use garage_api::s3::cors::{add_cors_headers, find_matching_cors_rule, handle_options_for_bucket};
 use garage_api::s3::error::{
@@ -88,13 +88,18 @@ impl WebServer {
 		let server_name = "Web".into();
 		info!("Web server listening on {}", bind_addr);
 
+		let options = match &self.garage.config.s3_web {
+			Some(web_config) => ServerOptions::from_config(&web_config.http)?,
+			None => ServerOptions::default(),
+		};
+
 		match bind_addr {
 			UnixOrTCPSocketAddress::TCPSocket(addr) => {
-				let listener = TcpListener::bind(addr).await?;
+				let listener = options.bind_tcp(addr)?;
 
 				let handler =
 					move |stream, socketaddr| self.clone().handle_request(stream, socketaddr);
-				server_loop(server_name, listener, handler, must_exit).await
+				server_loop(server_name, listener, handler, options, must_exit).await
 			}
 			UnixOrTCPSocketAddress::UnixSocket(ref path) => {
 				if path.exists() {
@@ -108,7 +113,7 @@ impl WebServer {
 
 				let handler =
 					move |stream, socketaddr| self.clone().handle_request(stream, socketaddr);
-				server_loop(server_name, listener, handler, must_exit).await
+				server_loop(server_name, listener, handler, options, must_exit).await
 			}
 		}
 	}
@@ -246,7 +251,15 @@ impl WebServer {
 				.map_err(ApiError::from)
 				.map(|res| res.map(|_empty_body: EmptyBody| empty_body())),
 			Method::HEAD => {
-				handle_head_without_ctx(self.garage.clone(), req, bucket_id, &key, None).await
+				handle_head_without_ctx(
+					self.garage.clone(),
+					req,
+					bucket_id,
+					&key,
+					None,
+					Default::default(),
+				)
+				.await
 			}
 			Method::GET => {
 				handle_get_without_ctx(